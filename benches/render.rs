@@ -0,0 +1,79 @@
+//! Times building the lookup table and drawing a frame at several resolutions and thread
+//! counts, so changes to the table layout or the render loop can be measured rather than
+//! eyeballed. Run with `cargo bench`, adding `--features simd` to include the SIMD loop.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::ThreadPoolBuilder;
+use tunnel::{LutQuality, Renderer, TunnelParams, World};
+
+const RESOLUTIONS: [(u32, u32); 3] = [(320, 240), (1280, 720), (1920, 1080)];
+
+fn params(width: u32, height: u32) -> TunnelParams {
+    TunnelParams {
+        width,
+        height,
+        lut_cache: false,
+        ..TunnelParams::default()
+    }
+}
+
+/// One, two and four threads and every core, without repeats.
+fn thread_counts() -> Vec<usize> {
+    let mut counts = vec![1, 2, 4, rayon::current_num_threads()];
+    counts.sort_unstable();
+    counts.dedup();
+    counts
+}
+
+fn lut(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lut");
+    group.sample_size(10);
+    for (width, height) in RESOLUTIONS {
+        group.throughput(Throughput::Elements(width as u64 * height as u64 * 4));
+        for quality in LutQuality::ALL {
+            let params = TunnelParams {
+                lut_quality: quality,
+                ..params(width, height)
+            };
+            let id = BenchmarkId::new(quality.name(), format!("{}x{}", width, height));
+            group.bench_with_input(id, &params, |b, &params| b.iter(|| World::new(params)));
+        }
+    }
+    group.finish();
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    let loops: &[(&str, bool)] = if cfg!(feature = "simd") {
+        &[("scalar", false), ("simd", true)]
+    } else {
+        &[("scalar", false)]
+    };
+    for (width, height) in RESOLUTIONS {
+        let mut world = World::new(params(width, height));
+        world.update();
+        let mut frame = vec![0u8; width as usize * height as usize * 4];
+        group.throughput(Throughput::Elements(width as u64 * height as u64));
+        for threads in thread_counts() {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("thread pool");
+            for &(name, simd) in loops {
+                let mut renderer = Renderer::new(threads);
+                renderer.set_simd(simd);
+                let id = BenchmarkId::new(
+                    format!("{}/{}x{}", name, width, height),
+                    format!("{} threads", threads),
+                );
+                group.bench_function(id, |b| {
+                    b.iter(|| pool.install(|| renderer.draw(&world, &mut frame)))
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lut, draw);
+criterion_main!(benches);
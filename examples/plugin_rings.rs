@@ -0,0 +1,66 @@
+//! An effect plugin: rings of palette colors rushing out of the middle of the frame.
+//!
+//! `cargo build --release --example plugin_rings` builds it as a shared library into
+//! `target/release/examples`; copy that into the `plugins` folder and build the demo with
+//! `--features plugins` to load it.
+
+use std::ffi::c_void;
+use std::slice;
+
+use tunnel::effect::plugin::{PluginApi, PluginFrame, API_VERSION};
+
+/// Rings per frame height.
+const RINGS: f64 = 12.0;
+
+struct Rings {
+    /// Distance from the middle of each pixel, in frame heights, for the frame size it was
+    /// worked out for.
+    distances: Vec<f64>,
+    size: (u32, u32),
+}
+
+extern "C" fn create() -> *mut c_void {
+    let rings = Rings {
+        distances: Vec::new(),
+        size: (0, 0),
+    };
+    Box::into_raw(Box::new(rings)).cast()
+}
+
+extern "C" fn draw(state: *mut c_void, frame: *const PluginFrame) {
+    // The demo passes the state `create` returned and a frame that is valid for this call.
+    let (rings, frame) = unsafe { (&mut *state.cast::<Rings>(), &*frame) };
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let pixels = unsafe { slice::from_raw_parts_mut(frame.pixels, width * height * 4) };
+    let palette = unsafe { slice::from_raw_parts(frame.palette, 256) };
+
+    if rings.size != (frame.width, frame.height) {
+        rings.size = (frame.width, frame.height);
+        rings.distances = (0..width * height)
+            .map(|i| {
+                let dx = (i % width) as f64 - width as f64 / 2.0;
+                let dy = (i / width) as f64 - height as f64 / 2.0;
+                dx.hypot(dy) / height as f64
+            })
+            .collect();
+    }
+    for (pixel, distance) in pixels.chunks_exact_mut(4).zip(&rings.distances) {
+        let phase = (distance * RINGS - frame.travel * 4.0).rem_euclid(1.0);
+        pixel.copy_from_slice(&palette[(phase * 255.0) as usize]);
+    }
+}
+
+extern "C" fn destroy(state: *mut c_void) {
+    drop(unsafe { Box::from_raw(state.cast::<Rings>()) });
+}
+
+#[no_mangle]
+pub extern "C" fn tunnel_plugin() -> PluginApi {
+    PluginApi {
+        api_version: API_VERSION,
+        name: c"rings".as_ptr(),
+        create,
+        draw,
+        destroy,
+    }
+}
@@ -0,0 +1,89 @@
+#[cfg(feature = "theme")]
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{Local, Timelike};
+use tunnel::color_map::Daylight;
+use tunnel::time::Instant;
+use tunnel::World;
+
+use crate::cli::Ambient;
+
+/// How often the surroundings are looked at again.
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Recolors the world with a `Daylight` color map following the local time or the system
+/// theme. Every change crossfades like a palette switch; switching palettes by hand lasts
+/// until the next one.
+pub struct AmbientPalette {
+    mode: Ambient,
+    checked: Option<Instant>,
+    daylight: Option<f64>,
+    #[cfg(feature = "theme")]
+    theme: Option<Receiver<f64>>,
+}
+
+impl AmbientPalette {
+    pub fn new(mode: Ambient) -> Self {
+        Self {
+            mode,
+            checked: None,
+            daylight: None,
+            #[cfg(feature = "theme")]
+            theme: (mode == Ambient::Theme).then(watch_theme),
+        }
+    }
+
+    pub fn update(&mut self, world: &mut World) {
+        let daylight = match self.mode {
+            Ambient::TimeOfDay => {
+                if self
+                    .checked
+                    .is_some_and(|checked| checked.elapsed() < INTERVAL)
+                {
+                    return;
+                }
+                self.checked = Some(Instant::now());
+                let now = Local::now();
+                Daylight::at_hour(now.hour() as f64 + now.minute() as f64 / 60.0).daylight
+            }
+            #[cfg(feature = "theme")]
+            Ambient::Theme => match self
+                .theme
+                .as_ref()
+                .and_then(|theme| theme.try_iter().last())
+            {
+                Some(daylight) => daylight,
+                None => return,
+            },
+        };
+        if self.daylight != Some(daylight) {
+            self.daylight = Some(daylight);
+            world.set_color_map(Arc::new(Daylight { daylight }));
+        }
+    }
+}
+
+/// Asks for the system theme every `INTERVAL` on a thread of its own, as the answer can take
+/// a round trip to the desktop (D-Bus on Linux), and sends the daylight it calls for: none
+/// for dark themes, full for light ones.
+#[cfg(feature = "theme")]
+fn watch_theme() -> Receiver<f64> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let daylight = match dark_light::detect() {
+            Ok(dark_light::Mode::Light) => 1.0,
+            Ok(_) => 0.0,
+            Err(err) => {
+                tracing::warn!("Failed to read the system theme: {}", err);
+                return;
+            }
+        };
+        if sender.send(daylight).is_err() {
+            return;
+        }
+        std::thread::sleep(INTERVAL);
+    });
+    receiver
+}
@@ -0,0 +1,305 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::{AnimationDecoder, Frame, ImageResult};
+use tunnel::texture::Noise3d;
+use tunnel::Texture;
+
+/// Video frames are scaled down to at most this width, which is plenty for a tunnel wall.
+const VIDEO_WIDTH: u32 = 512;
+/// Decoded video frames kept ready ahead of playback.
+const QUEUE_LENGTH: usize = 4;
+const VIDEO_EXTENSIONS: [&str; 7] = ["mp4", "m4v", "mkv", "webm", "mov", "avi", "mpg"];
+/// What browsers show frames without a delay for.
+const DEFAULT_DELAY: f64 = 0.1;
+/// How fast the noise slice moves through the volume, in lattice cells per second.
+const NOISE_SPEED: f64 = 0.25;
+
+/// A texture that changes over time, following the world clock: the frames of an animated
+/// GIF or PNG, the images of a folder in name order, a video that `ffmpeg` decodes while it
+/// plays, or slices of 3D noise. All but the noise loop.
+pub struct Animation {
+    source: Source,
+}
+
+enum Source {
+    /// Every frame with the time it starts at, the length of the loop and the frame last
+    /// shown.
+    Frames {
+        frames: Vec<(f64, Arc<Texture>)>,
+        length: f64,
+        shown: Option<usize>,
+    },
+    /// Frames arrive from the decoding thread at a fixed rate; `next` is when the next one is
+    /// due.
+    Video {
+        frames: Receiver<Texture>,
+        frame_time: f64,
+        next: f64,
+    },
+    /// A new slice whenever the time changes.
+    Noise {
+        noise: Noise3d,
+        width: usize,
+        height: usize,
+        shown: f64,
+    },
+}
+
+impl Animation {
+    /// Opens `path` if it is animated: a folder, a GIF or PNG with more than one frame, or a
+    /// video. Folders and videos play at `fps`. Anything else is a still image, left to
+    /// `Texture::load`.
+    pub fn open(path: &Path, fps: f64) -> Result<Option<Self>, Box<dyn Error>> {
+        let frame_time = 1.0 / fps.max(1.0);
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let frames = match extension.as_deref() {
+            _ if path.is_dir() => folder_frames(path, frame_time)?,
+            Some("gif") => animation_frames(GifDecoder::new(reader(path)?)?)?,
+            Some("png") => {
+                let decoder = PngDecoder::new(reader(path)?)?;
+                if !decoder.is_apng()? {
+                    return Ok(None);
+                }
+                animation_frames(decoder.apng()?)?
+            }
+            Some(extension) if VIDEO_EXTENSIONS.contains(&extension) => {
+                return Ok(Some(Self {
+                    source: Source::Video {
+                        frames: decode_video(path, fps.max(1.0))?,
+                        frame_time,
+                        next: 0.0,
+                    },
+                }));
+            }
+            _ => return Ok(None),
+        };
+        if frames.len() < 2 {
+            return Ok(None);
+        }
+
+        let mut start = 0.0;
+        let frames = frames
+            .into_iter()
+            .map(|(texture, delay)| {
+                let frame = (start, Arc::new(texture));
+                start += delay;
+                frame
+            })
+            .collect();
+        Ok(Some(Self {
+            source: Source::Frames {
+                frames,
+                length: start,
+                shown: None,
+            },
+        }))
+    }
+
+    /// Undulating noise of the given size, generated afresh for every frame.
+    pub fn noise(seed: u64, width: usize, height: usize) -> Self {
+        Self {
+            source: Source::Noise {
+                noise: Noise3d {
+                    seed,
+                    period: 4,
+                    octaves: 4,
+                },
+                width,
+                height,
+                shown: f64::NAN,
+            },
+        }
+    }
+
+    /// The frame shown at the start, waiting for the decoder if needed.
+    pub fn first_frame(&mut self) -> Result<Texture, Box<dyn Error>> {
+        match &mut self.source {
+            Source::Frames { frames, shown, .. } => {
+                *shown = Some(0);
+                Ok((*frames[0].1).clone())
+            }
+            Source::Video {
+                frames,
+                frame_time,
+                next,
+            } => {
+                *next = *frame_time;
+                Ok(frames.recv().map_err(|_| "the video has no frames")?)
+            }
+            Source::Noise {
+                noise,
+                width,
+                height,
+                shown,
+            } => {
+                *shown = 0.0;
+                Ok(noise.generate(*width, *height, 0.0))
+            }
+        }
+    }
+
+    /// The frame to show at `time` seconds if it differs from the last one returned. Video
+    /// only plays forwards and holds its frame while the decoder falls behind.
+    pub fn frame_at(&mut self, time: f64) -> Option<Arc<Texture>> {
+        match &mut self.source {
+            Source::Frames {
+                frames,
+                length,
+                shown,
+            } => {
+                let time = time.rem_euclid(*length);
+                let index = frames.partition_point(|(start, _)| *start <= time).max(1) - 1;
+                if *shown == Some(index) {
+                    return None;
+                }
+                *shown = Some(index);
+                Some(frames[index].1.clone())
+            }
+            Source::Video {
+                frames,
+                frame_time,
+                next,
+            } => {
+                let mut latest = None;
+                while *next <= time {
+                    match frames.try_recv() {
+                        Ok(frame) => latest = Some(frame),
+                        Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+                    }
+                    *next += *frame_time;
+                }
+                // Catches up in one go after a pause instead of fast-forwarding.
+                if time - *next > *frame_time * QUEUE_LENGTH as f64 {
+                    *next = time;
+                }
+                latest.map(Arc::new)
+            }
+            Source::Noise {
+                noise,
+                width,
+                height,
+                shown,
+            } => {
+                if *shown == time {
+                    return None;
+                }
+                *shown = time;
+                Some(Arc::new(noise.generate(
+                    *width,
+                    *height,
+                    time * NOISE_SPEED,
+                )))
+            }
+        }
+    }
+}
+
+fn reader(path: &Path) -> Result<BufReader<File>, Box<dyn Error>> {
+    Ok(BufReader::new(File::open(path)?))
+}
+
+fn texture(frame: Frame) -> Texture {
+    let image = frame.into_buffer();
+    Texture::from_rgba(
+        image.width() as usize,
+        image.height() as usize,
+        image.as_raw(),
+    )
+}
+
+/// The decoded frames with how long each is shown, in seconds.
+fn animation_frames<'a>(decoder: impl AnimationDecoder<'a>) -> ImageResult<Vec<(Texture, f64)>> {
+    decoder
+        .into_frames()
+        .map(|frame| {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = match numer {
+                0 => DEFAULT_DELAY,
+                _ => numer as f64 / denom.max(1) as f64 / 1000.0,
+            };
+            Ok((texture(frame), delay))
+        })
+        .collect()
+}
+
+/// Every image in `dir`, in name order; files that aren't images are skipped.
+fn folder_frames(dir: &Path, frame_time: f64) -> Result<Vec<(Texture, f64)>, Box<dyn Error>> {
+    let mut paths: Vec<_> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<_, _>>()?;
+    paths.sort();
+    Ok(paths
+        .iter()
+        .filter(|path| path.is_file())
+        .filter_map(|path| Texture::load(path).ok())
+        .map(|texture| (texture, frame_time))
+        .collect())
+}
+
+/// Starts `ffmpeg` decoding the video in a loop at `fps` frames per second, scaled down to
+/// `VIDEO_WIDTH`, and a thread turning its output into textures.
+fn decode_video(path: &Path, fps: f64) -> Result<Receiver<Texture>, Box<dyn Error>> {
+    let (width, height) = video_size(path)?;
+    let scaled_width = width.clamp(2, VIDEO_WIDTH) & !1;
+    let scaled_height =
+        ((height as u64 * scaled_width as u64 / width.max(1) as u64) as u32).max(2) & !1;
+    let mut child = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-stream_loop", "-1", "-i"])
+        .arg(path)
+        .args(["-an", "-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", scaled_width, scaled_height)])
+        .args(["-r", &fps.to_string(), "-"])
+        // ffmpeg takes keys from stdin, which would eat the terminal's and quit on `q`.
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("failed to start ffmpeg: {}", err))?;
+    let mut stdout = child.stdout.take().expect("ffmpeg stdout");
+
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+    let (width, height) = (scaled_width as usize, scaled_height as usize);
+    thread::spawn(move || {
+        let mut frame = vec![0; width * height * 4];
+        // Stops once playback is dropped or the video can't be read any further.
+        while stdout.read_exact(&mut frame).is_ok() {
+            if sender
+                .send(Texture::from_rgba(width, height, &frame))
+                .is_err()
+            {
+                break;
+            }
+        }
+        let _ = child.kill();
+        let _ = child.wait();
+    });
+    Ok(receiver)
+}
+
+/// The size of the video's first stream, asked from `ffprobe`.
+fn video_size(path: &Path) -> Result<(u32, u32), Box<dyn Error>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=width,height", "-of", "csv=p=0:s=x"])
+        .arg(path)
+        .output()
+        .map_err(|err| format!("failed to start ffprobe: {}", err))?;
+    let size = String::from_utf8_lossy(&output.stdout);
+    let (width, height) = size
+        .trim()
+        .split_once('x')
+        .ok_or_else(|| format!("{} has no video stream", path.display()))?;
+    Ok((width.parse()?, height.parse()?))
+}
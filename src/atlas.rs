@@ -0,0 +1,113 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Most tiles along either side of the grid.
+const MAX_GRID: usize = 256;
+
+/// Reads the texture as a grid of tiles and gives every band of the tunnel's length one of
+/// them, so that gates, ring markers and other structures repeat along the flight path. The
+/// bands are `band` tile lengths long and take the tiles of `tiles` in turn, starting over
+/// after the last one. Loaded from a TOML file:
+///
+/// ```toml
+/// columns = 2
+/// rows = 1
+/// band = 1
+/// tiles = [0, 0, 0, 1]
+/// ```
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Atlas {
+    pub columns: usize,
+    pub rows: usize,
+    /// Tile lengths every band lasts.
+    #[serde(default = "one")]
+    pub band: usize,
+    /// The tile of each band, counted along the rows from the top left.
+    pub tiles: Vec<usize>,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl Atlas {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?;
+        Ok(Self::parse(&source).map_err(|err| format!("`{}`: {}", path.display(), err))?)
+    }
+
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let atlas: Atlas = toml::from_str(source).map_err(|err| err.to_string())?;
+        if !(1..=MAX_GRID).contains(&atlas.columns) || !(1..=MAX_GRID).contains(&atlas.rows) {
+            return Err(format!(
+                "the grid must have 1 to {} columns and rows",
+                MAX_GRID
+            ));
+        }
+        if atlas.band == 0 {
+            return Err("bands must be at least one tile long".to_string());
+        }
+        if atlas.tiles.is_empty() {
+            return Err("no tiles given for the bands".to_string());
+        }
+        let count = atlas.columns * atlas.rows;
+        if let Some(tile) = atlas.tiles.iter().find(|&&tile| tile >= count) {
+            return Err(format!(
+                "tile {} is outside the {}x{} grid",
+                tile, atlas.columns, atlas.rows
+            ));
+        }
+        Ok(atlas)
+    }
+
+    /// The size of one tile of a texture this large; textures smaller than the grid have
+    /// tiles one texel across.
+    pub fn tile_size(&self, width: usize, height: usize) -> (usize, usize) {
+        ((width / self.columns).max(1), (height / self.rows).max(1))
+    }
+
+    /// Texels along the tunnel before the bands start over, a whole number of `period`s,
+    /// the texels the address mode repeats after within a tile.
+    pub(crate) fn period(&self, period: usize) -> usize {
+        period * self.band * self.tiles.len()
+    }
+
+    /// Where each band's tile starts in a texture this large, and how long bands are.
+    pub(crate) fn bands(&self, width: usize, height: usize) -> Bands {
+        let (tile_width, tile_height) = self.tile_size(width, height);
+        Bands {
+            length: (tile_width * self.band) as u64,
+            origins: self
+                .tiles
+                .iter()
+                .map(|&tile| {
+                    let (column, row) = (tile % self.columns, tile / self.columns);
+                    (
+                        (column * tile_width).min(width - 1),
+                        (row * tile_height).min(height - 1),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An atlas laid over a texture, for finding the tile of a depth at sample time.
+pub(crate) struct Bands {
+    /// In texels.
+    length: u64,
+    origins: Vec<(usize, usize)>,
+}
+
+impl Bands {
+    /// The first texel of the tile shown `texels` down the texture.
+    pub(crate) fn origin(&self, texels: u64) -> (usize, usize) {
+        self.origins[(texels / self.length) as usize % self.origins.len()]
+    }
+}
@@ -0,0 +1,143 @@
+use tunnel::{BlendMode, Flight, PaletteKind, Pipeline, Stage, World};
+
+use crate::menu::Choice;
+use crate::screensaver::Dice;
+
+/// Texture lengths per second the tunnel flies at, at the least and at the most, when picked
+/// at random; it spins up to `MAX_SPIN` turns per second either way.
+const TRAVEL: (f64, f64) = (0.25, 1.5);
+const MAX_SPIN: f64 = 0.3;
+
+/// What attract mode changes, one at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mutation {
+    Palette,
+    Texture,
+    Shape,
+    Effect,
+    Flight,
+}
+
+/// A new effect fading in over the old one, on a stage of its own until it covers it.
+struct EffectFade {
+    start: f64,
+    duration: f64,
+}
+
+/// Changes something about the look every `interval` seconds of animation for as long as
+/// the tunnel runs: the palette, texture or shape crossfade, a new effect fades in over the
+/// old one and new speeds are eased into.
+pub struct Attract {
+    interval: f64,
+    next: Option<f64>,
+    dice: Dice,
+    /// Whether the texture may be swapped for a generated one, i.e. none was given.
+    textures: bool,
+    last: Option<Mutation>,
+    fade: Option<EffectFade>,
+}
+
+impl Attract {
+    pub fn new(interval: f64, dice: Dice, textures: bool) -> Self {
+        Self {
+            interval: interval.max(1.0),
+            next: None,
+            dice,
+            textures,
+            last: None,
+            fade: None,
+        }
+    }
+
+    /// Carries on with an effect fading in, and when it's time, makes the next change: the
+    /// effect and the speeds are changed here, and the palette, texture or shape handed back
+    /// to be switched to like a pick from the menu.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        pipeline: &mut Pipeline,
+        generators: usize,
+        geometries: usize,
+    ) -> Option<Choice> {
+        let time = world.clock().time();
+        if let Some(fade) = &self.fade {
+            let mix = (time - fade.start) / fade.duration;
+            let stages = &mut pipeline.compositor.stages;
+            if mix >= 1.0 || stages.len() < 2 {
+                let effect = stages.pop().map(|stage| stage.effect);
+                if let Some(effect) = effect {
+                    pipeline.compositor.top().effect = effect;
+                }
+                self.fade = None;
+            } else {
+                pipeline.compositor.top().opacity = mix.max(0.0);
+            }
+        }
+
+        let next = *self.next.get_or_insert(time + self.interval);
+        if time < next || self.fade.is_some() {
+            return None;
+        }
+        self.next = Some(time + self.interval);
+        let mutations: Vec<Mutation> = [
+            Mutation::Palette,
+            Mutation::Texture,
+            Mutation::Shape,
+            Mutation::Effect,
+            Mutation::Flight,
+        ]
+        .into_iter()
+        .filter(|&mutation| Some(mutation) != self.last)
+        .filter(|&mutation| mutation != Mutation::Texture || self.textures)
+        .collect();
+        let mutation = mutations[self.dice.pick(mutations.len())];
+        self.last = Some(mutation);
+        // Something else than what there is now, where there is a choice.
+        let mut other = |count: usize, current: usize| {
+            (current + 1 + self.dice.pick(count.max(2) - 1)) % count.max(1)
+        };
+        match mutation {
+            Mutation::Palette => {
+                let current = PaletteKind::ALL
+                    .iter()
+                    .position(|&kind| kind == world.palette_kind())
+                    .unwrap_or(0);
+                Some(Choice::Palette(other(PaletteKind::ALL.len(), current)))
+            }
+            Mutation::Texture => Some(Choice::Texture(self.dice.pick(generators))),
+            Mutation::Shape => Some(Choice::Shape(self.dice.pick(geometries))),
+            Mutation::Effect => {
+                let effect = other(pipeline.effects.len(), pipeline.compositor.top().effect);
+                let duration = world.crossfade();
+                if duration > 0.0 {
+                    pipeline.compositor.stages.push(Stage {
+                        effect,
+                        mode: BlendMode::Alpha,
+                        opacity: 0.0,
+                    });
+                    self.fade = Some(EffectFade {
+                        start: time,
+                        duration,
+                    });
+                } else {
+                    pipeline.compositor.top().effect = effect;
+                }
+                None
+            }
+            Mutation::Flight => {
+                let flight = random_flight(&mut self.dice, world.flight());
+                world.set_flight(flight);
+                None
+            }
+        }
+    }
+}
+
+/// `flight` with a travel and spin speed picked at random.
+pub fn random_flight(dice: &mut Dice, flight: &Flight) -> Flight {
+    Flight {
+        travel: TRAVEL.0 + dice.unit() * (TRAVEL.1 - TRAVEL.0),
+        spin: (dice.unit() * 2.0 - 1.0) * MAX_SPIN,
+        easing: flight.easing,
+    }
+}
@@ -0,0 +1,110 @@
+const HOP: usize = 512;
+const THRESHOLD_WINDOW: usize = 16;
+const THRESHOLD_FACTOR: f64 = 1.5;
+const MIN_GAP: f64 = 0.12;
+const DECAY: f64 = 0.15;
+
+/// Onset envelope of a piece of music, sampled every `HOP` audio frames.
+///
+/// Every detected beat kicks the envelope to its relative strength, after which it decays
+/// exponentially, so `envelope` reads as a pulse in `[0, 1]` that can drive animation.
+pub struct BeatTrack {
+    rate: f64,
+    envelope: Vec<f64>,
+    integral: Vec<f64>,
+    beats: Vec<f64>,
+}
+
+impl BeatTrack {
+    /// Analyzes interleaved samples in `[-1, 1]`.
+    pub fn analyze(samples: &[f32], channels: u16, sample_rate: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        let rate = sample_rate.max(1) as f64 / HOP as f64;
+
+        let energy: Vec<f64> = samples
+            .chunks(HOP * channels)
+            .map(|hop| {
+                let sum: f64 = hop.iter().map(|&s| (s as f64) * (s as f64)).sum();
+                (1.0 + 1000.0 * sum / hop.len() as f64).ln()
+            })
+            .collect();
+        let flux: Vec<f64> = energy
+            .iter()
+            .scan(0.0, |previous, &e| {
+                let rise = (e - *previous).max(0.0);
+                *previous = e;
+                Some(rise)
+            })
+            .collect();
+
+        let mut beats = Vec::new();
+        let mut strengths = Vec::new();
+        for (i, &value) in flux.iter().enumerate() {
+            let window =
+                &flux[i.saturating_sub(THRESHOLD_WINDOW)..(i + THRESHOLD_WINDOW).min(flux.len())];
+            let mean = window.iter().sum::<f64>() / window.len() as f64;
+            let peak = window.iter().cloned().fold(0.0, f64::max) == value;
+            let time = i as f64 / rate;
+            let spaced = beats.last().is_none_or(|&last| time - last >= MIN_GAP);
+            if value > 0.0 && value > mean * THRESHOLD_FACTOR && peak && spaced {
+                beats.push(time);
+                strengths.push(value);
+            }
+        }
+
+        let loudest = strengths.iter().cloned().fold(0.0, f64::max);
+        let mut envelope = vec![0.0; flux.len()];
+        let mut next = 0;
+        let mut level = 0.0;
+        for (i, value) in envelope.iter_mut().enumerate() {
+            let time = i as f64 / rate;
+            level *= (-1.0 / (rate * DECAY)).exp();
+            while next < beats.len() && beats[next] <= time {
+                level = f64::max(level, strengths[next] / loudest);
+                next += 1;
+            }
+            *value = level;
+        }
+
+        let integral = envelope
+            .iter()
+            .scan(0.0, |sum, &value| {
+                *sum += value / rate;
+                Some(*sum)
+            })
+            .collect();
+
+        Self {
+            rate,
+            envelope,
+            integral,
+            beats,
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.envelope.len() as f64 / self.rate
+    }
+
+    /// Times of the detected beats in seconds.
+    pub fn beats(&self) -> &[f64] {
+        &self.beats
+    }
+
+    pub fn envelope(&self, time: f64) -> f64 {
+        self.sample(&self.envelope, time).unwrap_or(0.0)
+    }
+
+    /// Area under the envelope up to `time`, i.e. how far a beat-driven motion has advanced.
+    pub fn integral(&self, time: f64) -> f64 {
+        self.sample(&self.integral, time)
+            .unwrap_or_else(|| self.integral.last().copied().unwrap_or(0.0))
+    }
+
+    fn sample(&self, values: &[f64], time: f64) -> Option<f64> {
+        if time < 0.0 {
+            return Some(0.0);
+        }
+        values.get((time * self.rate) as usize).copied()
+    }
+}
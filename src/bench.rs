@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+use tunnel::time::Instant;
+use tunnel::{PostFx, Renderer, Texture, TunnelParams, World};
+
+struct Stage {
+    name: &'static str,
+    samples: Vec<Duration>,
+}
+
+impl Stage {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            samples: Vec::new(),
+        }
+    }
+
+    fn report(&self) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let total: Duration = self.samples.iter().sum();
+        let min = self.samples.iter().min().copied().unwrap_or_default();
+        let max = self.samples.iter().max().copied().unwrap_or_default();
+        println!(
+            "{:>8}: avg {:8.3} ms  min {:8.3} ms  max {:8.3} ms",
+            self.name,
+            ms(total) / self.samples.len() as f64,
+            ms(min),
+            ms(max),
+        );
+    }
+}
+
+pub fn run(
+    params: TunnelParams,
+    texture: Texture,
+    configure: impl FnOnce(&mut World),
+    renderer: &Renderer,
+    postfx: &mut PostFx,
+    frames: u32,
+) {
+    let mut lut = Stage::new("lut");
+    let mut update = Stage::new("update");
+    let mut draw = Stage::new("draw");
+    let mut band = Stage::new("band");
+    let mut post = Stage::new("postfx");
+
+    let start = Instant::now();
+    let mut world = World::with_texture(params, texture);
+    lut.samples.push(start.elapsed());
+    configure(&mut world);
+
+    let params = *world.params();
+    let mut frame = vec![0u8; params.width as usize * params.height as usize * 4];
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        let stage_start = Instant::now();
+        world.update();
+        update.samples.push(stage_start.elapsed());
+
+        let stats = renderer.draw(&world, &mut frame);
+        draw.samples.push(stats.total);
+        band.samples.extend(stats.bands);
+
+        let stage_start = Instant::now();
+        postfx.apply(&mut frame, params.width as usize, params.height as usize);
+        post.samples.push(stage_start.elapsed());
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "{} frames at {}x{} in {:.3} s: {:.1} fps",
+        frames,
+        params.width,
+        params.height,
+        elapsed.as_secs_f64(),
+        frames as f64 / elapsed.as_secs_f64(),
+    );
+    for stage in [&lut, &update, &draw, &band, &post] {
+        stage.report();
+    }
+}
@@ -0,0 +1,134 @@
+/// How quickly the view catches up with the cursor in `CameraMode::Follow`, per second.
+const FOLLOW_RATE: f64 = 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Auto,
+    Manual,
+    /// The tunnel's center eases towards the cursor, see `Camera::follow`.
+    Follow,
+}
+
+pub struct Camera {
+    mode: CameraMode,
+    look: (f64, f64),
+    offset: (f64, f64),
+    limit: (f64, f64),
+    eye: f64,
+}
+
+impl Camera {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            mode: CameraMode::Auto,
+            look: (0.0, 0.0),
+            offset: (0.0, 0.0),
+            limit: ((width / 2) as f64, (height / 2) as f64),
+            eye: 0.0,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.limit = ((width / 2) as f64, (height / 2) as f64);
+        self.look = (
+            self.look.0.clamp(-self.limit.0, self.limit.0),
+            self.look.1.clamp(-self.limit.1, self.limit.1),
+        );
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+    }
+
+    pub fn look(&self) -> (f64, f64) {
+        (
+            (self.look.0 + self.offset.0 * self.limit.0).clamp(-self.limit.0, self.limit.0),
+            (self.look.1 + self.offset.1 * self.limit.1).clamp(-self.limit.1, self.limit.1),
+        )
+    }
+
+    /// Where the view is turned, from -1 to 1 of how far it can turn either way, leaving out
+    /// the shake live audio adds. Independent of the frame size, unlike `look`.
+    pub fn heading(&self) -> (f64, f64) {
+        (
+            self.look.0 / self.limit.0.max(1.0),
+            self.look.1 / self.limit.1.max(1.0),
+        )
+    }
+
+    /// Turns the view to a `heading`; in `CameraMode::Auto` it moves on from there with the
+    /// next update.
+    pub fn set_heading(&mut self, x: f64, y: f64) {
+        self.look = (
+            x.clamp(-1.0, 1.0) * self.limit.0,
+            y.clamp(-1.0, 1.0) * self.limit.1,
+        );
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Auto => CameraMode::Manual,
+            CameraMode::Manual | CameraMode::Follow => CameraMode::Auto,
+        };
+    }
+
+    /// Switches between following the cursor and manual steering.
+    pub fn toggle_follow(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Follow => CameraMode::Manual,
+            _ => CameraMode::Follow,
+        };
+    }
+
+    /// In `CameraMode::Follow`, eases the tunnel's center `dt` seconds further towards the
+    /// cursor at `x`, `y` in window coordinates from -1 (left/top edge) to 1 (right/bottom
+    /// edge). Positions outside are clamped, so the view never leaves the lookup table.
+    pub fn follow(&mut self, x: f64, y: f64, dt: f64) {
+        if self.mode != CameraMode::Follow {
+            return;
+        }
+        // Looking to the right moves the tunnel's center to the left.
+        let target = (
+            -x.clamp(-1.0, 1.0) * self.limit.0,
+            -y.clamp(-1.0, 1.0) * self.limit.1,
+        );
+        let t = 1.0 - (-FOLLOW_RATE * dt).exp();
+        self.look = (
+            self.look.0 + (target.0 - self.look.0) * t,
+            self.look.1 + (target.1 - self.look.1) * t,
+        );
+    }
+
+    pub fn steer(&mut self, dx: f64, dy: f64) {
+        self.mode = CameraMode::Manual;
+        self.look = (
+            (self.look.0 + dx).clamp(-self.limit.0, self.limit.0),
+            (self.look.1 + dy).clamp(-self.limit.1, self.limit.1),
+        );
+    }
+
+    /// How far the eye sits to the right of the tunnel's axis, as a share of its radius.
+    pub fn eye(&self) -> f64 {
+        self.eye
+    }
+
+    /// Moves the eye sideways for stereo views; negative values are to the left.
+    pub fn set_eye(&mut self, eye: f64) {
+        self.eye = eye.clamp(-0.5, 0.5);
+    }
+
+    /// Displaces the view by a fraction of the steering range on top of `look`.
+    pub(crate) fn set_offset(&mut self, dx: f64, dy: f64) {
+        self.offset = (dx, dy);
+    }
+
+    pub(crate) fn update(&mut self, time: f64) {
+        if self.mode == CameraMode::Auto {
+            self.look = (self.limit.0 * time.sin(), self.limit.1 * (time * 2.0).sin());
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use tracing::warn;
+use tunnel::{Analyzer, AudioLevels};
+
+/// Analyzes the default input device (microphone or line-in) on the audio thread.
+pub struct Capture {
+    _stream: Stream,
+}
+
+impl Capture {
+    pub fn start(levels: Arc<AudioLevels>) -> Result<Self, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("no audio input device")?;
+        let supported = device.default_input_config()?;
+        let format = supported.sample_format();
+        let config: StreamConfig = supported.into();
+        let analyzer = Analyzer::new(config.sample_rate.0, levels);
+
+        let stream = match format {
+            SampleFormat::F32 => build::<f32>(&device, &config, analyzer)?,
+            SampleFormat::I16 => build::<i16>(&device, &config, analyzer)?,
+            SampleFormat::U16 => build::<u16>(&device, &config, analyzer)?,
+            SampleFormat::I32 => build::<i32>(&device, &config, analyzer)?,
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        };
+        stream.play()?;
+
+        Ok(Self { _stream: stream })
+    }
+}
+
+fn build<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut analyzer: Analyzer,
+) -> Result<Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels;
+    let mut converted = Vec::new();
+    device.build_input_stream(
+        config,
+        move |data: &[T], _| {
+            converted.clear();
+            converted.extend(data.iter().map(|&sample| sample.to_sample::<f32>()));
+            analyzer.push(&converted, channels);
+        },
+        |err| warn!("Audio input error: {}", err),
+        None,
+    )
+}
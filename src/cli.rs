@@ -0,0 +1,1086 @@
+use std::error::Error;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use pixels::wgpu::PresentMode;
+use tracing::level_filters::LevelFilter;
+
+use tunnel::color_map;
+use tunnel::postfx::{ColorLut, Dither, Grade, DEFAULT_TRAIL};
+use tunnel::{
+    AddressMode, BlendMode, Clock, Comparison, Curve, Flight, Fog, FogCurve, Hdr, Interlace, Light,
+    LutQuality, Modulator, PassKind, PostFx, Scroller, Shape, Split, Stamp, Stereo, StereoMode,
+    ToneMap,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::led::{GridSize, LedProtocol};
+use crate::recorder::RecordFormat;
+#[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+use crate::remote::RemoteParam;
+
+const MIN_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{}`", s))?;
+        let parse = |value: &str| match value.trim().parse::<u32>() {
+            Ok(n) if n >= MIN_SIZE => Ok(n),
+            _ => Err(format!("invalid dimension `{}`", value)),
+        };
+        Ok(Self {
+            width: parse(width)?,
+            height: parse(height)?,
+        })
+    }
+}
+
+/// Where on the texture a stamp is centred, as shares of its length and height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub along: f64,
+    pub around: f64,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (along, around) = s
+            .split_once(',')
+            .ok_or_else(|| format!("expected ALONG,AROUND, got `{}`", s))?;
+        let parse = |value: &str| match value.trim().parse::<f64>() {
+            Ok(share) if (0.0..=1.0).contains(&share) => Ok(share),
+            _ => Err(format!("invalid share `{}` (expected 0 to 1)", value)),
+        };
+        Ok(Self {
+            along: parse(along)?,
+            around: parse(around)?,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Backend {
+    Cpu,
+    Gpu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum FullscreenMode {
+    Borderless,
+    Exclusive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Vsync {
+    /// Wait for the display's refresh.
+    On,
+    /// Present immediately, which may tear.
+    Off,
+    /// Don't wait, but only ever show the newest complete frame at a refresh (mailbox).
+    Adaptive,
+}
+
+impl Vsync {
+    pub fn present_mode(self) -> PresentMode {
+        match self {
+            Vsync::On => PresentMode::Fifo,
+            Vsync::Off => PresentMode::Immediate,
+            Vsync::Adaptive => PresentMode::Mailbox,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Ambient {
+    /// Follow the local time: cool blues at night, warm tones in the day.
+    TimeOfDay,
+    /// Follow the system's dark or light theme.
+    #[cfg(feature = "theme")]
+    Theme,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BatterySaver {
+    /// Save power while the machine runs on battery.
+    Auto,
+    /// Always save power.
+    On,
+    /// Never save power, not even on battery.
+    Off,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReducedMotion {
+    /// Reduce motion in the window if the system is set to.
+    Auto,
+    On,
+    Off,
+}
+
+#[cfg(feature = "terminal")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Output {
+    Window,
+    /// Two pixels per character cell, as the colors of a half block.
+    Terminal,
+    /// Sixel graphics, for terminals that can show them.
+    Sixel,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    version,
+    about = "Tunnel effect with XOR texture",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Options of `run`, which is what happens without a subcommand
+    #[command(flatten)]
+    pub args: Args,
+}
+
+impl Cli {
+    /// Parses `args` like `Parser::parse_from`, also noting the options given in them rather
+    /// than left at their defaults.
+    pub fn parse_args(args: Vec<String>) -> Self {
+        let matches = <Self as CommandFactory>::command().get_matches_from(args);
+        let mut cli = Self::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+        let (args, matches) = match (&mut cli.command, matches.subcommand()) {
+            (Some(command), Some((_, matches))) => (command.args_mut(), matches),
+            _ => (&mut cli.args, &matches),
+        };
+        args.note_given(matches);
+        cli
+    }
+
+    /// The subcommand to run, `run` if none was given.
+    pub fn command(self) -> Command {
+        self.command.unwrap_or(Command::Run(self.args))
+    }
+}
+
+impl Command {
+    /// The options every subcommand shares.
+    pub fn args(&self) -> &Args {
+        match self {
+            Command::Run(args) => args,
+            Command::Render(render) => &render.args,
+            Command::RenderStill(still) => &still.args,
+            Command::Bench(bench) => &bench.args,
+            Command::InspectLut(inspect) => &inspect.args,
+            Command::Framebuffer(framebuffer) => &framebuffer.args,
+            Command::ExportShader(export) => &export.args,
+        }
+    }
+
+    fn args_mut(&mut self) -> &mut Args {
+        match self {
+            Command::Run(args) => args,
+            Command::Render(render) => &mut render.args,
+            Command::RenderStill(still) => &mut still.args,
+            Command::Bench(bench) => &mut bench.args,
+            Command::InspectLut(inspect) => &mut inspect.args,
+            Command::Framebuffer(framebuffer) => &mut framebuffer.args,
+            Command::ExportShader(export) => &mut export.args,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Open a window and play the tunnel interactively (the default)
+    Run(Args),
+    /// Render frames off-screen at a fixed frame rate and write them to files
+    Render(RenderArgs),
+    /// Render a single frame at any size, e.g. for wallpapers and print
+    RenderStill(StillArgs),
+    /// Render frames off-screen, print timings and exit
+    Bench(BenchArgs),
+    /// Build the lookup table, print statistics about it and optionally save it as an image
+    InspectLut(InspectArgs),
+    /// Play the tunnel full-screen on a Linux framebuffer device, without X or Wayland
+    Framebuffer(FramebufferArgs),
+    /// Write the tunnel out as a GLSL fragment shader to paste into Shadertoy
+    ExportShader(ExportArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub struct RenderArgs {
+    /// Number of frames to render
+    #[arg(long, default_value_t = 300)]
+    pub frames: u32,
+
+    /// Directory for PNG frames, or the GIF or MP4 file to write
+    #[arg(long, value_name = "PATH")]
+    pub out: PathBuf,
+
+    /// Output format: a folder of PNG frames, a GIF or an MP4 (through ffmpeg)
+    #[arg(long, value_enum, default_value_t = RecordFormat::Png)]
+    pub format: RecordFormat,
+
+    /// Animation frames per second
+    #[arg(long, default_value_t = 60)]
+    pub fps: u32,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct StillArgs {
+    /// Size of the image, e.g. 7680x4320
+    #[arg(long, value_name = "WxH")]
+    pub size: Resolution,
+
+    /// Animation time in seconds to render the frame at
+    #[arg(long, value_name = "SECONDS", default_value_t = 0.0)]
+    pub time: f64,
+
+    /// Image file to write; the format follows the extension (PNG, JPEG, ...)
+    #[arg(long, value_name = "PATH")]
+    pub out: PathBuf,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct BenchArgs {
+    /// Number of frames to render
+    #[arg(long, default_value_t = 300)]
+    pub frames: u32,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct InspectArgs {
+    /// Tunnel shape to build the table for (circle, square, ellipse, star, twisted)
+    #[arg(long, value_name = "SHAPE", default_value = "circle")]
+    pub shape: String,
+
+    /// PNG file to draw the table into: distance in red, angle in green
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FramebufferArgs {
+    /// Framebuffer device to draw on
+    #[arg(long, value_name = "PATH", default_value = "/dev/fb0")]
+    pub device: PathBuf,
+
+    /// Frames drawn per second at most
+    #[arg(long, default_value_t = 60.0)]
+    pub fps: f64,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(clap::Args, Debug)]
+pub struct ExportArgs {
+    /// Tunnel shape (circle, square, ellipse, star, twisted)
+    #[arg(long, value_name = "SHAPE", default_value = "circle")]
+    pub shape: String,
+
+    /// Texture generator (xor, checkerboard, noise, brick, spiral, stripes)
+    #[arg(long, value_name = "NAME", default_value = "xor")]
+    pub generator: String,
+
+    /// File to write the shader to instead of printing it
+    #[arg(long, value_name = "PATH")]
+    pub out: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub args: Args,
+}
+
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Config file that is reloaded whenever it changes [default: tunnel.toml if present]
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Image file (PNG or JPEG) to use as the tunnel texture; animated GIFs and PNGs, folders
+    /// of images and videos (through ffmpeg) play on the walls
+    #[arg(long, value_name = "PATH")]
+    pub texture: Option<PathBuf>,
+
+    /// Frame rate to play folders of images and videos used as the texture at
+    #[arg(long, value_name = "FPS", default_value_t = 30.0)]
+    pub texture_fps: f64,
+
+    /// Render width in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(MIN_SIZE as i64..))]
+    pub width: Option<u32>,
+
+    /// Render height in pixels
+    #[arg(long, value_parser = clap::value_parser!(u32).range(MIN_SIZE as i64..))]
+    pub height: Option<u32>,
+
+    /// Render resolution, e.g. 1920x1080
+    #[arg(long, value_name = "WxH", conflicts_with_all = ["width", "height"])]
+    pub resolution: Option<Resolution>,
+
+    /// Start in fullscreen; F11 or Alt+Enter toggles it at runtime
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "borderless"
+    )]
+    pub fullscreen: Option<FullscreenMode>,
+
+    /// Render at the window's native resolution instead of scaling; N toggles it at runtime
+    #[arg(long)]
+    pub native_resolution: bool,
+
+    /// How frames are synchronised with the display's refresh: on, off or adaptive
+    #[arg(long, value_name = "MODE", default_value = "on")]
+    pub vsync: Vsync,
+
+    /// Draw at most this many frames per second
+    #[arg(long, value_name = "FPS")]
+    pub max_fps: Option<f64>,
+
+    /// Lower the render resolution while frames can't keep up with this frame rate and raise
+    /// it again once they can (CPU backend only)
+    #[arg(
+        long,
+        value_name = "FPS",
+        num_args = 0..=1,
+        default_missing_value = "60"
+    )]
+    pub adaptive_resolution: Option<f64>,
+
+    /// Hold the window to 30 frames per second, half the threads and half the resolution to
+    /// save power: auto does so while the machine runs on battery, and restores full quality
+    /// once it's plugged in again
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(
+        long,
+        value_name = "MODE",
+        value_enum,
+        num_args = 0..=1,
+        default_value = "auto",
+        default_missing_value = "on"
+    )]
+    pub battery_saver: BatterySaver,
+
+    /// Open a fullscreen window on every monitor and spread one tunnel across them; the
+    /// resolution defaults to the whole wall's
+    #[arg(long, conflicts_with_all = ["fullscreen", "native_resolution"])]
+    pub span: bool,
+
+    /// Run as a screensaver: fullscreen without a cursor, a random palette, shape, texture and
+    /// effect on every launch, and exit at the first key press, click or mouse movement
+    #[arg(long, conflicts_with = "span")]
+    pub screensaver: bool,
+
+    /// Start with a random palette, shape, texture, effect and flight speed
+    #[arg(long)]
+    pub random: bool,
+
+    /// Attract mode: change the palette, texture, shape, effect or flight speed every this
+    /// many seconds, crossfading into the new look
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        num_args = 0..=1,
+        default_missing_value = "20"
+    )]
+    pub attract: Option<f64>,
+
+    /// Draw the tunnel as a live wallpaper behind the desktop icons, covering the primary
+    /// monitor
+    #[arg(long, conflicts_with_all = ["span", "fullscreen", "screensaver"])]
+    pub wallpaper: bool,
+
+    /// Window to draw the screensaver preview into, passed by Windows as `/p HWND`
+    #[cfg(windows)]
+    #[arg(long, value_name = "HWND", hide = true, requires = "screensaver")]
+    pub preview: Option<isize>,
+
+    /// Start with the tunnel's center following the mouse cursor; T toggles it at runtime
+    #[arg(long)]
+    pub mouse_look: bool,
+
+    /// How the texture wraps along the tunnel: repeat, mirror or clamp
+    #[arg(long, value_name = "MODE", default_value = "repeat")]
+    pub wrap_depth: AddressMode,
+
+    /// How the texture wraps around the tunnel: repeat, mirror or clamp
+    #[arg(long, value_name = "MODE", default_value = "repeat")]
+    pub wrap_angle: AddressMode,
+
+    /// TOML file splitting the texture into a grid of tiles, one for each band along the
+    /// tunnel, for gates and markers that repeat as it flies
+    #[arg(long, value_name = "PATH")]
+    pub atlas: Option<PathBuf>,
+
+    /// Share of the texture height (up to 0.5) to blend across the seam where it wraps around
+    /// the tunnel, for textures that don't tile vertically
+    #[arg(long, value_name = "SHARE", default_value_t = 0.0)]
+    pub seam_blend: f64,
+
+    /// Blend this much of every frame into the texture (up to 0.95) for recursive trails; E
+    /// toggles it at runtime
+    #[arg(long, value_name = "AMOUNT", default_value_t = 0.0)]
+    pub feedback: f64,
+
+    /// Depth fog curve: off, linear or exponential
+    #[arg(long, value_name = "CURVE", default_value = "off")]
+    pub fog: FogCurve,
+
+    /// Depth (in texture lengths) where the fog starts
+    #[arg(long, value_name = "DEPTH", default_value_t = Fog::default().start)]
+    pub fog_start: f64,
+
+    /// Depth (in texture lengths) beyond which everything is black
+    #[arg(long, value_name = "DEPTH", default_value_t = Fog::default().end)]
+    pub fog_end: f64,
+
+    /// Density of the exponential fog curve
+    #[arg(long, default_value_t = Fog::default().density)]
+    pub fog_density: f64,
+
+    /// Light the walls with a lamp circling the tunnel axis; Q toggles it at runtime
+    #[arg(long)]
+    pub light: bool,
+
+    /// Turns per second the light circles the axis at (negative goes the other way)
+    #[arg(long, value_name = "TURNS", default_value_t = Light::default().orbit)]
+    pub light_orbit: f64,
+
+    /// Depth (in texture lengths) of the light down the tunnel
+    #[arg(long, value_name = "DEPTH", default_value_t = 2.0)]
+    pub light_depth: f64,
+
+    /// Brightness of the walls facing the light, from 0 to 1
+    #[arg(long, value_name = "AMOUNT", default_value_t = 1.0)]
+    pub light_intensity: f64,
+
+    /// Pulse the light's intensity this many times per second (0 keeps it steady)
+    #[arg(long, value_name = "HZ", default_value_t = 0.0)]
+    pub light_pulse: f64,
+
+    /// Brightness of the walls facing away from the light, from 0 to 1
+    #[arg(long, value_name = "AMOUNT", default_value_t = Light::default().ambient)]
+    pub light_ambient: f64,
+
+    /// Distance (in texture lengths) along the tunnel at which the light has fallen to half
+    #[arg(long, value_name = "DEPTH", default_value_t = Light::default().falloff)]
+    pub light_falloff: f64,
+
+    /// Wind the tunnel along a wandering path: how far its far end drifts at most, as a share
+    /// of the frame height (0 keeps it straight)
+    #[arg(long, value_name = "AMOUNT", default_value_t = 0.0)]
+    pub curve: f64,
+
+    /// Bends of the curved tunnel per texture length
+    #[arg(long, value_name = "BENDS", default_value_t = Curve::default().frequency)]
+    pub curve_frequency: f64,
+
+    /// Swing the curve's drift back and forth this many times per second (0 keeps it steady)
+    #[arg(long, value_name = "HZ", default_value_t = 0.0)]
+    pub curve_sway: f64,
+
+    /// Bump map the lit walls this strongly, so the texture's bright parts stand out of them
+    #[arg(long, value_name = "STRENGTH", default_value_t = Light::default().bump)]
+    pub bump: f64,
+
+    /// Grayscale image to take the heights for `--bump` from instead of the texture
+    #[arg(long, value_name = "PATH")]
+    pub bump_map: Option<PathBuf>,
+
+    /// Text to write on the walls, where it scrolls past with the texture
+    #[arg(long, value_name = "TEXT")]
+    pub wall_text: Option<String>,
+
+    /// TrueType or OpenType font for `--wall-text` instead of the built-in one
+    #[arg(long, value_name = "PATH")]
+    pub wall_font: Option<PathBuf>,
+
+    /// Image (a PNG with transparency, say) to put on the walls, opposite `--wall-text`
+    #[arg(long, value_name = "PATH")]
+    pub wall_logo: Option<PathBuf>,
+
+    /// Where the wall text goes, as shares of the texture's length and of the way around
+    #[arg(long, value_name = "ALONG,AROUND", default_value = "0.5,0.25")]
+    pub wall_at: Placement,
+
+    /// Height of the wall text and logo as a share of the texture's
+    #[arg(long, value_name = "SHARE", default_value_t = 0.25)]
+    pub wall_size: f64,
+
+    /// Message to run across the frame on a sine wave; timelines can change, show and hide it
+    #[arg(long, value_name = "TEXT")]
+    pub scroller: Option<String>,
+
+    /// TrueType or OpenType font for `--scroller` and the timeline's messages
+    #[arg(long, value_name = "PATH")]
+    pub scroller_font: Option<PathBuf>,
+
+    /// Frame widths the scroller's message moves by per second
+    #[arg(long, value_name = "SPEED", default_value_t = 0.25)]
+    pub scroller_speed: f64,
+
+    /// How far the scroller's wave swings up and down, as a share of the frame height
+    #[arg(long, value_name = "SHARE", default_value_t = 0.1)]
+    pub scroller_amplitude: f64,
+
+    /// Comma-separated post-processing passes to enable, in the order they are applied
+    /// (bloom, scanlines, vignette, chromatic, pixelate, crt, motion-blur, dither, grade)
+    #[arg(long, value_name = "PASSES", value_delimiter = ',')]
+    pub postfx: Vec<PassKind>,
+
+    /// Share of the previous frames the motion blur keeps each frame, up to 0.95
+    #[arg(long, value_name = "TRAIL", default_value_t = DEFAULT_TRAIL)]
+    pub motion_blur: f32,
+
+    /// Values per colour channel the dither pass quantizes to; fewer than 256 give a retro,
+    /// reduced-palette look
+    #[arg(
+        long,
+        value_name = "LEVELS",
+        default_value_t = 256,
+        value_parser = clap::value_parser!(u32).range(2..=256)
+    )]
+    pub dither_levels: u32,
+
+    /// Grade the finished frame after all other passes with this gamma: above 1 lifts the
+    /// midtones, below 1 deepens them [default: 1 once the grade is asked for]
+    #[arg(long, value_name = "GAMMA")]
+    pub gamma: Option<f32>,
+
+    /// Brightness the grade adds, from -1 to 1 [default: 0 once the grade is asked for]
+    #[arg(long, value_name = "AMOUNT", allow_hyphen_values = true)]
+    pub brightness: Option<f32>,
+
+    /// Contrast of the grade: above 1 stretches the colors away from mid-gray [default: 1
+    /// once the grade is asked for]
+    #[arg(long, value_name = "AMOUNT")]
+    pub contrast: Option<f32>,
+
+    /// 3D LUT (a `.cube` file) to grade the finished frame with, after every other pass
+    #[arg(long, value_name = "PATH")]
+    pub color_lut: Option<PathBuf>,
+
+    /// Mix fog, the light, bilinear filtering, crossfades and blended effects in linear light
+    /// rather than sRGB values, which keeps the mixes from darkening (CPU only)
+    #[arg(long)]
+    pub gamma_correct: bool,
+
+    /// OGG or MP3 file to play; its beats drive the tunnel speed, radius and palette
+    #[cfg(feature = "audio")]
+    #[arg(long, value_name = "PATH")]
+    pub music: Option<PathBuf>,
+
+    /// React to the default audio input: bass speeds up the tunnel, treble shakes the camera
+    #[cfg(feature = "audio")]
+    #[arg(long)]
+    pub listen: bool,
+
+    /// MIDI mapping file: which control change messages drive speed, ratio, palette and fog
+    #[cfg(feature = "midi")]
+    #[arg(long, value_name = "PATH")]
+    pub midi: Option<PathBuf>,
+
+    /// Publish every frame as an NDI source with this name, e.g. for Resolume or OBS
+    #[cfg(feature = "ndi")]
+    #[arg(
+        long,
+        value_name = "NAME",
+        num_args = 0..=1,
+        default_missing_value = "tunnel-rs"
+    )]
+    pub ndi: Option<String>,
+
+    /// Folder of effect plugins (shared libraries) to load [default: plugins if present]
+    #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+    #[arg(long, value_name = "DIR")]
+    pub plugins: Option<PathBuf>,
+
+    /// Take commands like `palette ocean` from WebSocket clients on this port of localhost,
+    /// or on this address
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    #[arg(
+        long,
+        value_name = "[HOST:]PORT",
+        num_args = 0..=1,
+        default_missing_value = "9001"
+    )]
+    pub remote: Option<String>,
+
+    /// Parameters remote clients may change
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    #[arg(
+        long,
+        value_enum,
+        value_name = "PARAMS",
+        value_delimiter = ',',
+        default_value = "palette,speed,effect"
+    )]
+    pub remote_allow: Vec<RemoteParam>,
+
+    /// Commands a second each remote client may send
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    #[arg(long, value_name = "RATE", default_value_t = 2.0)]
+    pub remote_rate: f64,
+
+    /// Rhai script that sets the tunnel's parameters every frame, reloaded when it changes
+    #[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+    #[arg(long, value_name = "PATH")]
+    pub script: Option<PathBuf>,
+
+    /// Stream every frame to an LED matrix at this address, e.g. a WLED controller
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "HOST[:PORT]")]
+    pub led: Option<String>,
+
+    /// Columns and rows of LEDs in the matrix
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "COLUMNSxROWS", default_value = "32x32")]
+    pub led_size: GridSize,
+
+    /// Protocol to send LED frames in: ddp (WLED) or artnet
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "PROTOCOL", value_enum, default_value_t = LedProtocol::Ddp)]
+    pub led_protocol: LedProtocol,
+
+    /// Gamma the LED colors are corrected with; 1 sends them unchanged
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "GAMMA", default_value_t = 2.2)]
+    pub led_gamma: f64,
+
+    /// First Art-Net universe; larger matrices continue in the following ones
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "UNIVERSE", default_value_t = 0)]
+    pub led_universe: u16,
+
+    /// The matrix's rows run back and forth instead of all starting on the left
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long)]
+    pub led_serpentine: bool,
+
+    /// Don't draw the LED matrix's preview into the corner of the window
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long)]
+    pub no_led_preview: bool,
+
+    /// Where to play the tunnel: a window, or the terminal with colored half blocks or sixel
+    /// graphics
+    #[cfg(feature = "terminal")]
+    #[arg(long, value_name = "OUTPUT", value_enum, default_value_t = Output::Window)]
+    pub output: Output,
+
+    /// How texture values become colors: a palette (green, fire, ocean, plasma, grayscale), a
+    /// thermal heat map (heat) or a hue turning over time (hue-cycle)
+    #[arg(long, value_name = "NAME", value_parser = parse_color_map)]
+    pub color_map: Option<String>,
+
+    /// Keep recoloring the tunnel to suit its surroundings: time-of-day shifts from night blues
+    /// to daylight tones over the day, theme (with the `theme` feature) follows the system's
+    /// dark or light mode
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "MODE", value_enum)]
+    pub ambient: Option<Ambient>,
+
+    /// Effect drawn underneath the selected one (tunnel, plasma, rotozoomer, fire, starfield)
+    #[arg(long, value_name = "EFFECT")]
+    pub background: Option<String>,
+
+    /// How the selected effect is blended onto the background: alpha, add, multiply or screen
+    #[arg(long, value_name = "MODE", default_value = "alpha")]
+    pub blend: BlendMode,
+
+    /// Draw the tunnel once per eye and merge the views: anaglyph (red/cyan glasses) or
+    /// side-by-side (VR viewers, 3D displays)
+    #[arg(long, value_name = "MODE")]
+    pub stereo: Option<StereoMode>,
+
+    /// How far apart the eyes are for `--stereo`, as a share of the tunnel's radius
+    #[arg(long, value_name = "SHARE", default_value_t = 0.08)]
+    pub eye_separation: f64,
+
+    /// Split the window to compare the tunnel as it is on the left with the other texture
+    /// filter, the next palette or the GPU backend (needs `--backend gpu`) on the right
+    #[arg(long, value_name = "COMPARISON")]
+    pub split: Option<Comparison>,
+
+    /// Opacity of the selected effect over the background
+    #[arg(long, default_value_t = 0.5)]
+    pub opacity: f64,
+
+    /// Seconds to crossfade texture, palette and shape switches over (0 cuts immediately)
+    #[arg(long, value_name = "SECONDS", default_value_t = 1.0)]
+    pub crossfade: f64,
+
+    /// TOML script of scenes and keyframes to play; the demo exits when it ends unless it repeats
+    #[arg(long, value_name = "PATH")]
+    pub timeline: Option<PathBuf>,
+
+    /// Animation speed multiplier
+    #[arg(long, default_value_t = 1.0)]
+    pub speed: f64,
+
+    /// Spare viewers sensitive to motion and flashing: slow speeds, no strobing palette cycles
+    /// or beat jolts, and brightness that changes too slowly to flash
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_value = "auto",
+        default_missing_value = "on"
+    )]
+    pub reduced_motion: ReducedMotion,
+
+    /// Texture lengths per second to fly down the tunnel at; negative flies backwards
+    #[arg(long, value_name = "SPEED", default_value_t = Flight::default().travel, allow_negative_numbers = true)]
+    pub travel_speed: f64,
+
+    /// Turns per second the texture spins around the tunnel at
+    #[arg(long, value_name = "SPEED", default_value_t = Flight::default().spin, allow_negative_numbers = true)]
+    pub spin_speed: f64,
+
+    /// Turns to roll the view by around the tunnel's axis
+    #[arg(
+        long,
+        value_name = "TURNS",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    pub roll: f64,
+
+    /// Keep rolling the view by this many turns per second; Y toggles it at runtime
+    #[arg(
+        long,
+        value_name = "TURNS",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    pub auto_roll: f64,
+
+    /// Seconds flight speed changes take to mostly take effect (0 applies them at once)
+    #[arg(long, value_name = "SECONDS", default_value_t = Flight::default().easing)]
+    pub speed_easing: f64,
+
+    /// Advance time by exactly 1/record-fps per frame instead of following the wall clock
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Seed for procedural textures
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Exit after this many seconds of animation time
+    #[arg(long, value_name = "SECONDS")]
+    pub duration: Option<f64>,
+
+    /// Output format of recordings started with F9
+    #[arg(long, value_enum, default_value_t = RecordFormat::Png)]
+    pub record_format: RecordFormat,
+
+    /// Directory recordings are written to
+    #[arg(long, value_name = "DIR", default_value = "recordings")]
+    pub record_dir: PathBuf,
+
+    /// Frame rate of recorded GIF and MP4 files
+    #[arg(long, value_name = "FPS", default_value_t = 60)]
+    pub record_fps: u32,
+
+    /// Write every change to the settings while playing to this file, for --replay
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "PATH")]
+    pub record_performance: Option<PathBuf>,
+
+    /// Play back a performance written with --record-performance
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "PATH")]
+    pub replay: Option<PathBuf>,
+
+    /// Send the clock and settings to instances started with --sync-follow every frame, e.g.
+    /// at the network's broadcast address (port 7447 unless given)
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "HOST[:PORT]", conflicts_with = "sync_follow")]
+    pub sync_lead: Option<String>,
+
+    /// Follow the instance started with --sync-lead, listening on this port or address
+    #[cfg(not(target_arch = "wasm32"))]
+    #[arg(long, value_name = "[HOST:]PORT")]
+    pub sync_follow: Option<String>,
+
+    /// Directory F12 screenshots are written to
+    #[arg(long, value_name = "DIR", default_value = "screenshots")]
+    pub screenshot_dir: PathBuf,
+
+    /// Lookup table memory: high keeps every entry, medium half (exact) and low an eighth
+    #[arg(long, value_name = "QUALITY", default_value = "high")]
+    pub lut_quality: LutQuality,
+
+    /// Compute the lookup table from scratch instead of reusing one from the cache directory
+    #[arg(long)]
+    pub no_lut_cache: bool,
+
+    /// Where the tunnel is drawn: cpu fills the pixel buffer, gpu samples it in a shader
+    #[arg(long, value_name = "BACKEND", default_value = "cpu")]
+    pub backend: Backend,
+
+    /// Draw the tunnel through a float buffer in which its brightest colors go beyond SDR
+    /// white, shown as HDR on Windows and tone mapped elsewhere (GPU backend only)
+    #[arg(long)]
+    pub hdr: bool,
+
+    /// How many times SDR white the brightest colors reach with `--hdr`
+    #[arg(long, value_name = "TIMES", default_value_t = 3.0)]
+    pub hdr_peak: f32,
+
+    /// How `--hdr` brings colors into range on displays that can't show them: clamp, reinhard
+    /// or aces
+    #[arg(long, value_name = "CURVE", default_value = "reinhard")]
+    pub tone_map: ToneMap,
+
+    /// Use the one-pixel-at-a-time render loop even when built with the `simd` feature
+    #[arg(long)]
+    pub scalar: bool,
+
+    /// Threads to draw and build the lookup table with; one per core by default
+    #[arg(long, value_name = "N")]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Draw only some of the tunnel's pixels every frame on slow machines: off, rows
+    /// (alternate rows, half the work) or checkerboard (a quarter, over four frames)
+    #[arg(long, value_name = "MODE", default_value = "off")]
+    pub interlace: Interlace,
+
+    /// Most detailed messages to print: off, error, warn, info, debug or trace
+    #[arg(long, value_name = "LEVEL", default_value = "warn")]
+    pub log_level: LevelFilter,
+
+    /// Write the time spent in every stage of every frame to a Chrome trace file, to open in
+    /// chrome://tracing or Perfetto
+    #[cfg(feature = "chrome")]
+    #[arg(long, value_name = "PATH")]
+    pub trace_chrome: Option<PathBuf>,
+
+    /// Stream the time spent in every stage of every frame to a running Tracy profiler
+    #[cfg(feature = "tracy")]
+    #[arg(long)]
+    pub tracy: bool,
+
+    /// Ids of the options given on the command line, as opposed to left at their defaults.
+    #[arg(skip)]
+    given: Vec<String>,
+}
+
+fn parse_color_map(name: &str) -> Result<String, String> {
+    color_map::find(name).map(|_| name.to_string())
+}
+
+impl Args {
+    /// Remembers which options `matches`, that these were parsed from, got from the command
+    /// line, for `given`.
+    pub fn note_given(&mut self, matches: &ArgMatches) {
+        self.given = matches
+            .ids()
+            .filter(|id| matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+            .map(|id| id.to_string())
+            .collect();
+    }
+
+    /// Whether the option with this id, its field's name, was given on the command line,
+    /// for telling an option left at its default from one set to the same value.
+    pub fn given(&self, id: &str) -> bool {
+        self.given.iter().any(|given| given == id)
+    }
+
+    pub fn hdr(&self) -> Option<Hdr> {
+        (self.hdr && self.backend == Backend::Gpu).then(|| Hdr {
+            peak: self.hdr_peak.max(1.0),
+            tone_map: self.tone_map,
+        })
+    }
+
+    pub fn fog(&self) -> Fog {
+        Fog {
+            curve: self.fog,
+            start: self.fog_start,
+            end: self.fog_end,
+            density: self.fog_density,
+        }
+    }
+
+    /// The text and logo to put on the walls, the logo half a turn around from the text.
+    pub fn stamps(&self) -> Result<Vec<Stamp>, Box<dyn Error>> {
+        let Placement { along, around } = self.wall_at;
+        let placed = |stamp: Stamp, around: f64| Stamp {
+            along,
+            around,
+            size: self.wall_size.clamp(0.0, 1.0),
+            ..stamp
+        };
+        let mut stamps = Vec::new();
+        if let Some(text) = &self.wall_text {
+            let font = match &self.wall_font {
+                Some(path) => Some(
+                    fs::read(path)
+                        .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?,
+                ),
+                None => None,
+            };
+            stamps.push(placed(Stamp::text(text, font.as_deref())?, around));
+        }
+        if let Some(path) = &self.wall_logo {
+            let logo = Stamp::load(path)
+                .map_err(|err| format!("failed to load `{}`: {}", path.display(), err))?;
+            let around = match self.wall_text {
+                Some(_) => around + 0.5,
+                None => around,
+            };
+            stamps.push(placed(logo, around));
+        }
+        Ok(stamps)
+    }
+
+    /// The sine scroller: showing `--scroller`, or hidden until a timeline cues it when only
+    /// its font is given.
+    pub fn scroller(&self) -> Result<Option<Scroller>, Box<dyn Error>> {
+        let font: Option<Arc<[u8]>> = match &self.scroller_font {
+            Some(path) => Some(
+                fs::read(path)
+                    .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?
+                    .into(),
+            ),
+            None => None,
+        };
+        let mut scroller = match (&self.scroller, font) {
+            (Some(message), font) => Scroller::new(message, font)?,
+            (None, Some(font)) => Scroller::hidden(Some(font)),
+            (None, None) => return Ok(None),
+        };
+        scroller.speed = self.scroller_speed;
+        scroller.amplitude = self.scroller_amplitude.clamp(0.0, 0.5);
+        Ok(Some(scroller))
+    }
+
+    pub fn light(&self) -> Light {
+        let intensity = if self.light_pulse > 0.0 {
+            let half = self.light_intensity / 2.0;
+            Modulator::new(Shape::Sine, half, half, self.light_pulse)
+        } else {
+            Modulator::constant(self.light_intensity)
+        };
+        Light {
+            enabled: self.light,
+            orbit: self.light_orbit,
+            depth: Modulator::constant(self.light_depth),
+            intensity,
+            ambient: self.light_ambient,
+            falloff: self.light_falloff,
+            bump: self.bump,
+        }
+    }
+
+    pub fn curve(&self) -> Curve {
+        let amount = if self.curve_sway > 0.0 {
+            Modulator::new(Shape::Sine, 0.0, self.curve, self.curve_sway)
+        } else {
+            Modulator::constant(self.curve)
+        };
+        Curve {
+            amount,
+            frequency: self.curve_frequency,
+        }
+    }
+
+    pub fn stereo(&self) -> Option<Stereo> {
+        self.stereo
+            .map(|mode| Stereo::new(mode, self.eye_separation))
+    }
+
+    pub fn split(&self) -> Option<Split> {
+        self.split.map(Split::new)
+    }
+
+    pub fn postfx(&self) -> Result<PostFx, Box<dyn Error>> {
+        let mut postfx = PostFx::new(&self.postfx);
+        postfx.set_amount(PassKind::MotionBlur.name(), self.motion_blur);
+        if self.gamma.is_some() || self.brightness.is_some() || self.contrast.is_some() {
+            // A grade asked for goes last, after the passes named.
+            postfx.replace(Box::new(Grade::new(
+                self.gamma.unwrap_or(1.0),
+                self.brightness.unwrap_or(0.0),
+                self.contrast.unwrap_or(1.0),
+            )));
+            postfx.set_enabled(PassKind::Grade.name(), true);
+        }
+        postfx.replace(Box::new(Dither {
+            levels: self.dither_levels,
+        }));
+        if let Some(path) = &self.color_lut {
+            postfx.push(Box::new(ColorLut::load(path)?), true);
+        }
+        Ok(postfx)
+    }
+
+    /// `--plugins`, or the default folder if there is one.
+    #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+    pub fn plugins_dir(&self) -> Option<PathBuf> {
+        self.plugins.clone().or_else(|| {
+            let dir = PathBuf::from("plugins");
+            dir.is_dir().then_some(dir)
+        })
+    }
+
+    pub fn flight(&self) -> Flight {
+        Flight {
+            travel: self.travel_speed,
+            spin: self.spin_speed,
+            easing: self.speed_easing,
+        }
+    }
+
+    pub fn clock(&self) -> Clock {
+        let mut clock = if self.deterministic {
+            Clock::fixed(1.0 / self.record_fps.max(1) as f64)
+        } else {
+            Clock::default()
+        };
+        clock.set_speed(self.speed);
+        clock
+    }
+
+    pub fn resolution(&self, default: Resolution) -> Resolution {
+        self.resolution.unwrap_or(Resolution {
+            width: self.width.unwrap_or(default.width),
+            height: self.height.unwrap_or(default.height),
+        })
+    }
+}
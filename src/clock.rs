@@ -0,0 +1,93 @@
+use crate::time::Instant;
+
+const MAX_STEP: f64 = 0.25;
+
+pub struct Clock {
+    time: f64,
+    speed: f64,
+    paused: bool,
+    step: Option<f64>,
+    last_tick: Option<Instant>,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(1.0)
+    }
+}
+
+impl Clock {
+    pub fn new(speed: f64) -> Self {
+        Self {
+            time: 0.0,
+            speed,
+            paused: false,
+            step: None,
+            last_tick: None,
+        }
+    }
+
+    pub fn fixed(step: f64) -> Self {
+        Self {
+            step: Some(step),
+            ..Self::default()
+        }
+    }
+
+    pub fn is_fixed(&self) -> bool {
+        self.step.is_some()
+    }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    /// Jumps to `time`, e.g. to keep up with another instance's clock.
+    pub fn set_time(&mut self, time: f64) {
+        self.time = time;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    /// Moves on by `dt` seconds of wall-clock time, at most `MAX_STEP` so that a stall
+    /// doesn't make the animation jump.
+    pub fn advance(&mut self, dt: f64) {
+        if !self.paused {
+            self.time += dt.clamp(0.0, MAX_STEP) * self.speed;
+        }
+    }
+
+    /// Moves on by the fixed step if there is one, in full however long it is, or else by
+    /// the wall-clock time since the last tick.
+    pub fn tick(&mut self) {
+        if let Some(step) = self.step {
+            if !self.paused {
+                self.time += step.max(0.0) * self.speed;
+            }
+            return;
+        }
+        let now = Instant::now();
+        if let Some(last_tick) = self.last_tick {
+            self.advance(now.duration_since(last_tick).as_secs_f64());
+        }
+        self.last_tick = Some(now);
+    }
+}
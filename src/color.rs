@@ -0,0 +1,73 @@
+use std::sync::OnceLock;
+
+/// Bits of the linear light `to_linear` gives, enough for every sRGB value to get its own.
+pub const LINEAR_BITS: u32 = 12;
+const LINEAR_MAX: u32 = (1 << LINEAR_BITS) - 1;
+
+pub fn pack(rgba: [u8; 4]) -> u32 {
+    u32::from_le_bytes(rgba)
+}
+
+pub fn unpack(color: u32) -> [u8; 4] {
+    color.to_le_bytes()
+}
+
+pub fn opaque(rgba: [u8; 4]) -> u32 {
+    let [r, g, b, a] = rgba;
+    let over_black = |c: u8| (c as u16 * a as u16 / 0xff) as u8;
+    pack([over_black(r), over_black(g), over_black(b), 0xff])
+}
+
+/// The linear light of an sRGB channel value, from 0 to `(1 << LINEAR_BITS) - 1`.
+pub fn to_linear(value: u8) -> u32 {
+    tables().decode[value as usize] as u32
+}
+
+/// The sRGB channel value of linear light from 0 to `(1 << LINEAR_BITS) - 1`; more is white.
+pub fn from_linear(linear: u32) -> u8 {
+    tables().encode[linear.min(LINEAR_MAX) as usize]
+}
+
+/// Mixes `from` into `rgba`, which keeps `weight` out of 256, by their linear light.
+pub fn mix_linear(rgba: &mut [u8; 4], from: [u8; 4], weight: u32) {
+    for (channel, (value, from)) in rgba.iter_mut().zip(from).enumerate() {
+        *value = if channel < 3 {
+            let mixed = to_linear(*value) * weight + to_linear(from) * (256 - weight);
+            from_linear(mixed >> 8)
+        } else {
+            ((*value as u32 * weight + from as u32 * (256 - weight)) >> 8) as u8
+        };
+    }
+}
+
+struct Tables {
+    decode: [u16; 256],
+    encode: Vec<u8>,
+}
+
+fn tables() -> &'static Tables {
+    static TABLES: OnceLock<Tables> = OnceLock::new();
+    TABLES.get_or_init(|| {
+        let decode = std::array::from_fn(|value| {
+            let c = value as f64 / 255.0;
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            (linear * LINEAR_MAX as f64).round() as u16
+        });
+        let encode = (0..=LINEAR_MAX)
+            .map(|linear| {
+                let l = linear as f64 / LINEAR_MAX as f64;
+                let c = if l <= 0.0031308 {
+                    l * 12.92
+                } else {
+                    1.055 * l.powf(1.0 / 2.4) - 0.055
+                };
+                (c * 255.0).round() as u8
+            })
+            .collect();
+        Tables { decode, encode }
+    })
+}
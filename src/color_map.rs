@@ -0,0 +1,177 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::palette::PaletteKind;
+
+/// Turns the values of indexed textures into colors. The world maps all 256 values into a
+/// table whenever the mapper changes, and again every frame while it is `animated`, so the
+/// renderer only ever looks colors up.
+pub trait ColorMapper: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// The color of texel value `value`, `time` seconds into the animation.
+    fn color(&self, value: u8, time: f64) -> [u8; 3];
+
+    /// Whether the colors change with the time.
+    fn animated(&self) -> bool {
+        false
+    }
+}
+
+/// Looks the value up in one of the built-in palettes; `green` is the original monochrome
+/// look.
+impl ColorMapper for PaletteKind {
+    fn name(&self) -> &str {
+        PaletteKind::name(*self)
+    }
+
+    fn color(&self, value: u8, _time: f64) -> [u8; 3] {
+        self.rgb(value)
+    }
+}
+
+/// The colors of a thermal camera: black through blue, magenta, red and yellow to white.
+pub struct Heat;
+
+impl Heat {
+    const STOPS: [[u8; 3]; 6] = [
+        [0, 0, 0],
+        [32, 0, 140],
+        [190, 0, 150],
+        [255, 60, 0],
+        [255, 210, 0],
+        [255, 255, 255],
+    ];
+}
+
+impl ColorMapper for Heat {
+    fn name(&self) -> &str {
+        "heat"
+    }
+
+    fn color(&self, value: u8, _time: f64) -> [u8; 3] {
+        let position = value as f64 / 255.0 * (Self::STOPS.len() - 1) as f64;
+        let i = (position as usize).min(Self::STOPS.len() - 2);
+        let t = position - i as f64;
+        let (from, to) = (Self::STOPS[i], Self::STOPS[i + 1]);
+        [0, 1, 2].map(|c| (from[c] as f64 + (to[c] as f64 - from[c] as f64) * t).round() as u8)
+    }
+}
+
+/// Keeps the value as the brightness and turns the hue around the color wheel over time,
+/// spread by `spread` turns across the values.
+pub struct HueCycle {
+    /// Turns of the color wheel per second.
+    pub speed: f64,
+    pub spread: f64,
+    pub saturation: f64,
+}
+
+impl Default for HueCycle {
+    fn default() -> Self {
+        Self {
+            speed: 0.1,
+            spread: 0.5,
+            saturation: 0.8,
+        }
+    }
+}
+
+impl ColorMapper for HueCycle {
+    fn name(&self) -> &str {
+        "hue-cycle"
+    }
+
+    fn color(&self, value: u8, time: f64) -> [u8; 3] {
+        let value = value as f64 / 255.0;
+        let hue = (time * self.speed + value * self.spread).rem_euclid(1.0);
+        hsv(hue, self.saturation, value)
+    }
+
+    fn animated(&self) -> bool {
+        self.speed != 0.0
+    }
+}
+
+/// Dim, cool blues at night warming up to sunlit oranges and creams at noon, for ambient use:
+/// `daylight` goes from 0 at midnight to 1 at noon.
+pub struct Daylight {
+    pub daylight: f64,
+}
+
+impl Daylight {
+    /// The light at `hour` (0 to 24) of the local day, rising and setting smoothly.
+    pub fn at_hour(hour: f64) -> Self {
+        Self {
+            daylight: 0.5 - 0.5 * (hour / 24.0 * 2.0 * PI).cos(),
+        }
+    }
+}
+
+impl ColorMapper for Daylight {
+    fn name(&self) -> &str {
+        "daylight"
+    }
+
+    fn color(&self, value: u8, _time: f64) -> [u8; 3] {
+        let v = value as f64 / 255.0;
+        let night = [0.05 + 0.2 * v * v, 0.1 + 0.4 * v, 0.25 + 0.6 * v];
+        let day = [
+            0.15 + 0.85 * v,
+            0.1 + 0.75 * v * v.sqrt(),
+            0.05 + 0.55 * v * v,
+        ];
+        let t = self.daylight.clamp(0.0, 1.0);
+        [0, 1, 2].map(|c| ((night[c] + (day[c] - night[c]) * t) * 255.0).round() as u8)
+    }
+}
+
+/// Converts a hue in turns, a saturation and a value to RGB.
+fn hsv(hue: f64, saturation: f64, value: f64) -> [u8; 3] {
+    let sector = hue * 6.0;
+    let f = sector.fract();
+    let (p, q, t) = (
+        value * (1.0 - saturation),
+        value * (1.0 - saturation * f),
+        value * (1.0 - saturation * (1.0 - f)),
+    );
+    let [r, g, b] = match sector as u32 % 6 {
+        0 => [value, t, p],
+        1 => [q, value, p],
+        2 => [p, value, t],
+        3 => [p, q, value],
+        4 => [t, p, value],
+        _ => [value, p, q],
+    };
+    [r, g, b].map(|c| (c * 255.0).round() as u8)
+}
+
+/// Every palette, then the heat map and the hue cycle.
+pub fn builtin() -> Vec<Arc<dyn ColorMapper>> {
+    let mut mappers: Vec<Arc<dyn ColorMapper>> = PaletteKind::ALL
+        .into_iter()
+        .map(|kind| Arc::new(kind) as Arc<dyn ColorMapper>)
+        .collect();
+    mappers.push(Arc::new(Heat));
+    mappers.push(Arc::new(HueCycle::default()));
+    mappers
+}
+
+/// The built-in mapper called `name`.
+pub fn find(name: &str) -> Result<Arc<dyn ColorMapper>, String> {
+    let mappers = builtin();
+    let names: Vec<_> = mappers
+        .iter()
+        .map(|mapper| mapper.name().to_string())
+        .collect();
+    mappers
+        .into_iter()
+        .find(|mapper| mapper.name() == name)
+        .ok_or_else(|| {
+            format!(
+                "unknown color map `{}` (expected one of {})",
+                name,
+                names.join(", ")
+            )
+        })
+}
@@ -0,0 +1,205 @@
+use std::str::FromStr;
+
+use rayon::prelude::*;
+use tracing::debug_span;
+
+use crate::color::{self, LINEAR_BITS};
+use crate::effect::Effect;
+use crate::renderer::RenderStats;
+use crate::time::Instant;
+use crate::world::World;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Alpha,
+    Add,
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 4] = [
+        BlendMode::Alpha,
+        BlendMode::Add,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendMode::Alpha => "alpha",
+            BlendMode::Add => "add",
+            BlendMode::Multiply => "multiply",
+            BlendMode::Screen => "screen",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&mode| mode == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    /// Like `blend`, on linear light from 0 to `(1 << LINEAR_BITS) - 1`.
+    fn blend_linear(self, base: u32, layer: u32) -> u32 {
+        let max = (1 << LINEAR_BITS) - 1;
+        match self {
+            BlendMode::Alpha => layer,
+            BlendMode::Add => (base + layer).min(max),
+            BlendMode::Multiply => base * layer / max,
+            BlendMode::Screen => max - (max - base) * (max - layer) / max,
+        }
+    }
+
+    fn blend(self, base: u8, layer: u8) -> u32 {
+        let (base, layer) = (base as u32, layer as u32);
+        match self {
+            BlendMode::Alpha => layer,
+            BlendMode::Add => (base + layer).min(0xff),
+            BlendMode::Multiply => base * layer / 0xff,
+            BlendMode::Screen => 0xff - (0xff - base) * (0xff - layer) / 0xff,
+        }
+    }
+}
+
+impl FromStr for BlendMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|mode| mode.name()).collect();
+                format!(
+                    "unknown blend mode `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// One effect in the stack and how it is blended onto the stages below it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stage {
+    /// Index into the effect list passed to `Compositor::draw`.
+    pub effect: usize,
+    pub mode: BlendMode,
+    pub opacity: f64,
+}
+
+impl Stage {
+    pub fn new(effect: usize) -> Self {
+        Self {
+            effect,
+            mode: BlendMode::Alpha,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// Draws a stack of effects bottom to top. The first stage goes straight into the frame;
+/// every further stage is drawn into its own intermediate buffer and blended on top.
+#[derive(Default)]
+pub struct Compositor {
+    pub stages: Vec<Stage>,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Compositor {
+    pub fn new(effect: usize) -> Self {
+        Self {
+            stages: vec![Stage::new(effect)],
+            buffers: Vec::new(),
+        }
+    }
+
+    /// The topmost stage, which effect switches apply to.
+    pub fn top(&mut self) -> &mut Stage {
+        if self.stages.is_empty() {
+            self.stages.push(Stage::new(0));
+        }
+        self.stages.last_mut().expect("compositor has a stage")
+    }
+
+    /// Switches the top stage to the effect a timeline scene asks for once it starts; `scene`
+    /// keeps the scene followed last.
+    pub fn follow_scene(
+        &mut self,
+        world: &World,
+        scene: &mut Option<usize>,
+        effects: &[Box<dyn Effect>],
+    ) {
+        if world.scene() == *scene {
+            return;
+        }
+        *scene = world.scene();
+        let name = world
+            .timeline()
+            .zip(*scene)
+            .and_then(|(timeline, index)| timeline.scenes()[index].effect.as_deref());
+        if let Some(i) = name.and_then(|name| effects.iter().position(|e| e.name() == name)) {
+            self.top().effect = i;
+        }
+    }
+
+    pub fn update(&self, world: &World, effects: &mut [Box<dyn Effect>]) {
+        for stage in &self.stages {
+            if let Some(effect) = effects.get_mut(stage.effect) {
+                effect.update(world);
+            }
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        world: &World,
+        effects: &mut [Box<dyn Effect>],
+        frame: &mut [u8],
+    ) -> RenderStats {
+        let start = Instant::now();
+        let mut stats = RenderStats::default();
+        self.buffers.resize_with(self.stages.len(), Vec::new);
+
+        for (i, stage) in self.stages.iter().enumerate() {
+            let Some(effect) = effects.get_mut(stage.effect) else {
+                continue;
+            };
+            let _span = debug_span!("effect", name = effect.name()).entered();
+            if i == 0 {
+                stats = effect.draw(world, frame);
+                continue;
+            }
+            let buffer = &mut self.buffers[i];
+            buffer.resize(frame.len(), 0);
+            let layer = effect.draw(world, buffer);
+            stats.bands.extend(layer.bands);
+            composite(frame, buffer, stage, world.gamma_correct);
+        }
+
+        stats.total = start.elapsed();
+        stats
+    }
+}
+
+/// Blends `layer` onto `base` with the stage's mode and opacity; `linear` blends their linear
+/// light.
+fn composite(base: &mut [u8], layer: &[u8], stage: &Stage, linear: bool) {
+    let mode = stage.mode;
+    let opacity = (stage.opacity.clamp(0.0, 1.0) * 256.0) as u32;
+    base.par_chunks_exact_mut(4)
+        .zip(layer.par_chunks_exact(4))
+        .for_each(|(base, layer)| {
+            let weight = opacity * layer[3] as u32 / 0xff;
+            for (base, &layer) in base[..3].iter_mut().zip(&layer[..3]) {
+                *base = if linear {
+                    let (under, over) = (color::to_linear(*base), color::to_linear(layer));
+                    let blended = mode.blend_linear(under, over);
+                    color::from_linear((under * (256 - weight) + blended * weight) >> 8)
+                } else {
+                    let blended = mode.blend(*base, layer);
+                    ((*base as u32 * (256 - weight) + blended * weight) >> 8) as u8
+                };
+            }
+        });
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+use tunnel::{ModulationGraph, PaletteKind};
+
+use crate::cli::{Args, Resolution};
+use crate::keymap::{Action, Binding, Keymap};
+
+pub const DEFAULT_PATH: &str = "tunnel.toml";
+
+/// Settings read from `tunnel.toml`; everything except `threads` is reapplied whenever the
+/// file changes.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default, deserialize_with = "parse")]
+    pub resolution: Option<Resolution>,
+    /// Image file, relative to the config file.
+    pub texture: Option<PathBuf>,
+    #[serde(default, deserialize_with = "parse")]
+    pub palette: Option<PaletteKind>,
+    pub speed: Option<f64>,
+    pub ratio: Option<f64>,
+    /// Worker threads for rendering; only read at startup.
+    pub threads: Option<usize>,
+    /// The default keys with the actions of the `[keys]` table rebound.
+    #[serde(default, deserialize_with = "keymap")]
+    pub keys: Option<Keymap>,
+    /// Sources wired into parameters, evaluated every frame.
+    pub modulation: Option<ModulationGraph>,
+}
+
+fn parse<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// The keys of an action in the `[keys]` table: one or a list.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+fn keymap<'de, D>(deserializer: D) -> Result<Option<Keymap>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut keymap = Keymap::default();
+    for (name, keys) in HashMap::<String, Keys>::deserialize(deserializer)? {
+        let action: Action = name.parse().map_err(serde::de::Error::custom)?;
+        let keys = match keys {
+            Keys::One(key) => vec![key],
+            Keys::Many(keys) => keys,
+        };
+        let bindings = keys
+            .iter()
+            .map(|key| key.parse())
+            .collect::<Result<Vec<Binding>, _>>()
+            .map_err(serde::de::Error::custom)?;
+        keymap.bind(action, &bindings);
+    }
+    Ok(Some(keymap))
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut config: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        if let (Some(texture), Some(dir)) = (&config.texture, path.parent()) {
+            config.texture = Some(dir.join(texture));
+        }
+        Ok(config)
+    }
+
+    /// The settings with those given on the command line in `args` taking their place, as
+    /// flags take precedence over the file. A `--width` or `--height` alone replaces just that
+    /// side of the resolution.
+    pub fn under(mut self, args: &Args) -> Self {
+        self.resolution = self
+            .resolution
+            .map(|resolution| args.resolution(resolution));
+        if args.texture.is_some() {
+            self.texture = None;
+        }
+        if args.given("speed") {
+            self.speed = None;
+        }
+        if args.threads.is_some() {
+            self.threads = None;
+        }
+        self
+    }
+}
@@ -0,0 +1,104 @@
+use std::f64::consts::PI;
+
+use crate::modulator::Modulator;
+
+/// Ratio of the vertical to the horizontal sway, irrational so that the path never repeats.
+const VERTICAL_RATE: f64 = 0.618;
+
+/// A tunnel that winds left, right, up and down along a wandering path instead of running
+/// straight ahead: the farther down the tunnel, the farther its centre drifts from where the
+/// near end is, and the bends come towards the camera as it flies through them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Curve {
+    /// How far the far end of the tunnel drifts at most, as a share of the frame height; 0
+    /// keeps the tunnel straight.
+    pub amount: Modulator,
+    /// Bends per texture length along the tunnel.
+    pub frequency: f64,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self {
+            amount: Modulator::constant(0.0),
+            frequency: 0.25,
+        }
+    }
+}
+
+impl Curve {
+    /// The point of the path `position` texture lengths along the tunnel, each coordinate
+    /// from -1 to 1.
+    pub fn path(&self, position: f64) -> (f64, f64) {
+        let phase = 2.0 * PI * self.frequency * position;
+        (phase.sin(), (phase * VERTICAL_RATE).sin())
+    }
+
+    /// Where the centre of the tunnel `depth` texture lengths ahead appears, as seen from
+    /// `position`, each coordinate from -2 to 2. The depth is squeezed so that the farthest
+    /// visible point is a quarter bend ahead: the far end settles where the path heads next
+    /// instead of swirling through every bend beyond it.
+    pub fn drift(&self, position: f64, depth: f64) -> (f64, f64) {
+        let horizon = 0.25 / self.frequency.abs().max(f64::EPSILON);
+        let ahead = horizon * depth / (depth + horizon);
+        let (near, far) = (self.path(position), self.path(position + ahead));
+        (far.0 - near.0, far.1 - near.1)
+    }
+}
+
+/// Depth in texels the first band of a `Bend` ends at, and how much deeper each next one
+/// ends.
+const FIRST_BAND: f64 = 4.0;
+const BAND_RATIO: f64 = 1.15;
+
+/// The curve in one frame, as bands of depth in which it moves the tunnel's centre by the
+/// same number of LUT cells. They grow with the depth, like rings of the tunnel seen in
+/// perspective.
+pub(crate) struct Bend {
+    /// The offset of the centre along each band, nearest first.
+    offsets: Vec<(isize, isize)>,
+    /// The band each texel of depth is in.
+    bands: Vec<u16>,
+}
+
+impl Bend {
+    /// The bands for `len` texels of depth down from `travel`, with `texels_per_unit` to a
+    /// texture length and `cells` LUT cells to the frame height.
+    pub(crate) fn new(
+        curve: &Curve,
+        amount: f64,
+        travel: f64,
+        cells: f64,
+        texels_per_unit: usize,
+        len: usize,
+    ) -> Self {
+        let scale = amount * cells / 2.0;
+        let mut offsets = Vec::new();
+        let mut bands = Vec::with_capacity(len);
+        let mut start = 0.0;
+        while bands.len() < len {
+            let end = (start * BAND_RATIO).max(FIRST_BAND);
+            let (x, y) = curve.drift(travel, (start + end) / 2.0 / texels_per_unit as f64);
+            offsets.push(((x * scale).round() as isize, (y * scale).round() as isize));
+            while bands.len() < len && (bands.len() as f64) < end {
+                bands.push(offsets.len() as u16 - 1);
+            }
+            start = end;
+        }
+        Self { offsets, bands }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// The band `depth` texels down the tunnel is in.
+    pub(crate) fn band(&self, depth: usize) -> usize {
+        self.bands[depth.min(self.bands.len() - 1)] as usize
+    }
+
+    /// How far the centre moves along `band`.
+    pub(crate) fn offset(&self, band: usize) -> (isize, isize) {
+        self.offsets[band]
+    }
+}
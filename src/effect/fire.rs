@@ -0,0 +1,98 @@
+use crate::color;
+use crate::palette::{Palette, PaletteKind};
+use crate::renderer::RenderStats;
+use crate::texture::hash;
+use crate::world::World;
+
+use super::{render_rows, Effect};
+
+const CELL: usize = 4;
+const STEPS_PER_SECOND: f64 = 60.0;
+const MAX_STEPS_PER_FRAME: u64 = 4;
+
+/// Classic cellular fire: a randomly lit bottom row whose heat is averaged upwards and cools
+/// as it rises. Simulated on a grid of `CELL`-pixel cells and stepped at a fixed rate.
+pub struct Fire {
+    palette: Palette,
+    width: usize,
+    height: usize,
+    heat: Vec<u8>,
+    steps: u64,
+}
+
+impl Default for Fire {
+    fn default() -> Self {
+        Self {
+            palette: PaletteKind::Fire.palette(),
+            width: 0,
+            height: 0,
+            heat: Vec::new(),
+            steps: 0,
+        }
+    }
+}
+
+impl Fire {
+    fn step(&mut self) {
+        let (width, height) = (self.width, self.height);
+        let bottom = (height - 1) * width;
+        for x in 0..width {
+            let spark = hash(self.steps, x as u32, 0).is_multiple_of(3);
+            self.heat[bottom + x] = if spark { 0xff } else { 0 };
+        }
+        for y in 0..height - 1 {
+            let below = (y + 1) * width;
+            let further = (y + 2).min(height - 1) * width;
+            for x in 0..width {
+                let sum = self.heat[below + (x + width - 1) % width] as u32
+                    + self.heat[below + x] as u32
+                    + self.heat[below + (x + 1) % width] as u32
+                    + self.heat[further + x] as u32;
+                self.heat[y * width + x] = (sum * 32 / 129) as u8;
+            }
+        }
+        self.steps += 1;
+    }
+}
+
+impl Effect for Fire {
+    fn name(&self) -> &str {
+        "fire"
+    }
+
+    fn update(&mut self, world: &World) {
+        let width = (world.params.width as usize).div_ceil(CELL);
+        let height = (world.params.height as usize).div_ceil(CELL);
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.heat = vec![0; width * height];
+        }
+
+        let target = (world.clock.time() * STEPS_PER_SECOND) as u64;
+        self.steps = self.steps.max(target.saturating_sub(MAX_STEPS_PER_FRAME));
+        while self.steps < target {
+            self.step();
+        }
+    }
+
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+        // The simulation keeps running at the full size, so it doesn't restart when the frame
+        // size changes.
+        let width = world.frame_size().0 as usize;
+        let (step_x, step_y) = world.frame_step();
+        let cells = self.width;
+        let heat = &self.heat;
+        let palette = &self.palette;
+
+        render_rows(frame, width, |y, row| {
+            let cell_y = (y as f64 * step_y) as usize / CELL;
+            let cell_row = &heat[(cell_y * cells).min(heat.len())..];
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let cell_x = (x as f64 * step_x) as usize / CELL;
+                let value = cell_row.get(cell_x).copied().unwrap_or(0);
+                pixel.copy_from_slice(&color::unpack(palette.color(value)));
+            }
+        })
+    }
+}
@@ -0,0 +1,59 @@
+use rayon::prelude::*;
+
+use crate::renderer::{Filter, RenderStats, Renderer};
+use crate::time::Instant;
+use crate::world::World;
+
+mod fire;
+mod plasma;
+pub mod plugin;
+mod rotozoomer;
+mod starfield;
+
+pub use fire::Fire;
+pub use plasma::Plasma;
+pub use rotozoomer::Rotozoomer;
+pub use starfield::Starfield;
+
+/// A full-screen effect drawn from the shared world state (clock, camera, palette, texture).
+pub trait Effect: Send {
+    fn name(&self) -> &str;
+
+    /// Advances effect-specific state; called once per frame after `World::update`.
+    fn update(&mut self, _world: &World) {}
+
+    /// Draws into a tightly packed RGBA frame of `world.frame_size()`.
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats;
+
+    fn filter(&self) -> Filter {
+        Filter::Nearest
+    }
+
+    fn set_filter(&mut self, _filter: Filter) {}
+}
+
+/// The tunnel first, followed by plasma, rotozoomer, fire and starfield.
+pub fn builtin() -> Vec<Box<dyn Effect>> {
+    vec![
+        Box::new(Renderer::default()),
+        Box::new(Plasma),
+        Box::new(Rotozoomer::default()),
+        Box::new(Fire::default()),
+        Box::new(Starfield::new(0)),
+    ]
+}
+
+pub(crate) fn render_rows<F>(frame: &mut [u8], width: usize, render_row: F) -> RenderStats
+where
+    F: Fn(usize, &mut [u8]) + Sync,
+{
+    let start = Instant::now();
+    frame
+        .par_chunks_exact_mut(width * 4)
+        .enumerate()
+        .for_each(|(y, row)| render_row(y, row));
+    RenderStats {
+        total: start.elapsed(),
+        bands: Vec::new(),
+    }
+}
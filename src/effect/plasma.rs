@@ -0,0 +1,41 @@
+use crate::color;
+use crate::renderer::RenderStats;
+use crate::world::World;
+
+use super::{render_rows, Effect};
+
+/// Sum-of-sines plasma colored through the world's palette.
+pub struct Plasma;
+
+impl Effect for Plasma {
+    fn name(&self) -> &str {
+        "plasma"
+    }
+
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+        let (width, height) = world.frame_size();
+        let width = width as usize;
+        let scale = 8.0 / height as f64;
+        let time = world.clock.time();
+        let center = (
+            4.0 + 3.0 * (time / 3.0).sin(),
+            4.0 + 3.0 * (time / 2.0).cos(),
+        );
+        let palette = &world.layer.palette;
+        let shift = world.palette_shift;
+
+        render_rows(frame, width, |y, row| {
+            let v = y as f64 * scale;
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let u = x as f64 * scale;
+                let value = (u + time).sin()
+                    + ((v + time) / 2.0).sin()
+                    + ((u + v + time) / 2.0).sin()
+                    + ((u - center.0).hypot(v - center.1) + time).sin();
+                let index = ((value + 4.0) * 32.0) as u8;
+                let rgba = color::unpack(palette.color(index.wrapping_add(shift)));
+                pixel.copy_from_slice(&rgba);
+            }
+        })
+    }
+}
@@ -0,0 +1,175 @@
+//! The interface effects in shared libraries (`.so`, `.dll`, `.dylib`) implement to be loaded
+//! as plugins. A plugin exports an unmangled `extern "C" fn tunnel_plugin() -> PluginApi`;
+//! see `examples/plugin_rings.rs`. Loading them needs the `plugins` feature.
+
+use std::ffi::{c_char, c_void};
+
+/// Version of `PluginApi` and `PluginFrame`; plugins built against another one are skipped.
+pub const API_VERSION: u32 = 1;
+/// Name of the function every plugin exports.
+pub const ENTRY_POINT: &str = "tunnel_plugin";
+
+/// A plugin's effect: its name and the functions that make, draw and free its state.
+#[repr(C)]
+pub struct PluginApi {
+    pub api_version: u32,
+    /// NUL-terminated UTF-8, valid for as long as the library is loaded.
+    pub name: *const c_char,
+    /// Returns the effect's state, which is passed to the other functions and may be
+    /// moved between threads.
+    pub create: extern "C" fn() -> *mut c_void,
+    pub draw: extern "C" fn(state: *mut c_void, frame: *const PluginFrame),
+    pub destroy: extern "C" fn(state: *mut c_void),
+}
+
+/// What a plugin draws into and from, valid for the duration of a `draw` call.
+#[repr(C)]
+pub struct PluginFrame {
+    /// `width * height` tightly packed RGBA pixels.
+    pub pixels: *mut u8,
+    pub width: u32,
+    pub height: u32,
+    /// Animation time in seconds.
+    pub time: f64,
+    /// Texture lengths flown down the tunnel and turns the texture has spun around it.
+    pub travel: f64,
+    pub spin: f64,
+    /// The 256 colors of the palette as RGBA, already cycled.
+    pub palette: *const [u8; 4],
+}
+
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+pub use loader::load;
+
+#[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+#[allow(unsafe_code)]
+mod loader {
+    use std::ffi::{c_void, CStr};
+    use std::path::Path;
+
+    use libloading::Library;
+    use tracing::{info, warn};
+
+    use super::{PluginApi, PluginFrame, API_VERSION, ENTRY_POINT};
+    use crate::color;
+    use crate::effect::Effect;
+    use crate::renderer::RenderStats;
+    use crate::time::Instant;
+    use crate::world::World;
+
+    /// An effect drawn by a plugin.
+    struct Plugin {
+        name: String,
+        state: *mut c_void,
+        draw: extern "C" fn(*mut c_void, *const PluginFrame),
+        destroy: extern "C" fn(*mut c_void),
+        palette: Vec<[u8; 4]>,
+        // Declared last so that the state is freed before the library is unloaded.
+        _library: Library,
+    }
+
+    // Plugins promise that their state may move between threads.
+    unsafe impl Send for Plugin {}
+
+    impl Effect for Plugin {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+            let start = Instant::now();
+            let (width, height) = world.frame_size();
+            let palette = &world.layer.palette;
+            self.palette.clear();
+            self.palette.extend(
+                (0..=255u8)
+                    .map(|i| color::unpack(palette.color(i.wrapping_add(world.palette_shift)))),
+            );
+            let input = PluginFrame {
+                pixels: frame.as_mut_ptr(),
+                width,
+                height,
+                time: world.clock.time(),
+                travel: world.travel,
+                spin: world.spin,
+                palette: self.palette.as_ptr(),
+            };
+            (self.draw)(self.state, &input);
+            RenderStats {
+                total: start.elapsed(),
+                bands: Vec::new(),
+            }
+        }
+    }
+
+    impl Drop for Plugin {
+        fn drop(&mut self) {
+            (self.destroy)(self.state);
+        }
+    }
+
+    /// Loads every plugin in `dir`, in the order of their file names. Those that can't be
+    /// loaded are skipped with a warning.
+    pub fn load(dir: &Path) -> Vec<Box<dyn Effect>> {
+        let mut paths: Vec<_> = match dir.read_dir() {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION)
+                })
+                .collect(),
+            Err(err) => {
+                warn!("Failed to read the plugins in {}: {}", dir.display(), err);
+                return Vec::new();
+            }
+        };
+        paths.sort();
+        paths
+            .into_iter()
+            .filter_map(|path| match open(&path) {
+                Ok(plugin) => {
+                    info!("Loaded the {} effect from {}", plugin.name, path.display());
+                    Some(Box::new(plugin) as Box<dyn Effect>)
+                }
+                Err(err) => {
+                    warn!("Failed to load plugin {}: {}", path.display(), err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn open(path: &Path) -> Result<Plugin, String> {
+        // Loading runs the library's initialisers and trusts its entry point to have the
+        // right signature; there is no way to check either.
+        let library = unsafe { Library::new(path) }.map_err(|err| err.to_string())?;
+        let api = unsafe {
+            let entry = library
+                .get::<extern "C" fn() -> PluginApi>(ENTRY_POINT.as_bytes())
+                .map_err(|err| err.to_string())?;
+            entry()
+        };
+        if api.api_version != API_VERSION {
+            return Err(format!(
+                "built for plugin interface {}, not {}",
+                api.api_version, API_VERSION
+            ));
+        }
+        if api.name.is_null() {
+            return Err("the plugin has no name".to_string());
+        }
+        let name = unsafe { CStr::from_ptr(api.name) }
+            .to_str()
+            .map_err(|_| "the plugin's name isn't UTF-8".to_string())?
+            .to_string();
+        Ok(Plugin {
+            name,
+            state: (api.create)(),
+            draw: api.draw,
+            destroy: api.destroy,
+            palette: Vec::with_capacity(256),
+            _library: library,
+        })
+    }
+}
@@ -0,0 +1,84 @@
+use crate::color;
+use crate::renderer::{self, Filter, RenderStats, FRACTION_BITS};
+use crate::texture::AddressMode;
+use crate::world::World;
+
+use super::{render_rows, Effect};
+
+/// The plane is tiled with the texture regardless of the world's address modes.
+const REPEAT: (AddressMode, AddressMode) = (AddressMode::Repeat, AddressMode::Repeat);
+
+/// Spins and zooms the world's texture across the screen.
+pub struct Rotozoomer {
+    filter: Filter,
+}
+
+impl Default for Rotozoomer {
+    fn default() -> Self {
+        Self {
+            filter: Filter::Nearest,
+        }
+    }
+}
+
+impl Effect for Rotozoomer {
+    fn name(&self) -> &str {
+        "rotozoomer"
+    }
+
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+        let (width, height) = world.frame_size();
+        let (width, height) = (width as usize, height as usize);
+        let time = world.clock.time();
+        let (look_x, look_y) = world.camera.look();
+
+        let layer = &world.layer;
+        let shift = world.palette_shift;
+        let period = (
+            (layer.texture.width << FRACTION_BITS) as f64,
+            (layer.texture.height << FRACTION_BITS) as f64,
+        );
+        let angle = time * 0.5;
+        let zoom = (1.0 + 0.75 * (time * 0.7).sin()) * (1 << FRACTION_BITS) as f64;
+        // Smaller frames cover the same area with fewer, larger steps.
+        let zoom_step = zoom * world.frame_step().0;
+        let step = (angle.cos() * zoom_step, angle.sin() * zoom_step);
+        let origin = (
+            look_x * zoom / 4.0 + time * 64.0 * (1 << FRACTION_BITS) as f64,
+            look_y * zoom / 4.0,
+        );
+        let filter = self.filter;
+        let linear = world.gamma_correct;
+
+        render_rows(frame, width, |y, row| {
+            let dy = y as f64 - height as f64 / 2.0;
+            let dx = -(width as f64) / 2.0;
+            let mut u = origin.0 + dx * step.0 - dy * step.1;
+            let mut v = origin.1 + dx * step.1 + dy * step.0;
+            for pixel in row.chunks_exact_mut(4) {
+                let fu = u.rem_euclid(period.0) as u64;
+                let fv = v.rem_euclid(period.1) as u64;
+                let color = match filter {
+                    Filter::Nearest => renderer::texel(
+                        layer,
+                        shift,
+                        (fu >> FRACTION_BITS) as usize % layer.texture.width,
+                        (fv >> FRACTION_BITS) as usize % layer.texture.height,
+                    ),
+                    Filter::Bilinear => renderer::bilinear(layer, shift, fu, fv, REPEAT, linear),
+                };
+                pixel.copy_from_slice(&color::unpack(color));
+                u += step.0;
+                v += step.1;
+            }
+        })
+    }
+
+    fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+}
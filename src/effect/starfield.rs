@@ -0,0 +1,69 @@
+use crate::renderer::RenderStats;
+use crate::texture::hash;
+use crate::time::Instant;
+use crate::world::World;
+
+use super::{render_rows, Effect};
+
+const STARS: u32 = 2048;
+const NEAR: f64 = 0.02;
+const DEPTH_PER_TRAVEL: f64 = 0.25;
+
+/// Stars flying towards the viewer at the tunnel's travel speed, steered by the camera.
+pub struct Starfield {
+    stars: Vec<(f64, f64, f64)>,
+}
+
+impl Starfield {
+    pub fn new(seed: u64) -> Self {
+        let unit = |x: u32, y: u32| (hash(seed, x, y) >> 11) as f64 / (1u64 << 53) as f64;
+        let stars = (0..STARS)
+            .map(|i| (2.0 * unit(i, 0) - 1.0, 2.0 * unit(i, 1) - 1.0, unit(i, 2)))
+            .collect();
+        Self { stars }
+    }
+}
+
+impl Effect for Starfield {
+    fn name(&self) -> &str {
+        "starfield"
+    }
+
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+        let (width, height) = world.frame_size();
+        let (width, height) = (width as usize, height as usize);
+        let (look_x, look_y) = world.camera.look();
+        let (step_x, step_y) = world.frame_step();
+        let (look_x, look_y) = (look_x / step_x, look_y / step_y);
+
+        let mut stats = render_rows(frame, width, |_, row| {
+            for pixel in row.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[0, 0, 0, 0xff]);
+            }
+        });
+
+        let start = Instant::now();
+        let center = (width as f64 / 2.0 - look_x, height as f64 / 2.0 - look_y);
+        let spread = height as f64 / 2.0;
+        for &(x, y, z) in &self.stars {
+            let z = (z - world.travel * DEPTH_PER_TRAVEL)
+                .rem_euclid(1.0)
+                .max(NEAR);
+            let sx = center.0 + x / z * spread;
+            let sy = center.1 + y / z * spread;
+            if sx < 0.0 || sy < 0.0 || sx >= width as f64 || sy >= height as f64 {
+                continue;
+            }
+            let brightness = ((1.0 - z) * (1.0 - z) * 255.0) as u8;
+            let size = if z < 0.3 { 2 } else { 1 };
+            for py in sy as usize..(sy as usize + size).min(height) {
+                for px in sx as usize..(sx as usize + size).min(width) {
+                    let i = (py * width + px) * 4;
+                    frame[i..i + 4].copy_from_slice(&[brightness, brightness, brightness, 0xff]);
+                }
+            }
+        }
+        stats.total += start.elapsed();
+        stats
+    }
+}
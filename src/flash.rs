@@ -0,0 +1,99 @@
+use rayon::prelude::*;
+
+use crate::color::{self, LINEAR_BITS};
+
+/// Most relative luminance any part of the frame may gain or lose per second. A flash is a
+/// swing of a tenth of the luminance and back; at this rate no more than two and a half fit
+/// in a second, under the three that photosensitivity guidelines allow.
+const MAX_CHANGE: f64 = 0.5;
+/// Parts of the frame along each axis whose brightness is watched, each a sixteenth of the
+/// frame and so smaller than the quarter of the view a flash has to cover to count.
+const GRID: usize = 4;
+/// Seconds taken to have passed between frames drawn while the clock stands still.
+const PAUSED_STEP: f64 = 1.0 / 60.0;
+
+/// Holds back how fast the brightness of the frame changes, by blending each frame into the
+/// last just so far that no part of it brightens or darkens faster than `MAX_CHANGE`.
+#[derive(Default)]
+pub(crate) struct FlashLimiter {
+    /// The last frame shown, and the clock's time then.
+    previous: Vec<u8>,
+    time: f64,
+}
+
+impl FlashLimiter {
+    pub(crate) fn apply(&mut self, frame: &mut [u8], width: usize, height: usize, time: f64) {
+        let step = (time - self.time).abs();
+        self.time = time;
+        // A new size (or the first frame) has nothing to blend into.
+        if self.previous.len() != frame.len() || width == 0 || height == 0 {
+            self.previous = frame.to_vec();
+            return;
+        }
+
+        let limit = MAX_CHANGE * if step > 0.0 { step } else { PAUSED_STEP };
+        let before = luminance(&self.previous, width, height);
+        let after = luminance(frame, width, height);
+        let keep = before
+            .iter()
+            .zip(&after)
+            .map(|(before, after)| {
+                let change = (after - before).abs();
+                if change > limit {
+                    limit / change
+                } else {
+                    1.0
+                }
+            })
+            .fold(1.0, f64::min);
+        if keep < 1.0 {
+            let weight = (keep * 256.0) as u32;
+            frame
+                .par_chunks_exact_mut(4)
+                .zip(self.previous.par_chunks_exact(4))
+                .for_each(|(pixel, previous)| {
+                    let mut rgba = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                    color::mix_linear(&mut rgba, previous.try_into().unwrap(), weight);
+                    pixel.copy_from_slice(&rgba);
+                });
+        }
+        self.previous.copy_from_slice(frame);
+    }
+}
+
+/// The mean relative luminance of every part of the frame, from 0 to 1.
+fn luminance(frame: &[u8], width: usize, height: usize) -> [f64; GRID * GRID] {
+    let row_sums: Vec<[u64; GRID]> = frame
+        .par_chunks_exact(width * 4)
+        .map(|row| {
+            let mut sums = [0; GRID];
+            for (x, pixel) in row.chunks_exact(4).enumerate() {
+                // Rec. 709 weights out of 10000.
+                let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(color::to_linear);
+                sums[x * GRID / width] += (r * 2126 + g * 7152 + b * 722) as u64;
+            }
+            sums
+        })
+        .collect();
+    let mut sums = [0u64; GRID * GRID];
+    for (y, row) in row_sums.iter().enumerate() {
+        for (x, sum) in row.iter().enumerate() {
+            sums[y * GRID / height * GRID + x] += sum;
+        }
+    }
+    let (columns, rows) = (spans(width), spans(height));
+    let max = ((1u64 << LINEAR_BITS) - 1) * 10_000;
+    std::array::from_fn(|i| {
+        let pixels = (columns[i % GRID] * rows[i / GRID]).max(1);
+        sums[i] as f64 / (max * pixels as u64) as f64
+    })
+}
+
+/// How many of `len` columns or rows fall into each part.
+fn spans(len: usize) -> [usize; GRID] {
+    let mut spans = [0; GRID];
+    for i in 0..len {
+        spans[i * GRID / len] += 1;
+    }
+    spans
+}
@@ -0,0 +1,32 @@
+/// How fast the camera flies down the tunnel and spins around its axis. Changes ease in over
+/// `easing` seconds rather than jumping, so speeding up, stopping and turning around glide.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Flight {
+    /// Texture lengths per second; negative flies backwards.
+    pub travel: f64,
+    /// Turns of the texture per second.
+    pub spin: f64,
+    /// Seconds a change takes to mostly (about two thirds of the way) take effect; 0 applies
+    /// it at once.
+    pub easing: f64,
+}
+
+impl Default for Flight {
+    fn default() -> Self {
+        Self {
+            travel: 0.5,
+            spin: 0.1,
+            easing: 0.5,
+        }
+    }
+}
+
+impl Flight {
+    /// `speed` moved `dt` seconds of easing towards `target`.
+    pub(crate) fn ease(&self, speed: f64, target: f64, dt: f64) -> f64 {
+        if self.easing <= 0.0 {
+            return target;
+        }
+        speed + (target - speed) * (1.0 - (-dt.abs() / self.easing).exp())
+    }
+}
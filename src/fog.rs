@@ -0,0 +1,92 @@
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FogCurve {
+    Off,
+    Linear,
+    Exponential,
+}
+
+impl FogCurve {
+    pub const ALL: [FogCurve; 3] = [FogCurve::Off, FogCurve::Linear, FogCurve::Exponential];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FogCurve::Off => "off",
+            FogCurve::Linear => "linear",
+            FogCurve::Exponential => "exponential",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            FogCurve::Off => FogCurve::Linear,
+            FogCurve::Linear => FogCurve::Exponential,
+            FogCurve::Exponential => FogCurve::Off,
+        }
+    }
+}
+
+impl FromStr for FogCurve {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(FogCurve::Off),
+            "linear" => Ok(FogCurve::Linear),
+            "exponential" => Ok(FogCurve::Exponential),
+            _ => Err(format!(
+                "unknown fog curve `{}` (expected off, linear or exponential)",
+                s
+            )),
+        }
+    }
+}
+
+/// Depth attenuation; `start` and `end` are measured in texture lengths along the tunnel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub curve: FogCurve,
+    pub start: f64,
+    pub end: f64,
+    pub density: f64,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            curve: FogCurve::Off,
+            start: 1.0,
+            end: 8.0,
+            density: 0.5,
+        }
+    }
+}
+
+impl Fog {
+    pub fn brightness(&self, depth: f64) -> f64 {
+        if depth <= self.start {
+            return 1.0;
+        }
+        if depth >= self.end {
+            return match self.curve {
+                FogCurve::Off => 1.0,
+                _ => 0.0,
+            };
+        }
+        match self.curve {
+            FogCurve::Off => 1.0,
+            FogCurve::Linear => 1.0 - (depth - self.start) / (self.end - self.start),
+            FogCurve::Exponential => (-self.density * (depth - self.start)).exp(),
+        }
+    }
+
+    pub(crate) fn table(&self, texels_per_unit: usize, len: usize) -> Vec<u16> {
+        (0..len)
+            .map(|texels| {
+                let depth = texels as f64 / texels_per_unit as f64;
+                (self.brightness(depth).clamp(0.0, 1.0) * 256.0) as u16
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,250 @@
+//! Full-screen output straight to a Linux framebuffer device (fbdev), for boards like the
+//! Raspberry Pi that run without X or Wayland. The device's geometry comes from sysfs and
+//! frames are written to it with plain file IO, so nothing beyond `std` is needed; DRM/KMS
+//! would take ioctls this crate doesn't make.
+
+use std::error::Error;
+
+use crate::cli::FramebufferArgs;
+
+#[cfg(target_os = "linux")]
+pub fn play(framebuffer: &FramebufferArgs) -> Result<(), Box<dyn Error>> {
+    use tunnel::{effect, Filter, Renderer, World};
+
+    use crate::pacing::FrameLimiter;
+    use crate::performance;
+    #[cfg(feature = "scripting")]
+    use crate::script::Script;
+    use crate::sync::{SyncFollower, SyncLeader};
+    use crate::{apply_config, configure, pipeline, setup, Setup};
+
+    let args = &framebuffer.args;
+    let Setup {
+        config,
+        params,
+        mut animation,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+        ..
+    } = setup(args)?;
+    let duration = args.duration.or_else(|| {
+        timeline
+            .as_ref()
+            .filter(|timeline| !timeline.repeats())
+            .map(|timeline| timeline.duration())
+    });
+    let mut device = linux::Device::open(&framebuffer.device)?;
+    let mut world = World::with_texture(params, texture);
+    configure(&mut world, args, timeline, height_map, atlas, stamps);
+    apply_config(&mut world, &config);
+
+    let mut renderer = Renderer::default();
+    renderer.set_simd(!args.scalar);
+    renderer.set_interlace(args.interlace);
+    let mut effects = effect::builtin();
+    effects[0] = Box::new(renderer);
+    let mut pipeline = pipeline(args, effects)?;
+    #[cfg(feature = "scripting")]
+    let mut script = args.script.as_deref().map(Script::load).transpose()?;
+    let mut leader = args
+        .sync_lead
+        .as_deref()
+        .map(SyncLeader::start)
+        .transpose()?;
+    let mut follower = args
+        .sync_follow
+        .as_deref()
+        .map(|address| SyncFollower::listen(address, args.seed))
+        .transpose()?;
+    let mut filter = Filter::Nearest;
+
+    let (width, height) = world.frame_size();
+    println!(
+        "Drawing {}x{} frames on {} ({}x{}, {} bits per pixel)",
+        width,
+        height,
+        framebuffer.device.display(),
+        device.width,
+        device.height,
+        device.bits_per_pixel
+    );
+    let mut frame = vec![0u8; width as usize * height as usize * 4];
+    let mut limiter = FrameLimiter::new(framebuffer.fps);
+    while !duration.is_some_and(|duration| world.clock().time() >= duration) {
+        limiter.wait();
+        if let Some(texture) = animation
+            .as_mut()
+            .and_then(|animation| animation.frame_at(world.clock().time()))
+        {
+            world.set_texture_frame(texture);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut script {
+            script.update(&mut world, &Default::default());
+        }
+        if let Some(follower) = &mut follower {
+            follower.update(&mut world, &mut pipeline, &mut filter);
+        }
+        world.update();
+        if let Some(leader) = &mut leader {
+            // Nothing switches the texture here, so followers keep the one they started with.
+            leader.send(
+                &world,
+                performance::capture(&world, &pipeline, filter, None),
+            );
+        }
+        pipeline.update(&world);
+        pipeline.draw(&mut world, &mut frame);
+        device.present(&frame, width as usize, height as usize)?;
+        world.feed_back(&frame, width, height);
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn play(_framebuffer: &FramebufferArgs) -> Result<(), Box<dyn Error>> {
+    Err("framebuffer output is only available on Linux".into())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::error::Error;
+    use std::fs::{self, File, OpenOptions};
+    use std::os::unix::fs::FileExt;
+    use std::path::Path;
+
+    use rayon::prelude::*;
+
+    /// An open framebuffer and what its memory looks like.
+    pub struct Device {
+        file: File,
+        pub width: usize,
+        pub height: usize,
+        pub bits_per_pixel: usize,
+        /// Bytes from the start of one row to the next.
+        stride: usize,
+        buffer: Vec<u8>,
+        /// The frame's column and row every pixel of the screen shows, for the frame size
+        /// they were worked out for.
+        columns: Vec<usize>,
+        rows: Vec<usize>,
+        scaled_from: (usize, usize),
+    }
+
+    impl Device {
+        pub fn open(path: &Path) -> Result<Self, Box<dyn Error>> {
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| format!("{} is not a framebuffer device", path.display()))?;
+            let sysfs = Path::new("/sys/class/graphics").join(name);
+            let read = |attribute: &str| {
+                fs::read_to_string(sysfs.join(attribute))
+                    .map(|value| value.trim().to_string())
+                    .map_err(|err| {
+                        format!("failed to read {}/{}: {}", sysfs.display(), attribute, err)
+                    })
+            };
+            let number = |attribute: &str| -> Result<usize, Box<dyn Error>> {
+                let value = read(attribute)?;
+                value
+                    .parse()
+                    .map_err(|_| format!("unexpected {} `{}` in sysfs", attribute, value).into())
+            };
+
+            // The virtual size may be taller than the screen to allow for panning, so the
+            // visible size is taken from the current mode where there is one, like
+            // `U:1920x1080p-0`.
+            let mode = read("modes").ok().and_then(|modes| {
+                let mode = modes.lines().next()?.split_once(':')?.1.to_string();
+                let (width, rest) = mode.split_once('x')?;
+                let height: String = rest.chars().take_while(char::is_ascii_digit).collect();
+                Some((width.parse().ok()?, height.parse().ok()?))
+            });
+            let (width, height) = match mode {
+                Some(size) => size,
+                None => {
+                    let size = read("virtual_size")?;
+                    size.split_once(',')
+                        .and_then(|(width, height)| {
+                            Some((width.parse().ok()?, height.parse().ok()?))
+                        })
+                        .ok_or_else(|| format!("unexpected virtual_size `{}` in sysfs", size))?
+                }
+            };
+            let bits_per_pixel = number("bits_per_pixel")?;
+            if ![16, 24, 32].contains(&bits_per_pixel) {
+                return Err(format!(
+                    "{} uses {} bits per pixel; only 16, 24 and 32 are supported",
+                    path.display(),
+                    bits_per_pixel
+                )
+                .into());
+            }
+            let stride = number("stride")?;
+            if width == 0 || height == 0 || stride < width * bits_per_pixel / 8 {
+                return Err(format!("{} reports an unusable geometry", path.display()).into());
+            }
+            let file = OpenOptions::new()
+                .write(true)
+                .open(path)
+                .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+            Ok(Self {
+                file,
+                width,
+                height,
+                bits_per_pixel,
+                stride,
+                buffer: vec![0; stride * height],
+                columns: Vec::new(),
+                rows: Vec::new(),
+                scaled_from: (0, 0),
+            })
+        }
+
+        /// Stretches the RGBA `frame` over the whole screen and writes it out in the
+        /// framebuffer's pixel format: XRGB8888, RGB888 or RGB565, in little-endian order.
+        pub fn present(
+            &mut self,
+            frame: &[u8],
+            frame_width: usize,
+            frame_height: usize,
+        ) -> Result<(), Box<dyn Error>> {
+            if self.scaled_from != (frame_width, frame_height) {
+                self.columns = (0..self.width)
+                    .map(|x| x * frame_width / self.width)
+                    .collect();
+                self.rows = (0..self.height)
+                    .map(|y| y * frame_height / self.height)
+                    .collect();
+                self.scaled_from = (frame_width, frame_height);
+            }
+            let bytes = self.bits_per_pixel / 8;
+            let columns = &self.columns;
+            self.buffer
+                .par_chunks_exact_mut(self.stride)
+                .zip(&self.rows)
+                .for_each(|(row, &y)| {
+                    let source = &frame[y * frame_width * 4..(y + 1) * frame_width * 4];
+                    for (pixel, &x) in row.chunks_exact_mut(bytes).zip(columns) {
+                        let [r, g, b, _] = [0, 1, 2, 3].map(|i| source[x * 4 + i]);
+                        match bytes {
+                            2 => {
+                                let rgb565 = (u16::from(r) >> 3) << 11
+                                    | (u16::from(g) >> 2) << 5
+                                    | u16::from(b) >> 3;
+                                pixel.copy_from_slice(&rgb565.to_le_bytes());
+                            }
+                            3 => pixel.copy_from_slice(&[b, g, r]),
+                            _ => pixel.copy_from_slice(&[b, g, r, 0xff]),
+                        }
+                    }
+                });
+            self.file.write_all_at(&self.buffer, 0)?;
+            Ok(())
+        }
+    }
+}
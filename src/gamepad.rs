@@ -0,0 +1,77 @@
+use std::error::Error;
+
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use tunnel::{Modulator, World};
+
+use crate::{MAX_RATIO, MIN_RATIO, STEER_SPEED};
+
+/// Extra travel and spin with a stick pushed all the way, in texture lengths per second.
+const DRIVE_SPEED: f64 = 2.0;
+/// How fast a fully pulled trigger scales the ratio, as a factor of e per second.
+const ZOOM_SPEED: f64 = 1.0;
+
+/// Steers the world with whichever gamepad was used last: the left stick looks around, the
+/// right stick drives travel (up and down) and spin (left and right), the left and right
+/// triggers shrink and grow the ratio, and the bottom and right face buttons step forwards and
+/// backwards through the palettes.
+pub struct Gamepad {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+}
+
+impl Gamepad {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            gilrs: Gilrs::new().map_err(|err| err.to_string())?,
+            active: None,
+        })
+    }
+
+    /// Handles the buttons pressed since the last call and applies the sticks and triggers
+    /// as they are held now, over `dt` seconds.
+    pub fn update(&mut self, world: &mut World, dt: f64) {
+        while let Some(event) = self.gilrs.next_event() {
+            self.active = Some(event.id);
+            if let EventType::ButtonPressed(button, _) = event.event {
+                match button {
+                    Button::South => world.set_palette(world.palette_kind().next()),
+                    Button::East => world.set_palette(world.palette_kind().previous()),
+                    _ => {}
+                }
+            }
+        }
+
+        let Some(pad) = self.active.and_then(|id| self.gilrs.connected_gamepad(id)) else {
+            world.set_drive(0.0, 0.0);
+            return;
+        };
+        let trigger = |button| {
+            pad.button_data(button)
+                .map_or(0.0, |data| data.value() as f64)
+        };
+        let axis = |axis| pad.value(axis) as f64;
+
+        // Stick up is positive, screen up is negative.
+        let (dx, dy) = (axis(Axis::LeftStickX), -axis(Axis::LeftStickY));
+        if dx != 0.0 || dy != 0.0 {
+            world
+                .camera_mut()
+                .steer(dx * STEER_SPEED * dt, dy * STEER_SPEED * dt);
+        }
+
+        world.set_drive(
+            axis(Axis::RightStickY) * DRIVE_SPEED,
+            axis(Axis::RightStickX) * DRIVE_SPEED,
+        );
+
+        let zoom = trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2);
+        if zoom != 0.0 {
+            let modulator = *world.ratio_modulator();
+            let base = modulator.base * (zoom * ZOOM_SPEED * dt).exp();
+            world.set_ratio_modulator(Modulator {
+                base: base.clamp(MIN_RATIO, MAX_RATIO),
+                ..modulator
+            });
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub trait TunnelGeometry: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Maps an offset from the tunnel axis to a radius in pixels and an angle in radians.
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64);
+
+    /// Whether `project(-dx, -dy)` is `project(dx, dy)` turned by half a revolution, which lets
+    /// the lookup table store only half of its rows.
+    fn point_symmetric(&self) -> bool {
+        false
+    }
+
+    /// Names the mapping in lookup table cache files: geometries with the same key must project
+    /// alike, so it includes any parameters. Without one, the tables aren't cached.
+    fn cache_key(&self) -> Option<String> {
+        None
+    }
+
+    /// GLSL defining `vec2 project(vec2 d)`, which does what `project` does, for exporting
+    /// shaders. Without it, tunnels of this shape can't be exported.
+    fn glsl(&self) -> Option<String> {
+        None
+    }
+}
+
+pub fn builtin() -> Vec<Arc<dyn TunnelGeometry>> {
+    vec![
+        Arc::new(Circle),
+        Arc::new(Square),
+        Arc::new(Ellipse { aspect: 1.6 }),
+        Arc::new(Star {
+            points: 5,
+            depth: 0.3,
+        }),
+        Arc::new(Twisted { twist: 150.0 }),
+    ]
+}
+
+pub struct Circle;
+
+impl TunnelGeometry for Circle {
+    fn name(&self) -> &str {
+        "circle"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(self.name().to_string())
+    }
+
+    fn point_symmetric(&self) -> bool {
+        true
+    }
+
+    fn glsl(&self) -> Option<String> {
+        let source = "vec2 project(vec2 d) {
+    return vec2(length(d), atan(d.y, d.x));
+}
+";
+        Some(source.to_string())
+    }
+
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64) {
+        (dx.hypot(dy), dy.atan2(dx))
+    }
+}
+
+pub struct Square;
+
+impl TunnelGeometry for Square {
+    fn name(&self) -> &str {
+        "square"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(self.name().to_string())
+    }
+
+    fn point_symmetric(&self) -> bool {
+        true
+    }
+
+    fn glsl(&self) -> Option<String> {
+        let source = "vec2 project(vec2 d) {
+    return vec2(max(abs(d.x), abs(d.y)), atan(d.y, d.x));
+}
+";
+        Some(source.to_string())
+    }
+
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64) {
+        (dx.abs().max(dy.abs()), dy.atan2(dx))
+    }
+}
+
+pub struct Ellipse {
+    pub aspect: f64,
+}
+
+impl TunnelGeometry for Ellipse {
+    fn name(&self) -> &str {
+        "ellipse"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(format!("ellipse-{}", self.aspect))
+    }
+
+    fn point_symmetric(&self) -> bool {
+        true
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "vec2 project(vec2 d) {{
+    return vec2(length(vec2(d.x / {:?}, d.y)), atan(d.y, d.x));
+}}
+",
+            self.aspect
+        ))
+    }
+
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64) {
+        ((dx / self.aspect).hypot(dy), dy.atan2(dx))
+    }
+}
+
+pub struct Star {
+    pub points: u32,
+    pub depth: f64,
+}
+
+impl TunnelGeometry for Star {
+    fn name(&self) -> &str {
+        "star"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(format!("star-{}-{}", self.points, self.depth))
+    }
+
+    /// Only an even number of spikes maps onto itself after half a turn.
+    fn point_symmetric(&self) -> bool {
+        self.points.is_multiple_of(2)
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "vec2 project(vec2 d) {{
+    float angle = atan(d.y, d.x);
+    return vec2(length(d) / (1.0 + {:?} * cos({:?} * angle)), angle);
+}}
+",
+            self.depth, self.points as f64
+        ))
+    }
+
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let angle = dy.atan2(dx);
+        let spike = 1.0 + self.depth * (self.points as f64 * angle).cos();
+        (dx.hypot(dy) / spike, angle)
+    }
+}
+
+pub struct Twisted {
+    pub twist: f64,
+}
+
+impl TunnelGeometry for Twisted {
+    fn name(&self) -> &str {
+        "twisted"
+    }
+
+    fn cache_key(&self) -> Option<String> {
+        Some(format!("twisted-{}", self.twist))
+    }
+
+    fn point_symmetric(&self) -> bool {
+        true
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "vec2 project(vec2 d) {{
+    float radius = length(d);
+    float angle = atan(d.y, d.x) + {:?} / max(radius, 1.0);
+    return vec2(radius, mod(angle + PI, 2.0 * PI) - PI);
+}}
+",
+            self.twist
+        ))
+    }
+
+    fn project(&self, dx: f64, dy: f64) -> (f64, f64) {
+        let radius = dx.hypot(dy);
+        let angle = dy.atan2(dx) + self.twist / radius.max(1.0);
+        (radius, (angle + PI).rem_euclid(2.0 * PI) - PI)
+    }
+}
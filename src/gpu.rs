@@ -0,0 +1,668 @@
+use std::num::NonZeroU32;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use pixels::wgpu;
+
+use crate::fog::FogCurve;
+use crate::lut::Lut;
+use crate::renderer::{lut_origin, lut_scale, texture_shift, Filter};
+use crate::texture::{AddressMode, Texture, TextureFormat};
+use crate::world::{Layer, World};
+
+/// `gpu.wgsl` splits the scale into 16-bit halves; beyond this the products overflow.
+const MAX_SCALE: u64 = (256 << 16) - 1;
+const LOCALS_SIZE: u64 = 144;
+const HDR_LOCALS_SIZE: u64 = 16;
+/// The float buffer's format, and the target format HDR displays take.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// How colors beyond SDR white are brought into range for displays that can't show them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Cut off at white.
+    Clamp,
+    /// Roll off towards white, leaving dark colors as they are.
+    Reinhard,
+    /// The filmic curve of ACES, which also darkens and saturates.
+    Aces,
+}
+
+impl ToneMap {
+    pub const ALL: [ToneMap; 3] = [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::Aces];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ToneMap::Clamp => "clamp",
+            ToneMap::Reinhard => "reinhard",
+            ToneMap::Aces => "aces",
+        }
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|tone_map| tone_map.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown tone map `{}` (expected clamp, reinhard or aces)",
+                    s
+                )
+            })
+    }
+}
+
+/// Drawing through a float buffer in which the brightest colors reach `peak` times SDR
+/// white, shown as they are on `HDR_FORMAT` targets and with `tone_map` on others.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hdr {
+    pub peak: f32,
+    pub tone_map: ToneMap,
+}
+
+/// Draws the tunnel straight onto the window surface with a fragment shader that samples the
+/// LUT and texture on the GPU, instead of filling the pixel buffer on the CPU.
+pub struct GpuRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    locals: wgpu::Buffer,
+    srgb: bool,
+    filter: Filter,
+    /// The share of the target's width left of the drawing, which keeps what is there.
+    split: Option<f64>,
+    layers: Vec<GpuLayer>,
+    hdr: Option<HdrPass>,
+}
+
+/// The float buffer the tunnel is drawn into for HDR output, and the pass showing it.
+struct HdrPass {
+    settings: Hdr,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    locals: wgpu::Buffer,
+    /// Whether the target takes values beyond 1.
+    target_hdr: bool,
+    target_srgb: bool,
+    /// Covering the target up to the far corner of the clip rectangle, with that size.
+    buffer: Option<(wgpu::TextureView, (u32, u32))>,
+}
+
+/// A layer's LUT, texels and palette as GPU textures.
+struct GpuLayer {
+    lut: Arc<Lut>,
+    texture: Arc<Texture>,
+    lut_view: wgpu::TextureView,
+    texel_view: wgpu::TextureView,
+    palette: wgpu::Texture,
+    palette_view: wgpu::TextureView,
+}
+
+impl GpuRenderer {
+    /// `format` is the target's format, e.g. `Pixels::render_texture_format`.
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        Self::with_hdr(device, format, None)
+    }
+
+    /// Like `new`, drawing through a float buffer with `hdr`.
+    pub fn with_hdr(device: &wgpu::Device, format: wgpu::TextureFormat, hdr: Option<Hdr>) -> Self {
+        let hdr = hdr.map(|settings| HdrPass::new(device, format, settings));
+        // The float buffer holds linear colors.
+        let (format, srgb) = match hdr {
+            Some(_) => (HDR_FORMAT, true),
+            None => (format, format.describe().srgb),
+        };
+        let shader = device.create_shader_module(&wgpu::include_wgsl!("gpu.wgsl"));
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Uint,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tunnel_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(LOCALS_SIZE),
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                texture_entry(3),
+                texture_entry(4),
+                texture_entry(5),
+                texture_entry(6),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tunnel_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tunnel_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+        let locals = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("tunnel_locals"),
+            size: LOCALS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            locals,
+            srgb,
+            filter: Filter::Nearest,
+            split: None,
+            layers: Vec::new(),
+            hdr,
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn hdr(&self) -> Option<Hdr> {
+        self.hdr.as_ref().map(|hdr| hdr.settings)
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn split(&self) -> Option<f64> {
+        self.split
+    }
+
+    /// Draws only right of `split`, a share of the width, over what the target already
+    /// shows, for `Comparison::Backend`; `None` draws all of it.
+    pub fn set_split(&mut self, split: Option<f64>) {
+        self.split = split;
+    }
+
+    /// Whether the world's LUT is built, stored in full and fits into the device's textures,
+    /// the light, gamma-correct mixing, reduced motion and the atlas are off and the tunnel
+    /// straight and upright; otherwise the CPU has to draw.
+    pub fn supports(&self, device: &wgpu::Device, world: &World) -> bool {
+        if world.pending_lut.is_some()
+            || world.light.enabled
+            || world.atlas.is_some()
+            || world.gamma_correct
+            || world.reduced_motion
+            || world.curve_amount != 0.0
+            || world.view_roll != 0.0
+        {
+            return false;
+        }
+        let max = device.limits().max_texture_dimension_2d as usize;
+        match world.layer.lut.full() {
+            Some((width, height, _)) => width <= max && height <= max,
+            None => false,
+        }
+    }
+
+    /// Records the draw into `encoder`, scaled into `clip_rect` (x, y, width, height) of the
+    /// target; with `Pixels::render_with` that is the scaling renderer's clip rectangle.
+    pub fn render(
+        &mut self,
+        world: &World,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        clip_rect: (u32, u32, u32, u32),
+    ) {
+        let current = &world.layer;
+        let (from, weight) = match &world.fade {
+            Some(fade) => (&fade.from, (fade.mix.clamp(0.0, 1.0) * 256.0) as u32),
+            None => (current, 256),
+        };
+        self.layers
+            .retain(|gpu| [current, from].iter().any(|layer| gpu.matches(layer)));
+        let current_index = self.upload(device, queue, current);
+        let from_index = self.upload(device, queue, from);
+
+        let (clip_x, clip_y, clip_width, clip_height) = clip_rect;
+        let params = &world.params;
+        let fog = &world.view_fog;
+        let mut locals = Locals::default();
+        locals.f32(clip_x as f32);
+        locals.f32(clip_y as f32);
+        locals.f32(clip_width as f32 / params.width as f32);
+        locals.f32(clip_height as f32 / params.height as f32);
+        let (look_x, look_y) = lut_origin(world);
+        locals.u32(look_x as u32);
+        locals.u32(look_y as u32);
+        locals.u32(params.width);
+        locals.u32(params.height);
+        locals.f32(self.hdr.as_ref().map_or(1.0, |hdr| hdr.settings.peak));
+        locals.u32(world.palette_shift as u32);
+        locals.u32(match fog.curve {
+            FogCurve::Off => 0,
+            FogCurve::Linear => 1,
+            FogCurve::Exponential => 2,
+        });
+        locals.u32((self.filter == Filter::Bilinear) as u32);
+        locals.u32(weight);
+        locals.u32(self.srgb as u32);
+        let (depth, angle) = world.address;
+        locals.u32(address_mode(depth));
+        locals.u32(address_mode(angle));
+        locals.f32(fog.start as f32);
+        locals.f32(fog.end as f32);
+        locals.f32(fog.density as f32);
+        locals.f32(0.0);
+        for layer in [current, from] {
+            let (shift_x, shift_y) = texture_shift(layer, world);
+            locals.u32(shift_x as u32);
+            locals.u32(shift_y as u32);
+            locals.u32(layer.texture.width as u32);
+            locals.u32(layer.texture.height as u32);
+            locals.u32((layer.texture.format == TextureFormat::Indexed) as u32);
+            locals.u32(lut_scale(layer, world).min(MAX_SCALE) as u32);
+            locals.u32(0);
+            locals.u32(0);
+        }
+        queue.write_buffer(&self.locals, 0, &locals.bytes);
+
+        let current = &self.layers[current_index];
+        let from = &self.layers[from_index];
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tunnel_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.locals.as_entire_binding(),
+                },
+                view_entry(1, &current.lut_view),
+                view_entry(2, &current.texel_view),
+                view_entry(3, &current.palette_view),
+                view_entry(4, &from.lut_view),
+                view_entry(5, &from.texel_view),
+                view_entry(6, &from.palette_view),
+            ],
+        });
+
+        let (load, skipped) = match self.split {
+            Some(split) => {
+                let skipped = (split.clamp(0.0, 1.0) * clip_width as f64).round() as u32;
+                (wgpu::LoadOp::Load, skipped.min(clip_width))
+            }
+            None => (wgpu::LoadOp::Clear(wgpu::Color::BLACK), 0),
+        };
+        let scissor = (clip_x + skipped, clip_y, clip_width - skipped, clip_height);
+        let size = (clip_x + clip_width, clip_y + clip_height);
+        let (view, load) = match &mut self.hdr {
+            Some(hdr) => (
+                hdr.buffer(device, size),
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            ),
+            None => (target, load),
+        };
+        draw(
+            encoder,
+            "tunnel_render_pass",
+            view,
+            load,
+            scissor,
+            &self.pipeline,
+            &bind_group,
+        );
+        if let Some(hdr) = &self.hdr {
+            hdr.present(encoder, target, device, queue, load, scissor);
+        }
+    }
+
+    /// Returns the index of the layer's textures, creating them on first use. The palette is
+    /// rewritten every frame, as palette switches keep the LUT and texture.
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, layer: &Layer) -> usize {
+        let index = match self.layers.iter().position(|gpu| gpu.matches(layer)) {
+            Some(index) => index,
+            None => {
+                self.layers.push(GpuLayer::new(device, queue, layer));
+                self.layers.len() - 1
+            }
+        };
+        let palette: Vec<u32> = (0..=255).map(|i| layer.palette.color(i)).collect();
+        write_texture(queue, &self.layers[index].palette, 256, 1, 4, &palette);
+        index
+    }
+}
+
+impl HdrPass {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat, settings: Hdr) -> Self {
+        let shader = device.create_shader_module(&wgpu::include_wgsl!("hdr.wgsl"));
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("hdr_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(HDR_LOCALS_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("hdr_pipeline_layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("hdr_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            multiview: None,
+        });
+        let locals = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("hdr_locals"),
+            size: HDR_LOCALS_SIZE,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            settings,
+            pipeline,
+            bind_group_layout,
+            locals,
+            target_hdr: format == HDR_FORMAT,
+            target_srgb: format.describe().srgb,
+            buffer: None,
+        }
+    }
+
+    /// The float buffer for a target of `size`, made anew when that changes.
+    fn buffer(&mut self, device: &wgpu::Device, size: (u32, u32)) -> &wgpu::TextureView {
+        if self.buffer.as_ref().map(|(_, current)| *current) != Some(size) {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("hdr_buffer"),
+                size: extent(size.0 as usize, size.1 as usize),
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            self.buffer = Some((view, size));
+        }
+        &self.buffer.as_ref().expect("HDR buffer").0
+    }
+
+    /// Records showing the buffer's `scissor` rectangle on `target`.
+    fn present(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        load: wgpu::LoadOp<wgpu::Color>,
+        scissor: (u32, u32, u32, u32),
+    ) {
+        let Some((buffer, _)) = &self.buffer else {
+            return;
+        };
+        let mut locals = Locals::default();
+        locals.f32(self.settings.peak);
+        locals.u32(match self.settings.tone_map {
+            ToneMap::Clamp => 0,
+            ToneMap::Reinhard => 1,
+            ToneMap::Aces => 2,
+        });
+        locals.u32(self.target_hdr as u32);
+        locals.u32(self.target_srgb as u32);
+        queue.write_buffer(&self.locals, 0, &locals.bytes);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("hdr_bind_group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.locals.as_entire_binding(),
+                },
+                view_entry(1, buffer),
+            ],
+        });
+        draw(
+            encoder,
+            "hdr_render_pass",
+            target,
+            load,
+            scissor,
+            &self.pipeline,
+            &bind_group,
+        );
+    }
+}
+
+/// Records a pass drawing one triangle with `pipeline` over `view`, within `scissor` (x, y,
+/// width, height).
+fn draw(
+    encoder: &mut wgpu::CommandEncoder,
+    label: &str,
+    view: &wgpu::TextureView,
+    load: wgpu::LoadOp<wgpu::Color>,
+    scissor: (u32, u32, u32, u32),
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some(label),
+        color_attachments: &[wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        }],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    let (x, y, width, height) = scissor;
+    pass.set_scissor_rect(x, y, width, height);
+    pass.draw(0..3, 0..1);
+}
+
+impl GpuLayer {
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, layer: &Layer) -> Self {
+        let (lut_width, lut_height, entries) = layer.lut.full().expect("full LUT");
+        let lut = create_texture(
+            device,
+            "tunnel_lut",
+            lut_width,
+            lut_height,
+            wgpu::TextureFormat::Rg16Uint,
+        );
+        write_texture(queue, &lut, lut_width, lut_height, 4, entries);
+
+        let texture = &layer.texture;
+        let texels = create_texture(
+            device,
+            "tunnel_texels",
+            texture.width,
+            texture.height,
+            wgpu::TextureFormat::R32Uint,
+        );
+        write_texture(
+            queue,
+            &texels,
+            texture.width,
+            texture.height,
+            4,
+            &texture.texels,
+        );
+
+        let palette = create_texture(
+            device,
+            "tunnel_palette",
+            256,
+            1,
+            wgpu::TextureFormat::R32Uint,
+        );
+
+        Self {
+            lut: layer.lut.clone(),
+            texture: layer.texture.clone(),
+            lut_view: lut.create_view(&wgpu::TextureViewDescriptor::default()),
+            texel_view: texels.create_view(&wgpu::TextureViewDescriptor::default()),
+            palette_view: palette.create_view(&wgpu::TextureViewDescriptor::default()),
+            palette,
+        }
+    }
+
+    fn matches(&self, layer: &Layer) -> bool {
+        Arc::ptr_eq(&self.lut, &layer.lut) && Arc::ptr_eq(&self.texture, &layer.texture)
+    }
+}
+
+/// The uniform block of `gpu.wgsl`, written field by field in declaration order.
+#[derive(Default)]
+struct Locals {
+    bytes: Vec<u8>,
+}
+
+impl Locals {
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn view_entry(binding: u32, view: &wgpu::TextureView) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding,
+        resource: wgpu::BindingResource::TextureView(view),
+    }
+}
+
+fn create_texture(
+    device: &wgpu::Device,
+    label: &str,
+    width: usize,
+    height: usize,
+    format: wgpu::TextureFormat,
+) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(label),
+        size: extent(width, height),
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    })
+}
+
+fn write_texture<T: bytemuck::Pod>(
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    width: usize,
+    height: usize,
+    bytes_per_texel: usize,
+    data: &[T],
+) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        bytemuck::cast_slice(data),
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: NonZeroU32::new((width * bytes_per_texel) as u32),
+            rows_per_image: None,
+        },
+        extent(width, height),
+    );
+}
+
+fn extent(width: usize, height: usize) -> wgpu::Extent3d {
+    wgpu::Extent3d {
+        width: width as u32,
+        height: height as u32,
+        depth_or_array_layers: 1,
+    }
+}
+
+/// The mode's number in `gpu.wgsl`.
+fn address_mode(mode: AddressMode) -> u32 {
+    match mode {
+        AddressMode::Repeat => 0,
+        AddressMode::MirroredRepeat => 1,
+        AddressMode::ClampToEdge => 2,
+    }
+}
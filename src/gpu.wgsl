@@ -0,0 +1,162 @@
+// The tunnel sampling loop of `renderer.rs` as a fragment shader. All fixed-point maths
+// matches the CPU path, so both backends draw the same image.
+
+struct Layer {
+    // Texture scroll in 1/256 texels.
+    shift: vec2<u32>;
+    size: vec2<u32>;
+    indexed: u32;
+    // LUT distance multiplier for the ratio and texture height, in 16.16 fixed point.
+    scale: u32;
+    pad0: u32;
+    pad1: u32;
+};
+
+struct Locals {
+    // Top-left of the frame on the surface and surface pixels per frame pixel.
+    origin: vec2<f32>;
+    pixel_scale: vec2<f32>;
+    look: vec2<u32>;
+    frame: vec2<u32>;
+    // Times SDR white the brightest colors are drawn at, for HDR output; 1 otherwise.
+    peak: f32;
+    palette_shift: u32;
+    // 0 off, 1 linear, 2 exponential.
+    fog_curve: u32;
+    bilinear: u32;
+    // Weight of `current` against `from` out of 256.
+    weight: u32;
+    srgb: u32;
+    // Along the tunnel and around it: 0 repeat, 1 mirrored repeat, 2 clamp to edge.
+    address: vec2<u32>;
+    // Start, end and density.
+    fog: vec4<f32>;
+    current: Layer;
+    from: Layer;
+};
+
+[[group(0), binding(0)]] var<uniform> locals: Locals;
+[[group(0), binding(1)]] var current_lut: texture_2d<u32>;
+[[group(0), binding(2)]] var current_texels: texture_2d<u32>;
+[[group(0), binding(3)]] var current_palette: texture_2d<u32>;
+[[group(0), binding(4)]] var from_lut: texture_2d<u32>;
+[[group(0), binding(5)]] var from_texels: texture_2d<u32>;
+[[group(0), binding(6)]] var from_palette: texture_2d<u32>;
+
+let FOG_STEPS: u32 = 8192u;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    // One triangle covering the whole target.
+    let x = f32((index << 1u) & 2u) * 2.0 - 1.0;
+    let y = f32(index & 2u) * 2.0 - 1.0;
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+fn unpack(color: u32) -> vec4<u32> {
+    return vec4<u32>(color & 255u, (color >> 8u) & 255u, (color >> 16u) & 255u, color >> 24u);
+}
+
+fn texel(texels: texture_2d<u32>, palette: texture_2d<u32>, layer: Layer, x: u32, y: u32) -> vec4<u32> {
+    var color = textureLoad(texels, vec2<i32>(i32(x), i32(y)), 0).r;
+    if (layer.indexed != 0u) {
+        let index = (color + locals.palette_shift) & 255u;
+        color = textureLoad(palette, vec2<i32>(i32(index), 0), 0).r;
+    }
+    return unpack(color);
+}
+
+fn wrap(coord: u32, size: u32, mode: u32) -> u32 {
+    if (mode == 1u) {
+        let mirrored = coord % (size * 2u);
+        return min(mirrored, size * 2u - 1u - mirrored);
+    }
+    if (mode == 2u) {
+        return min(coord, size - 1u);
+    }
+    return coord % size;
+}
+
+fn brightness(depth: f32) -> f32 {
+    let start = locals.fog.x;
+    let end = locals.fog.y;
+    if (depth <= start) {
+        return 1.0;
+    }
+    if (depth >= end) {
+        return 0.0;
+    }
+    if (locals.fog_curve == 1u) {
+        return 1.0 - (depth - start) / (end - start);
+    }
+    return exp(-locals.fog.z * (depth - start));
+}
+
+fn sample(
+    lut: texture_2d<u32>,
+    texels: texture_2d<u32>,
+    palette: texture_2d<u32>,
+    layer: Layer,
+    pixel: vec2<u32>,
+) -> vec4<u32> {
+    let entry = textureLoad(lut, vec2<i32>(pixel + locals.look), 0);
+    // `(distance * scale) >> 12` without overflowing 32 bits; see `renderer/simd.rs`.
+    let depth = ((entry.r * (layer.scale >> 16u)) << 4u)
+        + ((entry.r * (layer.scale & 65535u)) >> 12u);
+    let u = depth + layer.shift.x;
+    // A mirrored texture goes around twice, see `angle_turns` in `renderer.rs`.
+    let turns = select(1u, 2u, locals.address.y == 1u);
+    let v = ((entry.g * layer.size.y * turns) >> 8u) + layer.shift.y;
+
+    var rgba: vec4<u32>;
+    if (locals.bilinear != 0u) {
+        let x0 = wrap(u >> 8u, layer.size.x, locals.address.x);
+        let y0 = wrap(v >> 8u, layer.size.y, locals.address.y);
+        let x1 = wrap((u >> 8u) + 1u, layer.size.x, locals.address.x);
+        let y1 = wrap((v >> 8u) + 1u, layer.size.y, locals.address.y);
+        let fx = u & 255u;
+        let fy = v & 255u;
+        rgba = (texel(texels, palette, layer, x0, y0) * ((256u - fx) * (256u - fy))
+            + texel(texels, palette, layer, x1, y0) * (fx * (256u - fy))
+            + texel(texels, palette, layer, x0, y1) * ((256u - fx) * fy)
+            + texel(texels, palette, layer, x1, y1) * (fx * fy)) >> vec4<u32>(16u);
+    } else {
+        let x = wrap(u >> 8u, layer.size.x, locals.address.x);
+        let y = wrap(v >> 8u, layer.size.y, locals.address.y);
+        rgba = texel(texels, palette, layer, x, y);
+    }
+
+    if (locals.fog_curve != 0u) {
+        let steps = min(depth >> 8u, FOG_STEPS - 1u);
+        let shade = u32(clamp(brightness(f32(steps) / f32(layer.size.x)), 0.0, 1.0) * 256.0);
+        rgba = vec4<u32>((rgba.rgb * shade) >> vec3<u32>(8u), rgba.a);
+    }
+    return rgba;
+}
+
+fn to_linear(color: vec3<f32>) -> vec3<f32> {
+    let curve = pow((color + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(curve, color / 12.92, color <= vec3<f32>(0.04045));
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    let offset = max(position.xy - locals.origin, vec2<f32>(0.0));
+    let pixel = min(vec2<u32>(offset / locals.pixel_scale), locals.frame - vec2<u32>(1u));
+
+    var rgba = sample(current_lut, current_texels, current_palette, locals.current, pixel);
+    if (locals.weight < 256u) {
+        let from = sample(from_lut, from_texels, from_palette, locals.from, pixel);
+        rgba = (rgba * locals.weight + from * (256u - locals.weight)) >> vec4<u32>(8u);
+    }
+
+    var color = vec3<f32>(rgba.rgb) / 255.0;
+    if (locals.srgb != 0u) {
+        color = to_linear(color);
+    }
+    if (locals.peak > 1.0) {
+        let level = max(color.r, max(color.g, color.b));
+        color = color * (1.0 + (locals.peak - 1.0) * smoothStep(0.5, 1.0, level));
+    }
+    return vec4<f32>(color, 1.0);
+}
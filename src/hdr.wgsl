@@ -0,0 +1,54 @@
+// Shows the tunnel's float buffer on the target: as it is on float targets, which HDR
+// displays take as scRGB (1 is SDR white), and tone mapped into range on the others.
+
+struct Locals {
+    peak: f32;
+    // 0 clamp, 1 Reinhard, 2 ACES.
+    tone_map: u32;
+    // Whether the target takes values beyond 1.
+    hdr: u32;
+    srgb: u32;
+};
+
+[[group(0), binding(0)]] var<uniform> locals: Locals;
+[[group(0), binding(1)]] var buffer: texture_2d<f32>;
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    // One triangle covering the whole target.
+    let x = f32((index << 1u) & 2u) * 2.0 - 1.0;
+    let y = f32(index & 2u) * 2.0 - 1.0;
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+fn tone_map(color: vec3<f32>) -> vec3<f32> {
+    if (locals.tone_map == 1u) {
+        // Extended Reinhard, which takes the peak to white and leaves dark colors as they are.
+        let white = locals.peak * locals.peak;
+        return color * (1.0 + color / white) / (1.0 + color);
+    }
+    if (locals.tone_map == 2u) {
+        // Narkowicz's fit of the ACES filmic curve.
+        let x = color * 0.6;
+        let mapped = (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14);
+        return clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+    }
+    return min(color, vec3<f32>(1.0));
+}
+
+fn to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let curve = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(curve, color * 12.92, color <= vec3<f32>(0.0031308));
+}
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    var color = textureLoad(buffer, vec2<i32>(position.xy), 0).rgb;
+    if (locals.hdr == 0u) {
+        color = tone_map(color);
+        if (locals.srgb == 0u) {
+            color = to_srgb(color);
+        }
+    }
+    return vec4<f32>(color, 1.0);
+}
@@ -0,0 +1,178 @@
+use tunnel::{font, CameraMode, Filter, Pipeline, World};
+
+use crate::keymap::Keymap;
+
+const PADDING: usize = 12;
+/// Space between two columns of the panel.
+const GUTTER: usize = 24;
+const TITLE_COLOR: [u8; 4] = [0xff, 0xc0, 0x40, 0xff];
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const KEY_COLOR: [u8; 4] = [0x90, 0xd0, 0xff, 0xff];
+
+/// A panel over the tunnel listing every key bound and what it does, as the `[keys]` table
+/// left them, and the settings as they are now, in as many columns as the frame needs.
+pub struct Help {
+    visible: bool,
+}
+
+impl Help {
+    pub fn new() -> Self {
+        Self { visible: false }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn draw(
+        &self,
+        frame: &mut [u8],
+        frame_width: usize,
+        keymap: &Keymap,
+        world: &World,
+        pipeline: &Pipeline,
+        filter: Filter,
+    ) {
+        if !self.visible {
+            return;
+        }
+
+        // Sections start with a title, and keys are colored apart from what they do.
+        let mut lines = vec![(String::new(), "KEYS".to_string(), TITLE_COLOR)];
+        let bindings = keymap.bindings();
+        let names: Vec<String> = bindings
+            .iter()
+            .map(|(_, keys)| {
+                let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+                keys.join(" ")
+            })
+            .collect();
+        let key_width = names.iter().map(|name| name.len()).max().unwrap_or(0) + 1;
+        for ((action, _), keys) in bindings.iter().zip(names) {
+            let text = format!("{:key_width$}{}", "", action.name().replace('_', " "));
+            lines.push((keys, text, TEXT_COLOR));
+        }
+        lines.push((String::new(), String::new(), TEXT_COLOR));
+        lines.push((String::new(), "NOW".to_string(), TITLE_COLOR));
+        for (name, value) in settings(world, pipeline, filter) {
+            lines.push((String::new(), format!("{:<10}{}", name, value), TEXT_COLOR));
+        }
+
+        let frame_height = frame.len() / 4 / frame_width;
+        let widest = lines
+            .iter()
+            .map(|(_, text, _)| text.as_str())
+            .max_by_key(|text| text.len())
+            .unwrap_or_default();
+        // As large as fits, in as many columns as it takes.
+        let layout = |scale: usize| {
+            let line_height = font::line_height(scale);
+            let rows = (frame_height.saturating_sub(PADDING * 2) / line_height).max(1);
+            let columns = lines.len().div_ceil(rows);
+            let column_width = font::text_width(widest, scale);
+            let width = columns * (column_width + GUTTER) - GUTTER + PADDING * 2;
+            (scale, rows, column_width, width)
+        };
+        let (scale, rows, column_width, width) = match layout(2) {
+            large if large.3 <= frame_width => large,
+            _ => layout(1),
+        };
+        let line_height = font::line_height(scale);
+        let width = width.min(frame_width);
+        let height = (rows.min(lines.len()) * line_height + PADDING * 2).min(frame_height);
+        let left = (frame_width - width) / 2;
+        let top = (frame_height - height) / 2;
+        for y in top..top + height {
+            let row =
+                &mut frame[(y * frame_width + left) * 4..(y * frame_width + left + width) * 4];
+            for pixel in row.chunks_exact_mut(4) {
+                for channel in &mut pixel[..3] {
+                    *channel /= 4;
+                }
+            }
+        }
+
+        for (i, (keys, text, color)) in lines.iter().enumerate() {
+            let x = left + PADDING + i / rows * (column_width + GUTTER);
+            let y = top + PADDING + i % rows * line_height;
+            font::draw_text(frame, frame_width, (x, y), scale, *color, text);
+            font::draw_text(frame, frame_width, (x, y), scale, KEY_COLOR, keys);
+        }
+    }
+}
+
+/// What the keys change, as it is now.
+fn settings(world: &World, pipeline: &Pipeline, filter: Filter) -> Vec<(&'static str, String)> {
+    let effect = |index: usize| {
+        pipeline
+            .effects
+            .get(index)
+            .map_or("", |effect| effect.name())
+    };
+    let stages = &pipeline.compositor.stages;
+    let top = stages.last().map_or(0, |stage| stage.effect);
+    let mut settings = vec![("EFFECT", effect(top).to_string())];
+    if let [background, layer] = stages.as_slice() {
+        settings.push((
+            "UNDER",
+            format!("{} ({})", effect(background.effect), layer.mode.name()),
+        ));
+    }
+    let clock = world.clock();
+    let (travel, spin) = world.flight_speed();
+    let speed = if clock.is_paused() {
+        "paused".to_string()
+    } else {
+        format!("{:.2}x", clock.speed())
+    };
+    settings.extend([
+        ("SPEED", speed),
+        ("FLIGHT", format!("{:.2} travel, {:.2} spin", travel, spin)),
+        ("RATIO", format!("{:.0}", world.ratio())),
+        ("SHAPE", world.geometry().name().to_string()),
+        ("PALETTE", world.palette_kind().name().to_string()),
+        ("COLORS", world.color_map().name().to_string()),
+    ]);
+    let filter = match filter {
+        Filter::Nearest => "nearest",
+        Filter::Bilinear => "bilinear",
+    };
+    let (depth, angle) = world.address_modes();
+    let on = |on: bool| if on { "on" } else { "off" }.to_string();
+    let camera = match world.camera().mode() {
+        CameraMode::Auto => "auto",
+        CameraMode::Manual => "manual",
+        CameraMode::Follow => "follow",
+    };
+    let passes: Vec<&str> = pipeline
+        .postfx
+        .passes()
+        .filter(|&(_, enabled)| enabled)
+        .map(|(name, _)| name)
+        .collect();
+    settings.extend([
+        ("FILTER", filter.to_string()),
+        (
+            "WRAP",
+            format!("{} along, {} around", depth.name(), angle.name()),
+        ),
+        ("FOG", world.fog().curve.name().to_string()),
+        ("LIGHT", on(world.light().enabled)),
+        ("ROLL", format!("{:.2} turns/s", world.roll_speed())),
+        ("FEEDBACK", format!("{:.2}", world.feedback())),
+        ("CAMERA", camera.to_string()),
+        (
+            "POSTFX",
+            if passes.is_empty() {
+                "none".to_string()
+            } else {
+                passes.join(" ")
+            },
+        ),
+    ]);
+    settings
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+use std::str::FromStr;
+
+use tunnel::PassKind;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+/// Something a key does, independent of the key it is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Menu,
+    Screenshot,
+    Record,
+    Fullscreen,
+    NativeResolution,
+    Overlay,
+    Help,
+    Ui,
+    Pause,
+    Slower,
+    Faster,
+    ResetSpeed,
+    TravelFaster,
+    TravelSlower,
+    Reverse,
+    Stop,
+    Palette,
+    ColorMap,
+    Filter,
+    Split,
+    Fog,
+    AutoRoll,
+    Light,
+    Feedback,
+    WrapAround,
+    WrapAlong,
+    PostFx(PassKind),
+    TrailLonger,
+    TrailShorter,
+    Breathing,
+    Shape,
+    /// Switches to the effect with this index.
+    Effect(usize),
+    Background,
+    Blend,
+    /// Makes the texture with the generator of this index.
+    Texture(usize),
+    Noise,
+    SteerLeft,
+    SteerRight,
+    SteerUp,
+    SteerDown,
+    CameraMode,
+    MouseLook,
+    RollLeft,
+    RollRight,
+}
+
+/// Every action, its name in the `[keys]` table of the config file and the keys bound to it
+/// unless that table rebinds it.
+const ACTIONS: [(Action, &str, &str); 61] = [
+    (Action::Menu, "menu", "escape"),
+    (Action::Screenshot, "screenshot", "f12"),
+    (Action::Record, "record", "f9"),
+    (Action::Fullscreen, "fullscreen", "f11 alt+enter"),
+    (Action::NativeResolution, "native_resolution", "n"),
+    (Action::Overlay, "overlay", "f1"),
+    (Action::Help, "help", "shift+h shift+f1"),
+    (Action::Ui, "ui", "f10"),
+    (Action::Pause, "pause", "space"),
+    (Action::Slower, "slower", "comma"),
+    (Action::Faster, "faster", "period"),
+    (Action::ResetSpeed, "reset_speed", "slash"),
+    (Action::TravelFaster, "travel_faster", "equals"),
+    (Action::TravelSlower, "travel_slower", "minus"),
+    (Action::Reverse, "reverse", "9"),
+    (Action::Stop, "stop", "0"),
+    (Action::Palette, "palette", "p"),
+    (Action::ColorMap, "color_map", "u"),
+    (Action::Filter, "filter", "b"),
+    (Action::Split, "split", "f8"),
+    (Action::Fog, "fog", "f"),
+    (Action::AutoRoll, "auto_roll", "y"),
+    (Action::Light, "light", "q"),
+    (Action::Feedback, "feedback", "e"),
+    (Action::WrapAround, "wrap_around", "r"),
+    (Action::WrapAlong, "wrap_along", "shift+r"),
+    (Action::PostFx(PassKind::Bloom), "bloom", "g"),
+    (Action::PostFx(PassKind::Scanlines), "scanlines", "l"),
+    (Action::PostFx(PassKind::Vignette), "vignette", "v"),
+    (
+        Action::PostFx(PassKind::ChromaticAberration),
+        "chromatic_aberration",
+        "x",
+    ),
+    (Action::PostFx(PassKind::Pixelate), "pixelate", "z"),
+    (Action::PostFx(PassKind::Crt), "crt", "k"),
+    (Action::PostFx(PassKind::MotionBlur), "motion_blur", "h"),
+    (Action::PostFx(PassKind::Dither), "dither", "j"),
+    (Action::PostFx(PassKind::Grade), "grade", "shift+g"),
+    (Action::TrailLonger, "trail_longer", "rbracket"),
+    (Action::TrailShorter, "trail_shorter", "lbracket"),
+    (Action::Breathing, "breathing", "m"),
+    (Action::Shape, "shape", "tab"),
+    (Action::Effect(0), "effect_tunnel", "f2"),
+    (Action::Effect(1), "effect_plasma", "f3"),
+    (Action::Effect(2), "effect_rotozoomer", "f4"),
+    (Action::Effect(3), "effect_fire", "f5"),
+    (Action::Effect(4), "effect_starfield", "f6"),
+    (Action::Background, "background", "o"),
+    (Action::Blend, "blend", "i"),
+    (Action::Texture(0), "texture_xor", "1"),
+    (Action::Texture(1), "texture_checkerboard", "2"),
+    (Action::Texture(2), "texture_noise", "3"),
+    (Action::Texture(3), "texture_brick", "4"),
+    (Action::Texture(4), "texture_spiral", "5"),
+    (Action::Texture(5), "texture_stripes", "6"),
+    (Action::Noise, "animated_noise", "7"),
+    (Action::SteerLeft, "steer_left", "a left"),
+    (Action::SteerRight, "steer_right", "d right"),
+    (Action::SteerUp, "steer_up", "w up"),
+    (Action::SteerDown, "steer_down", "s down"),
+    (Action::CameraMode, "camera_mode", "c"),
+    (Action::MouseLook, "mouse_look", "t"),
+    (Action::RollLeft, "roll_left", "shift+q"),
+    (Action::RollRight, "roll_right", "shift+e"),
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(action, _, _)| *action == self)
+            .map_or("", |(_, name, _)| name)
+    }
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACTIONS
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(action, _, _)| *action)
+            .ok_or_else(|| {
+                let names: Vec<_> = ACTIONS.iter().map(|(_, name, _)| *name).collect();
+                format!(
+                    "unknown action `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The names keys go by in bindings, besides letters and digits.
+const KEYS: [(&str, VirtualKeyCode); 38] = [
+    ("escape", VirtualKeyCode::Escape),
+    ("f1", VirtualKeyCode::F1),
+    ("f2", VirtualKeyCode::F2),
+    ("f3", VirtualKeyCode::F3),
+    ("f4", VirtualKeyCode::F4),
+    ("f5", VirtualKeyCode::F5),
+    ("f6", VirtualKeyCode::F6),
+    ("f7", VirtualKeyCode::F7),
+    ("f8", VirtualKeyCode::F8),
+    ("f9", VirtualKeyCode::F9),
+    ("f10", VirtualKeyCode::F10),
+    ("f11", VirtualKeyCode::F11),
+    ("f12", VirtualKeyCode::F12),
+    ("space", VirtualKeyCode::Space),
+    ("enter", VirtualKeyCode::Return),
+    ("tab", VirtualKeyCode::Tab),
+    ("backspace", VirtualKeyCode::Back),
+    ("insert", VirtualKeyCode::Insert),
+    ("delete", VirtualKeyCode::Delete),
+    ("home", VirtualKeyCode::Home),
+    ("end", VirtualKeyCode::End),
+    ("pageup", VirtualKeyCode::PageUp),
+    ("pagedown", VirtualKeyCode::PageDown),
+    ("left", VirtualKeyCode::Left),
+    ("right", VirtualKeyCode::Right),
+    ("up", VirtualKeyCode::Up),
+    ("down", VirtualKeyCode::Down),
+    ("comma", VirtualKeyCode::Comma),
+    ("period", VirtualKeyCode::Period),
+    ("slash", VirtualKeyCode::Slash),
+    ("backslash", VirtualKeyCode::Backslash),
+    ("semicolon", VirtualKeyCode::Semicolon),
+    ("apostrophe", VirtualKeyCode::Apostrophe),
+    ("grave", VirtualKeyCode::Grave),
+    ("minus", VirtualKeyCode::Minus),
+    ("equals", VirtualKeyCode::Equals),
+    ("lbracket", VirtualKeyCode::LBracket),
+    ("rbracket", VirtualKeyCode::RBracket),
+];
+
+const LETTERS: [VirtualKeyCode; 26] = [
+    VirtualKeyCode::A,
+    VirtualKeyCode::B,
+    VirtualKeyCode::C,
+    VirtualKeyCode::D,
+    VirtualKeyCode::E,
+    VirtualKeyCode::F,
+    VirtualKeyCode::G,
+    VirtualKeyCode::H,
+    VirtualKeyCode::I,
+    VirtualKeyCode::J,
+    VirtualKeyCode::K,
+    VirtualKeyCode::L,
+    VirtualKeyCode::M,
+    VirtualKeyCode::N,
+    VirtualKeyCode::O,
+    VirtualKeyCode::P,
+    VirtualKeyCode::Q,
+    VirtualKeyCode::R,
+    VirtualKeyCode::S,
+    VirtualKeyCode::T,
+    VirtualKeyCode::U,
+    VirtualKeyCode::V,
+    VirtualKeyCode::W,
+    VirtualKeyCode::X,
+    VirtualKeyCode::Y,
+    VirtualKeyCode::Z,
+];
+
+const DIGITS: [VirtualKeyCode; 10] = [
+    VirtualKeyCode::Key0,
+    VirtualKeyCode::Key1,
+    VirtualKeyCode::Key2,
+    VirtualKeyCode::Key3,
+    VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5,
+    VirtualKeyCode::Key6,
+    VirtualKeyCode::Key7,
+    VirtualKeyCode::Key8,
+    VirtualKeyCode::Key9,
+];
+
+/// A key together with the modifiers that have to be held with it, written like `shift+r`,
+/// `ctrl+alt+f12` or `space`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Binding {
+    pub key: VirtualKeyCode,
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+}
+
+impl Binding {
+    fn modifiers(&self) -> usize {
+        self.shift as usize + self.control as usize + self.alt as usize
+    }
+
+    fn modifiers_held(&self, input: &WinitInputHelper) -> bool {
+        (!self.shift || input.held_shift())
+            && (!self.control || input.held_control())
+            && (!self.alt || input.held_alt())
+    }
+
+    /// Whether `other` is this key with more modifiers, which take precedence while held.
+    fn is_shadowed_by(&self, other: &Binding) -> bool {
+        other.key == self.key
+            && other.modifiers() > self.modifiers()
+            && (other.shift || !self.shift)
+            && (other.control || !self.control)
+            && (other.alt || !self.alt)
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let mut parts: Vec<&str> = lower.split('+').map(str::trim).collect();
+        let name = parts.pop().unwrap_or_default();
+        let mut binding = Binding {
+            key: key(name).ok_or_else(|| format!("unknown key `{}` in `{}`", name, s))?,
+            shift: false,
+            control: false,
+            alt: false,
+        };
+        for modifier in parts {
+            match modifier {
+                "shift" => binding.shift = true,
+                "ctrl" | "control" => binding.control = true,
+                "alt" => binding.alt = true,
+                _ => {
+                    return Err(format!(
+                        "unknown modifier `{}` in `{}` (expected one of shift, ctrl, alt)",
+                        modifier, s
+                    ))
+                }
+            }
+        }
+        Ok(binding)
+    }
+}
+
+impl fmt::Display for Binding {
+    /// As bindings are written, e.g. `shift+r`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, held) in [
+            ("ctrl", self.control),
+            ("alt", self.alt),
+            ("shift", self.shift),
+        ] {
+            if held {
+                write!(f, "{}+", modifier)?;
+            }
+        }
+        let letters = ('a'..='z').zip(LETTERS);
+        let digits = ('0'..='9').zip(DIGITS);
+        match letters.chain(digits).find(|&(_, key)| key == self.key) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => {
+                let name = KEYS.iter().find(|&&(_, key)| key == self.key);
+                write!(f, "{}", name.map_or("?", |(name, _)| name))
+            }
+        }
+    }
+}
+
+/// The key called `name`: a letter, a digit or one of `KEYS`.
+fn key(name: &str) -> Option<VirtualKeyCode> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ 'a'..='z'), None) => Some(LETTERS[c as usize - 'a' as usize]),
+        (Some(c @ '0'..='9'), None) => Some(DIGITS[c as usize - '0' as usize]),
+        _ => KEYS
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|&(_, key)| key),
+    }
+}
+
+/// Names of the keys held down, as bindings write them, and of the modifiers held with them.
+#[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+pub fn held_keys(input: &WinitInputHelper) -> Vec<String> {
+    let letters = ('a'..='z').zip(LETTERS);
+    let digits = ('0'..='9').zip(DIGITS);
+    let mut names: Vec<String> = letters
+        .chain(digits)
+        .filter(|&(_, key)| input.key_held(key))
+        .map(|(name, _)| name.to_string())
+        .chain(
+            KEYS.iter()
+                .filter(|&&(_, key)| input.key_held(key))
+                .map(|(name, _)| name.to_string()),
+        )
+        .collect();
+    for (name, held) in [
+        ("shift", input.held_shift()),
+        ("ctrl", input.held_control()),
+        ("alt", input.held_alt()),
+    ] {
+        if held {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Which keys do what: every action's default keys, some of them possibly rebound by the
+/// `[keys]` table of the config file.
+#[derive(Clone)]
+pub struct Keymap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = ACTIONS
+            .iter()
+            .flat_map(|&(action, _, keys)| {
+                keys.split_whitespace()
+                    .map(move |key| (key.parse().expect("default key binding"), action))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl fmt::Debug for Keymap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.bindings
+                    .iter()
+                    .map(|(binding, action)| (action.name(), binding)),
+            )
+            .finish()
+    }
+}
+
+impl Keymap {
+    /// Binds `action` to `bindings` instead of its default keys; none leaves it unbound.
+    pub fn bind(&mut self, action: Action, bindings: &[Binding]) {
+        self.bindings.retain(|&(_, bound)| bound != action);
+        self.bindings
+            .extend(bindings.iter().map(|&binding| (binding, action)));
+    }
+
+    /// Every action with a key, in the order of the defaults, and its keys.
+    pub fn bindings(&self) -> Vec<(Action, Vec<Binding>)> {
+        ACTIONS
+            .iter()
+            .map(|&(action, _, _)| {
+                let keys = self
+                    .bindings
+                    .iter()
+                    .filter(|&&(_, bound)| bound == action)
+                    .map(|&(binding, _)| binding)
+                    .collect::<Vec<_>>();
+                (action, keys)
+            })
+            .filter(|(_, keys)| !keys.is_empty())
+            .collect()
+    }
+
+    /// Whether one of the keys of `action` went down in this update with its modifiers held.
+    pub fn pressed(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.active(input, action, |key| input.key_pressed(key))
+    }
+
+    /// Whether one of the keys of `action` is held down with its modifiers.
+    pub fn held(&self, input: &WinitInputHelper, action: Action) -> bool {
+        self.active(input, action, |key| input.key_held(key))
+    }
+
+    /// A binding is only active while no binding of the same key with more modifiers is
+    /// held, so that `shift+r` doesn't also do what `r` does.
+    fn active(
+        &self,
+        input: &WinitInputHelper,
+        action: Action,
+        down: impl Fn(VirtualKeyCode) -> bool,
+    ) -> bool {
+        self.bindings.iter().any(|(binding, bound)| {
+            *bound == action
+                && down(binding.key)
+                && binding.modifiers_held(input)
+                && !self
+                    .bindings
+                    .iter()
+                    .any(|(other, _)| binding.is_shadowed_by(other) && other.modifiers_held(input))
+        })
+    }
+}
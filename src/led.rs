@@ -0,0 +1,248 @@
+use std::error::Error;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::str::FromStr;
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+/// Bytes of color a DDP packet carries at most, a whole number of pixels.
+const DDP_PAYLOAD: usize = 480 * 3;
+/// Pixels in an Art-Net universe: 170 of them fill 510 of its 512 channels.
+const UNIVERSE_PIXELS: usize = 170;
+/// Largest part of the frame's width the preview takes up.
+const PREVIEW_SHARE: usize = 4;
+const PREVIEW_MARGIN: usize = 8;
+
+/// How frames travel to the LED controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum LedProtocol {
+    /// Distributed Display Protocol, e.g. for WLED.
+    Ddp,
+    /// Art-Net DMX, one universe per 170 LEDs.
+    Artnet,
+}
+
+impl LedProtocol {
+    fn default_port(self) -> u16 {
+        match self {
+            LedProtocol::Ddp => 4048,
+            LedProtocol::Artnet => 6454,
+        }
+    }
+}
+
+/// Columns and rows of LEDs in a matrix.
+#[derive(Clone, Copy, Debug)]
+pub struct GridSize {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl FromStr for GridSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (columns, rows) = s
+            .split_once(['x', 'X'])
+            .ok_or_else(|| format!("expected COLUMNSxROWS, got `{}`", s))?;
+        let parse = |value: &str| match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("invalid dimension `{}`", value)),
+        };
+        Ok(Self {
+            columns: parse(columns)?,
+            rows: parse(rows)?,
+        })
+    }
+}
+
+/// Streams every drawn frame to an LED matrix over UDP, scaled down to one pixel per LED
+/// and gamma corrected, and draws what the matrix shows into a corner of the frame.
+pub struct LedOutput {
+    socket: UdpSocket,
+    protocol: LedProtocol,
+    size: GridSize,
+    /// Rows run back and forth, like matrices wired in a zigzag.
+    serpentine: bool,
+    universe: u16,
+    gamma: [u8; 256],
+    preview: bool,
+    /// The matrix's colors before gamma correction, row by row.
+    pixels: Vec<[u8; 3]>,
+    data: Vec<u8>,
+    packet: Vec<u8>,
+    sequence: u8,
+}
+
+impl LedOutput {
+    /// Sends to `target`, a host with an optional port that defaults to the protocol's.
+    pub fn start(
+        target: &str,
+        protocol: LedProtocol,
+        size: GridSize,
+        gamma: f64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let address = target
+            .to_socket_addrs()
+            .or_else(|_| (target, protocol.default_port()).to_socket_addrs())
+            .map_err(|err| format!("failed to resolve `{}`: {}", target, err))?
+            .next()
+            .ok_or_else(|| format!("`{}` has no address", target))?;
+        let socket = UdpSocket::bind(if address.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        println!(
+            "Streaming {}x{} LEDs to {}",
+            size.columns, size.rows, address
+        );
+        let exponent = gamma.max(0.1);
+        let gamma =
+            std::array::from_fn(|i| ((i as f64 / 255.0).powf(exponent) * 255.0 + 0.5) as u8);
+        Ok(Self {
+            socket,
+            protocol,
+            size,
+            serpentine: false,
+            universe: 0,
+            gamma,
+            preview: true,
+            pixels: vec![[0; 3]; size.columns * size.rows],
+            data: Vec::new(),
+            packet: Vec::new(),
+            sequence: 0,
+        })
+    }
+
+    pub fn set_serpentine(&mut self, serpentine: bool) {
+        self.serpentine = serpentine;
+    }
+
+    /// The first Art-Net universe; the frame continues in the ones after it.
+    pub fn set_universe(&mut self, universe: u16) {
+        self.universe = universe;
+    }
+
+    pub fn set_preview(&mut self, preview: bool) {
+        self.preview = preview;
+    }
+
+    /// Scales `frame` down to the matrix and sends it. Packets the network can't take at
+    /// the moment are dropped rather than holding up the frame.
+    pub fn push(&mut self, frame: &[u8], width: u32, height: u32) {
+        let (width, height) = (width as usize, height as usize);
+        let GridSize { columns, rows } = self.size;
+        self.pixels
+            .par_chunks_exact_mut(columns)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let top = y * height / rows;
+                let bottom = ((y + 1) * height / rows).max(top + 1);
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let left = x * width / columns;
+                    let right = ((x + 1) * width / columns).max(left + 1);
+                    let mut sum = [0u32; 3];
+                    for y in top..bottom {
+                        for x in left..right {
+                            let i = (y * width + x) * 4;
+                            for (sum, &channel) in sum.iter_mut().zip(&frame[i..i + 3]) {
+                                *sum += u32::from(channel);
+                            }
+                        }
+                    }
+                    let count = ((bottom - top) * (right - left)) as u32;
+                    *pixel = sum.map(|sum| (sum / count) as u8);
+                }
+            });
+
+        self.data.clear();
+        for (y, row) in self.pixels.chunks_exact(columns).enumerate() {
+            let reversed = self.serpentine && y % 2 == 1;
+            for x in 0..columns {
+                let pixel = row[if reversed { columns - 1 - x } else { x }];
+                self.data
+                    .extend(pixel.map(|channel| self.gamma[channel as usize]));
+            }
+        }
+        match self.protocol {
+            LedProtocol::Ddp => self.send_ddp(),
+            LedProtocol::Artnet => self.send_artnet(),
+        }
+    }
+
+    /// DDP packets carry RGB data at an offset; the last one tells the controller to show it.
+    fn send_ddp(&mut self) {
+        self.sequence = self.sequence % 15 + 1;
+        let chunks = self.data.len().div_ceil(DDP_PAYLOAD);
+        for (i, chunk) in self.data.chunks(DDP_PAYLOAD).enumerate() {
+            let push = if i + 1 == chunks { 0x01 } else { 0x00 };
+            self.packet.clear();
+            // Version 1, the sequence number, 8-bit RGB and the default output.
+            self.packet
+                .extend_from_slice(&[0x40 | push, self.sequence, 0x0b, 0x01]);
+            self.packet
+                .extend_from_slice(&((i * DDP_PAYLOAD) as u32).to_be_bytes());
+            self.packet
+                .extend_from_slice(&(chunk.len() as u16).to_be_bytes());
+            self.packet.extend_from_slice(chunk);
+            let _ = self.socket.send(&self.packet);
+        }
+    }
+
+    /// An ArtDMX packet per universe, each holding the next 170 LEDs.
+    fn send_artnet(&mut self) {
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+        for (i, chunk) in self.data.chunks(UNIVERSE_PIXELS * 3).enumerate() {
+            let universe = self.universe.wrapping_add(i as u16);
+            // DMX frames have an even length.
+            let length = chunk.len() + chunk.len() % 2;
+            self.packet.clear();
+            self.packet.extend_from_slice(b"Art-Net\0");
+            // ArtDMX, protocol version 14, the sequence number and physical port 0.
+            self.packet
+                .extend_from_slice(&[0x00, 0x50, 0x00, 14, self.sequence, 0]);
+            self.packet.extend_from_slice(&universe.to_le_bytes());
+            self.packet
+                .extend_from_slice(&(length as u16).to_be_bytes());
+            self.packet.extend_from_slice(chunk);
+            self.packet
+                .resize(self.packet.len() + length - chunk.len(), 0);
+            let _ = self.socket.send(&self.packet);
+        }
+    }
+
+    /// Draws the matrix as it was last sent, one square per LED, into the bottom right
+    /// corner of the frame.
+    pub fn draw_preview(&self, frame: &mut [u8], frame_width: usize) {
+        if !self.preview {
+            return;
+        }
+        let GridSize { columns, rows } = self.size;
+        let frame_height = frame.len() / 4 / frame_width;
+        let room =
+            (frame_width / PREVIEW_SHARE).min(frame_height.saturating_sub(PREVIEW_MARGIN * 2));
+        let cell = (room / columns.max(rows)).max(1);
+        let (width, height) = (columns * cell, rows * cell);
+        if width + PREVIEW_MARGIN > frame_width || height + PREVIEW_MARGIN > frame_height {
+            return;
+        }
+        let left = frame_width - width - PREVIEW_MARGIN;
+        let top = frame_height - height - PREVIEW_MARGIN;
+        for y in 0..height {
+            let row = &mut frame[((top + y) * frame_width + left) * 4..][..width * 4];
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                // Leave a dark gap between the LEDs once they are big enough to show one.
+                let gap = cell >= 4 && (x % cell == cell - 1 || y % cell == cell - 1);
+                let [r, g, b] = if gap {
+                    [0; 3]
+                } else {
+                    self.pixels[y / cell * columns + x / cell]
+                };
+                pixel.copy_from_slice(&[r, g, b, 0xff]);
+            }
+        }
+    }
+}
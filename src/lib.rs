@@ -0,0 +1,66 @@
+#![deny(clippy::all)]
+// Loading plugins is the one thing that needs `unsafe`, and only with the feature.
+#![cfg_attr(not(feature = "plugins"), forbid(unsafe_code))]
+#![cfg_attr(feature = "plugins", deny(unsafe_code))]
+
+mod atlas;
+mod beat;
+mod camera;
+mod clock;
+pub mod color;
+pub mod color_map;
+mod compositor;
+mod curve;
+pub mod effect;
+mod flash;
+mod flight;
+mod fog;
+pub mod font;
+pub mod geometry;
+mod gpu;
+mod light;
+mod lut;
+pub mod modulation;
+mod modulator;
+mod palette;
+mod pipeline;
+pub mod postfx;
+mod renderer;
+mod scroller;
+mod shadertoy;
+mod spectrum;
+mod split;
+mod stereo;
+pub mod texture;
+pub mod time;
+pub mod timeline;
+mod world;
+
+pub use atlas::Atlas;
+pub use beat::BeatTrack;
+pub use camera::{Camera, CameraMode};
+pub use clock::Clock;
+pub use color_map::ColorMapper;
+pub use compositor::{BlendMode, Compositor, Stage};
+pub use curve::Curve;
+pub use effect::Effect;
+pub use flight::Flight;
+pub use fog::{Fog, FogCurve};
+pub use geometry::TunnelGeometry;
+pub use gpu::{GpuRenderer, Hdr, ToneMap, HDR_FORMAT};
+pub use light::{Light, LightPosition};
+pub use lut::{LutQuality, LutStats};
+pub use modulation::ModulationGraph;
+pub use modulator::{Modulator, Shape};
+pub use palette::{Palette, PaletteKind};
+pub use pipeline::Pipeline;
+pub use postfx::{Pass, PassKind, PostFx};
+pub use renderer::{Filter, Interlace, RenderStats, Renderer};
+pub use scroller::Scroller;
+pub use shadertoy::Shadertoy;
+pub use spectrum::{Analyzer, AudioLevels};
+pub use split::{Comparison, Split};
+pub use stereo::{Stereo, StereoMode};
+pub use texture::{AddressMode, HeightMap, Stamp, Texture, TextureFormat, TextureGenerator};
+pub use timeline::Timeline;
+pub use world::{Motion, TunnelParams, World, MIN_RENDER_SCALE};
@@ -0,0 +1,152 @@
+use std::f64::consts::PI;
+
+use crate::modulator::Modulator;
+
+/// Directions around the tunnel the light is worked out for, as a power of two.
+const ANGLE_BITS: u32 = 10;
+const ANGLE_STEPS: usize = 1 << ANGLE_BITS;
+
+/// A fake point light circling the tunnel axis some way down the tunnel, which shades the
+/// walls by how directly they face it and how far along the tunnel they are from it.
+/// `depth` and `falloff` are measured in texture lengths, like the fog.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Light {
+    pub enabled: bool,
+    /// Turns per second the light circles the axis at.
+    pub orbit: f64,
+    pub depth: Modulator,
+    /// How bright the walls facing the light are, from 0 to 1.
+    pub intensity: Modulator,
+    /// How bright the walls facing away from it are, from 0 to 1.
+    pub ambient: f64,
+    /// Distance along the tunnel at which the light has fallen to half.
+    pub falloff: f64,
+    /// How far the height map tilts the walls towards or away from the light; 0 lights them
+    /// flat.
+    pub bump: f64,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            orbit: 0.1,
+            depth: Modulator::constant(2.0),
+            intensity: Modulator::constant(1.0),
+            ambient: 0.25,
+            falloff: 2.0,
+            bump: 0.0,
+        }
+    }
+}
+
+/// Where the light is in a frame: `angle` in turns around the axis, `depth` down the tunnel
+/// and its `intensity`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LightPosition {
+    pub angle: f64,
+    pub depth: f64,
+    pub intensity: f64,
+}
+
+/// How far off the axis the light circles, as a share of the tunnel's radius.
+const ORBIT_RADIUS: f64 = 0.5;
+
+/// The light of one frame, looked up per pixel: the brightness of a wall is `ambient` plus
+/// `scale * angular * radial * relief`, all in 1/256ths, at most 256.
+pub(crate) struct Lighting {
+    /// How directly each of `ANGLE_STEPS` directions around the tunnel faces the light.
+    pub(crate) angular: Vec<u16>,
+    /// How much of the light reaches each texel of depth.
+    pub(crate) radial: Vec<u16>,
+    /// The part of the way to the light that runs around the tunnel, from each direction,
+    /// in 1/256ths of its length, positive towards increasing angles.
+    pub(crate) around: Vec<i16>,
+    /// The part of the way to the light that runs along the tunnel, from each texel of
+    /// depth, positive going deeper.
+    pub(crate) along: Vec<i16>,
+    pub(crate) ambient: u32,
+    pub(crate) scale: u32,
+    /// `Light::bump` in 1/256ths.
+    pub(crate) bump: i32,
+}
+
+impl Lighting {
+    pub(crate) fn new(
+        light: &Light,
+        position: &LightPosition,
+        texels_per_unit: usize,
+        len: usize,
+    ) -> Self {
+        let fixed = |value: f64| (value.clamp(0.0, 1.0) * 256.0) as u16;
+        let signed = |value: f64| (value.clamp(-1.0, 1.0) * 256.0) as i16;
+        // Half-Lambert, so that the shading wraps smoothly around the far side.
+        let angular = (0..ANGLE_STEPS)
+            .map(|step| {
+                let turn = step as f64 / ANGLE_STEPS as f64 - position.angle;
+                let facing = 0.5 + 0.5 * (2.0 * PI * turn).cos();
+                fixed(facing * facing)
+            })
+            .collect();
+        // From a wall to the light circling inside the tunnel: sideways and out from it.
+        let around = (0..ANGLE_STEPS)
+            .map(|step| {
+                let turn = position.angle - step as f64 / ANGLE_STEPS as f64;
+                let (sin, cos) = (2.0 * PI * turn).sin_cos();
+                let (sideways, out) = (ORBIT_RADIUS * sin, 1.0 - ORBIT_RADIUS * cos);
+                signed(sideways / sideways.hypot(out))
+            })
+            .collect();
+        let falloff = light.falloff.max(f64::EPSILON);
+        let distances = (0..len).map(|texels| {
+            let depth = texels as f64 / texels_per_unit as f64;
+            (position.depth - depth) / falloff
+        });
+        let radial = distances
+            .clone()
+            .map(|distance| fixed(1.0 / (1.0 + distance * distance)))
+            .collect();
+        let along = distances
+            .map(|distance| signed(distance / distance.hypot(1.0)))
+            .collect();
+        let ambient = light.ambient.clamp(0.0, 1.0);
+        Self {
+            angular,
+            radial,
+            around,
+            along,
+            ambient: (ambient * 256.0) as u32,
+            scale: ((1.0 - ambient) * position.intensity.clamp(0.0, 1.0) * 256.0) as u32,
+            bump: (light.bump.max(0.0) * 256.0) as i32,
+        }
+    }
+
+    /// The brightness, in 1/256ths, of the wall at `angle` (the LUT's 16-bit turn) and
+    /// `depth` texels down the tunnel, lit as if flat.
+    pub(crate) fn brightness(&self, angle: u16, depth: usize) -> u32 {
+        self.shade(angle, depth, 256)
+    }
+
+    /// The brightness of a wall that rises by `slope` (along, around; see
+    /// `HeightMap::slope`): the dot product of its tilted normal with the way to the light,
+    /// relative to a flat wall's, brightens slopes facing the light and darkens the others.
+    pub(crate) fn bumped_brightness(&self, angle: u16, depth: usize, slope: (i32, i32)) -> u32 {
+        let (along, around) = (
+            self.along[depth.min(self.along.len() - 1)] as i32,
+            self.around[angle_step(angle)] as i32,
+        );
+        let tilt = (self.bump * (slope.0 * along + slope.1 * around)) >> 12;
+        self.shade(angle, depth, (256 - tilt).clamp(0, 512) as u32)
+    }
+
+    fn shade(&self, angle: u16, depth: usize, relief: u32) -> u32 {
+        let angular = self.angular[angle_step(angle)] as u32;
+        let radial = self.radial[depth.min(self.radial.len() - 1)] as u32;
+        let direct = (self.scale * angular * radial) >> 16;
+        (self.ambient + ((direct * relief) >> 8)).min(256)
+    }
+}
+
+fn angle_step(angle: u16) -> usize {
+    (angle >> (16 - ANGLE_BITS)) as usize
+}
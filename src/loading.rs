@@ -0,0 +1,50 @@
+use tunnel::font;
+
+const SCALE: usize = 2;
+const MARGIN: usize = 8;
+const GAP: usize = 8;
+const BAR_WIDTH: usize = 240;
+const BAR_HEIGHT: usize = 6;
+/// Width of the highlight sweeping along the bar, and its sweeps per second.
+const GLINT_WIDTH: usize = 24;
+const GLINT_SPEED: f64 = 0.8;
+const BACKGROUND: [u8; 4] = [0x00, 0x00, 0x00, 0xff];
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const TRACK_COLOR: [u8; 4] = [0x30, 0x30, 0x30, 0xff];
+const BAR_COLOR: [u8; 4] = [0xff, 0xc0, 0x40, 0xff];
+const GLINT_COLOR: [u8; 4] = [0xff, 0xf0, 0xc0, 0xff];
+
+/// Clears the frame and draws `progress` (0 to 1) as a bar in the middle. A highlight keeps
+/// sweeping along the filled part, so the window looks alive even when the bar doesn't move.
+pub fn draw(frame: &mut [u8], width: usize, height: usize, progress: f64, time: f64) {
+    for pixel in frame.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&BACKGROUND);
+    }
+
+    let text = format!("LOADING {:.0}%", progress * 100.0);
+    let text_height = font::line_height(SCALE);
+    let top = height.saturating_sub(text_height + GAP + BAR_HEIGHT) / 2;
+    let text_left = width.saturating_sub(font::text_width(&text, SCALE)) / 2;
+    font::draw_text(frame, width, (text_left, top), SCALE, TEXT_COLOR, &text);
+
+    let bar_width = BAR_WIDTH.min(width.saturating_sub(MARGIN * 2));
+    let left = (width - bar_width) / 2;
+    let filled = (progress.clamp(0.0, 1.0) * bar_width as f64) as usize;
+    let sweep = (time * GLINT_SPEED).fract() * (bar_width + GLINT_WIDTH) as f64;
+    let glint = (sweep as usize).saturating_sub(GLINT_WIDTH)..sweep as usize;
+    let bar_top = top + text_height + GAP;
+    for y in bar_top..(bar_top + BAR_HEIGHT).min(height) {
+        let start = (y * width + left) * 4;
+        let row = &mut frame[start..start + bar_width * 4];
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let color = if x >= filled {
+                TRACK_COLOR
+            } else if glint.contains(&x) {
+                GLINT_COLOR
+            } else {
+                BAR_COLOR
+            };
+            pixel.copy_from_slice(&color);
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::error::Error;
+#[cfg(feature = "chrome")]
+use std::sync::Mutex;
+
+#[cfg(any(feature = "chrome", feature = "tracy"))]
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, Layer};
+
+use crate::cli::Args;
+
+/// Keeps the Chrome trace writer running until `finish`, since the event loop exits the
+/// process without dropping anything.
+#[cfg(feature = "chrome")]
+static CHROME: Mutex<Option<tracing_chrome::FlushGuard>> = Mutex::new(None);
+
+/// Prints messages up to `--log-level` to stderr and hands the spans around every stage of a
+/// frame to the profilers asked for. Those see debug spans too, and the one around every row
+/// at the trace level.
+pub fn init(args: &Args) -> Result<(), Box<dyn Error>> {
+    #[cfg(any(feature = "chrome", feature = "tracy"))]
+    let profile = args.log_level.max(LevelFilter::DEBUG);
+    let log = fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .with_filter(args.log_level);
+
+    #[cfg(feature = "chrome")]
+    let chrome = match &args.trace_chrome {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .map_err(|err| format!("can't write a trace to {}: {}", path.display(), err))?;
+            let (layer, guard) = tracing_chrome::ChromeLayerBuilder::new()
+                .writer(file)
+                .include_args(true)
+                .build();
+            *CHROME.lock().unwrap_or_else(|err| err.into_inner()) = Some(guard);
+            Some(layer.with_filter(profile))
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "chrome"))]
+    let chrome: Option<tracing_subscriber::layer::Identity> = None;
+
+    #[cfg(feature = "tracy")]
+    let tracy = args
+        .tracy
+        .then(|| tracing_tracy::TracyLayer::default().with_filter(profile));
+    #[cfg(not(feature = "tracy"))]
+    let tracy: Option<tracing_subscriber::layer::Identity> = None;
+
+    tracing_subscriber::registry()
+        .with(log)
+        .with(chrome)
+        .with(tracy)
+        .try_init()?;
+    Ok(())
+}
+
+/// Writes out what is left of the Chrome trace.
+pub fn finish() {
+    #[cfg(feature = "chrome")]
+    CHROME.lock().unwrap_or_else(|err| err.into_inner()).take();
+}
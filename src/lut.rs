@@ -0,0 +1,356 @@
+use std::borrow::Cow;
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+use tracing::{debug, debug_span};
+
+use crate::geometry::TunnelGeometry;
+use crate::world::TunnelParams;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod cache;
+
+/// Stand-in for the cache on the web, where there is no file system to keep tables in.
+#[cfg(target_arch = "wasm32")]
+mod cache {
+    use crate::geometry::TunnelGeometry;
+    use crate::world::TunnelParams;
+
+    pub(super) fn key(_params: &TunnelParams, _geometry: &dyn TunnelGeometry) -> Option<String> {
+        None
+    }
+
+    pub(super) fn load(_key: &str, _len: usize) -> Option<Vec<u16>> {
+        None
+    }
+
+    pub(super) fn store(_key: &str, _entries: &[u16]) {}
+}
+
+pub(crate) const DISTANCE_BITS: u32 = 4;
+/// Stored distances are this divided by the radius, which is the depth in texels for the
+/// default ratio and texture height; renderers scale it to the current ones.
+pub(crate) const DEPTH_REFERENCE: f64 = 64.0 * 256.0;
+pub(crate) const MAX_DISTANCE: f64 = (u16::MAX >> DISTANCE_BITS) as f64;
+const ANGLE_STEPS: f64 = (1 << 16) as f64;
+const HALF_TURN: u16 = 1 << 15;
+
+/// How much of the lookup table is kept in memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LutQuality {
+    /// Every entry.
+    High,
+    /// Only the rows down to the tunnel axis; the others mirror them through the axis. Exact
+    /// for point-symmetric geometries at half the memory, the same as `High` for the rest.
+    Medium,
+    /// Like `Medium`, but only every other entry in each direction, interpolated on lookup:
+    /// an eighth of the memory, slightly soft right around the vanishing point.
+    Low,
+}
+
+impl LutQuality {
+    pub const ALL: [LutQuality; 3] = [LutQuality::High, LutQuality::Medium, LutQuality::Low];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LutQuality::High => "high",
+            LutQuality::Medium => "medium",
+            LutQuality::Low => "low",
+        }
+    }
+}
+
+impl FromStr for LutQuality {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|quality| quality.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|quality| quality.name()).collect();
+                format!(
+                    "unknown LUT quality `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// What a lookup table holds and how it is stored, see `World::lut_stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LutStats {
+    /// Entries across and down the area the table covers.
+    pub width: usize,
+    pub height: usize,
+    /// Entries actually kept in memory and their size in bytes.
+    pub stored: usize,
+    pub bytes: usize,
+    pub mirrored: bool,
+    pub step: usize,
+    /// Smallest, mean and largest stored distance, in texels at the default ratio and
+    /// texture height.
+    pub distance: (f64, f64, f64),
+    /// Share of the entries so close to the axis that their distance is cut off.
+    pub saturated: f64,
+}
+
+/// How many rows of a table being built are done, readable from other threads.
+#[derive(Default)]
+pub(crate) struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl Progress {
+    /// Between 0 and 1.
+    pub(crate) fn fraction(&self) -> f64 {
+        let total = self.total.load(Ordering::Relaxed);
+        match total {
+            0 => 0.0,
+            _ => self.done.load(Ordering::Relaxed) as f64 / total as f64,
+        }
+    }
+}
+
+/// Distance and angle for every pixel of a 2W×2H area centred on the tunnel axis, so that the
+/// camera can look around without recomputing it. Distances are reciprocals of the radius,
+/// independent of the ratio and texture, so that both can change without rebuilding it.
+pub(crate) struct Lut {
+    width: usize,
+    height: usize,
+    /// Whether rows below the axis are mirrored from the ones above.
+    mirrored: bool,
+    /// 1 to store every entry, 2 for every other one.
+    step: usize,
+    stride: usize,
+    entries: Vec<u16>,
+}
+
+impl Lut {
+    pub(crate) fn new(params: &TunnelParams, geometry: &dyn TunnelGeometry) -> Self {
+        Self::with_progress(params, geometry, &Progress::default())
+    }
+
+    /// Reads the entries from the cache if they are there; otherwise fills the rows in
+    /// parallel on the rayon pool, counting them in `progress`, and caches the result.
+    pub(crate) fn with_progress(
+        params: &TunnelParams,
+        geometry: &dyn TunnelGeometry,
+        progress: &Progress,
+    ) -> Self {
+        let TunnelParams {
+            width,
+            height,
+            lut_quality,
+            ..
+        } = *params;
+        let _span = debug_span!("lut", width, height, geometry = geometry.name()).entered();
+
+        let w = width as f64;
+        let h = height as f64;
+        let width = width as usize * 2;
+        let height = height as usize * 2;
+
+        let mirrored = lut_quality != LutQuality::High && geometry.point_symmetric();
+        let step = if lut_quality == LutQuality::Low { 2 } else { 1 };
+        // The mirror of column 0 is column `width`, and interpolation reads one entry further.
+        let (last_x, last_y) = match mirrored {
+            true => (width, height / 2),
+            false => (width - 1, height - 1),
+        };
+        let stride = last_x / step + step;
+        let rows = last_y / step + step;
+
+        let key = cache::key(params, geometry);
+        let cached = key
+            .as_deref()
+            .and_then(|key| cache::load(key, stride * rows * 2));
+        debug!(cached = cached.is_some(), "building the lookup table");
+        let entries = cached.unwrap_or_else(|| {
+            progress.total.store(rows, Ordering::Relaxed);
+            let mut entries = vec![0u16; stride * rows * 2];
+            entries
+                .par_chunks_exact_mut(stride * 2)
+                .enumerate()
+                .for_each(|(j, row)| {
+                    let yf = (j * step) as f64;
+                    for (i, entry) in row.chunks_exact_mut(2).enumerate() {
+                        let xf = (i * step) as f64;
+                        let (radius, angle) = geometry.project(xf - w, yf - h);
+                        let distance = (DEPTH_REFERENCE / radius).min(MAX_DISTANCE);
+                        let turn = (angle / (2.0 * PI)).rem_euclid(1.0);
+                        entry[0] = (distance * (1 << DISTANCE_BITS) as f64) as u16;
+                        entry[1] = (turn * ANGLE_STEPS) as u32 as u16;
+                    }
+                    progress.done.fetch_add(1, Ordering::Relaxed);
+                });
+            if let Some(key) = &key {
+                cache::store(key, &entries);
+            }
+            entries
+        });
+
+        Self {
+            width,
+            height,
+            mirrored,
+            step,
+            stride,
+            entries,
+        }
+    }
+
+    /// A table without entries, to hold the place of one still being built.
+    pub(crate) fn empty() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            mirrored: false,
+            step: 1,
+            stride: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The whole table with its width and height in entries, if it is stored uncompressed.
+    pub(crate) fn full(&self) -> Option<(usize, usize, &[u16])> {
+        (!self.mirrored && self.step == 1).then_some((self.width, self.height, &self.entries))
+    }
+
+    /// `len` (distance, angle) pairs of row `y`, starting at column `x`.
+    pub(crate) fn row(&self, y: usize, x: usize, len: usize) -> Cow<'_, [u16]> {
+        let len = len.min(self.width - x);
+        if self.step == 1 && !self.is_mirror(y) {
+            let start = (y * self.stride + x) * 2;
+            return Cow::Borrowed(&self.entries[start..start + len * 2]);
+        }
+        Cow::Owned((x..x + len).flat_map(|x| self.entry(x, y)).collect())
+    }
+
+    /// Like `row`, but taking every `step`th column (rounded down) for frames drawn at a
+    /// lower resolution than the table was built for.
+    pub(crate) fn sampled_row(&self, y: usize, x: usize, len: usize, step: f64) -> Cow<'_, [u16]> {
+        if step == 1.0 {
+            return self.row(y, x, len);
+        }
+        Cow::Owned(
+            (0..len)
+                .flat_map(|i| self.entry(x + (i as f64 * step) as usize, y))
+                .collect(),
+        )
+    }
+
+    pub(crate) fn stats(&self) -> LutStats {
+        let scale = (1 << DISTANCE_BITS) as f64;
+        let stored = self.entries.len() / 2;
+        let distances = self.entries.iter().step_by(2);
+        let (min, max, sum, saturated) = distances.fold(
+            (u16::MAX, 0, 0u64, 0usize),
+            |(min, max, sum, saturated), &distance| {
+                let cut = distance as f64 >= MAX_DISTANCE * scale - 1.0;
+                (
+                    min.min(distance),
+                    max.max(distance),
+                    sum + distance as u64,
+                    saturated + cut as usize,
+                )
+            },
+        );
+        LutStats {
+            width: self.width,
+            height: self.height,
+            stored,
+            bytes: self.entries.len() * 2,
+            mirrored: self.mirrored,
+            step: self.step,
+            distance: (
+                min.min(max) as f64 / scale,
+                sum as f64 / stored.max(1) as f64 / scale,
+                max as f64 / scale,
+            ),
+            saturated: saturated as f64 / stored.max(1) as f64,
+        }
+    }
+
+    /// The whole area the table covers as a tightly packed RGBA image: the red channel is the
+    /// distance on a log scale from the nearest entry (dark) to the farthest (bright) and
+    /// green the angle once around the tunnel.
+    pub(crate) fn visualize(&self) -> Vec<u8> {
+        let stats = self.stats();
+        let scale = (1 << DISTANCE_BITS) as f64;
+        let (near, _, far) = stats.distance;
+        let (near, far) = (near.max(1.0 / scale).ln(), far.max(1.0 / scale).ln());
+        let mut image = vec![0u8; self.width * self.height * 4];
+        image
+            .par_chunks_exact_mut(self.width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (pixel, entry) in row
+                    .chunks_exact_mut(4)
+                    .zip(self.row(y, 0, self.width).chunks_exact(2))
+                {
+                    let distance = (entry[0] as f64 / scale).max(1.0 / scale).ln();
+                    let depth = (distance - near) / (far - near).max(f64::EPSILON);
+                    pixel.copy_from_slice(&[(depth * 255.0) as u8, (entry[1] >> 8) as u8, 0, 0xff]);
+                }
+            });
+        image
+    }
+
+    /// The entries at `columns` of row `y`, clamped to the table.
+    pub(crate) fn gathered_row(&self, y: usize, columns: impl Iterator<Item = usize>) -> Vec<u16> {
+        let last = self.width.saturating_sub(1);
+        columns.flat_map(|x| self.entry(x.min(last), y)).collect()
+    }
+
+    /// The entry at column `x` of row `y`, clamped to the table.
+    pub(crate) fn clamped_entry(&self, x: isize, y: isize) -> [u16; 2] {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.entry(x, y)
+    }
+
+    fn is_mirror(&self, y: usize) -> bool {
+        self.mirrored && y > self.height / 2
+    }
+
+    fn entry(&self, x: usize, y: usize) -> [u16; 2] {
+        if self.is_mirror(y) {
+            let [distance, angle] = self.stored(self.width - x, self.height - y);
+            return [distance, angle.wrapping_add(HALF_TURN)];
+        }
+        self.stored(x, y)
+    }
+
+    fn stored(&self, x: usize, y: usize) -> [u16; 2] {
+        if self.step == 1 {
+            return self.at(x, y);
+        }
+        let (i, j) = (x / 2, y / 2);
+        match (x % 2, y % 2) {
+            (0, 0) => self.at(i, j),
+            (1, 0) => mid(self.at(i, j), self.at(i + 1, j)),
+            (0, 1) => mid(self.at(i, j), self.at(i, j + 1)),
+            _ => mid(
+                mid(self.at(i, j), self.at(i + 1, j)),
+                mid(self.at(i, j + 1), self.at(i + 1, j + 1)),
+            ),
+        }
+    }
+
+    fn at(&self, i: usize, j: usize) -> [u16; 2] {
+        let k = (j * self.stride + i) * 2;
+        [self.entries[k], self.entries[k + 1]]
+    }
+}
+
+fn mid(a: [u16; 2], b: [u16; 2]) -> [u16; 2] {
+    let distance = ((a[0] as u32 + b[0] as u32) / 2) as u16;
+    // Angles wrap around, so step halfway along the shorter way between them.
+    let angle = a[1].wrapping_add((b[1].wrapping_sub(a[1]) as i16 / 2) as u16);
+    [distance, angle]
+}
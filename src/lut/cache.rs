@@ -0,0 +1,92 @@
+//! Lookup tables saved under the user's cache directory, so that each configuration is only
+//! computed once. The cache is best effort: a table that can't be read or written is simply
+//! computed again.
+
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::geometry::TunnelGeometry;
+use crate::world::TunnelParams;
+
+const MAGIC: &[u8] = b"TLUT";
+const VERSION: u8 = 2;
+/// Each table can take over 100 MB, so only the most recently used ones are kept.
+const MAX_FILES: usize = 8;
+
+/// Names the table for `params` and `geometry`, or `None` if it shouldn't be cached.
+pub(super) fn key(params: &TunnelParams, geometry: &dyn TunnelGeometry) -> Option<String> {
+    if !params.lut_cache {
+        return None;
+    }
+    Some(format!(
+        "{}-{}x{}-{}",
+        geometry.cache_key()?,
+        params.width,
+        params.height,
+        params.lut_quality.name()
+    ))
+}
+
+fn dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("tunnel-rs"))
+}
+
+/// The `len` entries stored under `key`, if the file is there and has exactly that many.
+pub(super) fn load(key: &str, len: usize) -> Option<Vec<u16>> {
+    let path = dir()?.join(format!("{}.lut", key));
+    let mut bytes = Vec::new();
+    File::open(&path).ok()?.read_to_end(&mut bytes).ok()?;
+    let data = bytes.strip_prefix(MAGIC)?.strip_prefix(&[VERSION])?;
+    if data.len() != len * 2 {
+        return None;
+    }
+    // Marks the table as recently used, so that pruning keeps it.
+    let _ = File::options()
+        .append(true)
+        .open(&path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    Some(
+        data.chunks_exact(2)
+            .map(|bytes| u16::from_ne_bytes([bytes[0], bytes[1]]))
+            .collect(),
+    )
+}
+
+pub(super) fn store(key: &str, entries: &[u16]) {
+    let _ = try_store(key, entries);
+}
+
+fn try_store(key: &str, entries: &[u16]) -> io::Result<()> {
+    let dir = dir().ok_or(io::ErrorKind::NotFound)?;
+    fs::create_dir_all(&dir)?;
+    // Written under a temporary name and renamed, so that another instance starting at the
+    // same time never reads half a table.
+    let temp = dir.join(format!("{}.{}.tmp", key, std::process::id()));
+    let mut file = BufWriter::new(File::create(&temp)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&[VERSION])?;
+    // In native byte order, as the cache never leaves the machine.
+    file.write_all(bytemuck::cast_slice(entries))?;
+    file.into_inner()?;
+    fs::rename(&temp, dir.join(format!("{}.lut", key)))?;
+    prune(&dir)
+}
+
+/// Deletes all but the `MAX_FILES` most recently used tables.
+fn prune(dir: &Path) -> io::Result<()> {
+    let mut tables = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "lut") {
+            tables.push((fs::metadata(&path)?.modified()?, path));
+        }
+    }
+    tables.sort_by_key(|(modified, _)| Reverse(*modified));
+    for (_, path) in tables.into_iter().skip(MAX_FILES) {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
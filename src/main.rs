@@ -1,187 +1,1653 @@
 #![deny(clippy::all)]
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 
-use std::f64::consts::PI;
-use std::time::SystemTime;
+#[cfg(not(target_arch = "wasm32"))]
+mod ambient;
+mod animation;
+mod attract;
+#[cfg(not(target_arch = "wasm32"))]
+mod bench;
+#[cfg(feature = "audio")]
+mod capture;
+mod cli;
+mod config;
+#[cfg(not(target_arch = "wasm32"))]
+mod framebuffer;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+mod help;
+mod keymap;
+#[cfg(not(target_arch = "wasm32"))]
+mod led;
+mod loading;
+#[cfg(not(target_arch = "wasm32"))]
+mod logging;
+mod menu;
+#[cfg(feature = "midi")]
+mod midi;
+#[cfg(feature = "audio")]
+mod music;
+#[cfg(feature = "ndi")]
+mod ndi;
+#[cfg(not(target_arch = "wasm32"))]
+mod offline;
+mod overlay;
+#[cfg(not(target_arch = "wasm32"))]
+mod pacing;
+#[cfg(not(target_arch = "wasm32"))]
+mod performance;
+#[cfg(not(target_arch = "wasm32"))]
+mod power;
+mod recorder;
+mod reduced_motion;
+#[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+mod remote;
+mod scaler;
+mod screensaver;
+#[cfg(not(target_arch = "wasm32"))]
+mod screenshot;
+#[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+mod script;
+mod span;
+mod surface;
+#[cfg(not(target_arch = "wasm32"))]
+mod sync;
+#[cfg(feature = "terminal")]
+mod terminal;
+mod touch;
+#[cfg(feature = "ui")]
+mod ui;
+mod wallpaper;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
-use pixels::{Error, Pixels, SurfaceTexture};
-use winit::dpi::LogicalSize;
-use winit::event::{Event, VirtualKeyCode};
+use std::error::Error;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use animation::Animation;
+use attract::Attract;
+use cli::{Backend, FullscreenMode, ReducedMotion};
+use config::Config;
+use help::Help;
+use keymap::{Action, Keymap};
+use menu::{Choice, Menu};
+use overlay::Overlay;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use recorder::Recorder;
+use scaler::ResolutionScaler;
+use screensaver::Dice;
+use span::{Layout, Span};
+use surface::Recovery;
+use touch::{Gesture, Touches};
+use tracing::{error, info, info_span, warn};
+use tunnel::postfx::DEFAULT_TRAIL;
+use tunnel::time::Instant;
+use tunnel::{
+    color_map, effect, geometry, texture, Atlas, Comparison, Compositor, Effect, Filter, Flight,
+    Fog, GpuRenderer, HeightMap, Light, Modulator, PaletteKind, PassKind, Pipeline, Renderer,
+    Shape, Split, Stage, Stamp, Texture, Timeline, TunnelParams, World, HDR_FORMAT,
+};
+use ui::Ui;
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event::{Event, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::WindowBuilder;
+use winit::window::{Fullscreen, Window, WindowBuilder};
 use winit_input_helper::WinitInputHelper;
 
-const WIDTH: u32 = 1200;
-const HEIGHT: u32 = 900;
+const STEER_SPEED: f64 = 600.0;
+/// Turns per second Shift+Q and Shift+E roll the view by, and the auto-roll Y switches on
+/// when `--auto-roll` doesn't set one.
+const ROLL_SPEED: f64 = 0.25;
+const DEFAULT_ROLL_SPEED: f64 = 0.05;
+/// Range the ratio can be zoomed through at runtime.
+const MIN_RATIO: f64 = 8.0;
+const MAX_RATIO: f64 = 512.0;
+/// Feedback E switches on when `--feedback` doesn't set any.
+const DEFAULT_FEEDBACK: f64 = 0.5;
+/// Factor one notch of the mouse wheel scales the ratio by.
+const WHEEL_ZOOM: f64 = 1.1;
+/// How much `[` and `]` shorten or lengthen the motion blur trail.
+const TRAIL_STEP: f32 = 0.05;
+/// How much `=` and `-` change the travel speed by, in texture lengths per second, and how
+/// fast they let it get either way.
+const TRAVEL_STEP: f64 = 0.25;
+const MAX_TRAVEL: f64 = 8.0;
+/// How many frame pixels off the split-screen divider a mouse press may be to grab it.
+const DIVIDER_GRAB: usize = 8;
+const RESIZE_DELAY: Duration = Duration::from_millis(250);
+
+const BREATHING_SHAPES: [Shape; 4] = [
+    Shape::Constant,
+    Shape::Sine,
+    Shape::Triangle,
+    Shape::Envelope {
+        attack: 0.1,
+        release: 0.25,
+    },
+];
+const BREATHING_DEPTH: f64 = 0.25;
+const BREATHING_FREQUENCY: f64 = 0.5;
+
+/// Stand-in for the parameter window when built without the `ui` feature.
+#[cfg(not(feature = "ui"))]
+mod ui {
+    use pixels::wgpu::{CommandEncoder, TextureView};
+    use pixels::{Pixels, PixelsContext};
+    use tunnel::World;
+    use winit::event::WindowEvent;
+    use winit::window::Window;
+
+    pub struct Ui;
+
+    impl Ui {
+        pub fn new(_window: &Window, _pixels: &Pixels) -> Self {
+            Ui
+        }
+
+        pub fn toggle(&mut self) {}
+
+        pub fn handle_event(&mut self, _event: &WindowEvent) {}
+
+        pub fn wants_pointer(&self) -> bool {
+            false
+        }
+
+        pub fn prepare(&mut self, _window: &Window, _world: &mut World, _threads: &mut usize) {}
+
+        pub fn render(
+            &mut self,
+            _encoder: &mut CommandEncoder,
+            _target: &TextureView,
+            _context: &PixelsContext,
+        ) {
+        }
+    }
+}
+
+// Public so the `mobile` example, which builds this file as a library, doesn't flag it as unused.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn main() -> Result<(), Box<dyn Error>> {
+    use cli::Command;
+
+    let Some(args) = screensaver::translate_args(std::env::args().collect()) else {
+        return Ok(());
+    };
+    let command = cli::Cli::parse_args(args).command();
+    logging::init(command.args())?;
+    let result = match command {
+        #[cfg(feature = "terminal")]
+        Command::Run(args) if args.output != cli::Output::Window => terminal::play(&args),
+        Command::Run(args) if args.wallpaper && wallpaper::on_wayland() => wallpaper::play(&args),
+        Command::Run(args) => pollster::block_on(run(args)),
+        Command::Render(render) => offline::render(&render),
+        Command::RenderStill(still) => offline::render_still(&still),
+        Command::Bench(bench) => offline::bench(&bench),
+        Command::InspectLut(inspect) => offline::inspect_lut(&inspect),
+        Command::Framebuffer(framebuffer) => framebuffer::play(&framebuffer),
+        Command::ExportShader(export) => offline::export_shader(&export),
+    };
+    logging::finish();
+    result
+}
+
+/// Plays the default tunnel for the phone entry points, which start without a command line.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+fn start() {
+    use clap::Parser;
 
-struct World {
-    tex_width: usize,
-    tex_height: usize,
-    texture: Vec<u32>,
-    distances: Vec<Vec<u32>>,
-    angles: Vec<Vec<u32>>,
-    clock: f64,
+    let args = cli::Args::parse_from(["tunnel-rs"]);
+    if let Err(err) = logging::init(&args) {
+        eprintln!("logging: {err}");
+    }
+    if let Err(err) = pollster::block_on(run(args)) {
+        error!("{err}");
+    }
+    logging::finish();
 }
 
-fn main() -> Result<(), Error> {
+#[cfg(target_os = "android")]
+#[allow(unsafe_code)]
+mod android {
+    // Expands to the `ANativeActivity_onCreate` export the NativeActivity looks up in the cdylib,
+    // which runs this on its own thread once the activity is created.
+    #[ndk_glue::main(backtrace = "on")]
+    fn android_main() {
+        crate::start()
+    }
+}
+
+#[cfg(target_os = "ios")]
+#[allow(unsafe_code)]
+mod ios {
+    /// Called from the `main` of the Xcode app the staticlib is linked into, on the main thread.
+    /// Doesn't return: winit's event loop takes the thread over.
+    #[no_mangle]
+    pub extern "C" fn tunnel_main() {
+        crate::start()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    console_error_panic_hook::set_once();
+    wasm_bindgen_futures::spawn_local(async {
+        let result = match web::args() {
+            Ok(args) => run(args).await,
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = result {
+            web_sys::console::error_1(&err.to_string().into());
+        }
+    });
+}
+
+/// What every subcommand starts from: the config file, the tunnel parameters and the first
+/// texture.
+struct Setup {
+    config_path: Option<PathBuf>,
+    config: Config,
+    params: TunnelParams,
+    texture_path: Option<PathBuf>,
+    animation: Option<Animation>,
+    texture: Texture,
+    timeline: Option<Timeline>,
+    height_map: Option<Arc<HeightMap>>,
+    atlas: Option<Atlas>,
+    stamps: Vec<Stamp>,
+}
+
+fn setup(args: &cli::Args) -> Result<Setup, Box<dyn Error>> {
+    let config_path = args.config.clone().or_else(|| {
+        let path = PathBuf::from(config::DEFAULT_PATH);
+        path.exists().then_some(path)
+    });
+    let config = config_path
+        .as_deref()
+        .map(Config::load)
+        .transpose()?
+        .unwrap_or_default()
+        .under(args);
+    if let Some(threads) = args.threads.map(NonZeroUsize::get).or(config.threads) {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let defaults = TunnelParams::default();
+    let resolution = args.resolution(config.resolution.unwrap_or(cli::Resolution {
+        width: defaults.width,
+        height: defaults.height,
+    }));
+    let params = TunnelParams {
+        width: resolution.width,
+        height: resolution.height,
+        lut_quality: args.lut_quality,
+        lut_cache: !args.no_lut_cache,
+        ..defaults
+    };
+    let texture_path = args.texture.clone().or_else(|| config.texture.clone());
+    let mut animation = match &texture_path {
+        Some(path) => Animation::open(path, args.texture_fps)?,
+        None => None,
+    };
+    let texture = match (&mut animation, &texture_path) {
+        (Some(animation), _) => animation.first_frame()?,
+        (None, Some(path)) => Texture::load(path)?,
+        (None, None) => Texture::xor(params.tex_width, params.tex_height),
+    };
+    let timeline = args.timeline.as_ref().map(Timeline::load).transpose()?;
+    let height_map = args.bump_map.as_ref().map(HeightMap::load).transpose()?;
+    let atlas = args.atlas.as_ref().map(Atlas::load).transpose()?;
+    let stamps = args.stamps()?;
+    Ok(Setup {
+        config_path,
+        config,
+        params,
+        texture_path,
+        animation,
+        texture,
+        timeline,
+        height_map: height_map.map(Arc::new),
+        atlas,
+        stamps,
+    })
+}
+
+/// The effect stack over `effects` and those of the plugins, the stereo output, the
+/// split-screen comparison, the scroller and the post-processing the command line asks for.
+fn pipeline(args: &cli::Args, effects: Vec<Box<dyn Effect>>) -> Result<Pipeline, Box<dyn Error>> {
+    #[cfg(all(feature = "plugins", not(target_arch = "wasm32")))]
+    let effects = {
+        let mut effects = effects;
+        if let Some(dir) = args.plugins_dir() {
+            effects.extend(effect::plugin::load(&dir));
+        }
+        effects
+    };
+    let mut compositor = Compositor::new(0);
+    if let Some(name) = &args.background {
+        let background = effects
+            .iter()
+            .position(|effect| effect.name() == name)
+            .ok_or_else(|| format!("unknown effect `{}`", name))?;
+        compositor.stages.insert(0, Stage::new(background));
+    }
+    *compositor.top() = Stage {
+        effect: 0,
+        mode: args.blend,
+        opacity: args.opacity,
+    };
+    let mut pipeline = Pipeline::new(compositor, effects);
+    pipeline.stereo = args.stereo();
+    if args.split == Some(Comparison::Backend) && args.backend != Backend::Gpu {
+        return Err("`--split backend` needs `--backend gpu`".into());
+    }
+    pipeline.split = args.split();
+    pipeline.postfx = args.postfx()?;
+    pipeline.scroller = args.scroller()?;
+    Ok(pipeline)
+}
+
+async fn run(args: cli::Args) -> Result<(), Box<dyn Error>> {
+    let Setup {
+        config_path,
+        config,
+        params,
+        texture_path,
+        mut animation,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+    } = setup(&args)?;
+    let mut resolution = cli::Resolution {
+        width: params.width,
+        height: params.height,
+    };
+    let duration = args.duration.or_else(|| {
+        timeline
+            .as_ref()
+            .filter(|timeline| !timeline.repeats())
+            .map(Timeline::duration)
+    });
+    let mut renderer = Renderer::default();
+    renderer.set_simd(!args.scalar);
+    renderer.set_interlace(args.interlace);
+
     let event_loop = EventLoop::new();
     let mut input = WinitInputHelper::new();
+    let layout = args.span.then(|| Layout::new(&event_loop)).transpose()?;
+    let wallpaper = args
+        .wallpaper
+        .then(|| wallpaper::monitor(&event_loop))
+        .transpose()?;
+    // Spanning renders the whole wall at its native size unless told otherwise, and a
+    // wallpaper its monitor.
+    let explicit_resolution = config.resolution.is_some()
+        || args.resolution.is_some()
+        || args.width.is_some()
+        || args.height.is_some();
+    if let Some(layout) = layout.as_ref().filter(|_| !explicit_resolution) {
+        resolution = cli::Resolution {
+            width: layout.width,
+            height: layout.height,
+        };
+    }
+    if let Some(monitor) = wallpaper.as_ref().filter(|_| !explicit_resolution) {
+        let size = monitor.size();
+        resolution = cli::Resolution {
+            width: size.width,
+            height: size.height,
+        };
+    }
+    #[cfg(windows)]
+    if args.preview.is_some() {
+        let (width, height) = screensaver::PREVIEW_SIZE;
+        resolution = cli::Resolution { width, height };
+    }
+    let params = TunnelParams {
+        width: resolution.width,
+        height: resolution.height,
+        ..params
+    };
     let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
-        WindowBuilder::new()
-            .with_title("tunnel-rs")
-            .with_inner_size(size)
-            .with_min_inner_size(size)
-            .build(&event_loop)
-            .unwrap()
+        let size = LogicalSize::new(params.width as f64, params.height as f64);
+        let builder = WindowBuilder::new().with_title("tunnel-rs");
+        // The settings dialog hosts the preview as a child window of its preview box.
+        #[cfg(windows)]
+        let builder = match args.preview {
+            Some(parent) => {
+                use winit::platform::windows::WindowBuilderExtWindows;
+                builder
+                    .with_decorations(false)
+                    .with_parent_window(parent as winapi::shared::windef::HWND)
+            }
+            None => builder,
+        };
+        match (&layout, &wallpaper) {
+            (Some(layout), _) => builder
+                .with_fullscreen(Some(Fullscreen::Borderless(Some(layout.primary().clone())))),
+            (None, Some(monitor)) => wallpaper::window(builder, monitor)?,
+            (None, None) => builder.with_inner_size(size).with_min_inner_size(size),
+        }
+        .build(&event_loop)?
     };
+    #[cfg(target_arch = "wasm32")]
+    web::attach(&window);
+    if wallpaper.is_some() {
+        wallpaper::place(&window);
+    }
+    let fullscreen_mode = args.fullscreen.unwrap_or(FullscreenMode::Borderless);
+    #[cfg(windows)]
+    let previewing = args.preview.is_some();
+    #[cfg(not(windows))]
+    let previewing = false;
+    // A preview only ends when the settings dialog closes it.
+    let mut watcher = (args.screensaver && !previewing).then(screensaver::Watcher::default);
+    if args.fullscreen.is_some() || watcher.is_some() {
+        window.set_fullscreen(fullscreen(&window, fullscreen_mode));
+    }
+    if watcher.is_some() {
+        window.set_cursor_visible(false);
+    }
 
-    let mut pixels = {
-        let window_size = window.inner_size();
-        let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture)?
+    // Android only hands the window a surface with the first `Resumed`, and takes it away
+    // again with every `Suspended`.
+    let mut pixels = match cfg!(target_os = "android") {
+        true => None,
+        false => Some(
+            pixels_builder(&args, &window, params.width, params.height)
+                .build_async()
+                .await?,
+        ),
+    };
+    if let (Some(hdr), Some(pixels)) = (args.hdr(), &pixels) {
+        if pixels.render_texture_format() != HDR_FORMAT {
+            info!(
+                "HDR output needs Windows, tone mapping with {} instead",
+                hdr.tone_map.name()
+            );
+        }
+    }
+    let mut span = match (layout, &mut pixels) {
+        (Some(layout), Some(pixels)) => {
+            let present_mode = args.vsync.present_mode();
+            let (width, height) = (params.width, params.height);
+            Some(Span::new(layout, &event_loop, pixels, present_mode, width, height).await?)
+        }
+        _ => None,
+    };
+    // Threads can't be spawned on the web, so the page waits for the lookup table there.
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut world = World::in_background(params, texture);
+    #[cfg(target_arch = "wasm32")]
+    let mut world = World::with_texture(params, texture);
+    configure(&mut world, &args, timeline, height_map, atlas, stamps);
+    // Only the window follows the system, so that renders come out the same anywhere.
+    if args.reduced_motion == ReducedMotion::Auto && reduced_motion::preferred() {
+        info!("Reducing motion as the system is set to");
+        world.set_reduced_motion(true);
+    }
+    apply_config(&mut world, &config);
+    let mut keymap = config.keys.clone().unwrap_or_default();
+    if args.mouse_look {
+        world.camera_mut().toggle_follow();
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let config_watcher = config_path.as_deref().and_then(watch_file);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut texture_watcher = texture_path
+        .as_deref()
+        .filter(|_| animation.is_none())
+        .and_then(watch_file);
+    #[cfg(feature = "audio")]
+    let music = match &args.music {
+        Some(path) => {
+            let (music, track) = music::Music::load(path)?;
+            world.set_beat_track(Some(track));
+            // Fixed-step clocks run at their own pace, so only the beats are used there. The
+            // music starts with the first update that finds the world running.
+            (!world.clock().is_fixed()).then_some(music)
+        }
+        None => None,
     };
-    let mut world = World::new();
+    #[cfg(feature = "audio")]
+    let _capture = if args.listen {
+        let levels = std::sync::Arc::new(tunnel::AudioLevels::default());
+        world.set_audio_levels(Some(levels.clone()));
+        Some(capture::Capture::start(levels)?)
+    } else {
+        None
+    };
+    #[cfg(feature = "gamepad")]
+    let mut gamepad = gamepad::Gamepad::new()
+        .map_err(|err| warn!("Gamepads unavailable: {}", err))
+        .ok();
+    #[cfg(feature = "midi")]
+    let midi = args.midi.as_deref().map(midi::Midi::start).transpose()?;
+    #[cfg(feature = "ndi")]
+    let mut ndi_output = args.ndi.as_deref().map(ndi::NdiOutput::start).transpose()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut led_output = match &args.led {
+        Some(target) => {
+            let mut led =
+                led::LedOutput::start(target, args.led_protocol, args.led_size, args.led_gamma)?;
+            led.set_serpentine(args.led_serpentine);
+            led.set_universe(args.led_universe);
+            led.set_preview(!args.no_led_preview);
+            Some(led)
+        }
+        None => None,
+    };
+    let mut effects = effect::builtin();
+    effects[0] = Box::new(renderer);
+    let mut pipeline = pipeline(&args, effects)?;
+    let mut filter = Filter::Nearest;
+    let generators = texture::builtin(args.seed);
+    let geometries = geometry::builtin();
+    let mut geometry_index = 0;
+    // The generator the texture was last made with, for the menu to show; without a texture
+    // file the tunnel starts with the first one, XOR.
+    let mut generator_index = texture_path.is_none().then_some(0);
+    if args.screensaver || args.random {
+        let mut dice = Dice::from_clock();
+        // The first look shows up at once instead of fading in.
+        world.set_crossfade(0.0);
+        world.set_palette(PaletteKind::ALL[dice.pick(PaletteKind::ALL.len())]);
+        geometry_index = dice.pick(geometries.len());
+        world.set_geometry(geometries[geometry_index].clone());
+        if texture_path.is_none() {
+            let generators = texture::builtin(dice.next());
+            let params = world.params();
+            let index = dice.pick(generators.len());
+            world.set_texture(generators[index].generate(params.tex_width, params.tex_height));
+            generator_index = Some(index);
+        }
+        pipeline.compositor.top().effect = dice.pick(pipeline.effects.len());
+        if args.random {
+            let flight = attract::random_flight(&mut dice, world.flight());
+            world.set_flight(flight);
+        }
+        world.set_crossfade(args.crossfade);
+    }
+    let mut attract = args
+        .attract
+        .map(|interval| Attract::new(interval, Dice::from_clock(), texture_path.is_none()));
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut ambient = args.ambient.map(ambient::AmbientPalette::new);
+    #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+    let remote = args
+        .remote
+        .as_deref()
+        .map(|address| {
+            remote::Remote::start(address, &args.remote_allow, args.remote_rate, args.seed)
+        })
+        .transpose()?;
+    #[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+    let mut script = args
+        .script
+        .as_deref()
+        .map(script::Script::load)
+        .transpose()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut performance = args
+        .record_performance
+        .as_deref()
+        .map(performance::PerformanceRecorder::create)
+        .transpose()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut replay = args
+        .replay
+        .as_deref()
+        .map(|path| performance::Replay::load(path, args.seed))
+        .transpose()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut leader = args
+        .sync_lead
+        .as_deref()
+        .map(sync::SyncLeader::start)
+        .transpose()?;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut follower = args
+        .sync_follow
+        .as_deref()
+        .map(|address| sync::SyncFollower::listen(address, args.seed))
+        .transpose()?;
+    let mut breathing_index = 0;
+    // The travel speed to go back to when 0 resumes the flight.
+    let mut stopped_travel: Option<f64> = None;
+    let mut dragging_divider = false;
+    let mut overlay = Overlay::new();
+    let mut help = Help::new();
+    let mut touches = Touches::default();
+    let mut menu = Menu::new(
+        pipeline
+            .effects
+            .iter()
+            .map(|effect| effect.name().to_string())
+            .collect(),
+        generators
+            .iter()
+            .map(|generator| generator.name().to_string())
+            .collect(),
+        PaletteKind::ALL
+            .map(|kind| kind.name().to_string())
+            .to_vec(),
+        geometries
+            .iter()
+            .map(|geometry| geometry.name().to_string())
+            .collect(),
+    );
+    // Whether the clock was paused before the menu paused it.
+    let mut paused_before_menu = false;
+    let mut ui = pixels.as_ref().map(|pixels| Ui::new(&window, pixels));
+    let mut threads = rayon::current_num_threads();
+    // Replaces the global pool for drawing once the thread count is changed at runtime.
+    let mut pool: Option<rayon::ThreadPool> = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut limiter = args.max_fps.map(pacing::FrameLimiter::new);
+    let mut scaler = args
+        .adaptive_resolution
+        .filter(|_| args.backend == Backend::Cpu)
+        .map(ResolutionScaler::new);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut power = (args.battery_saver != cli::BatterySaver::Off)
+        .then(|| power::PowerSaver::new(args.battery_saver));
+    // The thread count to go back to once power no longer needs saving.
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut full_threads = None;
+    let mut gpu = pixels
+        .as_ref()
+        .filter(|_| args.backend == Backend::Gpu)
+        .map(|pixels| {
+            let format = pixels.render_texture_format();
+            GpuRenderer::with_hdr(&pixels.context().device, format, args.hdr())
+        });
+    let started = Instant::now();
+    let mut last_update = started;
+    let mut native_resolution = args.native_resolution;
+    let mut pending_resize = native_resolution.then(Instant::now);
+    let mut recorder: Option<Recorder> = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut take_screenshot = false;
+    let mut recovery = Recovery::default();
 
     event_loop.run(move |event, _, control_flow| {
-        if let Event::RedrawRequested(_) = event {
-            world.draw(pixels.get_frame());
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Event::LoopDestroyed = event {
+            logging::finish();
+            return;
+        }
+
+        // Mobile systems take the window's surface away while the app is in the background:
+        // the pixel buffer and the parameter window go with it and nothing is drawn until it
+        // comes back. The GPU renderer is kept for its settings and made anew on the new
+        // device.
+        #[cfg(not(target_arch = "wasm32"))]
+        match event {
+            Event::Suspended => {
+                ui = None;
+                pixels = None;
+            }
+            Event::Resumed if pixels.is_none() => {
+                let (width, height) = world.frame_size();
+                match pixels_builder(&args, &window, width, height).build() {
+                    Ok(rebuilt) => {
+                        let device = &rebuilt.context().device;
+                        let format = rebuilt.render_texture_format();
+                        gpu = match &gpu {
+                            Some(old) => {
+                                let mut gpu = GpuRenderer::with_hdr(device, format, old.hdr());
+                                gpu.set_filter(old.filter());
+                                Some(gpu)
+                            }
+                            None => (args.backend == Backend::Gpu)
+                                .then(|| GpuRenderer::with_hdr(device, format, args.hdr())),
+                        };
+                        ui = Some(Ui::new(&window, &rebuilt));
+                        pixels = Some(rebuilt);
+                        window.request_redraw();
+                    }
+                    Err(err) => {
+                        error!("Failed to create the surface: {}", err);
+                        *control_flow = ControlFlow::Exit;
+                    }
+                }
+            }
+            _ => {}
+        }
+        let (Some(pixels), Some(ui)) = (&mut pixels, &mut ui) else {
+            return;
+        };
 
-            if pixels.render().is_err() {
+        if let (
+            Some(span),
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            },
+        ) = (&mut span, &event)
+        {
+            span.resize_surface(*window_id, *size);
+        }
+
+        if let Event::WindowEvent { window_id, event } = &event {
+            if *window_id == window.id() {
+                if watcher.as_mut().is_some_and(|watcher| watcher.woken(event)) {
+                    *control_flow = ControlFlow::Exit;
+                    return;
+                }
+                ui.handle_event(event);
+                touches.handle_event(event);
+            }
+        }
+
+        // The other windows of a span are drawn along with the main one.
+        if matches!(event, Event::RedrawRequested(id) if id == window.id()) {
+            let _span = info_span!("frame").entered();
+            // The shader only replaces the plain tunnel; anything that reads or draws over the
+            // pixel buffer needs the CPU to fill it.
+            #[cfg(not(target_arch = "wasm32"))]
+            let capturing = recorder.is_some() || take_screenshot || led_output.is_some();
+            #[cfg(target_arch = "wasm32")]
+            let capturing = recorder.is_some();
+            #[cfg(feature = "ndi")]
+            let capturing = capturing || ndi_output.is_some();
+            let gpu_frame = gpu.as_mut().filter(|gpu| {
+                pipeline.compositor.stages.len() == 1
+                    && pipeline.compositor.stages[0].effect == 0
+                    && !pipeline.postfx.passes().any(|(_, enabled)| enabled)
+                    && !overlay.is_visible()
+                    && !help.is_visible()
+                    && !menu.is_open()
+                    && !capturing
+                    && span.is_none()
+                    && world.feedback() == 0.0
+                    && pipeline.stereo.is_none()
+                    && pipeline.split.is_none()
+                    && gpu.supports(&pixels.context().device, &world)
+            });
+            let mut draw_time = None;
+            let rendered = match (world.loading(), gpu_frame) {
+                (Some(progress), _) => {
+                    let (width, height) = world.frame_size();
+                    let (width, height) = (width as usize, height as usize);
+                    let time = started.elapsed().as_secs_f64();
+                    let frame = match &mut span {
+                        Some(span) => span.canvas(),
+                        None => pixels.get_frame(),
+                    };
+                    loading::draw(frame, width, height, progress, time);
+                    present(pixels, &mut span, ui)
+                }
+                (None, Some(gpu)) => {
+                    let _span = info_span!("present").entered();
+                    pixels.render_with(|encoder, target, context| {
+                        let clip_rect = context.scaling_renderer.clip_rect();
+                        let (device, queue) = (&context.device, &context.queue);
+                        gpu.render(&world, encoder, target, device, queue, clip_rect);
+                        ui.render(encoder, target, context);
+                        Ok(())
+                    })
+                }
+                (None, None) => {
+                    let frame = match &mut span {
+                        Some(span) => span.canvas(),
+                        None => pixels.get_frame(),
+                    };
+                    let draw_start = Instant::now();
+                    let (width, height) = world.frame_size();
+                    let mut draw = || pipeline.draw(&mut world, frame);
+                    let stats = match &pool {
+                        Some(pool) => pool.install(draw),
+                        None => draw(),
+                    };
+                    draw_time = Some(draw_start.elapsed());
+                    if let Some(recorder) = &recorder {
+                        recorder.push(frame, width, height);
+                    }
+                    #[cfg(feature = "ndi")]
+                    if let Some(output) = &mut ndi_output {
+                        output.push(frame, width, height);
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if take_screenshot {
+                        take_screenshot = false;
+                        screenshot::save(frame, width, height, &args.screenshot_dir);
+                    }
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(output) = &mut led_output {
+                        output.push(frame, width, height);
+                    }
+                    world.feed_back(frame, width, height);
+                    overlay.record(&stats);
+                    #[cfg(not(target_arch = "wasm32"))]
+                    if let Some(output) = &led_output {
+                        output.draw_preview(frame, width as usize);
+                    }
+                    overlay.draw(frame, width as usize);
+                    help.draw(frame, width as usize, &keymap, &world, &pipeline, filter);
+                    menu.draw(frame, width as usize);
+                    // The GPU's half of a backend comparison goes right of the divider.
+                    let split_gpu = pipeline
+                        .split
+                        .as_ref()
+                        .filter(|split| split.comparison == Comparison::Backend)
+                        .map(|split| (split.column(width as usize) + 1) as f64 / width as f64)
+                        .zip(gpu.as_mut())
+                        .filter(|(_, gpu)| {
+                            span.is_none() && gpu.supports(&pixels.context().device, &world)
+                        });
+                    match split_gpu {
+                        Some((split, gpu)) => {
+                            let _span = info_span!("present").entered();
+                            gpu.set_split(Some(split));
+                            let rendered = pixels.render_with(|encoder, target, context| {
+                                context.scaling_renderer.render(encoder, target);
+                                let clip_rect = context.scaling_renderer.clip_rect();
+                                let (device, queue) = (&context.device, &context.queue);
+                                gpu.render(&world, encoder, target, device, queue, clip_rect);
+                                ui.render(encoder, target, context);
+                                Ok(())
+                            });
+                            gpu.set_split(None);
+                            rendered
+                        }
+                        None => present(pixels, &mut span, ui),
+                    }
+                }
+            };
+            let recovered = recovery.check(rendered, || {
+                let size = window.inner_size();
+                pixels.resize_surface(size.width, size.height);
+                if let Some(span) = &mut span {
+                    span.reconfigure();
+                }
+            });
+            if let Err(err) = recovered {
+                error!("Failed to show the frame: {}", err);
                 *control_flow = ControlFlow::Exit;
                 return;
             }
+
+            // Recordings keep the size they started with.
+            let scale = draw_time
+                .filter(|_| recorder.is_none())
+                .zip(scaler.as_mut())
+                .and_then(|(draw_time, scaler)| scaler.record(draw_time));
+            if let Some(scale) = scale {
+                world.set_render_scale(scale);
+                let params = *world.params();
+                resize(&mut world, pixels, &mut span, params.width, params.height);
+            }
         }
 
         if input.update(&event) {
-            if input.key_pressed(VirtualKeyCode::Escape) || input.quit() {
+            let finished = duration.is_some_and(|duration| world.clock().time() >= duration);
+            let choice = if keymap.pressed(&input, Action::Menu) {
+                if menu.is_open() {
+                    Some(menu.escape())
+                } else {
+                    let effect = pipeline.compositor.top().effect;
+                    let palette = PaletteKind::ALL
+                        .iter()
+                        .position(|&kind| kind == world.palette_kind());
+                    menu.open([Some(effect), generator_index, palette, Some(geometry_index)]);
+                    let clock = world.clock_mut();
+                    paused_before_menu = clock.is_paused();
+                    if !paused_before_menu {
+                        clock.toggle_pause();
+                    }
+                    None
+                }
+            } else if menu.is_open() {
+                menu.update(&input)
+            } else {
+                attract.as_mut().and_then(|attract| {
+                    attract.update(
+                        &mut world,
+                        &mut pipeline,
+                        generators.len(),
+                        geometries.len(),
+                    )
+                })
+            };
+            match choice {
+                Some(Choice::Resume) => {
+                    let clock = world.clock_mut();
+                    if clock.is_paused() != paused_before_menu {
+                        clock.toggle_pause();
+                    }
+                }
+                Some(Choice::Effect(index)) => pipeline.compositor.top().effect = index,
+                Some(Choice::Texture(index)) => {
+                    let params = world.params();
+                    let generator = &generators[index];
+                    world.set_texture(generator.generate(params.tex_width, params.tex_height));
+                    generator_index = Some(index);
+                    animation = None;
+                }
+                Some(Choice::Palette(index)) => world.set_palette(PaletteKind::ALL[index]),
+                Some(Choice::Shape(index)) => {
+                    geometry_index = index;
+                    world.set_geometry(geometries[index].clone());
+                }
+                Some(Choice::Quit) | None => {}
+            }
+            if choice == Some(Choice::Quit) || input.quit() || finished {
+                if let Some(recorder) = recorder.take() {
+                    finish_recording(recorder);
+                }
                 *control_flow = ControlFlow::Exit;
                 return;
             }
 
-            if let Some(size) = input.window_resized() {
+            // Screenshots are saved from a background thread, which browsers don't have.
+            #[cfg(not(target_arch = "wasm32"))]
+            if keymap.pressed(&input, Action::Screenshot) {
+                take_screenshot = true;
+            }
+
+            if keymap.pressed(&input, Action::Record) {
+                match recorder.take() {
+                    Some(recorder) => finish_recording(recorder),
+                    None => {
+                        let (width, height) = world.frame_size();
+                        match Recorder::start(
+                            args.record_format,
+                            &args.record_dir,
+                            width,
+                            height,
+                            args.record_fps,
+                        ) {
+                            Ok(started) => recorder = Some(started),
+                            Err(err) => warn!("Failed to start recording: {}", err),
+                        }
+                    }
+                }
+            }
+
+            if keymap.pressed(&input, Action::Fullscreen) && span.is_none() {
+                if window.fullscreen().is_some() {
+                    window.set_fullscreen(None);
+                } else {
+                    window.set_fullscreen(fullscreen(&window, fullscreen_mode));
+                }
+                let size = window.inner_size();
                 pixels.resize_surface(size.width, size.height);
             }
 
+            // Any window's resize is reported here, so the main one is asked for its own size.
+            if input.window_resized().is_some() {
+                let size = window.inner_size();
+                pixels.resize_surface(size.width, size.height);
+                if native_resolution {
+                    pending_resize = Some(Instant::now());
+                }
+            }
+
+            if keymap.pressed(&input, Action::NativeResolution) && span.is_none() {
+                native_resolution = !native_resolution;
+                if native_resolution {
+                    pending_resize = Some(Instant::now() - RESIZE_DELAY);
+                } else {
+                    pending_resize = None;
+                    let (width, height) = (resolution.width, resolution.height);
+                    resize(&mut world, pixels, &mut span, width, height);
+                }
+            }
+
+            if pending_resize.is_some_and(|since| since.elapsed() >= RESIZE_DELAY) {
+                pending_resize = None;
+                let size = window.inner_size();
+                if size.width > 0 && size.height > 0 {
+                    resize(&mut world, pixels, &mut span, size.width, size.height);
+                }
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(watcher) = config_watcher.as_ref().filter(|watcher| watcher.changed()) {
+                match Config::load(watcher.path()).map(|config| config.under(&args)) {
+                    Ok(config) => {
+                        if let Some(size) = config.resolution {
+                            resolution = size;
+                            if !native_resolution {
+                                let (width, height) = (size.width, size.height);
+                                resize(&mut world, pixels, &mut span, width, height);
+                            }
+                        }
+                        if let Some(path) = &config.texture {
+                            reload_texture(&mut world, path);
+                            texture_watcher = watch_file(path);
+                            generator_index = None;
+                            animation = None;
+                        }
+                        apply_config(&mut world, &config);
+                        keymap = config.keys.unwrap_or_default();
+                    }
+                    Err(err) => warn!("Failed to reload {}: {}", watcher.path().display(), err),
+                }
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(watcher) = texture_watcher.as_ref().filter(|watcher| watcher.changed()) {
+                reload_texture(&mut world, watcher.path());
+                generator_index = None;
+            }
+
+            if let Some(frame) = animation
+                .as_mut()
+                .and_then(|animation| animation.frame_at(world.clock().time()))
+            {
+                world.set_texture_frame(frame);
+            }
+
+            let now = Instant::now();
+            let dt = now.duration_since(last_update).as_secs_f64();
+            last_update = now;
+
+            if keymap.pressed(&input, Action::Overlay) {
+                overlay.toggle();
+            }
+
+            if keymap.pressed(&input, Action::Help) {
+                help.toggle();
+            }
+
+            if keymap.pressed(&input, Action::Ui) {
+                ui.toggle();
+            }
+
+            #[cfg(feature = "audio")]
+            let loading = world.loading().is_some();
+            let clock = world.clock_mut();
+            if keymap.pressed(&input, Action::Pause) {
+                clock.toggle_pause();
+            }
+            if keymap.pressed(&input, Action::Slower) {
+                clock.set_speed(clock.speed() * 0.5);
+            }
+            if keymap.pressed(&input, Action::Faster) {
+                clock.set_speed(clock.speed() * 2.0);
+            }
+            if keymap.pressed(&input, Action::ResetSpeed) {
+                clock.set_speed(args.speed);
+            }
+            #[cfg(feature = "audio")]
+            if let Some(music) = &music {
+                // The world stands still while loading, so the music waits with it.
+                music.set_paused(clock.is_paused() || loading);
+                music.set_speed(clock.speed());
+            }
+
+            let flight = *world.flight();
+            let travel = if keymap.pressed(&input, Action::TravelFaster) {
+                Some((flight.travel + TRAVEL_STEP).min(MAX_TRAVEL))
+            } else if keymap.pressed(&input, Action::TravelSlower) {
+                Some((flight.travel - TRAVEL_STEP).max(-MAX_TRAVEL))
+            } else if keymap.pressed(&input, Action::Reverse) {
+                stopped_travel = stopped_travel.map(|travel| -travel);
+                Some(-flight.travel)
+            } else if keymap.pressed(&input, Action::Stop) {
+                match stopped_travel.take() {
+                    Some(travel) => Some(travel),
+                    None if flight.travel != 0.0 => {
+                        stopped_travel = Some(flight.travel);
+                        Some(0.0)
+                    }
+                    None => Some(args.travel_speed),
+                }
+            } else {
+                None
+            };
+            if let Some(travel) = travel {
+                if travel != 0.0 {
+                    stopped_travel = None;
+                }
+                world.set_flight(Flight { travel, ..flight });
+            }
+
+            if keymap.pressed(&input, Action::Palette) {
+                world.set_palette(world.palette_kind().next());
+            }
+
+            if keymap.pressed(&input, Action::ColorMap) {
+                let color_maps = color_map::builtin();
+                let current = world.color_map().name();
+                let next = color_maps
+                    .iter()
+                    .position(|color_map| color_map.name() == current)
+                    .map_or(0, |i| (i + 1) % color_maps.len());
+                world.set_color_map(color_maps[next].clone());
+            }
+
+            if keymap.pressed(&input, Action::Filter) {
+                filter = match filter {
+                    Filter::Nearest => Filter::Bilinear,
+                    Filter::Bilinear => Filter::Nearest,
+                };
+                for effect in &mut pipeline.effects {
+                    effect.set_filter(filter);
+                }
+                if let Some(gpu) = &mut gpu {
+                    gpu.set_filter(filter);
+                }
+            }
+
+            if keymap.pressed(&input, Action::Split) {
+                let next = match &pipeline.split {
+                    Some(split) => Comparison::ALL
+                        .into_iter()
+                        .skip_while(|&comparison| comparison != split.comparison)
+                        .skip(1)
+                        .find(|&comparison| comparison != Comparison::Backend || gpu.is_some()),
+                    None => Some(Comparison::ALL[0]),
+                };
+                let divider = pipeline.split.as_ref().map_or(0.5, |split| split.divider);
+                pipeline.split = next.map(|comparison| {
+                    let mut split = Split::new(comparison);
+                    split.divider = divider;
+                    split
+                });
+            }
+
+            if keymap.pressed(&input, Action::Fog) {
+                let fog = *world.fog();
+                world.set_fog(Fog {
+                    curve: fog.curve.next(),
+                    ..fog
+                });
+            }
+
+            if keymap.pressed(&input, Action::AutoRoll) {
+                let speed = match world.roll_speed() {
+                    0.0 if args.auto_roll != 0.0 => args.auto_roll,
+                    0.0 => DEFAULT_ROLL_SPEED,
+                    _ => 0.0,
+                };
+                world.set_roll_speed(speed);
+            }
+
+            if keymap.pressed(&input, Action::Light) {
+                let light = *world.light();
+                world.set_light(Light {
+                    enabled: !light.enabled,
+                    ..light
+                });
+            }
+
+            if keymap.pressed(&input, Action::Feedback) {
+                let amount = if world.feedback() > 0.0 {
+                    0.0
+                } else if args.feedback > 0.0 {
+                    args.feedback
+                } else {
+                    DEFAULT_FEEDBACK
+                };
+                world.set_feedback(amount);
+            }
+
+            let (depth, angle) = world.address_modes();
+            if keymap.pressed(&input, Action::WrapAround) {
+                world.set_address_modes(depth, angle.next());
+            }
+            if keymap.pressed(&input, Action::WrapAlong) {
+                world.set_address_modes(depth.next(), angle);
+            }
+
+            for kind in PassKind::ALL {
+                if keymap.pressed(&input, Action::PostFx(kind)) {
+                    pipeline.postfx.toggle(kind.name());
+                }
+            }
+
+            let trail_step = if keymap.pressed(&input, Action::TrailLonger) {
+                TRAIL_STEP
+            } else if keymap.pressed(&input, Action::TrailShorter) {
+                -TRAIL_STEP
+            } else {
+                0.0
+            };
+            if trail_step != 0.0 {
+                let name = PassKind::MotionBlur.name();
+                let trail = pipeline.postfx.amount(name).unwrap_or(DEFAULT_TRAIL);
+                pipeline.postfx.set_amount(name, trail + trail_step);
+            }
+
+            if keymap.pressed(&input, Action::Breathing) {
+                breathing_index = (breathing_index + 1) % BREATHING_SHAPES.len();
+                let ratio = world.ratio_modulator().base;
+                world.set_ratio_modulator(Modulator::new(
+                    BREATHING_SHAPES[breathing_index],
+                    ratio,
+                    ratio * BREATHING_DEPTH,
+                    BREATHING_FREQUENCY,
+                ));
+            }
+
+            if keymap.pressed(&input, Action::Shape) {
+                geometry_index = (geometry_index + 1) % geometries.len();
+                world.set_geometry(geometries[geometry_index].clone());
+            }
+
+            for i in 0..pipeline.effects.len() {
+                if keymap.pressed(&input, Action::Effect(i)) {
+                    pipeline.compositor.top().effect = i;
+                }
+            }
+
+            if keymap.pressed(&input, Action::Background) {
+                cycle_background(&mut pipeline.compositor, pipeline.effects.len());
+            }
+
+            if keymap.pressed(&input, Action::Blend) {
+                let top = pipeline.compositor.top();
+                top.mode = top.mode.next();
+            }
+
+            for (index, generator) in generators.iter().enumerate() {
+                if keymap.pressed(&input, Action::Texture(index)) {
+                    let params = world.params();
+                    world.set_texture(generator.generate(params.tex_width, params.tex_height));
+                    generator_index = Some(index);
+                    animation = None;
+                }
+            }
+
+            if keymap.pressed(&input, Action::Noise) {
+                let params = world.params();
+                let mut noise = Animation::noise(args.seed, params.tex_width, params.tex_height);
+                if let Ok(texture) = noise.first_frame() {
+                    world.set_texture(texture);
+                    generator_index = None;
+                    animation = Some(noise);
+                }
+            }
+
+            // Pressing next to the split-screen divider drags it instead of steering.
+            if let Some(split) = &mut pipeline.split {
+                if let Some(position) = input.mouse() {
+                    let (x, _) = pixels
+                        .window_pos_to_pixel(position)
+                        .unwrap_or_else(|position| pixels.clamp_pixel_pos(position));
+                    let (width, _) = world.frame_size();
+                    let column = split.column(width as usize);
+                    if input.mouse_pressed(0) && x.abs_diff(column) <= DIVIDER_GRAB {
+                        dragging_divider = true;
+                    }
+                    if dragging_divider {
+                        split.divider = x as f64 / width as f64;
+                    }
+                }
+            }
+            if input.mouse_released(0) || pipeline.split.is_none() {
+                dragging_divider = false;
+            }
+            let mouse = !ui.wants_pointer() && !dragging_divider;
+            // The menu takes the arrow keys while it is open.
+            if !menu.is_open() {
+                let gesture = touches.take();
+                let size = window.inner_size();
+                steer(&input, &keymap, &gesture, &mut world, dt, mouse, size);
+            }
+            #[cfg(feature = "gamepad")]
+            if let Some(gamepad) = &mut gamepad {
+                gamepad.update(&mut world, dt);
+            }
+            #[cfg(feature = "midi")]
+            if let Some(midi) = &midi {
+                midi.update(&mut world);
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(ambient) = &mut ambient {
+                ambient.update(&mut world);
+            }
+            #[cfg(all(feature = "remote", not(target_arch = "wasm32")))]
+            if let Some(remote) = &remote {
+                let previous = filter;
+                remote.update(&mut world, &mut pipeline, &mut filter);
+                if let Some(gpu) = gpu.as_mut().filter(|_| filter != previous) {
+                    gpu.set_filter(filter);
+                }
+            }
+            #[cfg(all(feature = "scripting", not(target_arch = "wasm32")))]
+            if let Some(script) = &mut script {
+                let size = window.inner_size();
+                let state = script::ScriptInput {
+                    mouse: input.mouse().map(|(x, y)| {
+                        (
+                            x as f64 / size.width.max(1) as f64 * 2.0 - 1.0,
+                            y as f64 / size.height.max(1) as f64 * 2.0 - 1.0,
+                        )
+                    }),
+                    mouse_down: input.mouse_held(0),
+                    keys: keymap::held_keys(&input),
+                };
+                script.update(&mut world, &state);
+            }
+
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(replay) = &mut replay {
+                let previous = filter;
+                replay.update(&mut world, &mut pipeline, &mut filter);
+                if let Some(gpu) = gpu.as_mut().filter(|_| filter != previous) {
+                    gpu.set_filter(filter);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(follower) = &mut follower {
+                let previous = filter;
+                follower.update(&mut world, &mut pipeline, &mut filter);
+                if let Some(gpu) = gpu.as_mut().filter(|_| filter != previous) {
+                    gpu.set_filter(filter);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(recorder) = &mut performance {
+                let texture = generator_index.map(|index| generators[index].name());
+                let state = performance::capture(&world, &pipeline, filter, texture);
+                if let Err(err) = recorder.record(world.clock().time(), state) {
+                    warn!("Stopped recording the performance: {}", err);
+                    performance = None;
+                }
+            }
+
             world.update();
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(leader) = &mut leader {
+                let texture = generator_index.map(|index| generators[index].name());
+                leader.send(
+                    &world,
+                    performance::capture(&world, &pipeline, filter, texture),
+                );
+            }
+            pipeline.update(&world);
+            // Recordings keep the size they started with.
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(saving) = power
+                .as_mut()
+                .filter(|_| recorder.is_none())
+                .and_then(|power| power.update())
+            {
+                let fps = match args.max_fps {
+                    Some(fps) if saving => Some(fps.min(power::SAVER_FPS)),
+                    None if saving => Some(power::SAVER_FPS),
+                    fps => fps,
+                };
+                limiter = fps.map(pacing::FrameLimiter::new);
+                if saving {
+                    full_threads = Some(threads);
+                    threads = (threads / 2).max(1);
+                } else if let Some(full) = full_threads.take() {
+                    threads = full;
+                }
+                if args.backend == Backend::Cpu {
+                    world.set_render_scale(if saving { power::SAVER_SCALE } else { 1.0 });
+                    scaler = args
+                        .adaptive_resolution
+                        .filter(|_| !saving)
+                        .map(ResolutionScaler::new);
+                    let params = *world.params();
+                    resize(&mut world, pixels, &mut span, params.width, params.height);
+                }
+            }
+            ui.prepare(&window, &mut world, &mut threads);
+            let current = pool
+                .as_ref()
+                .map_or_else(rayon::current_num_threads, |pool| {
+                    pool.current_num_threads()
+                });
+            if threads != current {
+                pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .map_err(|err| warn!("Failed to resize the thread pool: {}", err))
+                    .ok();
+            }
+            // The browser paces redraws itself and can't be blocked.
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(limiter) = &mut limiter {
+                limiter.wait();
+            }
             window.request_redraw();
         }
     });
 }
 
-fn generate_texture(width: usize, height: usize) -> Vec<u32> {
-    let size = width * height;
-    let mut texture = vec![0u32; size];
-    for i in 0..size {
-        let x = i % width as usize;
-        let y = i / width as usize;
-        texture[i] = ((x * 256 / width) ^ (y * 256 / height)) as u32;
+fn configure(
+    world: &mut World,
+    args: &cli::Args,
+    timeline: Option<Timeline>,
+    height_map: Option<Arc<HeightMap>>,
+    atlas: Option<Atlas>,
+    stamps: Vec<Stamp>,
+) {
+    *world.clock_mut() = args.clock();
+    world.set_fog(args.fog());
+    world.set_light(args.light());
+    world.set_height_map(height_map);
+    world.set_curve(args.curve());
+    world.set_flight(args.flight());
+    world.set_roll(args.roll);
+    world.set_roll_speed(args.auto_roll);
+    world.set_address_modes(args.wrap_depth, args.wrap_angle);
+    world.set_atlas(atlas);
+    world.set_seam_blend(args.seam_blend);
+    world.set_stamps(stamps);
+    world.set_feedback(args.feedback);
+    world.set_crossfade(args.crossfade);
+    world.set_gamma_correct(args.gamma_correct);
+    world.set_reduced_motion(args.reduced_motion == ReducedMotion::On);
+    world.set_timeline(timeline);
+    if let Some(color_map) = args
+        .color_map
+        .as_deref()
+        .and_then(|name| color_map::find(name).ok())
+    {
+        world.set_color_map(color_map);
     }
-    texture
 }
 
-fn now() -> f64 {
-    let now = SystemTime::now();
-    let duration = now
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .expect("Time went backwards!");
-    duration.as_secs_f64()
+/// Steps the background layer through every effect and then back to none.
+fn cycle_background(compositor: &mut Compositor, effects: usize) {
+    if compositor.stages.len() < 2 {
+        compositor.stages.insert(0, Stage::new(0));
+    } else if compositor.stages[0].effect + 1 < effects {
+        compositor.stages[0].effect += 1;
+    } else {
+        compositor.stages.remove(0);
+    }
 }
 
-impl World {
-    fn new() -> Self {
-        let tex_width = 256usize;
-        let tex_height = 256usize;
+#[cfg(not(target_arch = "wasm32"))]
+fn watch_file(path: &std::path::Path) -> Option<watch::FileWatcher> {
+    watch::FileWatcher::new(path)
+        .map_err(|err| warn!("Not watching {}: {}", path.display(), err))
+        .ok()
+}
 
-        let mut distances = vec![vec![0u32; (WIDTH * 2) as usize]; (HEIGHT * 2) as usize];
-        let mut angles = vec![vec![0u32; (WIDTH * 2) as usize]; (HEIGHT * 2) as usize];
+/// Keeps the current texture if the file can't be read, e.g. while an editor is still
+/// writing it.
+#[cfg(not(target_arch = "wasm32"))]
+fn reload_texture(world: &mut World, path: &std::path::Path) {
+    match Texture::load(path) {
+        Ok(texture) => world.set_texture(texture),
+        Err(err) => warn!("Failed to load {}: {}", path.display(), err),
+    }
+}
 
-        let w = WIDTH as f64;
-        let h = HEIGHT as f64;
-        let tw = tex_width as f64;
-        let th = tex_height as f64;
+/// Applies the settings that can change without touching the window or the texture.
+fn apply_config(world: &mut World, config: &Config) {
+    if let Some(palette) = config.palette {
+        world.set_palette(palette);
+    }
+    if let Some(speed) = config.speed {
+        world.clock_mut().set_speed(speed);
+    }
+    if let Some(ratio) = config.ratio {
+        world.set_ratio_modulator(Modulator {
+            base: ratio,
+            ..*world.ratio_modulator()
+        });
+    }
+    world.set_modulation(config.modulation.clone());
+}
 
-        let ratio = 64.0;
+/// Resizes the world and the frame its effects are drawn into: the canvas of a span, or the
+/// pixel buffer.
+fn resize(
+    world: &mut World,
+    pixels: &mut Pixels,
+    span: &mut Option<Span>,
+    width: u32,
+    height: u32,
+) {
+    world.resize(width, height);
+    let (width, height) = world.frame_size();
+    match span {
+        Some(span) => span.resize(pixels, width, height),
+        None => pixels.resize_buffer(width, height),
+    }
+}
 
-        for y in 0..HEIGHT * 2 {
-            for x in 0..WIDTH * 2 {
-                let xf = x as f64;
-                let yf = y as f64;
-                let sq_sum = (xf - w) * (xf - w) + (yf - h) * (yf - h);
-                let distance = (ratio * th / sq_sum.sqrt()) as u32 % tex_height as u32;
-                let angle = ((0.5 * tw * (yf - h).atan2(xf - w) / PI) as i32) as u32;
-                distances[y as usize][x as usize] = distance;
-                angles[y as usize][x as usize] = angle;
-            }
-        }
+/// A pixel buffer `width` by `height` pixels on a surface for `window`, set up as the command
+/// line asks, at startup and whenever a surface taken away is given back.
+fn pixels_builder<'win>(
+    args: &cli::Args,
+    window: &'win Window,
+    width: u32,
+    height: u32,
+) -> PixelsBuilder<'static, 'static, 'win, Window> {
+    let size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(size.width, size.height, window);
+    let builder =
+        PixelsBuilder::new(width, height, surface_texture).present_mode(args.vsync.present_mode());
+    // Windows composes a float surface as scRGB, in which values beyond 1 are brighter than
+    // SDR white. wgpu can't tell which surfaces take one, so only DX12, where all do, is asked
+    // for it.
+    if cfg!(windows) && args.hdr().is_some() {
+        builder
+            .wgpu_backend(pixels::wgpu::Backends::DX12)
+            .render_texture_format(HDR_FORMAT)
+    } else {
+        builder
+    }
+}
 
-        Self {
-            tex_width,
-            tex_height,
-            texture: generate_texture(tex_width, tex_height),
-            distances,
-            angles,
-            clock: now(),
-        }
+/// Shows the frame drawn into `resize`'s target, with the parameter window on top.
+fn present(pixels: &mut Pixels, span: &mut Option<Span>, ui: &mut Ui) -> Result<(), pixels::Error> {
+    let _span = info_span!("present").entered();
+    if let Some(span) = span {
+        span.render(pixels)?;
     }
+    pixels.render_with(|encoder, target, context| {
+        context.scaling_renderer.render(encoder, target);
+        ui.render(encoder, target, context);
+        Ok(())
+    })
+}
 
-    fn update(&mut self) {
-        self.clock = now();
+fn finish_recording(recorder: Recorder) {
+    match recorder.finish() {
+        Ok(path) => println!("Saved recording to {}", path.display()),
+        Err(err) => warn!("Recording failed: {}", err),
     }
+}
 
-    fn draw(&self, frame: &mut [u8]) {
-        let shift_x = (self.tex_width as f64 * self.clock * 0.5) as u64;
-        let shift_y = (self.tex_height as f64 * self.clock * 0.1) as u64;
+fn fullscreen(window: &Window, mode: FullscreenMode) -> Option<Fullscreen> {
+    match mode {
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(None)),
+        FullscreenMode::Exclusive => window
+            .current_monitor()
+            .and_then(|monitor| {
+                monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate())
+                })
+            })
+            .map(Fullscreen::Exclusive)
+            .or(Some(Fullscreen::Borderless(None))),
+    }
+}
 
-        let look_x_dist = (WIDTH / 2) as f64 * self.clock.sin();
-        let look_y_dist = (HEIGHT / 2) as f64 * (self.clock * 2.0).sin();
+/// Steers with the keys `keymap` binds, and with mouse drags, the cursor in a window of
+/// `size` or a finger dragged across it unless `mouse` is false; the mouse wheel and pinches
+/// zoom by scaling the ratio, a double tap toggles the fly-through and the roll keys roll
+/// the view.
+fn steer(
+    input: &WinitInputHelper,
+    keymap: &Keymap,
+    gesture: &Gesture,
+    world: &mut World,
+    dt: f64,
+    mouse: bool,
+    size: PhysicalSize<u32>,
+) {
+    let camera = world.camera_mut();
 
-        let shift_look_x = (WIDTH as i32 / 2 + look_x_dist as i32) as usize;
-        let shift_look_y = (HEIGHT as i32 / 2 + look_y_dist as i32) as usize;
+    if keymap.pressed(input, Action::CameraMode) || (mouse && gesture.double_tap) {
+        camera.toggle_mode();
+    }
+    if keymap.pressed(input, Action::MouseLook) {
+        camera.toggle_follow();
+    }
 
-        let threads = 20;
-        let rows_per_band = (HEIGHT / threads + 1) as usize;
+    let held = |action| keymap.held(input, action);
+    let mut dx = 0.0;
+    let mut dy = 0.0;
+    if held(Action::SteerLeft) {
+        dx -= 1.0;
+    }
+    if held(Action::SteerRight) {
+        dx += 1.0;
+    }
+    if held(Action::SteerUp) {
+        dy -= 1.0;
+    }
+    if held(Action::SteerDown) {
+        dy += 1.0;
+    }
+    if dx != 0.0 || dy != 0.0 {
+        camera.steer(dx * STEER_SPEED * dt, dy * STEER_SPEED * dt);
+    }
 
-        let band_size = rows_per_band * WIDTH as usize * 4;
-        let bands: Vec<&mut [u8]> = frame.chunks_mut(band_size).collect();
+    if mouse && input.mouse_held(0) {
+        let (mx, my) = input.mouse_diff();
+        if mx != 0.0 || my != 0.0 {
+            camera.steer(-mx as f64, -my as f64);
+        }
+    }
+    let (tx, ty) = gesture.drag;
+    if mouse && (tx != 0.0 || ty != 0.0) {
+        camera.steer(-tx, -ty);
+    }
 
-        fn render_band(
-            band: &mut [u8],
-            offset: usize,
-            shift: (u64, u64),
-            shift_look: (usize, usize),
-            world: &World,
-        ) {
-            for (i, pixel) in band.chunks_exact_mut(4).enumerate() {
-                let j = i + offset;
-                let x = j % WIDTH as usize;
-                let y = j / WIDTH as usize;
-                let dist = world.distances[y + shift_look.1][x + shift_look.0];
-                let tex_x = (dist as u64 + shift.0) % world.tex_width as u64;
-                let angle = world.angles[y + shift_look.1][x + shift_look.0];
-                let tex_y = (angle as u64 + shift.1) % world.tex_height as u64;
-                let tex_i = tex_y as usize * world.tex_width + tex_x as usize;
-                let color = world.texture[tex_i];
-                let rgba = [0u8, color as u8, 0u8, 0xff];
-                pixel.copy_from_slice(&rgba);
-            }
-        }
-
-        crossbeam::scope(|spawner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let offset = i * rows_per_band * WIDTH as usize;
-
-                spawner.spawn(move |_| {
-                    render_band(
-                        band,
-                        offset,
-                        (shift_x, shift_y),
-                        (shift_look_x, shift_look_y),
-                        self,
-                    );
-                });
-            }
-        })
-        .unwrap();
+    if let Some((x, y)) = input.mouse().filter(|_| mouse) {
+        let x = x as f64 / size.width.max(1) as f64 * 2.0 - 1.0;
+        let y = y as f64 / size.height.max(1) as f64 * 2.0 - 1.0;
+        camera.follow(x, y, dt);
+    }
+
+    let scroll = input.scroll_diff() as f64;
+    if mouse && (scroll != 0.0 || gesture.pinch != 1.0) {
+        let modulator = *world.ratio_modulator();
+        // Spreading the fingers zooms in, like the wheel turned down.
+        let base = modulator.base * WHEEL_ZOOM.powf(scroll) / gesture.pinch;
+        world.set_ratio_modulator(Modulator {
+            base: base.clamp(MIN_RATIO, MAX_RATIO),
+            ..modulator
+        });
+    }
+
+    let direction = held(Action::RollRight) as i32 - held(Action::RollLeft) as i32;
+    if direction != 0 {
+        world.set_roll(world.roll() + direction as f64 * ROLL_SPEED * dt);
     }
 }
@@ -0,0 +1,219 @@
+use std::time::Duration;
+
+use tunnel::font;
+use tunnel::time::Instant;
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+const SCALE: usize = 2;
+const PADDING: usize = 12;
+const TITLE: &str = "PAUSED";
+const HINT: &str = "ENTER RESUMES, ESC ESC QUITS";
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const SELECTED_COLOR: [u8; 4] = [0xff, 0xc0, 0x40, 0xff];
+const HINT_COLOR: [u8; 4] = [0x90, 0x90, 0x90, 0xff];
+/// How soon a second Esc has to follow the one that opened the menu to quit at once.
+const DOUBLE_PRESS: Duration = Duration::from_millis(400);
+
+/// What the menu asks for; the browsers' choices are indices into the lists it was made with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Choice {
+    Resume,
+    Quit,
+    Effect(usize),
+    Texture(usize),
+    Palette(usize),
+    Shape(usize),
+}
+
+/// The browsers in the order they are listed.
+const BROWSERS: [&str; 4] = ["EFFECT", "TEXTURE", "PALETTE", "SHAPE"];
+
+struct Browser {
+    items: Vec<String>,
+    /// The item on show, if the current one is in the list.
+    current: Option<usize>,
+}
+
+/// The pause menu Esc opens: a resume entry, a browser per kind of thing to switch between
+/// (left and right step through it and apply the pick at once) and a quit entry that asks
+/// to be confirmed. Up and down move between the entries.
+pub struct Menu {
+    opened: Option<Instant>,
+    browsers: Vec<Browser>,
+    /// Index into the entries: resume, the browsers, quit.
+    selected: usize,
+    confirming: bool,
+}
+
+impl Menu {
+    /// Browses the effects, textures, palettes and shapes by these names.
+    pub fn new(
+        effects: Vec<String>,
+        textures: Vec<String>,
+        palettes: Vec<String>,
+        shapes: Vec<String>,
+    ) -> Self {
+        let browsers = [effects, textures, palettes, shapes]
+            .into_iter()
+            .map(|items| Browser {
+                items,
+                current: None,
+            })
+            .collect();
+        Self {
+            opened: None,
+            browsers,
+            selected: 0,
+            confirming: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.opened.is_some()
+    }
+
+    /// Opens the menu on the resume entry, showing the `current` effect, texture, palette and
+    /// shape.
+    pub fn open(&mut self, current: [Option<usize>; 4]) {
+        for (browser, current) in self.browsers.iter_mut().zip(current) {
+            browser.current = current;
+        }
+        self.opened = Some(Instant::now());
+        self.selected = 0;
+        self.confirming = false;
+    }
+
+    /// Handles Esc while the menu is open: right after opening it quits, otherwise resumes.
+    pub fn escape(&mut self) -> Choice {
+        let double = self
+            .opened
+            .is_some_and(|opened| opened.elapsed() < DOUBLE_PRESS);
+        if double {
+            Choice::Quit
+        } else {
+            self.opened = None;
+            Choice::Resume
+        }
+    }
+
+    /// Moves through the open menu with the keys of this update.
+    pub fn update(&mut self, input: &WinitInputHelper) -> Option<Choice> {
+        let pressed = |keys: &[VirtualKeyCode]| keys.iter().any(|&key| input.key_pressed(key));
+        let entries = self.browsers.len() + 2;
+        if pressed(&[VirtualKeyCode::Up, VirtualKeyCode::W]) {
+            self.selected = (self.selected + entries - 1) % entries;
+            self.confirming = false;
+        }
+        if pressed(&[VirtualKeyCode::Down, VirtualKeyCode::S]) {
+            self.selected = (self.selected + 1) % entries;
+            self.confirming = false;
+        }
+
+        let step = if pressed(&[VirtualKeyCode::Right, VirtualKeyCode::D]) {
+            1
+        } else if pressed(&[VirtualKeyCode::Left, VirtualKeyCode::A]) {
+            -1
+        } else {
+            0
+        };
+        if let Some(index) = self.selected.checked_sub(1) {
+            if let Some(browser) = self.browsers.get_mut(index) {
+                let len = browser.items.len() as isize;
+                if step != 0 && len > 0 {
+                    let current = match browser.current {
+                        Some(current) => (current as isize + step).rem_euclid(len) as usize,
+                        None if step > 0 => 0,
+                        None => len as usize - 1,
+                    };
+                    browser.current = Some(current);
+                    return Some(pick(index, current));
+                }
+            }
+        }
+
+        let enter = [VirtualKeyCode::Return, VirtualKeyCode::NumpadEnter]
+            .iter()
+            .any(|&key| input.key_pressed(key));
+        if !enter || input.held_alt() {
+            return None;
+        }
+        if self.selected == entries - 1 && !self.confirming {
+            self.confirming = true;
+            return None;
+        }
+        if self.selected == entries - 1 {
+            return Some(Choice::Quit);
+        }
+        self.opened = None;
+        Some(Choice::Resume)
+    }
+
+    /// Draws the open menu over the middle of the frame, on a darkened panel.
+    pub fn draw(&self, frame: &mut [u8], frame_width: usize) {
+        if !self.is_open() {
+            return;
+        }
+        let mut lines = vec![("RESUME".to_string(), 0)];
+        for (index, (title, browser)) in BROWSERS.iter().zip(&self.browsers).enumerate() {
+            let item = browser
+                .current
+                .and_then(|current| browser.items.get(current))
+                .map_or("-", String::as_str);
+            lines.push((format!("{:<8}< {} >", title, item), index + 1));
+        }
+        let quit = if self.confirming {
+            "QUIT? ENTER AGAIN"
+        } else {
+            "QUIT"
+        };
+        lines.push((quit.to_string(), lines.len()));
+
+        let line_height = font::line_height(SCALE);
+        let text_width = lines
+            .iter()
+            .map(|(line, _)| font::text_width(line, SCALE))
+            .chain([TITLE, HINT].map(|text| font::text_width(text, SCALE)))
+            .max()
+            .unwrap_or(0);
+        let frame_height = frame.len() / 4 / frame_width;
+        let width = (text_width + PADDING * 2).min(frame_width);
+        let height = ((lines.len() + 3) * line_height + PADDING * 2).min(frame_height);
+        let left = (frame_width - width) / 2;
+        let top = (frame_height - height) / 2;
+        for y in top..top + height {
+            let row =
+                &mut frame[(y * frame_width + left) * 4..(y * frame_width + left + width) * 4];
+            for pixel in row.chunks_exact_mut(4) {
+                for channel in &mut pixel[..3] {
+                    *channel /= 4;
+                }
+            }
+        }
+
+        let x = left + PADDING;
+        let mut y = top + PADDING;
+        font::draw_text(frame, frame_width, (x, y), SCALE, TEXT_COLOR, TITLE);
+        y += line_height * 2;
+        for (line, entry) in &lines {
+            let color = if *entry == self.selected {
+                SELECTED_COLOR
+            } else {
+                TEXT_COLOR
+            };
+            font::draw_text(frame, frame_width, (x, y), SCALE, color, line);
+            y += line_height;
+        }
+        font::draw_text(frame, frame_width, (x, y), SCALE, HINT_COLOR, HINT);
+    }
+}
+
+/// The choice of `item` in the browser at `index`.
+fn pick(index: usize, item: usize) -> Choice {
+    match index {
+        0 => Choice::Effect(item),
+        1 => Choice::Texture(item),
+        2 => Choice::Palette(item),
+        _ => Choice::Shape(item),
+    }
+}
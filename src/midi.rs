@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+
+use midir::{MidiInput, MidiInputConnection};
+use serde::Deserialize;
+use tunnel::{Fog, FogCurve, Modulator, PaletteKind, World};
+
+const CONTROL_CHANGE: u8 = 0xb0;
+const FOG_CURVES: [FogCurve; 3] = [FogCurve::Off, FogCurve::Linear, FogCurve::Exponential];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Target {
+    Speed,
+    Ratio,
+    Palette,
+    Fog,
+    FogStart,
+    FogEnd,
+    FogDensity,
+}
+
+impl Target {
+    /// What a control sweeps when the mapping doesn't say. Palettes and fog curves are picked
+    /// by dividing the control's travel into equal parts instead.
+    fn range(self) -> (f64, f64) {
+        match self {
+            Target::Speed => (0.0, 4.0),
+            Target::Ratio => (16.0, 256.0),
+            Target::FogStart => (0.0, 16.0),
+            Target::FogEnd => (1.0, 32.0),
+            Target::FogDensity => (0.0, 2.0),
+            Target::Palette | Target::Fog => (0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Control {
+    cc: u8,
+    /// 1 to 16; any channel if left out.
+    channel: Option<u8>,
+    target: Target,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl Control {
+    /// Sets the target from the control's `position` between 0 and 1.
+    fn apply(&self, world: &mut World, position: f64) {
+        let (min, max) = self.target.range();
+        let (min, max) = (self.min.unwrap_or(min), self.max.unwrap_or(max));
+        let value = min + position * (max - min);
+        let pick = |count: usize| ((position * count as f64) as usize).min(count - 1);
+        let fog = *world.fog();
+        match self.target {
+            Target::Speed => world.clock_mut().set_speed(value),
+            Target::Ratio => world.set_ratio_modulator(Modulator {
+                base: value,
+                ..*world.ratio_modulator()
+            }),
+            // Knobs send a stream of values, so only an actual change starts a crossfade.
+            Target::Palette => {
+                let kind = PaletteKind::ALL[pick(PaletteKind::ALL.len())];
+                if kind != world.palette_kind() {
+                    world.set_palette(kind);
+                }
+            }
+            Target::Fog => world.set_fog(Fog {
+                curve: FOG_CURVES[pick(FOG_CURVES.len())],
+                ..fog
+            }),
+            Target::FogStart => world.set_fog(Fog {
+                start: value,
+                ..fog
+            }),
+            Target::FogEnd => world.set_fog(Fog { end: value, ..fog }),
+            Target::FogDensity => world.set_fog(Fog {
+                density: value,
+                ..fog
+            }),
+        }
+    }
+}
+
+/// A mapping file: the port to listen to and which controllers drive which parameters.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Mapping {
+    /// Part of the input port's name; the first port if left out.
+    port: Option<String>,
+    #[serde(default, rename = "control")]
+    controls: Vec<Control>,
+}
+
+/// Control change messages from a MIDI input, applied to the world as the mapping says and
+/// handed to its modulation graph.
+pub struct Midi {
+    controls: Vec<Control>,
+    /// Channel (0 to 15), controller and value of each message.
+    messages: Receiver<[u8; 3]>,
+    _connection: MidiInputConnection<()>,
+}
+
+impl Midi {
+    pub fn start(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mapping: Mapping = toml::from_str(&fs::read_to_string(path)?)?;
+        for control in &mapping.controls {
+            if control.cc > 127 {
+                return Err(format!("controller {} is out of range 0-127", control.cc).into());
+            }
+            if control
+                .channel
+                .is_some_and(|channel| !(1..=16).contains(&channel))
+            {
+                return Err(format!("controller {}: channel must be 1-16", control.cc).into());
+            }
+        }
+
+        let input = MidiInput::new("tunnel-rs")?;
+        let ports = input.ports();
+        let port = ports
+            .iter()
+            .find(|port| match &mapping.port {
+                Some(name) => input
+                    .port_name(port)
+                    .is_ok_and(|port_name| port_name.contains(name.as_str())),
+                None => true,
+            })
+            .ok_or_else(|| match &mapping.port {
+                Some(name) => format!("no MIDI input matching `{}`", name),
+                None => "no MIDI input".to_string(),
+            })?;
+        println!("Listening to MIDI input {}", input.port_name(port)?);
+
+        let (sender, messages) = mpsc::channel();
+        let connection = input
+            .connect(
+                port,
+                "tunnel-rs",
+                move |_, message, _| {
+                    if let &[status, controller, value] = message {
+                        if status & 0xf0 == CONTROL_CHANGE {
+                            let _ = sender.send([status & 0x0f, controller, value]);
+                        }
+                    }
+                },
+                (),
+            )
+            .map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            controls: mapping.controls,
+            messages,
+            _connection: connection,
+        })
+    }
+
+    /// Applies the messages received since the last call.
+    pub fn update(&self, world: &mut World) {
+        for [channel, controller, value] in self.messages.try_iter() {
+            let position = value as f64 / 127.0;
+            world.set_controller(controller, position);
+            let controls = self.controls.iter().filter(|control| {
+                control.cc == controller
+                    && control.channel.is_none_or(|number| number == channel + 1)
+            });
+            for control in controls {
+                control.apply(world, position);
+            }
+        }
+    }
+}
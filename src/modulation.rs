@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::modulator::Shape;
+
+/// Continuous parameters a modulation graph can move, by adding to what they are otherwise.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Param {
+    /// Tunnel radius ratio.
+    Ratio,
+    /// Travel and spin speed in texture lengths and turns per second.
+    Speed,
+    Spin,
+    PaletteSpeed,
+    /// Palette entries the colors are cycled by.
+    Palette,
+    FogStart,
+    FogEnd,
+    FogDensity,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+}
+
+impl LfoShape {
+    /// One cycle at `phase` in `[0, 1)`, from -1 to 1.
+    fn eval(self, phase: f64) -> f64 {
+        match self {
+            LfoShape::Sine => Shape::Sine.eval(phase),
+            LfoShape::Triangle => Shape::Triangle.eval(phase),
+            LfoShape::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::Saw => 2.0 * phase - 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Band {
+    Bass,
+    Treble,
+}
+
+/// A node of the graph as the config writes it, with its inputs named.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum NodeSpec {
+    /// Swings from -1 to 1 `frequency` times a second, starting `phase` into the cycle.
+    Lfo {
+        #[serde(default)]
+        shape: LfoShape,
+        frequency: f64,
+        #[serde(default)]
+        phase: f64,
+    },
+    /// Rises to 1 and decays back towards 0 `frequency` times a second; `attack` and
+    /// `release` are shares of the cycle.
+    Envelope {
+        frequency: f64,
+        attack: f64,
+        release: f64,
+    },
+    /// The beat track's envelope, from 0 to 1.
+    Beat,
+    /// A band of the live audio, from 0 to 1.
+    Audio {
+        band: Band,
+    },
+    /// The last value of a MIDI controller, from 0 to 1.
+    Midi {
+        cc: u8,
+    },
+    Constant {
+        value: f64,
+    },
+    Add {
+        inputs: Vec<String>,
+    },
+    Multiply {
+        inputs: Vec<String>,
+    },
+    /// Follows its input, closing most of the gap every `seconds`.
+    Smooth {
+        input: String,
+        seconds: f64,
+    },
+}
+
+impl NodeSpec {
+    fn inputs(&self) -> Vec<&String> {
+        match self {
+            NodeSpec::Add { inputs } | NodeSpec::Multiply { inputs } => inputs.iter().collect(),
+            NodeSpec::Smooth { input, .. } => vec![input],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TargetSpec {
+    param: Param,
+    input: String,
+    #[serde(default = "one")]
+    amount: f64,
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GraphSpec {
+    #[serde(default, rename = "node")]
+    nodes: BTreeMap<String, NodeSpec>,
+    #[serde(default, rename = "target")]
+    targets: Vec<TargetSpec>,
+}
+
+/// A node with its inputs resolved to the indices of nodes evaluated before it.
+#[derive(Clone, Debug)]
+enum Node {
+    Lfo {
+        shape: LfoShape,
+        frequency: f64,
+        phase: f64,
+    },
+    Envelope {
+        frequency: f64,
+        shape: Shape,
+    },
+    Beat,
+    Audio(Band),
+    Midi(u8),
+    Constant(f64),
+    Add(Vec<usize>),
+    Multiply(Vec<usize>),
+    Smooth {
+        input: usize,
+        seconds: f64,
+    },
+}
+
+/// What the graph's sources read, gathered by `World::update`.
+pub(crate) struct Sources<'a> {
+    pub(crate) time: f64,
+    pub(crate) dt: f64,
+    pub(crate) beat: f64,
+    pub(crate) bass: f64,
+    pub(crate) treble: f64,
+    pub(crate) controllers: &'a [f64; 128],
+}
+
+#[derive(Debug)]
+pub struct GraphError(String);
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Sources (LFOs, envelopes, the beat, audio bands and MIDI controllers) wired through
+/// operators (sums, products and smoothing) into offsets of the world's parameters,
+/// evaluated once per update. Reads from the `[modulation]` table of a config, e.g.
+///
+/// ```toml
+/// [modulation.node.wobble]
+/// type = "lfo"
+/// frequency = 0.25
+///
+/// [modulation.node.kick]
+/// type = "audio"
+/// band = "bass"
+///
+/// [modulation.node.mix]
+/// type = "add"
+/// inputs = ["wobble", "kick"]
+///
+/// [[modulation.target]]
+/// param = "ratio"
+/// input = "mix"
+/// amount = 16.0
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "GraphSpec")]
+pub struct ModulationGraph {
+    /// In an order where every node comes after its inputs.
+    nodes: Vec<Node>,
+    /// Every node's output at the last evaluation.
+    values: Vec<f64>,
+    targets: Vec<(Param, usize, f64)>,
+    /// Whether `values` hold a previous evaluation for smoothing to start from.
+    started: bool,
+}
+
+impl TryFrom<GraphSpec> for ModulationGraph {
+    type Error = GraphError;
+
+    fn try_from(spec: GraphSpec) -> Result<Self, Self::Error> {
+        for (name, node) in &spec.nodes {
+            for input in node.inputs() {
+                if !spec.nodes.contains_key(input) {
+                    return Err(GraphError(format!(
+                        "unknown modulation node `{}` feeding `{}`",
+                        input, name
+                    )));
+                }
+            }
+            match node {
+                NodeSpec::Midi { cc } if *cc > 127 => {
+                    return Err(GraphError(format!(
+                        "modulation node `{}`: controller {} is out of range 0-127",
+                        name, cc
+                    )))
+                }
+                NodeSpec::Envelope {
+                    attack, release, ..
+                } if *attack <= 0.0 || *release <= 0.0 => {
+                    return Err(GraphError(format!(
+                        "modulation node `{}`: attack and release must be positive",
+                        name
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        // Depth-first, so that inputs are placed before the nodes they feed.
+        let mut order: Vec<&str> = Vec::new();
+        let mut visiting: Vec<&str> = Vec::new();
+        fn visit<'a>(
+            name: &'a str,
+            nodes: &'a BTreeMap<String, NodeSpec>,
+            order: &mut Vec<&'a str>,
+            visiting: &mut Vec<&'a str>,
+        ) -> Result<(), GraphError> {
+            if order.contains(&name) {
+                return Ok(());
+            }
+            if visiting.contains(&name) {
+                return Err(GraphError(format!(
+                    "modulation node `{}` feeds back into itself",
+                    name
+                )));
+            }
+            visiting.push(name);
+            for input in nodes[name].inputs() {
+                visit(input, nodes, order, visiting)?;
+            }
+            visiting.pop();
+            order.push(name);
+            Ok(())
+        }
+        for name in spec.nodes.keys() {
+            visit(name, &spec.nodes, &mut order, &mut visiting)?;
+        }
+
+        let index = |name: &str| order.iter().position(|&other| other == name);
+        let nodes = order
+            .iter()
+            .map(|&name| {
+                let inputs =
+                    |inputs: &[String]| inputs.iter().filter_map(|input| index(input)).collect();
+                match &spec.nodes[name] {
+                    &NodeSpec::Lfo {
+                        shape,
+                        frequency,
+                        phase,
+                    } => Node::Lfo {
+                        shape,
+                        frequency,
+                        phase,
+                    },
+                    &NodeSpec::Envelope {
+                        frequency,
+                        attack,
+                        release,
+                    } => Node::Envelope {
+                        frequency,
+                        shape: Shape::Envelope { attack, release },
+                    },
+                    NodeSpec::Beat => Node::Beat,
+                    &NodeSpec::Audio { band } => Node::Audio(band),
+                    &NodeSpec::Midi { cc } => Node::Midi(cc),
+                    &NodeSpec::Constant { value } => Node::Constant(value),
+                    NodeSpec::Add { inputs: names } => Node::Add(inputs(names)),
+                    NodeSpec::Multiply { inputs: names } => Node::Multiply(inputs(names)),
+                    NodeSpec::Smooth { input, seconds } => Node::Smooth {
+                        input: index(input).unwrap_or_default(),
+                        seconds: *seconds,
+                    },
+                }
+            })
+            .collect();
+        let targets = spec
+            .targets
+            .iter()
+            .map(|target| {
+                index(&target.input)
+                    .map(|input| (target.param, input, target.amount))
+                    .ok_or_else(|| {
+                        GraphError(format!(
+                            "unknown modulation node `{}` in a target",
+                            target.input
+                        ))
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            values: vec![0.0; order.len()],
+            nodes,
+            targets,
+            started: false,
+        })
+    }
+}
+
+impl ModulationGraph {
+    /// Works out every node's output for this update.
+    pub(crate) fn evaluate(&mut self, sources: &Sources) {
+        for (i, node) in self.nodes.iter().enumerate() {
+            let values = &self.values;
+            let value = match node {
+                Node::Lfo {
+                    shape,
+                    frequency,
+                    phase,
+                } => shape.eval((sources.time * frequency + phase).rem_euclid(1.0)),
+                Node::Envelope { frequency, shape } => {
+                    (shape.eval((sources.time * frequency).rem_euclid(1.0)) + 1.0) / 2.0
+                }
+                Node::Beat => sources.beat,
+                Node::Audio(Band::Bass) => sources.bass,
+                Node::Audio(Band::Treble) => sources.treble,
+                Node::Midi(cc) => sources.controllers[*cc as usize],
+                Node::Constant(value) => *value,
+                Node::Add(inputs) => inputs.iter().map(|&input| values[input]).sum(),
+                Node::Multiply(inputs) => inputs.iter().map(|&input| values[input]).product(),
+                Node::Smooth { input, seconds } => {
+                    let target = values[*input];
+                    if self.started && *seconds > 0.0 {
+                        values[i] + (target - values[i]) * (1.0 - (-sources.dt / seconds).exp())
+                    } else {
+                        target
+                    }
+                }
+            };
+            self.values[i] = value;
+        }
+        self.started = true;
+    }
+
+    /// What the targets of `param` add up to at the last evaluation.
+    pub fn offset(&self, param: Param) -> f64 {
+        self.targets
+            .iter()
+            .filter(|(target, _, _)| *target == param)
+            .map(|&(_, input, amount)| self.values[input] * amount)
+            .sum()
+    }
+}
@@ -0,0 +1,61 @@
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Shape {
+    Constant,
+    Sine,
+    Triangle,
+    Envelope { attack: f64, release: f64 },
+}
+
+impl Shape {
+    /// Evaluates one cycle at `phase` in `[0, 1)`, yielding a value in `[-1, 1]`.
+    pub fn eval(self, phase: f64) -> f64 {
+        match self {
+            Shape::Constant => 0.0,
+            Shape::Sine => (2.0 * PI * phase).sin(),
+            Shape::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Shape::Envelope { attack, release } => {
+                let level = if phase < attack {
+                    phase / attack
+                } else {
+                    (-(phase - attack) / release).exp()
+                };
+                2.0 * level - 1.0
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modulator {
+    pub shape: Shape,
+    pub base: f64,
+    pub amplitude: f64,
+    pub frequency: f64,
+}
+
+impl Modulator {
+    pub fn constant(base: f64) -> Self {
+        Self {
+            shape: Shape::Constant,
+            base,
+            amplitude: 0.0,
+            frequency: 0.0,
+        }
+    }
+
+    pub fn new(shape: Shape, base: f64, amplitude: f64, frequency: f64) -> Self {
+        Self {
+            shape,
+            base,
+            amplitude,
+            frequency,
+        }
+    }
+
+    pub fn value(&self, time: f64) -> f64 {
+        let phase = (time * self.frequency).rem_euclid(1.0);
+        self.base + self.amplitude * self.shape.eval(phase)
+    }
+}
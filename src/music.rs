@@ -0,0 +1,50 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use rodio::buffer::SamplesBuffer;
+use rodio::{Decoder, OutputStream, Sink, Source};
+use tunnel::BeatTrack;
+
+/// Background music decoded up front, so its beats can be analyzed before playback starts.
+pub struct Music {
+    _stream: OutputStream,
+    sink: Sink,
+}
+
+impl Music {
+    pub fn load(path: &Path) -> Result<(Self, BeatTrack), Box<dyn Error>> {
+        let decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let channels = decoder.channels();
+        let sample_rate = decoder.sample_rate();
+        let samples: Vec<f32> = decoder.convert_samples().collect();
+        let track = BeatTrack::analyze(&samples, channels, sample_rate);
+
+        let (stream, handle) = OutputStream::try_default()?;
+        let sink = Sink::try_new(&handle)?;
+        sink.pause();
+        sink.append(SamplesBuffer::new(channels, sample_rate, samples));
+
+        Ok((
+            Self {
+                _stream: stream,
+                sink,
+            },
+            track,
+        ))
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
+        }
+    }
+
+    /// Follows the animation speed; this shifts the pitch but keeps the beats in sync.
+    pub fn set_speed(&self, speed: f64) {
+        self.sink.set_speed(speed as f32);
+    }
+}
@@ -0,0 +1,55 @@
+use std::error::Error;
+
+use ndi::{FourCCVideoType, FrameFormatType, SendBuilder, VideoData};
+
+/// Frame rate advertised to receivers; frames are sent whenever one is drawn regardless.
+const FRAME_RATE: (i32, i32) = (60, 1);
+
+/// Publishes every drawn frame as an NDI source, so VJ software such as Resolume or OBS can
+/// take the tunnel as a live input.
+pub struct NdiOutput {
+    // Dropping the sender waits for the frame in flight, so it has to go before the buffers.
+    sender: ndi::Send,
+    /// Frames are sent asynchronously: the one in flight is only released by the next send,
+    /// so they alternate between two buffers.
+    buffers: [Vec<u8>; 2],
+    next: usize,
+}
+
+impl NdiOutput {
+    pub fn start(name: &str) -> Result<Self, Box<dyn Error>> {
+        ndi::initialize().map_err(|_| "NDI is not supported on this CPU")?;
+        let sender = SendBuilder::new()
+            .ndi_name(name.to_string())
+            .clock_video(false)
+            .build()
+            .map_err(|_| "failed to create the NDI sender")?;
+        println!("Publishing NDI source {}", name);
+        Ok(Self {
+            sender,
+            buffers: [Vec::new(), Vec::new()],
+            next: 0,
+        })
+    }
+
+    pub fn push(&mut self, frame: &[u8], width: u32, height: u32) {
+        let buffer = &mut self.buffers[self.next];
+        self.next = 1 - self.next;
+        buffer.clear();
+        buffer.extend_from_slice(frame);
+        let video = VideoData::from_buffer(
+            width as i32,
+            height as i32,
+            // Frames are always opaque.
+            FourCCVideoType::RGBX,
+            FRAME_RATE.0,
+            FRAME_RATE.1,
+            FrameFormatType::Progressive,
+            0,
+            width as i32 * 4,
+            None,
+            buffer,
+        );
+        self.sender.send_video_async(&video);
+    }
+}
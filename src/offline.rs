@@ -0,0 +1,299 @@
+use std::error::Error;
+
+use image::RgbaImage;
+use tunnel::time::Instant;
+use tunnel::{
+    effect, geometry, texture, Clock, Filter, PaletteKind, Renderer, Shadertoy, TunnelParams, World,
+};
+
+use crate::cli::{BenchArgs, ExportArgs, InspectArgs, RenderArgs, StillArgs};
+use crate::performance::Replay;
+use crate::recorder::Recorder;
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::{apply_config, bench, configure, pipeline, setup, Setup};
+
+/// Longest step the clock takes on its way to the time of a still, so that everything that
+/// accumulates over time (like the distance travelled) ends up where it would when playing.
+const STILL_STEP: f64 = 1.0 / 60.0;
+
+/// Draws `--frames` frames at a fixed step of `--fps`, the same way the window would, and
+/// writes them out without opening one.
+pub fn render(render: &RenderArgs) -> Result<(), Box<dyn Error>> {
+    let args = &render.args;
+    let Setup {
+        config,
+        params,
+        mut animation,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+        ..
+    } = setup(args)?;
+    let mut world = World::with_texture(params, texture);
+    configure(&mut world, args, timeline, height_map, atlas, stamps);
+    apply_config(&mut world, &config);
+    let speed = world.clock().speed();
+    *world.clock_mut() = Clock::fixed(1.0 / render.fps.max(1) as f64);
+    world.clock_mut().set_speed(speed);
+
+    let mut renderer = Renderer::default();
+    renderer.set_simd(!args.scalar);
+    renderer.set_interlace(args.interlace);
+    let mut effects = effect::builtin();
+    effects[0] = Box::new(renderer);
+    let mut pipeline = pipeline(args, effects)?;
+    #[cfg(feature = "scripting")]
+    let mut script = args.script.as_deref().map(Script::load).transpose()?;
+    let mut replay = args
+        .replay
+        .as_deref()
+        .map(|path| Replay::load(path, args.seed))
+        .transpose()?;
+    let mut filter = Filter::Nearest;
+
+    let (width, height) = world.frame_size();
+    let recorder = Recorder::create(render.format, render.out.clone(), width, height, render.fps)?;
+    let mut frame = vec![0u8; width as usize * height as usize * 4];
+    let start = Instant::now();
+    for _ in 0..render.frames {
+        if let Some(texture) = animation
+            .as_mut()
+            .and_then(|animation| animation.frame_at(world.clock().time()))
+        {
+            world.set_texture_frame(texture);
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut script {
+            script.update(&mut world, &Default::default());
+        }
+        if let Some(replay) = &mut replay {
+            replay.update(&mut world, &mut pipeline, &mut filter);
+        }
+        world.update();
+        pipeline.update(&world);
+        pipeline.draw(&mut world, &mut frame);
+        recorder.push(&frame, width, height);
+        world.feed_back(&frame, width, height);
+    }
+    let path = recorder.finish()?;
+    println!(
+        "Rendered {} frames at {}x{} to {} in {:.1} s",
+        render.frames,
+        width,
+        height,
+        path.display(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Builds the lookup table for `--size` and draws the one frame shown `--time` seconds into
+/// the animation, without opening a window.
+pub fn render_still(still: &StillArgs) -> Result<(), Box<dyn Error>> {
+    let args = &still.args;
+    let Setup {
+        config,
+        params,
+        mut animation,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+        ..
+    } = setup(args)?;
+    let params = TunnelParams {
+        width: still.size.width,
+        height: still.size.height,
+        ..params
+    };
+    let start = Instant::now();
+    let mut world = World::with_texture(params, texture);
+    configure(&mut world, args, timeline, height_map, atlas, stamps);
+    apply_config(&mut world, &config);
+    let time = still.time.max(0.0);
+    let steps = ((time / STILL_STEP).ceil() as u64).max(1);
+    *world.clock_mut() = Clock::fixed(time / steps as f64);
+    if let Some(texture) = animation
+        .as_mut()
+        .and_then(|animation| animation.frame_at(time))
+    {
+        world.set_texture_frame(texture);
+    }
+    #[cfg(feature = "scripting")]
+    let mut script = args.script.as_deref().map(Script::load).transpose()?;
+    for _ in 0..steps {
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut script {
+            script.update(&mut world, &Default::default());
+        }
+        world.update();
+    }
+
+    // Wallpapers and prints magnify the texture far more than a window does.
+    let mut renderer = Renderer::default();
+    renderer.set_filter(Filter::Bilinear);
+    let mut effects = effect::builtin();
+    effects[0] = Box::new(renderer);
+    let mut pipeline = pipeline(args, effects)?;
+    pipeline.update(&world);
+    let (width, height) = world.frame_size();
+    let mut frame = vec![0u8; width as usize * height as usize * 4];
+    pipeline.draw(&mut world, &mut frame);
+
+    let image = RgbaImage::from_raw(width, height, frame).ok_or("frame has the wrong size")?;
+    image.save(&still.out)?;
+    println!(
+        "Rendered {}x{} at {:.2} s to {} in {:.1} s",
+        width,
+        height,
+        time,
+        still.out.display(),
+        start.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+pub fn bench(bench: &BenchArgs) -> Result<(), Box<dyn Error>> {
+    let args = &bench.args;
+    let Setup {
+        config,
+        params,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+        ..
+    } = setup(args)?;
+    let mut renderer = Renderer::default();
+    renderer.set_simd(!args.scalar);
+    bench::run(
+        params,
+        texture,
+        |world| {
+            configure(world, args, timeline, height_map, atlas, stamps);
+            apply_config(world, &config);
+        },
+        &renderer,
+        &mut args.postfx()?,
+        bench.frames,
+    );
+    Ok(())
+}
+
+/// Builds the lookup table for `--shape` at the render resolution and reports how it is
+/// stored and what it holds.
+pub fn inspect_lut(inspect: &InspectArgs) -> Result<(), Box<dyn Error>> {
+    let Setup {
+        params, texture, ..
+    } = setup(&inspect.args)?;
+    let shape = geometry::builtin()
+        .into_iter()
+        .find(|geometry| geometry.name() == inspect.shape)
+        .ok_or_else(|| format!("unknown shape `{}`", inspect.shape))?;
+
+    let start = Instant::now();
+    let mut world = World::with_texture(params, texture);
+    let mut elapsed = start.elapsed();
+    if shape.name() != world.geometry().name() {
+        let start = Instant::now();
+        world.set_geometry(shape);
+        elapsed = start.elapsed();
+    }
+    let stats = world.lut_stats();
+    let (near, mean, far) = stats.distance;
+    println!(
+        "{} at {}x{}, {} quality",
+        world.geometry().name(),
+        params.width,
+        params.height,
+        params.lut_quality.name()
+    );
+    println!("covers:    {}x{} entries", stats.width, stats.height);
+    println!(
+        "stored:    {} entries, {:.1} MB{}{}",
+        stats.stored,
+        stats.bytes as f64 / (1024.0 * 1024.0),
+        if stats.mirrored { ", mirrored" } else { "" },
+        if stats.step > 1 {
+            ", every other entry"
+        } else {
+            ""
+        },
+    );
+    println!(
+        "distance:  min {:.2}  mean {:.2}  max {:.2} texels, {:.3}% cut off at the axis",
+        near,
+        mean,
+        far,
+        stats.saturated * 100.0
+    );
+    println!(
+        "ready in:  {:.3} s (loaded from the cache if it was there)",
+        elapsed.as_secs_f64()
+    );
+
+    if let Some(path) = &inspect.out {
+        let image = RgbaImage::from_raw(stats.width as u32, stats.height as u32, world.lut_image())
+            .ok_or("lookup table image has the wrong size")?;
+        image.save(path)?;
+        println!("Saved the table to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Writes the look the options describe as a Shadertoy shader. Textures from files and the
+/// color maps that aren't palettes have no GLSL version, so those are refused.
+pub fn export_shader(export: &ExportArgs) -> Result<(), Box<dyn Error>> {
+    let args = &export.args;
+    if args.texture.is_some() {
+        return Err("textures from files can't be exported; pick a --generator".into());
+    }
+    let Setup { config, params, .. } = setup(args)?;
+    let geometry = geometry::builtin()
+        .into_iter()
+        .find(|geometry| geometry.name() == export.shape)
+        .ok_or_else(|| format!("unknown shape `{}`", export.shape))?;
+    let generator = texture::builtin(args.seed)
+        .into_iter()
+        .find(|generator| generator.name() == export.generator)
+        .ok_or_else(|| format!("unknown texture generator `{}`", export.generator))?;
+    let palette = match (config.palette, &args.color_map) {
+        (Some(palette), _) => palette,
+        (None, Some(name)) => name.parse::<PaletteKind>().map_err(|_| {
+            format!(
+                "the {} color map can't be exported; pick a palette with --color-map",
+                name
+            )
+        })?,
+        (None, None) => PaletteKind::Green,
+    };
+
+    let source = Shadertoy {
+        geometry: geometry.as_ref(),
+        generator: generator.as_ref(),
+        palette,
+        height: params.height,
+        texture_size: (params.tex_width, params.tex_height),
+        ratio: config.ratio.unwrap_or(params.ratio),
+        speed: config.speed.unwrap_or(args.speed),
+        flight: args.flight(),
+        palette_speed: params.palette_speed,
+        fog: args.fog(),
+        roll: args.roll,
+        roll_speed: args.auto_roll,
+    }
+    .source()?;
+    match &export.out {
+        Some(path) => {
+            std::fs::write(path, source)?;
+            println!("Wrote the shader to {}", path.display());
+        }
+        None => print!("{}", source),
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use tunnel::time::Instant;
+use tunnel::{font, RenderStats};
+
+const SMOOTHING: f64 = 0.1;
+const SCALE: usize = 2;
+const MARGIN: usize = 8;
+const BAR_WIDTH: usize = 120;
+const BAR_HEIGHT: usize = 3;
+const TEXT_COLOR: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+const BAR_COLOR: [u8; 4] = [0xff, 0xc0, 0x40, 0xff];
+
+pub struct Overlay {
+    visible: bool,
+    last_frame: Option<Instant>,
+    frame_time: f64,
+    draw_time: f64,
+    bands: Vec<f64>,
+}
+
+fn smooth(average: f64, sample: Duration) -> f64 {
+    let sample = sample.as_secs_f64();
+    if average == 0.0 {
+        sample
+    } else {
+        average + (sample - average) * SMOOTHING
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            last_frame: None,
+            frame_time: 0.0,
+            draw_time: 0.0,
+            bands: Vec::new(),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn record(&mut self, stats: &RenderStats) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.frame_time = smooth(self.frame_time, now - last_frame);
+        }
+        self.last_frame = Some(now);
+
+        self.draw_time = smooth(self.draw_time, stats.total);
+        self.bands.resize(stats.bands.len(), 0.0);
+        for (average, &sample) in self.bands.iter_mut().zip(&stats.bands) {
+            *average = smooth(*average, sample);
+        }
+    }
+
+    pub fn draw(&self, frame: &mut [u8], frame_width: usize) {
+        if !self.visible {
+            return;
+        }
+
+        let fps = if self.frame_time > 0.0 {
+            1.0 / self.frame_time
+        } else {
+            0.0
+        };
+        let slowest = self.bands.iter().cloned().fold(0.0, f64::max);
+        let text = format!(
+            "FPS {:.1}\nFRAME {:.2} MS\nDRAW {:.2} MS\nBANDS {} MAX {:.2} MS",
+            fps,
+            self.frame_time * 1000.0,
+            self.draw_time * 1000.0,
+            self.bands.len(),
+            slowest * 1000.0,
+        );
+        font::draw_text(
+            frame,
+            frame_width,
+            (MARGIN, MARGIN),
+            SCALE,
+            TEXT_COLOR,
+            &text,
+        );
+
+        let top = MARGIN + text.lines().count() * font::line_height(SCALE);
+        for (i, &band) in self.bands.iter().enumerate() {
+            let length = if slowest > 0.0 {
+                (band / slowest * BAR_WIDTH as f64) as usize
+            } else {
+                0
+            };
+            let y = top + i * (BAR_HEIGHT + 1);
+            for row in y..y + BAR_HEIGHT {
+                let start = (row * frame_width + MARGIN) * 4;
+                let end = start + length.min(frame_width.saturating_sub(MARGIN)) * 4;
+                if let Some(bar) = frame.get_mut(start..end) {
+                    for pixel in bar.chunks_exact_mut(4) {
+                        pixel.copy_from_slice(&BAR_COLOR);
+                    }
+                }
+            }
+        }
+    }
+}
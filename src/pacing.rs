@@ -0,0 +1,35 @@
+use std::hint;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sleeps can overshoot by a scheduler tick, so the last stretch before a frame is spun.
+const SPIN: Duration = Duration::from_millis(2);
+
+/// Holds frames back so that at most a given number are drawn per second.
+pub struct FrameLimiter {
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(fps: f64) -> Self {
+        Self {
+            interval: Duration::from_secs_f64(1.0 / fps.max(1.0)),
+            next: None,
+        }
+    }
+
+    /// Blocks until the next frame is due. A frame that ran late pushes the following ones
+    /// back rather than having them hurry to catch up.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let next = self.next.unwrap_or(now);
+        if next > now + SPIN {
+            thread::sleep(next - now - SPIN);
+        }
+        while Instant::now() < next {
+            hint::spin_loop();
+        }
+        self.next = Some(next.max(Instant::now()) + self.interval);
+    }
+}
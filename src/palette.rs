@@ -0,0 +1,136 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use crate::color;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteKind {
+    Green,
+    Fire,
+    Ocean,
+    Plasma,
+    Grayscale,
+}
+
+impl PaletteKind {
+    pub const ALL: [PaletteKind; 5] = [
+        PaletteKind::Green,
+        PaletteKind::Fire,
+        PaletteKind::Ocean,
+        PaletteKind::Plasma,
+        PaletteKind::Grayscale,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaletteKind::Green => "green",
+            PaletteKind::Fire => "fire",
+            PaletteKind::Ocean => "ocean",
+            PaletteKind::Plasma => "plasma",
+            PaletteKind::Grayscale => "grayscale",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        let i = Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+        Self::ALL[(i + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    pub fn palette(self) -> Palette {
+        Palette::from_fn(|i| self.rgb(i))
+    }
+
+    /// The color of index `i`.
+    pub fn rgb(self, i: u8) -> [u8; 3] {
+        match self {
+            PaletteKind::Green => [0, i, 0],
+            PaletteKind::Fire => {
+                let ramp = |from: u8| (i.saturating_sub(from) as u16 * 3).min(0xff) as u8;
+                [ramp(0), ramp(85), ramp(170)]
+            }
+            PaletteKind::Ocean => {
+                let i = i as u16;
+                [
+                    (i * i / 0x1ff) as u8,
+                    (i * 3 / 4) as u8,
+                    (0x40 + i * 3 / 4) as u8,
+                ]
+            }
+            PaletteKind::Plasma => {
+                let wave = |frequency: f64, phase: f64| {
+                    let t = 2.0 * PI * frequency * i as f64 / 256.0 + phase;
+                    (127.5 + 127.5 * t.sin()) as u8
+                };
+                [
+                    wave(1.0, 0.0),
+                    wave(2.0, 2.0 * PI / 3.0),
+                    wave(3.0, 4.0 * PI / 3.0),
+                ]
+            }
+            PaletteKind::Grayscale => [i, i, i],
+        }
+    }
+
+    /// GLSL defining `vec3 palette(float i)`, which gives the color of index `i` like `rgb`
+    /// does, with channels from 0 to 1.
+    pub fn glsl(self) -> String {
+        let color = match self {
+            PaletteKind::Green => "vec3(0.0, i, 0.0)",
+            PaletteKind::Fire => "min(max(vec3(i) - vec3(0.0, 85.0, 170.0), 0.0) * 3.0, 255.0)",
+            PaletteKind::Ocean => "floor(vec3(i * i / 511.0, i * 0.75, 64.0 + i * 0.75))",
+            PaletteKind::Plasma => {
+                "floor(127.5 + 127.5 * sin(2.0 * PI * (vec3(1.0, 2.0, 3.0) * i / 256.0 \
+                 + vec3(0.0, 1.0, 2.0) / 3.0)))"
+            }
+            PaletteKind::Grayscale => "vec3(i)",
+        };
+        format!(
+            "vec3 palette(float i) {{\n    return {} / 255.0;\n}}\n",
+            color
+        )
+    }
+}
+
+impl FromStr for PaletteKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown palette `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Palette {
+    colors: Vec<u32>,
+}
+
+impl Palette {
+    pub fn from_fn<F: Fn(u8) -> [u8; 3]>(f: F) -> Self {
+        let colors = (0..=255u8)
+            .map(|i| {
+                let [r, g, b] = f(i);
+                color::pack([r, g, b, 0xff])
+            })
+            .collect();
+        Self { colors }
+    }
+
+    pub fn color(&self, index: u8) -> u32 {
+        self.colors[index as usize]
+    }
+}
@@ -0,0 +1,472 @@
+//! Recording a live performance, and playing it back in the window or in `render`, e.g. to
+//! draw it again at a higher resolution. What is recorded is every change to the settings
+//! that can be steered while the tunnel plays, stamped with the animation time, rather than
+//! the keys pressed: the replay doesn't depend on the key bindings or the frame rate.
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Deserialize;
+use toml::{Table, Value};
+use tracing::warn;
+use tunnel::{
+    color_map, geometry, texture, AddressMode, BlendMode, CameraMode, Filter, Flight, Fog, Light,
+    Modulator, PaletteKind, PassKind, Pipeline, Shape, Stage, TextureGenerator, World,
+};
+
+/// The settings a performance follows.
+const CHANNELS: [&str; 20] = [
+    "speed",
+    "travel",
+    "spin",
+    "ratio",
+    "breathing",
+    "palette",
+    "color_map",
+    "shape",
+    "texture",
+    "fog",
+    "roll",
+    "roll_speed",
+    "light",
+    "feedback",
+    "wrap",
+    "camera",
+    "stages",
+    "postfx",
+    "trail",
+    "filter",
+];
+/// How far the roll may stray from where rolling by itself takes it before it counts as
+/// turned by hand.
+const ROLL_TOLERANCE: f64 = 1e-6;
+
+/// The settings as they are now, by channel. The texture is only known by name while it
+/// comes from one of the generators.
+pub fn capture(world: &World, pipeline: &Pipeline, filter: Filter, texture: Option<&str>) -> Table {
+    let mut state = Table::new();
+    let mut set = |channel: &str, value: Value| {
+        state.insert(channel.to_string(), value);
+    };
+    let flight = world.flight();
+    set("speed", world.clock().speed().into());
+    set("travel", flight.travel.into());
+    set("spin", flight.spin.into());
+    let modulator = world.ratio_modulator();
+    set("ratio", modulator.base.into());
+    set("breathing", breathing(modulator));
+    set("palette", world.palette_kind().name().into());
+    set("color_map", world.color_map().name().into());
+    set("shape", world.geometry().name().into());
+    if let Some(name) = texture {
+        set("texture", name.into());
+    }
+    let fog = world.fog();
+    let mut table = Table::new();
+    table.insert("curve".to_string(), fog.curve.name().into());
+    table.insert("start".to_string(), fog.start.into());
+    table.insert("end".to_string(), fog.end.into());
+    table.insert("density".to_string(), fog.density.into());
+    set("fog", table.into());
+    set("roll", world.roll().into());
+    set("roll_speed", world.roll_speed().into());
+    set("light", world.light().enabled.into());
+    set("feedback", world.feedback().into());
+    let (depth, angle) = world.address_modes();
+    set("wrap", vec![depth.name(), angle.name()].into());
+    let camera = world.camera();
+    let mode = match camera.mode() {
+        CameraMode::Auto => "auto",
+        CameraMode::Manual => "manual",
+        CameraMode::Follow => "follow",
+    };
+    let mut table = Table::new();
+    table.insert("mode".to_string(), mode.into());
+    // Steering by itself, the view goes where the time takes it.
+    if camera.mode() != CameraMode::Auto {
+        let (x, y) = camera.heading();
+        table.insert("heading".to_string(), vec![x, y].into());
+    }
+    set("camera", table.into());
+    let stages: Vec<Value> = pipeline
+        .compositor
+        .stages
+        .iter()
+        .map(|stage| {
+            let mut table = Table::new();
+            let effect = pipeline
+                .effects
+                .get(stage.effect)
+                .map_or("", |effect| effect.name());
+            table.insert("effect".to_string(), effect.into());
+            table.insert("blend".to_string(), stage.mode.name().into());
+            table.insert("opacity".to_string(), stage.opacity.into());
+            table.into()
+        })
+        .collect();
+    set("stages", stages.into());
+    let passes: Vec<&str> = pipeline
+        .postfx
+        .passes()
+        .filter(|&(_, enabled)| enabled)
+        .map(|(name, _)| name)
+        .collect();
+    set("postfx", passes.into());
+    if let Some(trail) = pipeline.postfx.amount(PassKind::MotionBlur.name()) {
+        set("trail", f64::from(trail).into());
+    }
+    let filter = match filter {
+        Filter::Nearest => "nearest",
+        Filter::Bilinear => "bilinear",
+    };
+    set("filter", filter.into());
+    state
+}
+
+fn breathing(modulator: &Modulator) -> Value {
+    let mut table = Table::new();
+    let shape = match modulator.shape {
+        Shape::Constant => "constant",
+        Shape::Sine => "sine",
+        Shape::Triangle => "triangle",
+        Shape::Envelope { attack, release } => {
+            table.insert("attack".to_string(), attack.into());
+            table.insert("release".to_string(), release.into());
+            "envelope"
+        }
+    };
+    table.insert("shape".to_string(), shape.into());
+    table.insert("amplitude".to_string(), modulator.amplitude.into());
+    table.insert("frequency".to_string(), modulator.frequency.into());
+    table.into()
+}
+
+/// Writes a performance to a file as it happens: the settings at the start, then whatever
+/// changed before each frame, as `[[change]]` tables.
+pub struct PerformanceRecorder {
+    file: BufWriter<File>,
+    last: Table,
+    last_time: f64,
+}
+
+impl PerformanceRecorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        writeln!(
+            file,
+            "# A tunnel-rs performance; play it back with --replay {}.",
+            path.display()
+        )?;
+        Ok(Self {
+            file,
+            last: Table::new(),
+            last_time: 0.0,
+        })
+    }
+
+    /// Records `state` at animation `time`, if anything changed. Every change is flushed
+    /// right away, since the window may close without a chance to finish the file.
+    pub fn record(&mut self, time: f64, state: Table) -> io::Result<()> {
+        let mut change: Table = state
+            .iter()
+            .filter(|&(channel, value)| self.last.get(channel) != Some(value))
+            .map(|(channel, value)| (channel.clone(), value.clone()))
+            .collect();
+        // Rolling by itself is left to the replay, which rolls the same way.
+        let number = |state: &Table, channel: &str| state.get(channel).and_then(Value::as_float);
+        if let (Some(roll), Some(last), Some(speed)) = (
+            number(&state, "roll"),
+            number(&self.last, "roll"),
+            number(&self.last, "roll_speed"),
+        ) {
+            let expected = (last + (time - self.last_time) * speed).rem_euclid(1.0);
+            let off = (roll - expected).abs();
+            if off.min(1.0 - off) < ROLL_TOLERANCE {
+                change.remove("roll");
+            }
+        }
+        self.last = state;
+        self.last_time = time;
+        if change.is_empty() {
+            return Ok(());
+        }
+        // One line per setting, with tables inline so that they stay inside the change.
+        writeln!(self.file, "\n[[change]]\ntime = {}", Value::from(time))?;
+        for (channel, value) in change {
+            writeln!(self.file, "{} = {}", channel, value)?;
+        }
+        self.file.flush()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PerformanceFile {
+    #[serde(default, rename = "change")]
+    changes: Vec<Table>,
+}
+
+/// A recorded performance played back against the world's clock.
+pub struct Replay {
+    /// Animation times and what changed at them, in order.
+    changes: Vec<(f64, Table)>,
+    next: usize,
+    settings: Settings,
+}
+
+impl Replay {
+    /// Reads a performance written by `PerformanceRecorder`; named textures are generated
+    /// with `seed`.
+    pub fn load(path: &Path, seed: u64) -> Result<Self, Box<dyn Error>> {
+        let file: PerformanceFile = toml::from_str(&fs::read_to_string(path)?)?;
+        let mut changes = Vec::with_capacity(file.changes.len());
+        for mut change in file.changes {
+            let time = change
+                .remove("time")
+                .and_then(|time| time.as_float())
+                .ok_or("every change needs a `time`")?;
+            if let Some(channel) = change
+                .keys()
+                .find(|channel| !CHANNELS.contains(&channel.as_str()))
+            {
+                return Err(format!(
+                    "unknown setting `{}` (expected one of {})",
+                    channel,
+                    CHANNELS.join(", ")
+                )
+                .into());
+            }
+            changes.push((time, change));
+        }
+        changes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(Self {
+            changes,
+            next: 0,
+            settings: Settings::new(seed),
+        })
+    }
+
+    /// Applies the changes that are due by the world's time. Settings that don't fit are
+    /// skipped with a warning.
+    pub fn update(&mut self, world: &mut World, pipeline: &mut Pipeline, filter: &mut Filter) {
+        let time = world.clock().time();
+        while let Some((at, change)) = self.changes.get(self.next) {
+            if *at > time {
+                break;
+            }
+            for (channel, value) in change {
+                if let Err(err) = self.settings.apply(world, pipeline, filter, channel, value) {
+                    warn!(
+                        "Skipping `{}` of the replay at {:.2} s: {}",
+                        channel, at, err
+                    );
+                }
+            }
+            self.next += 1;
+        }
+    }
+}
+
+/// Puts settings as `capture` takes them back into the world and the pipeline.
+pub struct Settings {
+    /// Textures are only known by name, and generated again.
+    generators: Vec<Box<dyn TextureGenerator>>,
+}
+
+impl Settings {
+    /// Named textures are generated with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            generators: texture::builtin(seed),
+        }
+    }
+
+    /// Sets `channel` to `value`; channels that aren't one of `CHANNELS` are ignored.
+    pub fn apply(
+        &self,
+        world: &mut World,
+        pipeline: &mut Pipeline,
+        filter: &mut Filter,
+        channel: &str,
+        value: &Value,
+    ) -> Result<(), String> {
+        let number = |value: &Value| {
+            value
+                .as_float()
+                .or_else(|| value.as_integer().map(|n| n as f64))
+                .ok_or_else(|| format!("expected a number, got {}", value))
+        };
+        let text = |value: &Value| {
+            value
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| format!("expected a string, got {}", value))
+        };
+        let field = |value: &Value, name: &str| {
+            value
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("missing `{}`", name))
+        };
+        let flight = *world.flight();
+        match channel {
+            "speed" => world.clock_mut().set_speed(number(value)?),
+            "travel" => world.set_flight(Flight {
+                travel: number(value)?,
+                ..flight
+            }),
+            "spin" => world.set_flight(Flight {
+                spin: number(value)?,
+                ..flight
+            }),
+            "ratio" => world.set_ratio_modulator(Modulator {
+                base: number(value)?,
+                ..*world.ratio_modulator()
+            }),
+            "breathing" => {
+                let shape = match text(&field(value, "shape")?)?.as_str() {
+                    "constant" => Shape::Constant,
+                    "sine" => Shape::Sine,
+                    "triangle" => Shape::Triangle,
+                    "envelope" => Shape::Envelope {
+                        attack: number(&field(value, "attack")?)?,
+                        release: number(&field(value, "release")?)?,
+                    },
+                    shape => return Err(format!("unknown shape `{}`", shape)),
+                };
+                world.set_ratio_modulator(Modulator {
+                    shape,
+                    amplitude: number(&field(value, "amplitude")?)?,
+                    frequency: number(&field(value, "frequency")?)?,
+                    ..*world.ratio_modulator()
+                });
+            }
+            "palette" => {
+                let kind: PaletteKind = text(value)?.parse()?;
+                if kind != world.palette_kind() {
+                    world.set_palette(kind);
+                }
+            }
+            "color_map" => {
+                let name = text(value)?;
+                if name != world.color_map().name() {
+                    world.set_color_map(color_map::find(&name)?);
+                }
+            }
+            "shape" => {
+                let name = text(value)?;
+                if name != world.geometry().name() {
+                    let shape = geometry::builtin()
+                        .into_iter()
+                        .find(|shape| shape.name() == name)
+                        .ok_or_else(|| format!("unknown shape `{}`", name))?;
+                    world.set_geometry(shape);
+                }
+            }
+            "texture" => {
+                let name = text(value)?;
+                let generator = self
+                    .generators
+                    .iter()
+                    .find(|generator| generator.name() == name)
+                    .ok_or_else(|| format!("unknown texture `{}`", name))?;
+                let params = world.params();
+                world.set_texture(generator.generate(params.tex_width, params.tex_height));
+            }
+            "fog" => world.set_fog(Fog {
+                curve: text(&field(value, "curve")?)?.parse()?,
+                start: number(&field(value, "start")?)?,
+                end: number(&field(value, "end")?)?,
+                density: number(&field(value, "density")?)?,
+            }),
+            "roll" => world.set_roll(number(value)?),
+            "roll_speed" => world.set_roll_speed(number(value)?),
+            "light" => {
+                let enabled = value
+                    .as_bool()
+                    .ok_or_else(|| format!("expected true or false, got {}", value))?;
+                world.set_light(Light {
+                    enabled,
+                    ..*world.light()
+                });
+            }
+            "feedback" => world.set_feedback(number(value)?),
+            "wrap" => {
+                let modes = value
+                    .as_array()
+                    .filter(|modes| modes.len() == 2)
+                    .ok_or_else(|| format!("expected two wrap modes, got {}", value))?;
+                let depth: AddressMode = text(&modes[0])?.parse()?;
+                let angle: AddressMode = text(&modes[1])?.parse()?;
+                world.set_address_modes(depth, angle);
+            }
+            "camera" => {
+                let camera = world.camera_mut();
+                camera.set_mode(match text(&field(value, "mode")?)?.as_str() {
+                    "auto" => CameraMode::Auto,
+                    "manual" => CameraMode::Manual,
+                    "follow" => CameraMode::Follow,
+                    mode => return Err(format!("unknown camera mode `{}`", mode)),
+                });
+                if let Some(heading) = value.get("heading").and_then(Value::as_array) {
+                    if let [x, y] = heading.as_slice() {
+                        camera.set_heading(number(x)?, number(y)?);
+                    }
+                }
+            }
+            "stages" => {
+                let stages = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of stages, got {}", value))?;
+                let mut compositor_stages = Vec::with_capacity(stages.len());
+                for stage in stages {
+                    let name = text(&field(stage, "effect")?)?;
+                    let effect = pipeline
+                        .effects
+                        .iter()
+                        .position(|effect| effect.name() == name)
+                        .ok_or_else(|| format!("unknown effect `{}`", name))?;
+                    let mode: BlendMode = text(&field(stage, "blend")?)?.parse()?;
+                    compositor_stages.push(Stage {
+                        effect,
+                        mode,
+                        opacity: number(&field(stage, "opacity")?)?,
+                    });
+                }
+                pipeline.compositor.stages = compositor_stages;
+            }
+            "postfx" => {
+                let passes = value
+                    .as_array()
+                    .ok_or_else(|| format!("expected a list of passes, got {}", value))?;
+                let enabled: Vec<&str> = passes.iter().filter_map(Value::as_str).collect();
+                let names: Vec<String> = pipeline
+                    .postfx
+                    .passes()
+                    .map(|(name, _)| name.to_string())
+                    .collect();
+                for name in names {
+                    let on = enabled.contains(&name.as_str());
+                    pipeline.postfx.set_enabled(&name, on);
+                }
+            }
+            "trail" => pipeline
+                .postfx
+                .set_amount(PassKind::MotionBlur.name(), number(value)? as f32),
+            "filter" => {
+                *filter = match text(value)?.as_str() {
+                    "nearest" => Filter::Nearest,
+                    "bilinear" => Filter::Bilinear,
+                    name => return Err(format!("unknown filter `{}`", name)),
+                };
+                for effect in &mut pipeline.effects {
+                    effect.set_filter(*filter);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::compositor::Compositor;
+use crate::effect::{self, Effect};
+use crate::flash::FlashLimiter;
+use crate::postfx::PostFx;
+use crate::renderer::RenderStats;
+use crate::scroller::Scroller;
+use crate::split::Split;
+use crate::stereo::Stereo;
+use crate::world::World;
+
+/// Everything that turns an updated world into a frame: the effects stacked by the
+/// compositor, stereo output, a split-screen comparison, a sine scroller, post-processing
+/// and, for reduced motion, a limit on flashing. It needs no window, so the frames it draws
+/// depend on nothing but the world, which makes them reproducible for a fixed clock.
+pub struct Pipeline {
+    pub compositor: Compositor,
+    pub effects: Vec<Box<dyn Effect>>,
+    pub stereo: Option<Stereo>,
+    pub split: Option<Split>,
+    pub postfx: PostFx,
+    /// Made hidden when a timeline cues one and there is none.
+    pub scroller: Option<Scroller>,
+    scene: Option<usize>,
+    /// Kept while the world asks for reduced motion.
+    flash: Option<FlashLimiter>,
+}
+
+impl Default for Pipeline {
+    /// The plain tunnel, with every built-in effect available to the compositor.
+    fn default() -> Self {
+        Self::new(Compositor::new(0), effect::builtin())
+    }
+}
+
+impl Pipeline {
+    pub fn new(compositor: Compositor, effects: Vec<Box<dyn Effect>>) -> Self {
+        Self {
+            compositor,
+            effects,
+            stereo: None,
+            split: None,
+            postfx: PostFx::default(),
+            scroller: None,
+            scene: None,
+            flash: None,
+        }
+    }
+
+    /// Follows the timeline to the effect and scroller cue of its current scene and advances
+    /// the effects and the scroller, once per `World::update`.
+    pub fn update(&mut self, world: &World) {
+        if world.scene() != self.scene {
+            let cue = world
+                .timeline()
+                .zip(world.scene())
+                .and_then(|(timeline, index)| timeline.scenes()[index].scroller.as_ref());
+            if let Some(cue) = cue {
+                self.scroller
+                    .get_or_insert_with(|| Scroller::hidden(None))
+                    .cue(cue);
+            }
+        }
+        if let Some(scroller) = &mut self.scroller {
+            scroller.update(world);
+        }
+        self.compositor
+            .follow_scene(world, &mut self.scene, &self.effects);
+        self.compositor.update(world, &mut self.effects);
+    }
+
+    /// Draws the frame of `world` into `frame`, whose size is `World::frame_size`.
+    pub fn draw(&mut self, world: &mut World, frame: &mut [u8]) -> RenderStats {
+        let Self {
+            compositor,
+            effects,
+            stereo,
+            split,
+            postfx,
+            scroller,
+            flash,
+            ..
+        } = self;
+        let mut draw =
+            |world: &mut World, effects: &mut [Box<dyn Effect>], frame: &mut [u8]| match stereo {
+                Some(stereo) => stereo.draw(world, frame, |world, frame| {
+                    compositor.draw(world, effects, frame)
+                }),
+                None => compositor.draw(world, effects, frame),
+            };
+        let stats = match split {
+            Some(split) => split.draw(world, effects, frame, draw),
+            None => draw(world, effects, frame),
+        };
+        let (width, height) = world.frame_size();
+        if let Some(scroller) = scroller {
+            scroller.draw(frame, width as usize, height as usize);
+        }
+        postfx.apply(frame, width as usize, height as usize);
+        if world.reduced_motion() {
+            flash.get_or_insert_with(FlashLimiter::default).apply(
+                frame,
+                width as usize,
+                height as usize,
+                world.clock().time(),
+            );
+        } else {
+            *flash = None;
+        }
+        stats
+    }
+}
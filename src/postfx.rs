@@ -0,0 +1,844 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use rayon::prelude::*;
+use tracing::debug_span;
+
+pub trait Pass: Send {
+    fn name(&self) -> &str;
+
+    /// Processes a tightly packed RGBA frame in place.
+    fn apply(&mut self, frame: &mut [u8], width: usize, height: usize);
+
+    /// The strength of a pass that has one adjustable at runtime, between 0 and 1.
+    fn amount(&self) -> Option<f32> {
+        None
+    }
+
+    fn set_amount(&mut self, _amount: f32) {}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PassKind {
+    Bloom,
+    Scanlines,
+    Vignette,
+    ChromaticAberration,
+    Pixelate,
+    Crt,
+    MotionBlur,
+    Dither,
+    Grade,
+}
+
+impl PassKind {
+    pub const ALL: [PassKind; 9] = [
+        PassKind::Bloom,
+        PassKind::Scanlines,
+        PassKind::Vignette,
+        PassKind::ChromaticAberration,
+        PassKind::Pixelate,
+        PassKind::Crt,
+        PassKind::MotionBlur,
+        PassKind::Dither,
+        PassKind::Grade,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PassKind::Bloom => "bloom",
+            PassKind::Scanlines => "scanlines",
+            PassKind::Vignette => "vignette",
+            PassKind::ChromaticAberration => "chromatic",
+            PassKind::Pixelate => "pixelate",
+            PassKind::Crt => "crt",
+            PassKind::MotionBlur => "motion-blur",
+            PassKind::Dither => "dither",
+            PassKind::Grade => "grade",
+        }
+    }
+
+    pub fn pass(self) -> Box<dyn Pass> {
+        match self {
+            PassKind::Bloom => Box::new(Bloom::default()),
+            PassKind::Scanlines => Box::new(Scanlines {
+                period: 2,
+                darkness: 0.5,
+            }),
+            PassKind::Vignette => Box::new(Vignette::new(0.8)),
+            PassKind::ChromaticAberration => Box::new(ChromaticAberration::new(6.0)),
+            PassKind::Pixelate => Box::new(Pixelate { size: 6 }),
+            PassKind::Crt => Box::new(Crt::default()),
+            PassKind::MotionBlur => Box::new(MotionBlur::new(DEFAULT_TRAIL)),
+            PassKind::Dither => Box::new(Dither { levels: 256 }),
+            PassKind::Grade => Box::new(Grade::default()),
+        }
+    }
+}
+
+impl FromStr for PassKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown post-processing pass `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+struct Slot {
+    pass: Box<dyn Pass>,
+    enabled: bool,
+}
+
+/// An ordered chain of passes applied to the frame after the tunnel is drawn.
+#[derive(Default)]
+pub struct PostFx {
+    slots: Vec<Slot>,
+}
+
+impl PostFx {
+    /// Enables the given builtin passes in order, followed by the remaining builtins disabled.
+    pub fn new(order: &[PassKind]) -> Self {
+        let mut postfx = Self::default();
+        for &kind in order {
+            if !postfx.contains(kind.name()) {
+                postfx.push(kind.pass(), true);
+            }
+        }
+        for kind in PassKind::ALL {
+            if !postfx.contains(kind.name()) {
+                postfx.push(kind.pass(), false);
+            }
+        }
+        postfx
+    }
+
+    pub fn push(&mut self, pass: Box<dyn Pass>, enabled: bool) {
+        self.slots.push(Slot { pass, enabled });
+    }
+
+    /// Swaps in `pass` for the one of the same name, keeping its place and whether it's on.
+    pub fn replace(&mut self, pass: Box<dyn Pass>) {
+        match self
+            .slots
+            .iter_mut()
+            .find(|slot| slot.pass.name() == pass.name())
+        {
+            Some(slot) => slot.pass = pass,
+            None => self.push(pass, false),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.slots.iter().any(|slot| slot.pass.name() == name)
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = (&str, bool)> {
+        self.slots
+            .iter()
+            .map(|slot| (slot.pass.name(), slot.enabled))
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.slots
+            .iter()
+            .any(|slot| slot.enabled && slot.pass.name() == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        for slot in &mut self.slots {
+            if slot.pass.name() == name {
+                slot.enabled = enabled;
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, name: &str) {
+        let enabled = self.is_enabled(name);
+        self.set_enabled(name, !enabled);
+    }
+
+    pub fn amount(&self, name: &str) -> Option<f32> {
+        self.slots
+            .iter()
+            .find(|slot| slot.pass.name() == name)
+            .and_then(|slot| slot.pass.amount())
+    }
+
+    pub fn set_amount(&mut self, name: &str, amount: f32) {
+        for slot in &mut self.slots {
+            if slot.pass.name() == name {
+                slot.pass.set_amount(amount);
+            }
+        }
+    }
+
+    pub fn apply(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        for slot in &mut self.slots {
+            if slot.enabled {
+                let _span = debug_span!("postfx", pass = slot.pass.name()).entered();
+                slot.pass.apply(frame, width, height);
+            }
+        }
+    }
+}
+
+/// Adds a blurred copy of everything brighter than `threshold` back onto the frame.
+pub struct Bloom {
+    pub threshold: u8,
+    pub radius: usize,
+    pub intensity: f32,
+    bright: Vec<[u16; 3]>,
+    blurred: Vec<[u16; 3]>,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            threshold: 160,
+            radius: 8,
+            intensity: 1.5,
+            bright: Vec::new(),
+            blurred: Vec::new(),
+        }
+    }
+}
+
+impl Pass for Bloom {
+    fn name(&self) -> &str {
+        "bloom"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        let len = width * height;
+        self.bright.resize(len, [0; 3]);
+        self.blurred.resize(len, [0; 3]);
+        let threshold = self.threshold;
+        let radius = self.radius.min(width.max(height));
+        let window = (2 * radius + 1) as u32;
+
+        // Horizontal box blur of the bright pass, using a running sum per row.
+        self.bright
+            .par_chunks_exact_mut(width)
+            .zip(frame.par_chunks_exact(width * 4))
+            .for_each(|(out, row)| {
+                let bright = |x: usize| {
+                    let pixel = &row[x.min(width - 1) * 4..][..3];
+                    [0, 1, 2].map(|c| pixel[c].saturating_sub(threshold) as u32)
+                };
+                let mut sum = [0u32; 3];
+                for x in 0..=radius {
+                    let texel = bright(x);
+                    for c in 0..3 {
+                        sum[c] += texel[c] * if x == 0 { radius as u32 + 1 } else { 1 };
+                    }
+                }
+                for (x, out) in out.iter_mut().enumerate() {
+                    *out = sum.map(|s| (s / window) as u16);
+                    let incoming = bright(x + radius + 1);
+                    let outgoing = bright(x.saturating_sub(radius));
+                    for c in 0..3 {
+                        sum[c] = sum[c] + incoming[c] - outgoing[c];
+                    }
+                }
+            });
+
+        let bright = &self.bright;
+        self.blurred
+            .par_chunks_exact_mut(width)
+            .enumerate()
+            .for_each(|(y, out)| {
+                let top = y.saturating_sub(radius);
+                let bottom = (y + radius).min(height - 1);
+                let rows = (bottom - top + 1) as u32;
+                for (x, out) in out.iter_mut().enumerate() {
+                    let mut sum = [0u32; 3];
+                    for row in top..=bottom {
+                        let texel = bright[row * width + x];
+                        for c in 0..3 {
+                            sum[c] += texel[c] as u32;
+                        }
+                    }
+                    *out = sum.map(|s| (s / rows) as u16);
+                }
+            });
+
+        let gain = (self.intensity.max(0.0) * 256.0) as u32;
+        let blurred = &self.blurred;
+        frame
+            .par_chunks_exact_mut(4)
+            .zip(blurred.par_iter())
+            .for_each(|(pixel, glow)| {
+                for c in 0..3 {
+                    let value = pixel[c] as u32 + ((glow[c] as u32 * gain) >> 8);
+                    pixel[c] = value.min(0xff) as u8;
+                }
+            });
+    }
+}
+
+/// Darkens every `period`-th row, imitating the gaps between CRT scanlines.
+pub struct Scanlines {
+    pub period: usize,
+    pub darkness: f32,
+}
+
+impl Pass for Scanlines {
+    fn name(&self) -> &str {
+        "scanlines"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, _height: usize) {
+        let period = self.period.max(2);
+        let keep = ((1.0 - self.darkness.clamp(0.0, 1.0)) * 256.0) as u32;
+        frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .filter(|(y, _)| y % period == period - 1)
+            .for_each(|(_, row)| {
+                for pixel in row.chunks_exact_mut(4) {
+                    for channel in &mut pixel[..3] {
+                        *channel = ((*channel as u32 * keep) >> 8) as u8;
+                    }
+                }
+            });
+    }
+}
+
+/// Darkens the corners; `strength` is the attenuation at the far corners.
+pub struct Vignette {
+    pub strength: f32,
+    weights: Vec<u16>,
+    cached: (usize, usize, u32),
+}
+
+impl Vignette {
+    pub fn new(strength: f32) -> Self {
+        Self {
+            strength,
+            weights: Vec::new(),
+            cached: (0, 0, 0),
+        }
+    }
+}
+
+impl Pass for Vignette {
+    fn name(&self) -> &str {
+        "vignette"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        let key = (width, height, self.strength.to_bits());
+        if self.cached != key {
+            let strength = self.strength.clamp(0.0, 1.0);
+            let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+            let corner = cx * cx + cy * cy;
+            self.weights = (0..width * height)
+                .map(|i| {
+                    let dx = (i % width) as f32 + 0.5 - cx;
+                    let dy = (i / width) as f32 + 0.5 - cy;
+                    let falloff = (dx * dx + dy * dy) / corner;
+                    ((1.0 - strength * falloff * falloff) * 256.0) as u16
+                })
+                .collect();
+            self.cached = key;
+        }
+
+        frame
+            .par_chunks_exact_mut(4)
+            .zip(self.weights.par_iter())
+            .for_each(|(pixel, &weight)| {
+                for channel in &mut pixel[..3] {
+                    *channel = ((*channel as u32 * weight as u32) >> 8) as u8;
+                }
+            });
+    }
+}
+
+/// Pulls the red and blue channels apart radially; `offset` is the shift at the frame edges.
+pub struct ChromaticAberration {
+    pub offset: f32,
+    source: Vec<u8>,
+}
+
+impl ChromaticAberration {
+    pub fn new(offset: f32) -> Self {
+        Self {
+            offset,
+            source: Vec::new(),
+        }
+    }
+}
+
+impl Pass for ChromaticAberration {
+    fn name(&self) -> &str {
+        "chromatic"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        self.source.clear();
+        self.source.extend_from_slice(frame);
+        let source = &self.source;
+        let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+        let spread = self.offset / cx.max(1.0);
+        let sample = |x: f32, y: f32, channel: usize| {
+            let x = (x.round().max(0.0) as usize).min(width - 1);
+            let y = (y.round().max(0.0) as usize).min(height - 1);
+            source[(y * width + x) * 4 + channel]
+        };
+
+        frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let dy = y as f32 - cy;
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let dx = x as f32 - cx;
+                    pixel[0] = sample(cx + dx * (1.0 + spread), cy + dy * (1.0 + spread), 0);
+                    pixel[2] = sample(cx + dx * (1.0 - spread), cy + dy * (1.0 - spread), 2);
+                }
+            });
+    }
+}
+
+/// Replaces every `size`×`size` block with its average color.
+pub struct Pixelate {
+    pub size: usize,
+}
+
+impl Pass for Pixelate {
+    fn name(&self) -> &str {
+        "pixelate"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, _height: usize) {
+        let size = self.size.max(1);
+        if size == 1 {
+            return;
+        }
+        frame
+            .par_chunks_mut(width * 4 * size)
+            .for_each(|block_row| {
+                let rows = block_row.len() / (width * 4);
+                for x0 in (0..width).step_by(size) {
+                    let x1 = (x0 + size).min(width);
+                    let mut sum = [0u32; 4];
+                    for y in 0..rows {
+                        for pixel in
+                            block_row[(y * width + x0) * 4..(y * width + x1) * 4].chunks_exact(4)
+                        {
+                            for c in 0..4 {
+                                sum[c] += pixel[c] as u32;
+                            }
+                        }
+                    }
+                    let count = ((x1 - x0) * rows) as u32;
+                    let average = sum.map(|s| (s / count) as u8);
+                    for y in 0..rows {
+                        for pixel in block_row[(y * width + x0) * 4..(y * width + x1) * 4]
+                            .chunks_exact_mut(4)
+                        {
+                            pixel.copy_from_slice(&average);
+                        }
+                    }
+                }
+            });
+    }
+}
+
+/// Barrel distortion, an aperture-grille phosphor mask and interlace flicker.
+///
+/// The distortion and mask only depend on the frame size and settings, so they are baked into
+/// a remap table and a per-pixel weight table that are rebuilt only when either changes.
+pub struct Crt {
+    pub curvature: f32,
+    pub mask: f32,
+    pub flicker: f32,
+    remap: Vec<u32>,
+    weights: Vec<[u16; 3]>,
+    source: Vec<u8>,
+    cached: (usize, usize, u32, u32),
+    field: usize,
+}
+
+impl Default for Crt {
+    fn default() -> Self {
+        Self {
+            curvature: 0.12,
+            mask: 0.35,
+            flicker: 0.1,
+            remap: Vec::new(),
+            weights: Vec::new(),
+            source: Vec::new(),
+            cached: (0, 0, 0, 0),
+            field: 0,
+        }
+    }
+}
+
+const OFFSCREEN: u32 = u32::MAX;
+
+impl Crt {
+    fn rebuild(&mut self, width: usize, height: usize) {
+        let curvature = self.curvature.max(0.0);
+        // Shrink the picture so the distorted edges land exactly on the frame edges.
+        let fit = 1.0 / (1.0 + curvature);
+        self.remap = (0..width * height)
+            .map(|i| {
+                let nx = ((i % width) as f32 + 0.5) / width as f32 * 2.0 - 1.0;
+                let ny = ((i / width) as f32 + 0.5) / height as f32 * 2.0 - 1.0;
+                let bend = (1.0 + curvature * (nx * nx + ny * ny)) * fit;
+                let (sx, sy) = (nx * bend, ny * bend);
+                if sx.abs() > 1.0 || sy.abs() > 1.0 {
+                    return OFFSCREEN;
+                }
+                let x = (((sx + 1.0) / 2.0 * width as f32) as usize).min(width - 1);
+                let y = (((sy + 1.0) / 2.0 * height as f32) as usize).min(height - 1);
+                (y * width + x) as u32
+            })
+            .collect();
+
+        let dim = ((1.0 - self.mask.clamp(0.0, 1.0)) * 256.0) as u16;
+        self.weights = (0..width * height)
+            .map(|i| {
+                let lit = i % width % 3;
+                [0, 1, 2].map(|channel| if channel == lit { 256 } else { dim })
+            })
+            .collect();
+    }
+}
+
+impl Pass for Crt {
+    fn name(&self) -> &str {
+        "crt"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, height: usize) {
+        let key = (width, height, self.curvature.to_bits(), self.mask.to_bits());
+        if self.cached != key {
+            self.rebuild(width, height);
+            self.cached = key;
+        }
+        self.field ^= 1;
+
+        self.source.clear();
+        self.source.extend_from_slice(frame);
+        let source = &self.source;
+        let remap = &self.remap;
+        let weights = &self.weights;
+        let field = self.field;
+        let flicker = ((1.0 - self.flicker.clamp(0.0, 1.0)) * 256.0) as u32;
+
+        frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let gain = if y % 2 == field { flicker } else { 256 };
+                let offset = y * width;
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let from = remap[offset + x];
+                    if from == OFFSCREEN {
+                        pixel[..3].fill(0);
+                        continue;
+                    }
+                    let texel = &source[from as usize * 4..][..3];
+                    let weight = weights[offset + x];
+                    for c in 0..3 {
+                        pixel[c] = ((texel[c] as u32 * weight[c] as u32 * gain) >> 16) as u8;
+                    }
+                }
+            });
+    }
+}
+
+/// How much of the previous frames `MotionBlur` keeps by default.
+pub const DEFAULT_TRAIL: f32 = 0.8;
+
+/// Blends every frame into an exponentially weighted running average of the previous ones,
+/// leaving a smooth trail behind anything that moves. `trail` is the share of the average
+/// kept each frame; the average is held with 8 extra bits per channel so long trails fade out
+/// completely instead of getting stuck one step short of the new colour.
+pub struct MotionBlur {
+    trail: f32,
+    history: Vec<u16>,
+}
+
+impl MotionBlur {
+    pub fn new(trail: f32) -> Self {
+        let mut blur = Self {
+            trail: 0.0,
+            history: Vec::new(),
+        };
+        blur.set_amount(trail);
+        blur
+    }
+}
+
+impl Pass for MotionBlur {
+    fn name(&self) -> &str {
+        "motion-blur"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], _width: usize, _height: usize) {
+        // A new size (or the first frame) starts the trail afresh.
+        if self.history.len() != frame.len() {
+            self.history = frame.iter().map(|&value| (value as u16) << 8).collect();
+            return;
+        }
+        let keep = (self.trail * 256.0) as u32;
+        let fresh = 256 - keep;
+        self.history
+            .par_chunks_mut(4)
+            .zip(frame.par_chunks_mut(4))
+            .for_each(|(history, pixel)| {
+                for (average, value) in history[..3].iter_mut().zip(&mut pixel[..3]) {
+                    let blended = (*average as u32 * keep + ((*value as u32) << 8) * fresh) >> 8;
+                    *average = blended as u16;
+                    *value = ((blended + 0x80) >> 8).min(255) as u8;
+                }
+            });
+    }
+
+    fn amount(&self) -> Option<f32> {
+        Some(self.trail)
+    }
+
+    fn set_amount(&mut self, amount: f32) {
+        self.trail = amount.clamp(0.0, 0.95);
+    }
+}
+
+/// Thresholds of the 8x8 Bayer matrix, in the order they are reached.
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Ordered dithering with an 8x8 Bayer matrix, quantizing every channel to `levels` evenly
+/// spaced values. With all 256 levels nothing is lost: the values are only spread by a level
+/// either side, which breaks up the bands fog and shading leave in smooth gradients. Fewer
+/// levels give a retro, reduced-palette look.
+pub struct Dither {
+    pub levels: u32,
+}
+
+impl Pass for Dither {
+    fn name(&self) -> &str {
+        "dither"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], width: usize, _height: usize) {
+        let step = 255.0 / (self.levels.clamp(2, 256) - 1) as f32;
+        let spread = step.max(2.0);
+        frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let thresholds = &BAYER[y % 8];
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    let offset = (thresholds[x % 8] as f32 + 0.5) / 64.0 - 0.5;
+                    for channel in &mut pixel[..3] {
+                        let value = *channel as f32 + offset * spread;
+                        *channel = ((value / step).round() * step).clamp(0.0, 255.0) as u8;
+                    }
+                }
+            });
+    }
+}
+
+/// Adjusts the finished frame's gamma, brightness and contrast, in that order, through a
+/// table of what every channel value becomes. A `gamma` above 1 lifts the midtones,
+/// `brightness` is added (-1 to 1) and `contrast` stretches the values around mid-gray.
+pub struct Grade {
+    pub gamma: f32,
+    pub brightness: f32,
+    pub contrast: f32,
+    table: [u8; 256],
+    cached: Option<[u32; 3]>,
+}
+
+impl Grade {
+    pub fn new(gamma: f32, brightness: f32, contrast: f32) -> Self {
+        Self {
+            gamma,
+            brightness,
+            contrast,
+            table: [0; 256],
+            cached: None,
+        }
+    }
+}
+
+/// A slightly punchier look, with lifted midtones and a little more contrast.
+impl Default for Grade {
+    fn default() -> Self {
+        Self::new(1.1, 0.0, 1.15)
+    }
+}
+
+impl Pass for Grade {
+    fn name(&self) -> &str {
+        "grade"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], _width: usize, _height: usize) {
+        let key = [self.gamma, self.brightness, self.contrast].map(f32::to_bits);
+        if self.cached != Some(key) {
+            let gamma = self.gamma.max(0.01);
+            for (value, out) in self.table.iter_mut().enumerate() {
+                let value = (value as f32 / 255.0).powf(1.0 / gamma) + self.brightness;
+                let value = (value - 0.5) * self.contrast.max(0.0) + 0.5;
+                *out = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+            self.cached = Some(key);
+        }
+
+        let table = &self.table;
+        frame.par_chunks_exact_mut(4).for_each(|pixel| {
+            for channel in &mut pixel[..3] {
+                *channel = table[*channel as usize];
+            }
+        });
+    }
+}
+
+/// Grades the frame through a 3D lookup table from a `.cube` file, as used by grading and
+/// editing tools, interpolating trilinearly between its points.
+pub struct ColorLut {
+    /// Points along each axis.
+    size: usize,
+    /// The output color of every point, red changing fastest, then green, then blue.
+    points: Vec<[f32; 3]>,
+    /// Where each channel value falls along its axis: the point below and how far past it.
+    axes: [[(usize, f32); 256]; 3],
+}
+
+impl ColorLut {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read `{}`: {}", path.display(), err))?;
+        Ok(Self::parse(&source).map_err(|err| format!("`{}`: {}", path.display(), err))?)
+    }
+
+    /// Parses the text of a `.cube` file with a `LUT_3D_SIZE`; its `DOMAIN_MIN` and
+    /// `DOMAIN_MAX` (or `LUT_3D_INPUT_RANGE`) default to 0 and 1.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut size = None;
+        let mut domain = ([0.0f32; 3], [1.0f32; 3]);
+        let mut points = Vec::new();
+        for (number, line) in source.lines().enumerate() {
+            let at = |message: String| format!("line {}: {}", number + 1, message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap_or_default();
+            let numbers = |words: std::str::SplitWhitespace, count: usize| {
+                let numbers = words
+                    .map(str::parse::<f32>)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| at(format!("expected numbers in `{}`", line)))?;
+                if numbers.len() != count {
+                    return Err(at(format!("expected {} numbers in `{}`", count, line)));
+                }
+                Ok(numbers)
+            };
+            match keyword {
+                "LUT_3D_SIZE" => {
+                    let points = words
+                        .next()
+                        .and_then(|size| size.parse().ok())
+                        .filter(|size| (2..=256).contains(size))
+                        .ok_or_else(|| at("LUT_3D_SIZE must be from 2 to 256".to_string()))?;
+                    size = Some(points);
+                }
+                "LUT_1D_SIZE" => return Err(at("only 3D LUTs are supported".to_string())),
+                "DOMAIN_MIN" => domain.0.copy_from_slice(&numbers(words, 3)?),
+                "DOMAIN_MAX" => domain.1.copy_from_slice(&numbers(words, 3)?),
+                "LUT_3D_INPUT_RANGE" => {
+                    let range = numbers(words, 2)?;
+                    domain = ([range[0]; 3], [range[1]; 3]);
+                }
+                _ if keyword.parse::<f32>().is_ok() => {
+                    let point = numbers(line.split_whitespace(), 3)?;
+                    points.push([point[0], point[1], point[2]]);
+                }
+                // Titles and the keywords of other tools.
+                _ => {}
+            }
+        }
+
+        let size = size.ok_or("missing LUT_3D_SIZE")?;
+        if points.len() != size * size * size {
+            return Err(format!(
+                "expected {} points for LUT_3D_SIZE {}, found {}",
+                size * size * size,
+                size,
+                points.len()
+            ));
+        }
+        let axes = std::array::from_fn(|channel| {
+            let (min, max) = (domain.0[channel], domain.1[channel]);
+            std::array::from_fn(|value| {
+                let span = if max > min { max - min } else { 1.0 };
+                let position = ((value as f32 / 255.0 - min) / span).clamp(0.0, 1.0);
+                let position = position * (size - 1) as f32;
+                let below = (position as usize).min(size - 2);
+                (below, position - below as f32)
+            })
+        });
+        Ok(Self { size, points, axes })
+    }
+}
+
+impl Pass for ColorLut {
+    fn name(&self) -> &str {
+        "color-lut"
+    }
+
+    fn apply(&mut self, frame: &mut [u8], _width: usize, _height: usize) {
+        let size = self.size;
+        let points = &self.points;
+        let axes = &self.axes;
+        frame.par_chunks_exact_mut(4).for_each(|pixel| {
+            let (r, fr) = axes[0][pixel[0] as usize];
+            let (g, fg) = axes[1][pixel[1] as usize];
+            let (b, fb) = axes[2][pixel[2] as usize];
+            let at = |r: usize, g: usize, b: usize| points[(b * size + g) * size + r];
+            let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+                std::array::from_fn::<f32, 3, _>(|i| a[i] + (b[i] - a[i]) * t)
+            };
+            // Along red on the four edges around the color, then green, then blue.
+            let plane = |b: usize| {
+                lerp(
+                    lerp(at(r, g, b), at(r + 1, g, b), fr),
+                    lerp(at(r, g + 1, b), at(r + 1, g + 1, b), fr),
+                    fg,
+                )
+            };
+            let color = lerp(plane(b), plane(b + 1), fb);
+            for (channel, value) in pixel[..3].iter_mut().zip(color) {
+                *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        });
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use tracing::{info, warn};
+
+use crate::cli::BatterySaver;
+
+/// How often the power source is looked at again.
+const INTERVAL: Duration = Duration::from_secs(15);
+/// Frame rate the window is held to while saving power.
+pub const SAVER_FPS: f64 = 30.0;
+/// Render scale while saving power, a quarter of the pixels.
+pub const SAVER_SCALE: f64 = 0.5;
+
+/// Tells the window when to save power: always, or while the machine runs on battery.
+pub struct PowerSaver {
+    source: Option<Receiver<bool>>,
+    /// The window starts at full quality.
+    saving: bool,
+}
+
+impl PowerSaver {
+    pub fn new(mode: BatterySaver) -> Self {
+        Self {
+            source: (mode == BatterySaver::Auto).then(watch_battery),
+            saving: false,
+        }
+    }
+
+    /// Whether to save power, if that changed since the last call.
+    pub fn update(&mut self) -> Option<bool> {
+        let saving = match &self.source {
+            Some(source) => source.try_iter().last()?,
+            None => true,
+        };
+        if self.saving == saving {
+            return None;
+        }
+        self.saving = saving;
+        if saving {
+            info!("Saving power");
+        } else {
+            info!("Back to full quality");
+        }
+        Some(saving)
+    }
+}
+
+/// Asks whether the machine runs on battery every `INTERVAL` on a thread of its own, as some
+/// systems answer through another process, and sends the answer whenever it changes.
+/// Machines that can't tell are taken to be plugged in.
+fn watch_battery() -> Receiver<bool> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut last = None;
+        loop {
+            let battery = on_battery().unwrap_or_else(|err| {
+                if last.is_none() {
+                    warn!("Failed to read the power source: {}", err);
+                }
+                false
+            });
+            if last != Some(battery) {
+                last = Some(battery);
+                if sender.send(battery).is_err() {
+                    return;
+                }
+            }
+            thread::sleep(INTERVAL);
+        }
+    });
+    receiver
+}
+
+/// On battery when no charger is online and a battery of the system's own (not a mouse's or
+/// a headset's) is discharging.
+#[cfg(target_os = "linux")]
+fn on_battery() -> Result<bool, String> {
+    use std::fs;
+
+    let read = |path: &std::path::Path, name: &str| {
+        fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    // Containers and some desktops don't list any power supply.
+    let Ok(supplies) = fs::read_dir("/sys/class/power_supply") else {
+        return Ok(false);
+    };
+    let mut discharging = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        match read(&path, "type").as_str() {
+            "Mains" | "USB" if read(&path, "online") == "1" => return Ok(false),
+            "Battery" if read(&path, "scope") != "Device" => {
+                discharging |= read(&path, "status") == "Discharging";
+            }
+            _ => {}
+        }
+    }
+    Ok(discharging)
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> Result<bool, String> {
+    let output = std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map_err(|err| format!("pmset: {}", err))?;
+    Ok(String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+}
+
+/// Asks CIM, as the battery status API would take `unsafe`.
+#[cfg(windows)]
+fn on_battery() -> Result<bool, String> {
+    use std::os::windows::process::CommandExt;
+
+    /// Keeps a console window from flashing up for PowerShell.
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "(Get-CimInstance Win32_Battery).BatteryStatus",
+        ])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map_err(|err| format!("powershell: {}", err))?;
+    // 1 is discharging; machines without a battery print nothing.
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|line| line.trim() == "1"))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn on_battery() -> Result<bool, String> {
+    Err("not supported on this system".to_string())
+}
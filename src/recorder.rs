@@ -0,0 +1,181 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use clap::ValueEnum;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use tunnel::time::timestamp;
+
+const QUEUE_LENGTH: usize = 8;
+const GIF_SPEED: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RecordFormat {
+    Png,
+    Gif,
+    Mp4,
+}
+
+pub struct Recorder {
+    width: u32,
+    height: u32,
+    sender: SyncSender<Vec<u8>>,
+    worker: JoinHandle<io::Result<PathBuf>>,
+}
+
+enum Sink {
+    Png { dir: PathBuf, index: usize },
+    Gif(Box<GifEncoder<BufWriter<File>>>),
+    Ffmpeg(Child),
+}
+
+fn image_error(err: image::ImageError) -> io::Error {
+    io::Error::other(err)
+}
+
+impl Sink {
+    fn open(
+        format: RecordFormat,
+        path: &Path,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> io::Result<Self> {
+        match format {
+            RecordFormat::Png => {
+                fs::create_dir_all(path)?;
+                Ok(Sink::Png {
+                    dir: path.to_path_buf(),
+                    index: 0,
+                })
+            }
+            RecordFormat::Gif => {
+                let file = BufWriter::new(File::create(path)?);
+                let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+                encoder.set_repeat(Repeat::Infinite).map_err(image_error)?;
+                Ok(Sink::Gif(Box::new(encoder)))
+            }
+            RecordFormat::Mp4 => {
+                let child = Command::new("ffmpeg")
+                    .args(["-y", "-loglevel", "error", "-f", "rawvideo"])
+                    .args(["-pixel_format", "rgba"])
+                    .args(["-video_size", &format!("{}x{}", width, height)])
+                    .args(["-framerate", &fps.to_string()])
+                    .args(["-i", "-", "-c:v", "libx264", "-pix_fmt", "yuv420p"])
+                    .arg(path)
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                Ok(Sink::Ffmpeg(child))
+            }
+        }
+    }
+
+    fn write(&mut self, frame: Vec<u8>, width: u32, height: u32, fps: u32) -> io::Result<()> {
+        match self {
+            Sink::Png { dir, index } => {
+                let image = RgbaImage::from_raw(width, height, frame).expect("frame size mismatch");
+                let path = dir.join(format!("frame-{:05}.png", index));
+                *index += 1;
+                image.save(path).map_err(image_error)
+            }
+            Sink::Gif(encoder) => {
+                let image = RgbaImage::from_raw(width, height, frame).expect("frame size mismatch");
+                let delay = Delay::from_numer_denom_ms(1000, fps);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .map_err(image_error)
+            }
+            Sink::Ffmpeg(child) => child
+                .stdin
+                .as_mut()
+                .expect("ffmpeg stdin")
+                .write_all(&frame),
+        }
+    }
+
+    fn close(self) -> io::Result<()> {
+        match self {
+            Sink::Png { .. } | Sink::Gif(_) => Ok(()),
+            Sink::Ffmpeg(mut child) => {
+                child.stdin = None;
+                let status = child.wait()?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(io::Error::other(format!("ffmpeg exited with {}", status)))
+                }
+            }
+        }
+    }
+}
+
+fn run(
+    mut sink: Sink,
+    frames: Receiver<Vec<u8>>,
+    width: u32,
+    height: u32,
+    fps: u32,
+) -> io::Result<()> {
+    for frame in frames {
+        sink.write(frame, width, height, fps)?;
+    }
+    sink.close()
+}
+
+impl Recorder {
+    pub fn start(
+        format: RecordFormat,
+        dir: &Path,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let name = format!("tunnel-{}", timestamp());
+        let path = match format {
+            RecordFormat::Png => dir.join(name),
+            RecordFormat::Gif => dir.join(name + ".gif"),
+            RecordFormat::Mp4 => dir.join(name + ".mp4"),
+        };
+        Self::create(format, path, width, height, fps)
+    }
+
+    /// Like `start`, but writes to `path` itself: the folder of PNG frames, or the file.
+    pub fn create(
+        format: RecordFormat,
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        fps: u32,
+    ) -> io::Result<Self> {
+        let fps = fps.max(1);
+        let sink = Sink::open(format, &path, width, height, fps)?;
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_LENGTH);
+        let worker = thread::spawn(move || run(sink, receiver, width, height, fps).map(|_| path));
+
+        Ok(Self {
+            width,
+            height,
+            sender,
+            worker,
+        })
+    }
+
+    pub fn push(&self, frame: &[u8], width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            let _ = self.sender.send(frame.to_vec());
+        }
+    }
+
+    pub fn finish(self) -> io::Result<PathBuf> {
+        drop(self.sender);
+        self.worker
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("recording thread panicked")))
+    }
+}
@@ -0,0 +1,79 @@
+//! Whether the user asked the system for less motion on screen, for `--reduced-motion auto`.
+//! Systems that don't say, or can't be asked, are taken to allow motion.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::process::Command;
+
+use tracing::debug;
+
+pub fn preferred() -> bool {
+    match ask() {
+        Ok(reduce) => reduce,
+        Err(err) => {
+            debug!("Failed to read the reduced motion preference: {}", err);
+            false
+        }
+    }
+}
+
+/// The output of a program asked for a setting.
+#[cfg(not(target_arch = "wasm32"))]
+fn output(command: &mut Command) -> Result<String, String> {
+    let output = command.output().map_err(|err| err.to_string())?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// GNOME's and most GTK desktops' "Reduce animation" switch.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn ask() -> Result<bool, String> {
+    let enabled = output(Command::new("gsettings").args([
+        "get",
+        "org.gnome.desktop.interface",
+        "enable-animations",
+    ]))?;
+    Ok(enabled == "false")
+}
+
+#[cfg(target_os = "macos")]
+fn ask() -> Result<bool, String> {
+    let reduce = output(Command::new("defaults").args([
+        "read",
+        "com.apple.universalaccess",
+        "reduceMotion",
+    ]))?;
+    Ok(reduce == "1")
+}
+
+/// "Show animations in Windows", which turns off window animations along with the others.
+#[cfg(windows)]
+fn ask() -> Result<bool, String> {
+    use std::os::windows::process::CommandExt;
+
+    /// Keeps a console window from flashing up.
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let value = output(
+        Command::new("reg")
+            .args([
+                "query",
+                r"HKCU\Control Panel\Desktop\WindowMetrics",
+                "/v",
+                "MinAnimate",
+            ])
+            .creation_flags(CREATE_NO_WINDOW),
+    )?;
+    Ok(value.ends_with("REG_SZ    0"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn ask() -> Result<bool, String> {
+    let window = web_sys::window().ok_or("no window")?;
+    let query = window
+        .match_media("(prefers-reduced-motion: reduce)")
+        .map_err(|err| format!("{:?}", err))?;
+    Ok(query.is_some_and(|query| query.matches()))
+}
+
+#[cfg(not(any(unix, windows, target_arch = "wasm32")))]
+fn ask() -> Result<bool, String> {
+    Err("not supported on this system".to_string())
+}
@@ -0,0 +1,294 @@
+//! A WebSocket server for changing the tunnel from elsewhere, e.g. a web page or a chat bot.
+//! Every text message is a command, a parameter and its value like `palette ocean` or
+//! `speed 1.5`, and is answered with `ok` or what went wrong. Only the parameters allowed on
+//! the command line can be changed, and each client may only send so many commands a second.
+
+use std::error::Error;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use toml::Value;
+use tracing::{debug, info};
+use tungstenite::Message;
+use tunnel::{Filter, Pipeline, World};
+
+use crate::performance::Settings;
+
+/// How long a client waits for the window to apply its command before it's told the command
+/// is queued, e.g. while the window is minimized.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+/// Clients served at once; more are turned away until one leaves.
+const MAX_CLIENTS: usize = 8;
+/// How long a client may stay silent, handshake included, before it's disconnected.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What the remote can change.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RemoteParam {
+    Palette,
+    ColorMap,
+    Shape,
+    /// A texture generator, by name.
+    Texture,
+    /// The effect of the top compositor stage.
+    Effect,
+    /// Animation speed.
+    Speed,
+    /// Texture lengths flown per second.
+    Travel,
+    /// Turns spun around the tunnel per second.
+    Spin,
+    Ratio,
+    RollSpeed,
+    Feedback,
+    Light,
+    Filter,
+}
+
+impl RemoteParam {
+    fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    /// The performance channel the parameter sets, except for the effect.
+    fn channel(self) -> &'static str {
+        match self {
+            RemoteParam::Palette => "palette",
+            RemoteParam::ColorMap => "color_map",
+            RemoteParam::Shape => "shape",
+            RemoteParam::Texture => "texture",
+            RemoteParam::Effect => "effect",
+            RemoteParam::Speed => "speed",
+            RemoteParam::Travel => "travel",
+            RemoteParam::Spin => "spin",
+            RemoteParam::Ratio => "ratio",
+            RemoteParam::RollSpeed => "roll_speed",
+            RemoteParam::Feedback => "feedback",
+            RemoteParam::Light => "light",
+            RemoteParam::Filter => "filter",
+        }
+    }
+
+    /// The range numbers are clamped to, so that nobody can send the tunnel flying.
+    fn range(self) -> Option<(f64, f64)> {
+        match self {
+            RemoteParam::Speed => Some((0.0, 4.0)),
+            RemoteParam::Travel => Some((-4.0, 4.0)),
+            RemoteParam::Spin | RemoteParam::RollSpeed => Some((-1.0, 1.0)),
+            RemoteParam::Ratio => Some((16.0, 256.0)),
+            RemoteParam::Feedback => Some((0.0, 1.0)),
+            _ => None,
+        }
+    }
+
+    fn parse_value(self, value: &str) -> Result<Value, String> {
+        if let Some((min, max)) = self.range() {
+            return match value.parse::<f64>() {
+                Ok(number) if number.is_finite() => Ok(number.clamp(min, max).into()),
+                _ => Err(format!("expected a number, got `{}`", value)),
+            };
+        }
+        if self == RemoteParam::Light {
+            return match value {
+                "on" | "true" => Ok(true.into()),
+                "off" | "false" => Ok(false.into()),
+                _ => Err(format!("expected on or off, got `{}`", value)),
+            };
+        }
+        Ok(value.into())
+    }
+}
+
+/// A client's command, and where to send the outcome.
+struct Command {
+    param: RemoteParam,
+    value: Value,
+    reply: Sender<Result<(), String>>,
+}
+
+/// Commands from the clients of the WebSocket server, applied before every frame.
+pub struct Remote {
+    commands: Receiver<Command>,
+    settings: Settings,
+}
+
+impl Remote {
+    /// Serves on `address`, a port (on this machine only) or an address with one, letting
+    /// clients change the `allowed` parameters `rate` times a second each; named textures are
+    /// generated with `seed`.
+    pub fn start(
+        address: &str,
+        allowed: &[RemoteParam],
+        rate: f64,
+        seed: u64,
+    ) -> Result<Self, Box<dyn Error>> {
+        let address = match address.parse::<u16>() {
+            Ok(port) => SocketAddr::from(([127, 0, 0, 1], port)),
+            Err(_) => address
+                .to_socket_addrs()
+                .map_err(|err| format!("failed to resolve `{}`: {}", address, err))?
+                .next()
+                .ok_or_else(|| format!("`{}` has no address", address))?,
+        };
+        let listener = TcpListener::bind(address)
+            .map_err(|err| format!("failed to listen on {}: {}", address, err))?;
+        let names: Vec<String> = allowed.iter().map(|param| param.name()).collect();
+        println!(
+            "Taking remote commands for {} on ws://{}",
+            names.join(", "),
+            address
+        );
+
+        let allowed: Arc<[RemoteParam]> = allowed.into();
+        let (sender, commands) = mpsc::channel();
+        let clients = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Some(slot) = Slot::take(&clients) else {
+                    debug!(
+                        "Refused a remote connection: {} clients already",
+                        MAX_CLIENTS
+                    );
+                    continue;
+                };
+                if let Err(err) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+                    debug!("Refused a remote connection: {}", err);
+                    continue;
+                }
+                let allowed = allowed.clone();
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let _slot = slot;
+                    serve(stream, &allowed, rate, sender);
+                });
+            }
+        });
+        Ok(Self {
+            commands,
+            settings: Settings::new(seed),
+        })
+    }
+
+    /// Applies the commands received since the last call.
+    pub fn update(&self, world: &mut World, pipeline: &mut Pipeline, filter: &mut Filter) {
+        for command in self.commands.try_iter() {
+            let result = match command.param {
+                RemoteParam::Effect => {
+                    let name = command.value.as_str().unwrap_or_default();
+                    pipeline
+                        .effects
+                        .iter()
+                        .position(|effect| effect.name() == name)
+                        .map(|index| pipeline.compositor.top().effect = index)
+                        .ok_or_else(|| format!("unknown effect `{}`", name))
+                }
+                param => {
+                    self.settings
+                        .apply(world, pipeline, filter, param.channel(), &command.value)
+                }
+            };
+            // The client may have gone in the meantime.
+            let _ = command.reply.send(result);
+        }
+    }
+}
+
+/// One of the `MAX_CLIENTS` clients served at once, given back when dropped, even by a
+/// panicking client thread.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    fn take(clients: &Arc<AtomicUsize>) -> Option<Self> {
+        let slot = Self(clients.clone());
+        (clients.fetch_add(1, Ordering::SeqCst) < MAX_CLIENTS).then_some(slot)
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Talks to one client until it leaves.
+fn serve(stream: TcpStream, allowed: &[RemoteParam], rate: f64, commands: Sender<Command>) {
+    let peer = stream
+        .peer_addr()
+        .map_or_else(|_| "a client".to_string(), |peer| peer.to_string());
+    let mut socket = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(err) => {
+            debug!("Refused a remote connection from {}: {}", peer, err);
+            return;
+        }
+    };
+    info!("Remote client {} connected", peer);
+    let names: Vec<String> = allowed.iter().map(|param| param.name()).collect();
+    let greeting = format!("allowed: {}", names.join(" "));
+    if socket.send(Message::text(greeting)).is_err() {
+        return;
+    }
+
+    // A bucket of commands that refills at `rate`, so that a short burst goes through.
+    let burst = rate.max(1.0);
+    let mut tokens = burst;
+    let mut last = Instant::now();
+    while let Ok(message) = socket.read() {
+        let Message::Text(text) = message else {
+            continue;
+        };
+        let now = Instant::now();
+        tokens = (tokens + now.duration_since(last).as_secs_f64() * rate).min(burst);
+        last = now;
+        let reply = if tokens < 1.0 {
+            "error: too many commands, slow down".to_string()
+        } else {
+            tokens -= 1.0;
+            match run(&text, allowed, &commands) {
+                Ok(reply) => reply.to_string(),
+                Err(err) => format!("error: {}", err),
+            }
+        };
+        if socket.send(Message::text(reply)).is_err() {
+            break;
+        }
+    }
+    info!("Remote client {} left", peer);
+}
+
+/// Hands a command to the window and waits for the outcome.
+fn run(
+    text: &str,
+    allowed: &[RemoteParam],
+    commands: &Sender<Command>,
+) -> Result<&'static str, String> {
+    let (name, value) = text
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or("expected a parameter and a value, e.g. `palette ocean`")?;
+    let param = RemoteParam::from_str(&name.replace('_', "-"), true)
+        .map_err(|_| format!("unknown parameter `{}`", name))?;
+    if !allowed.contains(&param) {
+        return Err(format!("`{}` can't be changed remotely", param.name()));
+    }
+    let (reply, outcome) = mpsc::channel();
+    commands
+        .send(Command {
+            param,
+            value: param.parse_value(value.trim())?,
+            reply,
+        })
+        .map_err(|_| "the tunnel has stopped")?;
+    match outcome.recv_timeout(REPLY_TIMEOUT) {
+        Ok(result) => result.map(|_| "ok"),
+        Err(RecvTimeoutError::Timeout) => Ok("queued"),
+        Err(RecvTimeoutError::Disconnected) => Err("the tunnel has stopped".to_string()),
+    }
+}
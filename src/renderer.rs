@@ -0,0 +1,879 @@
+use std::borrow::Cow;
+use std::f64::consts::PI;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rayon::prelude::*;
+use tracing::{debug_span, trace_span};
+
+use crate::atlas::{Atlas, Bands};
+use crate::color;
+use crate::curve::{Bend, Curve};
+use crate::effect::Effect;
+use crate::fog::{Fog, FogCurve};
+use crate::light::{Light, LightPosition, Lighting};
+use crate::lut::{Lut, DEPTH_REFERENCE, DISTANCE_BITS};
+use crate::palette::Palette;
+use crate::texture::{AddressMode, HeightMap, Texture, TextureFormat};
+use crate::time::Instant;
+use crate::world::{Layer, World};
+
+#[cfg(feature = "simd")]
+mod simd;
+
+/// Stand-in for the eight-lane loop when built without the `simd` feature.
+#[cfg(not(feature = "simd"))]
+mod simd {
+    use super::{LayerSampler, Sampler};
+
+    pub(super) fn supports(_sampler: &Sampler) -> bool {
+        false
+    }
+
+    pub(super) fn render_row(
+        _row: &mut [u8],
+        _entries: &[u16],
+        _fading: Option<(&LayerSampler, &[u16], u32)>,
+        _sampler: &Sampler,
+    ) -> usize {
+        0
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RenderStats {
+    pub total: Duration,
+    pub bands: Vec<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/// Which of a frame's pixels the tunnel draws, leaving the rest as the frames before drew
+/// them: less work a frame for slow machines, at the cost of some shimmer while moving.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interlace {
+    /// Every pixel, every frame.
+    #[default]
+    Off,
+    /// Even rows one frame and odd rows the next.
+    Rows,
+    /// One pixel of every 2×2 block, taking four frames to draw them all.
+    Checkerboard,
+}
+
+impl Interlace {
+    pub const ALL: [Interlace; 3] = [Interlace::Off, Interlace::Rows, Interlace::Checkerboard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Interlace::Off => "off",
+            Interlace::Rows => "rows",
+            Interlace::Checkerboard => "checkerboard",
+        }
+    }
+
+    /// Frames it takes to draw every pixel once.
+    fn fields(self) -> usize {
+        match self {
+            Interlace::Off => 1,
+            Interlace::Rows => 2,
+            Interlace::Checkerboard => 4,
+        }
+    }
+
+    /// The rows (`y % 2`) and, if not all of them, the columns (`x % 2`) drawn in `field`.
+    fn pattern(self, field: usize) -> Option<(usize, Option<usize>)> {
+        match self {
+            Interlace::Off => None,
+            Interlace::Rows => Some((field % 2, None)),
+            // Going diagonally across the block shimmers less than going along its rows.
+            Interlace::Checkerboard => {
+                let (row, column) = [(0, 0), (1, 1), (0, 1), (1, 0)][field % 4];
+                Some((row, Some(column)))
+            }
+        }
+    }
+}
+
+impl FromStr for Interlace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                format!(
+                    "unknown interlace mode `{}` (expected off, rows or checkerboard)",
+                    s
+                )
+            })
+    }
+}
+
+pub struct Renderer {
+    /// Runs of rows the frame is split into, drawn as tasks on the rayon pool, whose threads
+    /// live from one frame to the next.
+    bands: usize,
+    filter: Filter,
+    simd: bool,
+    interlace: Interlace,
+    /// The field drawn this frame.
+    field: usize,
+    /// Draws since the last update; stereo and split screens draw more than once a frame.
+    pass: usize,
+    /// Draws between the last two updates. Draws past that many before the next update,
+    /// like redraws on expose, draw the frame again and go to the same histories.
+    passes: usize,
+    /// What every draw of a frame last drew.
+    history: Vec<History>,
+}
+
+/// The tunnel as a draw of the frame last left it, for interlacing to draw into and for
+/// showing again as long as nothing it is drawn from changes.
+#[derive(Default)]
+struct History {
+    frame: Vec<u8>,
+    signature: Option<Signature>,
+    /// The field last drawn into the frame.
+    field: usize,
+    /// Fields drawn since the signature last changed; once every field has been, the frame
+    /// is complete.
+    settled: usize,
+}
+
+/// Everything the frame's pixels are drawn from, compared with the last frame's to tell
+/// whether drawing it again would change any of them. The texture, lookup table and height
+/// map are compared by identity, since the world replaces rather than changes them.
+#[derive(Clone, PartialEq)]
+struct Signature {
+    size: (u32, u32),
+    view: (u32, u32),
+    filter: Filter,
+    loading: bool,
+    current: LayerSignature,
+    fading: Option<(LayerSignature, u32)>,
+    palette_shift: u8,
+    address: (AddressMode, AddressMode),
+    atlas: Option<Atlas>,
+    shift_look: (usize, usize),
+    eye: f64,
+    view_roll: f64,
+    fog: Fog,
+    light: Light,
+    light_position: LightPosition,
+    height_map: Option<Same<HeightMap>>,
+    curve: Curve,
+    curve_amount: f64,
+    gamma_correct: bool,
+    /// How far the tunnel has travelled, which moves its bends; 0 while it is straight.
+    bend_travel: f64,
+}
+
+#[derive(Clone, PartialEq)]
+struct LayerSignature {
+    texture: Same<Texture>,
+    lut: Same<Lut>,
+    palette: Palette,
+    /// Texture shift and scale in fixed point: movements finer than that draw the same pixels.
+    shift: (u64, u64),
+    scale: u64,
+}
+
+/// Compares equal to the same allocation only. Holding on to it keeps the allocation from
+/// being reused for something else that would then compare equal.
+struct Same<T>(Arc<T>);
+
+impl<T> Clone for Same<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> PartialEq for Same<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl LayerSignature {
+    fn new(layer: &Layer, world: &World) -> Self {
+        Self {
+            texture: Same(layer.texture.clone()),
+            lut: Same(layer.lut.clone()),
+            palette: layer.palette.clone(),
+            shift: texture_shift(layer, world),
+            scale: lut_scale(layer, world),
+        }
+    }
+}
+
+impl Signature {
+    fn new(world: &World, filter: Filter) -> Self {
+        Self {
+            size: world.frame_size(),
+            view: (world.params.width, world.params.height),
+            filter,
+            loading: world.pending_lut.is_some(),
+            current: LayerSignature::new(&world.layer, world),
+            fading: world.fade.as_ref().map(|fade| {
+                let weight = (fade.mix.clamp(0.0, 1.0) * 256.0) as u32;
+                (LayerSignature::new(&fade.from, world), weight)
+            }),
+            palette_shift: world.palette_shift,
+            address: world.address,
+            atlas: world.atlas.clone(),
+            shift_look: lut_origin(world),
+            eye: world.camera.eye(),
+            view_roll: world.view_roll,
+            fog: world.view_fog,
+            light: world.light,
+            light_position: world.light_position,
+            height_map: world.height_map.clone().map(Same),
+            curve: world.curve,
+            curve_amount: world.curve_amount,
+            gamma_correct: world.gamma_correct,
+            bend_travel: if world.curve_amount != 0.0 {
+                world.travel
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new(rayon::current_num_threads())
+    }
+}
+
+const SCALE_BITS: u32 = 16;
+pub(crate) const FRACTION_BITS: u32 = 8;
+const FRACTION_MASK: u64 = (1 << FRACTION_BITS) - 1;
+const FOG_STEPS: usize = 1 << 13;
+
+struct LayerSampler<'a> {
+    layer: &'a Layer,
+    fog: Option<Vec<u16>>,
+    light: Option<Lighting>,
+    /// Heights to bump map the walls with, while the light is on.
+    relief: Option<&'a HeightMap>,
+    /// Where the curve moves the tunnel's centre at each depth.
+    bend: Option<Bend>,
+    /// The atlas's tiles, and the size of the texture or tile that is wrapped.
+    bands: Option<Bands>,
+    size: (usize, usize),
+    shift: (u64, u64),
+    scale: u64,
+}
+
+struct Sampler<'a> {
+    current: LayerSampler<'a>,
+    /// The layer being faded out and the current layer's weight out of 256.
+    fading: Option<(LayerSampler<'a>, u32)>,
+    palette_shift: u8,
+    address: (AddressMode, AddressMode),
+    angle_turns: u64,
+    shift_look: (usize, usize),
+    /// LUT cells per frame pixel, above one when drawing below the LUT's resolution.
+    step: (f64, f64),
+    /// `Camera::eye` and the LUT cell on the tunnel's axis.
+    eye: f64,
+    center: (f64, f64),
+    /// Sine and cosine of the roll, if the view is rolled, and the LUT cell in the middle of
+    /// the view it turns around.
+    roll: Option<(f64, f64)>,
+    view_center: (f64, f64),
+    gamma_correct: bool,
+}
+
+impl<'a> LayerSampler<'a> {
+    fn new(layer: &'a Layer, world: &'a World) -> Self {
+        let size = tile_size(layer, world);
+        let tex_width = size.0;
+        let fog = (world.view_fog.curve != FogCurve::Off)
+            .then(|| world.view_fog.table(tex_width, FOG_STEPS));
+        let light = world
+            .light
+            .enabled
+            .then(|| Lighting::new(&world.light, &world.light_position, tex_width, FOG_STEPS));
+        let relief = (light.is_some() && world.light.bump > 0.0).then(|| match &world.height_map {
+            Some(heights) => &**heights,
+            None => &**layer
+                .relief
+                .get_or_init(|| Arc::new(HeightMap::from_texture(&layer.source))),
+        });
+        let bend = (world.curve_amount != 0.0).then(|| {
+            Bend::new(
+                &world.curve,
+                world.curve_amount,
+                world.travel,
+                world.params.height as f64,
+                tex_width,
+                FOG_STEPS,
+            )
+        });
+        Self {
+            layer,
+            fog,
+            light,
+            relief,
+            bend,
+            bands: world
+                .atlas
+                .as_ref()
+                .map(|atlas| atlas.bands(layer.texture.width, layer.texture.height)),
+            size,
+            shift: texture_shift(layer, world),
+            scale: lut_scale(layer, world),
+        }
+    }
+}
+
+/// The size of what wraps around the tunnel: the layer's texture, or a tile of it with an
+/// atlas.
+fn tile_size(layer: &Layer, world: &World) -> (usize, usize) {
+    let (width, height) = (layer.texture.width, layer.texture.height);
+    match &world.atlas {
+        Some(atlas) => atlas.tile_size(width, height),
+        None => (width, height),
+    }
+}
+
+/// How far the texture has scrolled along and around the tunnel, in fixed-point texels,
+/// wrapped to the period of the address modes (and of an atlas's bands) so that the
+/// scrolling never jumps.
+pub(crate) fn texture_shift(layer: &Layer, world: &World) -> (u64, u64) {
+    let (width, height) = tile_size(layer, world);
+    let (depth, angle) = world.address;
+    let period = match &world.atlas {
+        Some(atlas) => atlas.period(depth.period(width)),
+        None => depth.period(width),
+    };
+    let fixed = |texels: f64| (texels * (1 << FRACTION_BITS) as f64) as u64;
+    let shift_x = fixed((width as f64 * world.travel).rem_euclid(period as f64));
+    let shift_y = fixed((height as f64 * world.spin).rem_euclid(angle.period(height) as f64));
+    (shift_x, shift_y)
+}
+
+/// How many times the texture goes around the tunnel: twice when mirrored, so that the flipped
+/// copy closes the circle without a seam.
+pub(crate) fn angle_turns(world: &World) -> u64 {
+    match world.address.1 {
+        AddressMode::MirroredRepeat => 2,
+        AddressMode::Repeat | AddressMode::ClampToEdge => 1,
+    }
+}
+
+/// The LUT cell under the frame's top-left pixel, which moves with the camera.
+pub(crate) fn lut_origin(world: &World) -> (usize, usize) {
+    let width = world.params.width as i32;
+    let height = world.params.height as i32;
+    let (look_x_dist, look_y_dist) = world.camera.look();
+    (
+        (width / 2 + look_x_dist as i32) as usize,
+        (height / 2 + look_y_dist as i32) as usize,
+    )
+}
+
+/// What the LUT's distances are multiplied by for the current ratio and the height of the
+/// layer's texture (or tile), in 16.16 fixed point.
+pub(crate) fn lut_scale(layer: &Layer, world: &World) -> u64 {
+    let scale = world.ratio * tile_size(layer, world).1 as f64 / DEPTH_REFERENCE;
+    (scale.max(0.0) * (1u64 << SCALE_BITS) as f64) as u64
+}
+
+impl Renderer {
+    pub fn new(bands: usize) -> Self {
+        Self {
+            bands: bands.max(1),
+            filter: Filter::Nearest,
+            simd: cfg!(feature = "simd"),
+            interlace: Interlace::Off,
+            field: 0,
+            pass: 0,
+            passes: 0,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn filter(&self) -> Filter {
+        self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    /// Whether nearest-neighbour frames are drawn eight pixels at a time. Always false unless
+    /// built with the `simd` feature.
+    pub fn simd(&self) -> bool {
+        self.simd
+    }
+
+    pub fn set_simd(&mut self, simd: bool) {
+        self.simd = simd && cfg!(feature = "simd");
+    }
+
+    pub fn interlace(&self) -> Interlace {
+        self.interlace
+    }
+
+    /// Only applies when drawn as an effect, which keeps the previous frames to fill in the
+    /// pixels that aren't drawn; `draw` always draws all of them.
+    pub fn set_interlace(&mut self, interlace: Interlace) {
+        self.interlace = interlace;
+    }
+
+    pub fn draw(&self, world: &World, frame: &mut [u8]) -> RenderStats {
+        self.draw_field(world, frame, None)
+    }
+
+    /// Draws the rows and columns of `pattern` (see `Interlace::pattern`), or all of them.
+    fn draw_field(
+        &self,
+        world: &World,
+        frame: &mut [u8],
+        pattern: Option<(usize, Option<usize>)>,
+    ) -> RenderStats {
+        let _span = debug_span!("render").entered();
+        let start = Instant::now();
+        if world.pending_lut.is_some() {
+            for pixel in frame.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[0, 0, 0, 0xff]);
+            }
+            return RenderStats {
+                total: start.elapsed(),
+                bands: Vec::new(),
+            };
+        }
+        let (width, height) = world.frame_size();
+        let (width, height) = (width as usize, height as usize);
+        let shift_look = lut_origin(world);
+        let sampler = Sampler {
+            current: LayerSampler::new(&world.layer, world),
+            fading: world.fade.as_ref().map(|fade| {
+                let weight = (fade.mix.clamp(0.0, 1.0) * 256.0) as u32;
+                (LayerSampler::new(&fade.from, world), weight)
+            }),
+            palette_shift: world.palette_shift,
+            address: world.address,
+            angle_turns: angle_turns(world),
+            shift_look,
+            step: world.frame_step(),
+            eye: world.camera.eye(),
+            center: (world.params.width as f64, world.params.height as f64),
+            roll: (world.view_roll != 0.0).then(|| (2.0 * PI * world.view_roll).sin_cos()),
+            view_center: (
+                shift_look.0 as f64 + world.params.width as f64 / 2.0,
+                shift_look.1 as f64 + world.params.height as f64 / 2.0,
+            ),
+            gamma_correct: world.gamma_correct,
+        };
+        let rows_per_band = height.div_ceil(self.bands);
+        let filter = self.filter;
+        let column = pattern.and_then(|(_, column)| column);
+        // The SIMD loop draws whole runs of pixels.
+        let simd =
+            self.simd && filter == Filter::Nearest && column.is_none() && simd::supports(&sampler);
+
+        // Every band keeps its rows, so skipped rows take the same share off each of them.
+        let bands = frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .fold_chunks(rows_per_band, Duration::default, |elapsed, (y, row)| {
+                if pattern.is_some_and(|(parity, _)| y % 2 != parity) {
+                    return elapsed;
+                }
+                let _span = trace_span!("row", y, band = y / rows_per_band).entered();
+                let row_start = Instant::now();
+                render_row(row, y, &sampler, filter, simd, column);
+                elapsed + row_start.elapsed()
+            })
+            .collect();
+
+        RenderStats {
+            total: start.elapsed(),
+            bands,
+        }
+    }
+}
+
+impl Effect for Renderer {
+    fn name(&self) -> &str {
+        "tunnel"
+    }
+
+    fn update(&mut self, _world: &World) {
+        self.passes = self.pass;
+        self.pass = 0;
+    }
+
+    /// Shows the last frame again instead if nothing it is drawn from changed, e.g. while
+    /// paused, or if it is drawn again before the next update.
+    fn draw(&mut self, world: &World, frame: &mut [u8]) -> RenderStats {
+        let start = Instant::now();
+        if self.pass == 0 {
+            self.field = (self.field + 1) % self.interlace.fields();
+        }
+        let index = match self.passes {
+            0 => self.pass,
+            passes => self.pass % passes,
+        };
+        self.pass += 1;
+        if self.history.len() <= index {
+            self.history.resize_with(index + 1, History::default);
+        }
+        let history = &self.history[index];
+        let signature = Signature::new(world, self.filter);
+        let fields = self.interlace.fields();
+        let unchanged =
+            history.frame.len() == frame.len() && history.signature.as_ref() == Some(&signature);
+        if unchanged && (history.settled >= fields || history.field == self.field) {
+            frame.copy_from_slice(&history.frame);
+            return RenderStats {
+                total: start.elapsed(),
+                bands: Vec::new(),
+            };
+        }
+
+        let mut history = std::mem::take(&mut self.history[index]);
+        let stats = if history.frame.len() != frame.len() {
+            // The first frame, and the first at a new size, has nothing to fill in from.
+            history.frame.resize(frame.len(), 0);
+            history.settled = fields;
+            self.draw_field(world, &mut history.frame, None)
+        } else {
+            history.settled = if unchanged { history.settled + 1 } else { 1 };
+            let pattern = self.interlace.pattern(self.field);
+            self.draw_field(world, &mut history.frame, pattern)
+        };
+        frame.copy_from_slice(&history.frame);
+        history.signature = Some(signature);
+        history.field = self.field;
+        self.history[index] = history;
+        stats
+    }
+
+    fn filter(&self) -> Filter {
+        Renderer::filter(self)
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        Renderer::set_filter(self, filter);
+    }
+}
+
+pub(crate) fn texel(layer: &Layer, palette_shift: u8, x: usize, y: usize) -> u32 {
+    let texture = &layer.texture;
+    let texel = texture.texels[y * texture.width + x];
+    match texture.format {
+        TextureFormat::Indexed => layer
+            .palette
+            .color((texel as u8).wrapping_add(palette_shift)),
+        TextureFormat::Rgba => texel,
+    }
+}
+
+/// Blends the four texels around `u`, `v`, mapping the ones outside the texture with
+/// `address` horizontally and vertically; `linear` blends their linear light.
+pub(crate) fn bilinear(
+    layer: &Layer,
+    palette_shift: u8,
+    u: u64,
+    v: u64,
+    address: (AddressMode, AddressMode),
+    linear: bool,
+) -> u32 {
+    let size = (layer.texture.width, layer.texture.height);
+    bilinear_tile(layer, palette_shift, u, v, address, linear, ((0, 0), size))
+}
+
+/// `bilinear` within the tile at `origin` of the given size, which wraps like a texture.
+fn bilinear_tile(
+    layer: &Layer,
+    palette_shift: u8,
+    u: u64,
+    v: u64,
+    address: (AddressMode, AddressMode),
+    linear: bool,
+    ((left, top), (tex_width, tex_height)): ((usize, usize), (usize, usize)),
+) -> u32 {
+    let (x, y) = ((u >> FRACTION_BITS) as usize, (v >> FRACTION_BITS) as usize);
+    let x0 = left + address.0.wrap(x, tex_width);
+    let y0 = top + address.1.wrap(y, tex_height);
+    let x1 = left + address.0.wrap(x + 1, tex_width);
+    let y1 = top + address.1.wrap(y + 1, tex_height);
+    let fx = (u & FRACTION_MASK) as u32;
+    let fy = (v & FRACTION_MASK) as u32;
+
+    let one = 1 << FRACTION_BITS;
+    let weights = [
+        (one - fx) * (one - fy),
+        fx * (one - fy),
+        (one - fx) * fy,
+        fx * fy,
+    ];
+    let texels = [
+        color::unpack(texel(layer, palette_shift, x0, y0)),
+        color::unpack(texel(layer, palette_shift, x1, y0)),
+        color::unpack(texel(layer, palette_shift, x0, y1)),
+        color::unpack(texel(layer, palette_shift, x1, y1)),
+    ];
+
+    let mut rgba = [0u8; 4];
+    for (channel, out) in rgba.iter_mut().enumerate() {
+        // Alpha is linear already.
+        let linear = linear && channel < 3;
+        let sum: u32 = texels
+            .iter()
+            .zip(weights)
+            .map(|(texel, weight)| {
+                let value = match linear {
+                    true => color::to_linear(texel[channel]),
+                    false => texel[channel] as u32,
+                };
+                value * weight
+            })
+            .sum();
+        let value = sum >> (2 * FRACTION_BITS);
+        *out = match linear {
+            true => color::from_linear(value),
+            false => value as u8,
+        };
+    }
+    color::pack(rgba)
+}
+
+/// Draws row `y`, or with `parity` only the pixels whose `x % 2` is that.
+fn render_row<'a>(
+    row: &mut [u8],
+    y: usize,
+    sampler: &Sampler<'a>,
+    filter: Filter,
+    simd: bool,
+    parity: Option<usize>,
+) {
+    let lut_x = sampler.shift_look.0;
+    let lut_y = sampler.shift_look.1 + (y as f64 * sampler.step.1) as usize;
+    let width = row.len() / 4;
+    let step = sampler.step.0;
+    let columns = (sampler.eye != 0.0).then(|| eye_columns(sampler, lut_x, lut_y, width));
+    let column = |x: usize| match &columns {
+        Some(columns) => columns[x],
+        None => lut_x + (x as f64 * step) as usize,
+    };
+    // Rolling turns the cells around the middle of the view.
+    let cell = |x: usize| {
+        let (x, y) = (column(x) as f64, lut_y as f64);
+        let (x, y) = match sampler.roll {
+            Some((sin, cos)) => {
+                let (cx, cy) = sampler.view_center;
+                let (dx, dy) = (x - cx, y - cy);
+                (cx + dx * cos - dy * sin, cy + dx * sin + dy * cos)
+            }
+            None => (x, y),
+        };
+        (x.round() as isize, y.round() as isize)
+    };
+    let row_of = |layer: &LayerSampler<'a>| {
+        let lut = &layer.layer.lut;
+        let entries = match sampler.roll {
+            Some(_) => Cow::Owned(
+                (0..width)
+                    .flat_map(|x| {
+                        let (x, y) = cell(x);
+                        lut.clamped_entry(x, y)
+                    })
+                    .collect(),
+            ),
+            None => lut_row(lut, columns.as_deref(), lut_x, lut_y, width, step),
+        };
+        bend_row(layer, entries, cell)
+    };
+    let entries = row_of(&sampler.current);
+    let fading = sampler.fading.as_ref().map(|(layer, weight)| {
+        let entries = row_of(layer);
+        (layer, entries, *weight)
+    });
+
+    let start = if simd {
+        let fading = fading
+            .as_ref()
+            .map(|(layer, entries, weight)| (*layer, &entries[..], *weight));
+        simd::render_row(row, &entries, fading, sampler)
+    } else {
+        0
+    };
+
+    for (x, (pixel, entry)) in row
+        .chunks_exact_mut(4)
+        .zip(entries.chunks_exact(2))
+        .enumerate()
+        .skip(start)
+        .filter(|(x, _)| parity.is_none_or(|parity| x % 2 == parity))
+    {
+        let mut rgba = sample(&sampler.current, sampler, entry, filter);
+        if let Some((layer, entries, weight)) = &fading {
+            let from = sample(layer, sampler, &entries[x * 2..x * 2 + 2], filter);
+            if sampler.gamma_correct {
+                color::mix_linear(&mut rgba, from, *weight);
+            } else {
+                for (channel, from) in rgba.iter_mut().zip(from) {
+                    *channel =
+                        ((*channel as u32 * weight + from as u32 * (256 - weight)) >> 8) as u8;
+                }
+            }
+        }
+        pixel.copy_from_slice(&rgba);
+    }
+}
+
+/// Looks the entries of a row up again through the curve: like rings of the tunnel drawn
+/// back to front, each pixel shows the band of depth whose moved rings pass through it.
+/// The search starts from the band of the depth the straight tunnel has there and walks
+/// towards the one it finds until it passes it; `cell` is the LUT cell of each pixel.
+fn bend_row<'a>(
+    layer: &LayerSampler<'a>,
+    entries: Cow<'a, [u16]>,
+    cell: impl Fn(usize) -> (isize, isize),
+) -> Cow<'a, [u16]> {
+    let Some(bend) = &layer.bend else {
+        return entries;
+    };
+    let lut = &layer.layer.lut;
+    let band_of = |distance| bend.band((depth(layer, distance) >> FRACTION_BITS) as usize);
+    let entries = entries
+        .chunks_exact(2)
+        .enumerate()
+        .flat_map(|(x, entry)| {
+            let look_up = |band: usize| {
+                let ((x, y), (dx, dy)) = (cell(x), bend.offset(band));
+                let entry = lut.clamped_entry(x - dx, y - dy);
+                (entry, band_of(entry[0]))
+            };
+            let mut band = band_of(entry[0]);
+            let (mut entry, found) = look_up(band);
+            if found != band {
+                let deeper = found > band;
+                while (deeper && band + 1 < bend.len()) || (!deeper && band > 0) {
+                    band = if deeper { band + 1 } else { band - 1 };
+                    let (next, found) = look_up(band);
+                    entry = next;
+                    if (found > band) != deeper || found == band {
+                        break;
+                    }
+                }
+            }
+            entry
+        })
+        .collect();
+    Cow::Owned(entries)
+}
+
+fn lut_row<'a>(
+    lut: &'a Lut,
+    columns: Option<&[usize]>,
+    x: usize,
+    y: usize,
+    width: usize,
+    step: f64,
+) -> Cow<'a, [u16]> {
+    match columns {
+        Some(columns) => Cow::Owned(lut.gathered_row(y, columns.iter().copied())),
+        None => lut.sampled_row(y, x, width, step),
+    }
+}
+
+/// The LUT columns an eye `Camera::eye` away from the axis sees along row `lut_y`. Seen from
+/// there, a point on a round tunnel's wall moves sideways by the eye's share of its radius on
+/// screen, so every pixel looks up the point whose shifted position lands on it.
+fn eye_columns(sampler: &Sampler, lut_x: usize, lut_y: usize, width: usize) -> Vec<usize> {
+    let eye = sampler.eye;
+    let squeeze = 1.0 - eye * eye;
+    let dy = lut_y as f64 - sampler.center.1;
+    (0..width)
+        .map(|x| {
+            let seen = lut_x as f64 + x as f64 * sampler.step.0 - sampler.center.0;
+            let dx = (seen + eye * (seen * seen + squeeze * dy * dy).sqrt()) / squeeze;
+            (sampler.center.0 + dx).max(0.0) as usize
+        })
+        .collect()
+}
+
+/// How far down the tunnel a LUT distance is for the layer, in fixed-point texels.
+fn depth(layer: &LayerSampler, distance: u16) -> u64 {
+    let distance_shift = SCALE_BITS + DISTANCE_BITS - FRACTION_BITS;
+    (distance as u64 * layer.scale) >> distance_shift
+}
+
+fn sample(layer: &LayerSampler, sampler: &Sampler, entry: &[u16], filter: Filter) -> [u8; 4] {
+    let (tex_width, tex_height) = layer.size;
+    let angle_shift = 16 - FRACTION_BITS;
+
+    let depth = depth(layer, entry[0]);
+    let u = depth + layer.shift.0;
+    let v = ((entry[1] as u64 * tex_height as u64 * sampler.angle_turns) >> angle_shift)
+        + layer.shift.1;
+    // The band the texel falls in picks the atlas's tile.
+    let (left, top) = layer
+        .bands
+        .as_ref()
+        .map_or((0, 0), |bands| bands.origin(u >> FRACTION_BITS));
+    let color = match filter {
+        Filter::Nearest => texel(
+            layer.layer,
+            sampler.palette_shift,
+            left + sampler
+                .address
+                .0
+                .wrap((u >> FRACTION_BITS) as usize, tex_width),
+            top + sampler
+                .address
+                .1
+                .wrap((v >> FRACTION_BITS) as usize, tex_height),
+        ),
+        Filter::Bilinear => bilinear_tile(
+            layer.layer,
+            sampler.palette_shift,
+            u,
+            v,
+            sampler.address,
+            sampler.gamma_correct,
+            ((left, top), layer.size),
+        ),
+    };
+    let mut rgba = color::unpack(color);
+    if layer.fog.is_some() || layer.light.is_some() {
+        let step = ((depth >> FRACTION_BITS) as usize).min(FOG_STEPS - 1);
+        let mut brightness = layer.fog.as_ref().map_or(256, |fog| fog[step] as u32);
+        if let Some(light) = &layer.light {
+            let lit = match layer.relief {
+                Some(heights) => {
+                    let (x, y) = ((u >> FRACTION_BITS) as usize, (v >> FRACTION_BITS) as usize);
+                    let x = left + sampler.address.0.wrap(x, tex_width);
+                    let y = top + sampler.address.1.wrap(y, tex_height);
+                    let texture = &layer.layer.texture;
+                    let size = (texture.width, texture.height);
+                    let slope = heights.slope(x, y, size, sampler.address);
+                    light.bumped_brightness(entry[1], step, slope)
+                }
+                None => light.brightness(entry[1], step),
+            };
+            brightness = (brightness * lit) >> 8;
+        }
+        for channel in &mut rgba[..3] {
+            *channel = match sampler.gamma_correct {
+                true => color::from_linear((color::to_linear(*channel) * brightness) >> 8),
+                false => ((*channel as u32 * brightness) >> 8) as u8,
+            };
+        }
+    }
+    rgba
+}
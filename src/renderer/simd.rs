@@ -0,0 +1,133 @@
+//! The nearest-neighbour inner loop, eight pixels at a time. LUT decoding, texture wrap, fog
+//! and light shading and the crossfade blend run on `u32x8` lanes; only the texel, fog and
+//! light table fetches are per-lane gathers. Results are bit-identical to the scalar loop.
+
+use wide::u32x8;
+
+use super::{LayerSampler, Sampler, FOG_STEPS, FRACTION_BITS, SCALE_BITS};
+use crate::lut::DISTANCE_BITS;
+use crate::texture::{AddressMode, TextureFormat};
+
+const LANES: usize = 8;
+/// Beyond this, `distance * scale` no longer fits the 32-bit lanes.
+const MAX_SCALE: u64 = 256 << SCALE_BITS;
+const MAX_TEXTURE_SIZE: usize = 1 << 15;
+
+/// Whether the lanes can reproduce the scalar result: textures must repeat and be powers of
+/// two (so the wrap is a mask) and small enough, together with the scale, not to overflow
+/// 32 bits. Bump mapping's neighbouring height fetches, an atlas's tiles and linear light
+/// are left to the scalar loop.
+pub(super) fn supports(sampler: &Sampler) -> bool {
+    let fits = |layer: &LayerSampler| {
+        let texture = &layer.layer.texture;
+        layer.relief.is_none()
+            && layer.bands.is_none()
+            && layer.scale < MAX_SCALE
+            && [texture.width, texture.height]
+                .iter()
+                .all(|&size| size.is_power_of_two() && size <= MAX_TEXTURE_SIZE)
+    };
+    sampler.address == (AddressMode::Repeat, AddressMode::Repeat)
+        && !sampler.gamma_correct
+        && fits(&sampler.current)
+        && sampler.fading.as_ref().is_none_or(|(layer, _)| fits(layer))
+}
+
+/// Draws the row in groups of eight pixels and returns how many pixels it drew; the caller
+/// finishes the remainder.
+pub(super) fn render_row(
+    row: &mut [u8],
+    entries: &[u16],
+    fading: Option<(&LayerSampler, &[u16], u32)>,
+    sampler: &Sampler,
+) -> usize {
+    let mut drawn = 0;
+    for (pixels, lut) in row
+        .chunks_exact_mut(4 * LANES)
+        .zip(entries.chunks_exact(2 * LANES))
+    {
+        let mut color = sample(&sampler.current, sampler, lut);
+        if let Some((layer, entries, weight)) = fading {
+            let from = sample(layer, sampler, &entries[drawn * 2..(drawn + LANES) * 2]);
+            color = mix(color, from, weight);
+        }
+        for (pixel, color) in pixels.chunks_exact_mut(4).zip(color.to_array()) {
+            pixel.copy_from_slice(&color.to_le_bytes());
+        }
+        drawn += LANES;
+    }
+    drawn
+}
+
+fn sample(layer: &LayerSampler, sampler: &Sampler, entries: &[u16]) -> u32x8 {
+    let texture = &layer.layer.texture;
+    let mut distance = [0u32; LANES];
+    let mut angle = [0u32; LANES];
+    for (i, entry) in entries.chunks_exact(2).enumerate() {
+        distance[i] = entry[0] as u32;
+        angle[i] = entry[1] as u32;
+    }
+    let distance = u32x8::new(distance);
+    let angle = u32x8::new(angle);
+
+    // `(distance * scale) >> shift` with the scale split into 16-bit halves, so that neither
+    // product overflows; the high half's part is a multiple of 2^16 and shifts exactly.
+    let scale = layer.scale as u32;
+    let distance_shift = SCALE_BITS + DISTANCE_BITS - FRACTION_BITS;
+    let depth = ((distance * u32x8::splat(scale >> 16)) << (16 - distance_shift))
+        + ((distance * u32x8::splat(scale & 0xffff)) >> distance_shift);
+    let u = depth + u32x8::splat(layer.shift.0 as u32);
+    let v = ((angle * u32x8::splat(texture.height as u32)) >> (16 - FRACTION_BITS))
+        + u32x8::splat(layer.shift.1 as u32);
+    let x = (u >> FRACTION_BITS) & u32x8::splat(texture.width as u32 - 1);
+    let y = (v >> FRACTION_BITS) & u32x8::splat(texture.height as u32 - 1);
+    let index = y * u32x8::splat(texture.width as u32) + x;
+
+    let texels = index.to_array().map(|i| texture.texels[i as usize]);
+    let colors = match texture.format {
+        TextureFormat::Indexed => texels.map(|texel| {
+            layer
+                .layer
+                .palette
+                .color((texel as u8).wrapping_add(sampler.palette_shift))
+        }),
+        TextureFormat::Rgba => texels,
+    };
+    let color = u32x8::new(colors);
+
+    if layer.fog.is_none() && layer.light.is_none() {
+        return color;
+    }
+    let steps = (depth >> FRACTION_BITS)
+        .min(u32x8::splat(FOG_STEPS as u32 - 1))
+        .to_array();
+    let mut brightness = match &layer.fog {
+        Some(fog) => u32x8::new(steps.map(|step| fog[step as usize] as u32)),
+        None => u32x8::splat(256),
+    };
+    if let Some(light) = &layer.light {
+        let angle = angle.to_array();
+        let lit = std::array::from_fn(|i| light.brightness(angle[i] as u16, steps[i] as usize));
+        brightness = (brightness * u32x8::new(lit)) >> 8;
+    }
+    shade(color, brightness)
+}
+
+/// Scales red, green and blue by `brightness / 256` (at most 1) and keeps alpha. Red and blue
+/// are multiplied together, as the gap between them leaves room for the 16-bit products.
+fn shade(color: u32x8, brightness: u32x8) -> u32x8 {
+    let rb = u32x8::splat(0x00ff_00ff);
+    let g = u32x8::splat(0x0000_ff00);
+    let a = u32x8::splat(0xff00_0000);
+    (((color & rb) * brightness) >> 8) & rb | (((color & g) * brightness) >> 8) & g | color & a
+}
+
+/// `color * weight + from * (256 - weight)`, per channel, in 1/256ths.
+fn mix(color: u32x8, from: u32x8, weight: u32) -> u32x8 {
+    let mask = u32x8::splat(0x00ff_00ff);
+    let weight_to = u32x8::splat(weight);
+    let weight_from = u32x8::splat(256 - weight);
+    let rb = (color & mask) * weight_to + (from & mask) * weight_from;
+    let ga = ((color >> 8) & mask) * weight_to + ((from >> 8) & mask) * weight_from;
+    (rb >> 8) & mask | ga & !mask
+}
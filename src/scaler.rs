@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use tunnel::MIN_RENDER_SCALE;
+
+/// Render scales to step through, from full resolution down.
+const LEVELS: [f64; 7] = [1.0, 0.85, 0.7, 0.6, 0.5, 0.4, MIN_RENDER_SCALE];
+/// Share of the frame time drawing may take; the rest goes to presenting the frame.
+const BUDGET_SHARE: f64 = 0.75;
+/// Only step back up once the larger frames are expected to stay this far under budget, so
+/// that the scale doesn't flip back and forth.
+const HEADROOM: f64 = 0.7;
+const SMOOTHING: f64 = 0.1;
+/// Frames to wait after a change before judging the new scale.
+const SETTLE_FRAMES: u32 = 30;
+
+/// Picks the render scale that keeps drawing within the time a target frame rate allows,
+/// lowering it while frames take too long and raising it again once there is headroom.
+pub struct ResolutionScaler {
+    budget: f64,
+    level: usize,
+    average: f64,
+    settle: u32,
+}
+
+impl ResolutionScaler {
+    pub fn new(fps: f64) -> Self {
+        Self {
+            budget: BUDGET_SHARE / fps.max(1.0),
+            level: 0,
+            average: 0.0,
+            settle: SETTLE_FRAMES,
+        }
+    }
+
+    /// Records how long a frame took to draw and returns the scale to switch to, if any.
+    pub fn record(&mut self, draw: Duration) -> Option<f64> {
+        let sample = draw.as_secs_f64();
+        self.average = if self.average == 0.0 {
+            sample
+        } else {
+            self.average + (sample - self.average) * SMOOTHING
+        };
+        if self.settle > 0 {
+            self.settle -= 1;
+            return None;
+        }
+
+        let scale = LEVELS[self.level];
+        // Drawing time grows with the number of pixels.
+        let cost = |level: usize| self.average * (LEVELS[level] / scale).powi(2);
+        let level = if self.average > self.budget && self.level + 1 < LEVELS.len() {
+            self.level + 1
+        } else if self.level > 0 && cost(self.level - 1) < self.budget * HEADROOM {
+            self.level - 1
+        } else {
+            return None;
+        };
+        self.level = level;
+        self.average = 0.0;
+        self.settle = SETTLE_FRAMES;
+        Some(LEVELS[level])
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use tunnel::time::timestamp;
+use winit::dpi::PhysicalPosition;
+use winit::event::WindowEvent;
+
+/// How far the cursor may drift before it counts as the user coming back, in pixels.
+const MOUSE_SLACK: f64 = 8.0;
+/// The size of the preview box in the Windows screensaver settings, which can't be asked for
+/// without calling into Win32 directly.
+#[cfg(windows)]
+pub const PREVIEW_SIZE: (u32, u32) = (152, 112);
+
+/// Turns the arguments Windows starts a screensaver with into ours: `/s` shows it, `/p HWND`
+/// (or `/p:HWND`) draws it into the preview box of the settings dialog, and `/c`, or a
+/// `.scr` started without any, asks for a settings dialog, which there isn't: settings come
+/// from the config file. Returns `None` when there is nothing to show. Other arguments are
+/// left alone.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn translate_args(args: Vec<String>) -> Option<Vec<String>> {
+    let program = args.first().cloned().unwrap_or_default();
+    let is_scr = Path::new(&program)
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("scr"));
+    let Some(first) = args.get(1) else {
+        return (!is_scr).then_some(args);
+    };
+    let (switch, value) = match first.split_once(':') {
+        Some((switch, value)) => (switch.to_ascii_lowercase(), Some(value.to_string())),
+        None => (first.to_ascii_lowercase(), args.get(2).cloned()),
+    };
+    match switch.as_str() {
+        "/s" => Some(vec![program, "--screensaver".to_string()]),
+        "/p" => {
+            let window = value?;
+            Some(vec![
+                program,
+                "--screensaver".to_string(),
+                "--preview".to_string(),
+                window,
+            ])
+        }
+        "/c" => None,
+        _ => Some(args),
+    }
+}
+
+/// Notices the user coming back: a key, a click, the wheel or the cursor moving further than
+/// `MOUSE_SLACK` from where it first showed up.
+#[derive(Default)]
+pub struct Watcher {
+    origin: Option<PhysicalPosition<f64>>,
+}
+
+impl Watcher {
+    pub fn woken(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::KeyboardInput { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::MouseWheel { .. } => true,
+            WindowEvent::CursorMoved { position, .. } => {
+                let origin = *self.origin.get_or_insert(*position);
+                (position.x - origin.x).hypot(position.y - origin.y) > MOUSE_SLACK
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Picks the look of each launch, seeded from the clock.
+pub struct Dice {
+    state: u64,
+}
+
+impl Dice {
+    pub fn from_clock() -> Self {
+        Self { state: timestamp() }
+    }
+
+    /// The next number of a splitmix64 sequence.
+    pub fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number below `n`.
+    pub fn pick(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    /// A number from 0 up to 1.
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
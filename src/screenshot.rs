@@ -0,0 +1,26 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use image::{ImageError, RgbaImage};
+use tracing::warn;
+use tunnel::time::timestamp;
+
+pub fn save(frame: &[u8], width: u32, height: u32, dir: &Path) {
+    let image = match RgbaImage::from_raw(width, height, frame.to_vec()) {
+        Some(image) => image,
+        None => return,
+    };
+    let dir = dir.to_path_buf();
+    let path = dir.join(format!("tunnel-{}.png", timestamp()));
+
+    thread::spawn(move || {
+        let result = fs::create_dir_all(&dir)
+            .map_err(ImageError::from)
+            .and_then(|_| image.save(&path));
+        match result {
+            Ok(()) => println!("Saved screenshot to {}", path.display()),
+            Err(err) => warn!("Failed to save screenshot: {}", err),
+        }
+    });
+}
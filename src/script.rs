@@ -0,0 +1,167 @@
+//! Tunnel parameters computed every frame by a [Rhai](https://rhai.rs) script, e.g.
+//! `speed = 0.5 + 0.3 * sin(t);`. The script runs from the top each frame with the time,
+//! the audio levels and the input state as constants, and the parameters as variables
+//! holding their current values; the ones it changes are applied to the world. Saving the
+//! file reloads it.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use tracing::{info, warn};
+use tunnel::{Flight, Fog, Modulator, World};
+
+use crate::watch::FileWatcher;
+
+/// Operations a run may take before it is stopped, so that an endless loop can't hang the
+/// demo.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What the player is doing, for the script to react to. Outputs without a window have none.
+#[derive(Default)]
+pub struct ScriptInput {
+    /// The pointer from -1 to 1 across and down the window.
+    pub mouse: Option<(f64, f64)>,
+    pub mouse_down: bool,
+    /// Names of the keys held, as key bindings write them.
+    pub keys: Vec<String>,
+}
+
+pub struct Script {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    watcher: Option<FileWatcher>,
+    /// The script failed since it was last loaded; the error is logged only the first time.
+    failed: bool,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let ast = compile(&engine, path)
+            .map_err(|err| format!("failed to load {}: {}", path.display(), err))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            engine,
+            ast,
+            scope: Scope::new(),
+            watcher: crate::watch_file(path),
+            failed: false,
+        })
+    }
+
+    /// Runs the script for the coming frame, first reloading it if the file changed. A
+    /// script that doesn't compile keeps the previous version running.
+    pub fn update(&mut self, world: &mut World, input: &ScriptInput) {
+        if self
+            .watcher
+            .as_ref()
+            .is_some_and(|watcher| watcher.changed())
+        {
+            match compile(&self.engine, &self.path) {
+                Ok(ast) => {
+                    info!("Reloaded {}", self.path.display());
+                    self.ast = ast;
+                    self.failed = false;
+                }
+                Err(err) => warn!("Failed to reload {}: {}", self.path.display(), err),
+            }
+        }
+
+        let time = world.clock().time();
+        let (bass, treble) = world
+            .audio_levels()
+            .map_or((0.0, 0.0), |levels| (levels.bass(), levels.treble()));
+        let (mouse_x, mouse_y) = input.mouse.unwrap_or_default();
+        let keys: Array = input.keys.iter().cloned().map(Dynamic::from).collect();
+        let flight = *world.flight();
+        let fog = *world.fog();
+        let modulator = *world.ratio_modulator();
+        let params = [
+            ("speed", flight.travel),
+            ("spin", flight.spin),
+            ("ratio", modulator.base),
+            ("palette_speed", world.params().palette_speed),
+            ("fog_start", fog.start),
+            ("fog_end", fog.end),
+            ("fog_density", fog.density),
+            ("roll_speed", world.roll_speed()),
+        ];
+
+        let scope = &mut self.scope;
+        scope.clear();
+        scope
+            .push_constant("time", time)
+            .push_constant("t", time)
+            .push_constant("bass", bass)
+            .push_constant("treble", treble)
+            .push_constant("beat", world.pulse())
+            .push_constant("mouse_x", mouse_x)
+            .push_constant("mouse_y", mouse_y)
+            .push_constant("mouse_down", input.mouse_down)
+            .push_constant("keys", keys);
+        for (name, value) in params {
+            scope.push(name, value);
+        }
+        if let Err(err) = self.engine.run_ast_with_scope(scope, &self.ast) {
+            if !self.failed {
+                warn!("{}: {}", self.path.display(), err);
+                self.failed = true;
+            }
+            return;
+        }
+
+        // Only what the script assigned is applied, so that the keys keep working for the rest.
+        let [speed, spin, ratio, palette_speed, fog_start, fog_end, fog_density, roll_speed] =
+            params.map(|(name, value)| {
+                scope
+                    .get(name)
+                    .and_then(number)
+                    .filter(|number| number.is_finite() && *number != value)
+            });
+        if speed.is_some() || spin.is_some() {
+            world.set_flight(Flight {
+                travel: speed.unwrap_or(flight.travel),
+                spin: spin.unwrap_or(flight.spin),
+                ..flight
+            });
+        }
+        if let Some(ratio) = ratio.filter(|&ratio| ratio > 0.0) {
+            world.set_ratio_modulator(Modulator {
+                base: ratio,
+                ..modulator
+            });
+        }
+        if let Some(speed) = palette_speed {
+            world.set_palette_speed(speed);
+        }
+        if fog_start.is_some() || fog_end.is_some() || fog_density.is_some() {
+            world.set_fog(Fog {
+                start: fog_start.unwrap_or(fog.start),
+                end: fog_end.unwrap_or(fog.end),
+                density: fog_density.unwrap_or(fog.density),
+                ..fog
+            });
+        }
+        if let Some(turns) = roll_speed {
+            world.set_roll_speed(turns);
+        }
+    }
+}
+
+fn compile(engine: &Engine, path: &Path) -> Result<AST, String> {
+    let source = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    engine.compile(source).map_err(|err| err.to_string())
+}
+
+/// Scripts may assign whole numbers as well as decimals.
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|n| n as f64))
+}
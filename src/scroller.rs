@@ -0,0 +1,160 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use image::RgbaImage;
+use tracing::warn;
+
+use crate::palette::PaletteKind;
+use crate::texture::Stamp;
+use crate::timeline::ScrollerCue;
+use crate::world::World;
+
+/// Height of the letters as a share of the frame's, and where the wave's middle is.
+const SIZE: f64 = 0.12;
+const BASELINE: f64 = 0.75;
+/// Waves across the frame, and how many of them go by per second.
+const WAVES: f64 = 1.5;
+const WAVE_SPEED: f64 = 0.5;
+/// Times per second the colors cycle along the message.
+const COLOR_SPEED: f64 = 0.25;
+/// Seconds taken to fade in or out.
+const FADE: f64 = 0.5;
+
+/// An oldschool scroller: a message running across the frame from right to left on a sine
+/// wave, in colors cycling along it. It starts over once the message has gone by, and comes
+/// and goes as the timeline's scenes cue it.
+pub struct Scroller {
+    message: String,
+    font: Option<Arc<[u8]>>,
+    /// The message as drawn by `Stamp::text`.
+    image: RgbaImage,
+    /// Frame widths the message moves by per second.
+    pub speed: f64,
+    /// How far the wave takes the letters up and down, as a share of the frame's height.
+    pub amplitude: f64,
+    shown: bool,
+    opacity: f64,
+    /// When the message started from the right edge, and the clock's time at the last update.
+    start: f64,
+    time: f64,
+}
+
+impl Scroller {
+    /// A scroller showing `message` from the start, in the TrueType or OpenType font in
+    /// `font` or the built-in one.
+    pub fn new(message: &str, font: Option<Arc<[u8]>>) -> Result<Self, String> {
+        let image = Stamp::text(message, font.as_deref())?.image;
+        Ok(Self {
+            message: message.to_string(),
+            font,
+            image,
+            speed: 0.25,
+            amplitude: 0.1,
+            shown: true,
+            opacity: 1.0,
+            start: 0.0,
+            time: 0.0,
+        })
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Runs `message` from the right edge again, in the same font.
+    pub fn set_message(&mut self, message: &str) -> Result<(), String> {
+        self.image = Stamp::text(message, self.font.as_deref())?.image;
+        self.message = message.to_string();
+        self.start = self.time;
+        Ok(())
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.shown
+    }
+
+    /// Fades the scroller in, from the right edge, or out.
+    pub fn set_shown(&mut self, shown: bool) {
+        if shown && !self.shown {
+            self.start = self.time;
+        }
+        self.shown = shown;
+    }
+
+    /// Hidden, for a timeline to show once it cues it.
+    pub fn hidden(font: Option<Arc<[u8]>>) -> Self {
+        let mut scroller = Self::new("", None).expect("the built-in font draws anything");
+        scroller.font = font;
+        scroller.shown = false;
+        scroller.opacity = 0.0;
+        scroller
+    }
+
+    /// Follows a scene's cue; a message that can't be drawn leaves the last one.
+    pub(crate) fn cue(&mut self, cue: &ScrollerCue) {
+        match cue {
+            ScrollerCue::Show => self.set_shown(true),
+            ScrollerCue::Hide => self.set_shown(false),
+            ScrollerCue::Message(message) => {
+                if let Err(err) = self.set_message(message) {
+                    warn!("Failed to draw the scroller's message: {}", err);
+                }
+                self.set_shown(true);
+            }
+        }
+    }
+
+    /// Fades in or out with the clock, once per `World::update`.
+    pub fn update(&mut self, world: &World) {
+        let time = world.clock().time();
+        let step = (time - self.time).abs() / FADE;
+        self.time = time;
+        self.opacity = match self.shown {
+            true => (self.opacity + step).min(1.0),
+            false => (self.opacity - step).max(0.0),
+        };
+    }
+
+    pub fn draw(&self, frame: &mut [u8], width: usize, height: usize) {
+        let (image_width, image_height) = (self.image.width() as f64, self.image.height() as f64);
+        if self.opacity <= 0.0 || width == 0 || height == 0 || image_width <= 1.0 {
+            return;
+        }
+        let scale = SIZE * height as f64 / image_height;
+        let text_width = image_width * scale;
+        let text_height = (image_height * scale) as usize;
+        // From just off the right edge until the end has left on the left.
+        let travelled = (self.time - self.start).max(0.0) * self.speed * width as f64;
+        let left = width as f64 - travelled.rem_euclid(width as f64 + text_width);
+        let weight = (self.opacity * 256.0) as u32;
+        for x in 0..width {
+            let column = (x as f64 - left) / scale;
+            if !(0.0..image_width).contains(&column) {
+                continue;
+            }
+            let phase = x as f64 / width as f64;
+            let wave = (2.0 * PI * (phase * WAVES + self.time * WAVE_SPEED)).sin();
+            let middle = (BASELINE + wave * self.amplitude) * height as f64;
+            let top = middle as isize - text_height as isize / 2;
+            let hue = ((phase + self.time * COLOR_SPEED).rem_euclid(1.0) * 256.0) as u8;
+            let [r, g, b] = PaletteKind::Plasma.rgb(hue);
+            for dy in 0..text_height {
+                let y = top + dy as isize;
+                if !(0..height as isize).contains(&y) {
+                    continue;
+                }
+                let row = ((dy as f64 / scale) as u32).min(self.image.height() - 1);
+                let alpha = self.image.get_pixel(column as u32, row).0[3] as u32;
+                if alpha == 0 {
+                    continue;
+                }
+                let alpha = alpha * weight / 255;
+                let pixel = &mut frame[(y as usize * width + x) * 4..][..3];
+                for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+                    *channel =
+                        ((*channel as u32 * (256 - alpha) + value as u32 * alpha) >> 8) as u8;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use crate::flight::Flight;
+use crate::fog::{Fog, FogCurve};
+use crate::geometry::TunnelGeometry;
+use crate::lut::{DEPTH_REFERENCE, MAX_DISTANCE};
+use crate::palette::PaletteKind;
+use crate::texture::TextureGenerator;
+
+/// A look to write out as a fragment shader for [Shadertoy](https://www.shadertoy.com):
+/// the tunnel's shape, texture, palette and speeds, computed per pixel in floating point
+/// instead of sampled from the lookup table and texture.
+pub struct Shadertoy<'a> {
+    pub geometry: &'a dyn TunnelGeometry,
+    pub generator: &'a dyn TextureGenerator,
+    pub palette: PaletteKind,
+    /// Height of the frame the look was made at, which the shader scales to its canvas.
+    pub height: u32,
+    pub texture_size: (usize, usize),
+    pub ratio: f64,
+    pub speed: f64,
+    pub flight: Flight,
+    pub palette_speed: f64,
+    pub fog: Fog,
+    pub roll: f64,
+    pub roll_speed: f64,
+}
+
+impl Shadertoy<'_> {
+    /// The shader's source, to paste over the `mainImage` of a new shader. Fails for shapes
+    /// and textures that have no GLSL version.
+    pub fn source(&self) -> Result<String, String> {
+        let project = self.geometry.glsl().ok_or_else(|| {
+            format!(
+                "the {} shape can't be exported as a shader",
+                self.geometry.name()
+            )
+        })?;
+        let texel = self.generator.glsl().ok_or_else(|| {
+            format!(
+                "the {} texture can't be exported as a shader",
+                self.generator.name()
+            )
+        })?;
+        let fog = match self.fog.curve {
+            FogCurve::Off => "1.0".to_string(),
+            FogCurve::Linear => "1.0 - (depth - FOG_START) / (FOG_END - FOG_START)".to_string(),
+            FogCurve::Exponential => format!("exp({:?} * (depth - FOG_START))", -self.fog.density),
+        };
+        let float = |value: f64| format!("{:?}", value);
+        Ok(format!(
+            "// A {shape} tunnel with the {texture} texture and the {palette} palette, exported from
+// tunnel-rs.
+
+const float PI = 3.14159265358979;
+// Height of the frame the tunnel was made for, in pixels.
+const float HEIGHT = {height};
+const vec2 TEXTURE_SIZE = vec2({texture_width}, {texture_height});
+const float RATIO = {ratio};
+// Nearest the axis the tunnel goes, in pixels.
+const float MIN_RADIUS = {min_radius};
+const float SPEED = {speed};
+// Texture lengths and turns per second.
+const float TRAVEL = {travel};
+const float SPIN = {spin};
+// Palette entries per second.
+const float PALETTE_SPEED = {palette_speed};
+const float ROLL = {roll};
+const float ROLL_SPEED = {roll_speed};
+// In texture lengths.
+const float FOG_START = {fog_start};
+const float FOG_END = {fog_end};
+
+{project}
+{texel}
+{palette_source}
+float fog(float depth) {{
+    if (depth <= FOG_START) {{
+        return 1.0;
+    }}
+    if (depth >= FOG_END) {{
+        return {fog_end_brightness};
+    }}
+    return {fog};
+}}
+
+void mainImage(out vec4 fragColor, in vec2 fragCoord) {{
+    float t = iTime * SPEED;
+    // From the middle to the pixel in pixels of the original frame, y pointing down.
+    vec2 d = vec2(fragCoord.x - 0.5 * iResolution.x, 0.5 * iResolution.y - fragCoord.y)
+        * (HEIGHT / iResolution.y);
+    float roll = 2.0 * PI * (ROLL + ROLL_SPEED * t);
+    d = vec2(d.x * cos(roll) - d.y * sin(roll), d.x * sin(roll) + d.y * cos(roll));
+
+    vec2 polar = project(d);
+    float depth = RATIO * TEXTURE_SIZE.y / max(polar.x, MIN_RADIUS) / TEXTURE_SIZE.x;
+    vec2 uv = fract(vec2(depth + TRAVEL * t, polar.y / (2.0 * PI) + SPIN * t));
+    uv = floor(uv * TEXTURE_SIZE) / TEXTURE_SIZE;
+    float index = mod(texel(uv) + floor(PALETTE_SPEED * t), 256.0);
+    fragColor = vec4(palette(index) * fog(depth), 1.0);
+}}
+",
+            shape = self.geometry.name(),
+            texture = self.generator.name(),
+            palette = self.palette.name(),
+            height = float(self.height as f64),
+            texture_width = float(self.texture_size.0 as f64),
+            texture_height = float(self.texture_size.1 as f64),
+            ratio = float(self.ratio),
+            min_radius = float(DEPTH_REFERENCE / MAX_DISTANCE),
+            speed = float(self.speed),
+            travel = float(self.flight.travel),
+            spin = float(self.flight.spin),
+            palette_speed = float(self.palette_speed),
+            roll = float(self.roll),
+            roll_speed = float(self.roll_speed),
+            fog_start = float(self.fog.start),
+            fog_end = float(self.fog.end),
+            fog_end_brightness = if self.fog.curve == FogCurve::Off {
+                "1.0"
+            } else {
+                "0.0"
+            },
+            palette_source = self.palette.glsl(),
+        ))
+    }
+}
@@ -0,0 +1,202 @@
+use std::error::Error;
+
+use pixels::wgpu::PresentMode;
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowBuilder, WindowId};
+
+/// A rectangle in pixels: x, y, width and height.
+type Rect = (u32, u32, u32, u32);
+
+/// The monitors the tunnel is spread across, leftmost (then topmost) first, with where each
+/// sits on the wall in physical pixels relative to its top-left corner.
+pub struct Layout {
+    monitors: Vec<(MonitorHandle, Rect)>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Layout {
+    pub fn new<T>(event_loop: &EventLoop<T>) -> Result<Self, Box<dyn Error>> {
+        let mut monitors: Vec<_> = event_loop.available_monitors().collect();
+        if monitors.is_empty() {
+            return Err("no monitors to span".into());
+        }
+        monitors.sort_by_key(|monitor| {
+            let position = monitor.position();
+            (position.x, position.y)
+        });
+        let left = monitors.iter().map(|m| m.position().x).min().unwrap_or(0);
+        let top = monitors.iter().map(|m| m.position().y).min().unwrap_or(0);
+        let monitors: Vec<_> = monitors
+            .into_iter()
+            .map(|monitor| {
+                let (position, size) = (monitor.position(), monitor.size());
+                let rect = (
+                    (position.x - left) as u32,
+                    (position.y - top) as u32,
+                    size.width,
+                    size.height,
+                );
+                (monitor, rect)
+            })
+            .collect();
+        let width = monitors.iter().map(|(_, (x, _, w, _))| x + w).max();
+        let height = monitors.iter().map(|(_, (_, y, _, h))| y + h).max();
+        Ok(Self {
+            width: width.unwrap_or(0),
+            height: height.unwrap_or(0),
+            monitors,
+        })
+    }
+
+    /// Where the main window goes.
+    pub fn primary(&self) -> &MonitorHandle {
+        &self.monitors[0].0
+    }
+}
+
+/// A window on one of the other monitors.
+struct Output {
+    window: Window,
+    pixels: Pixels,
+    rect: Rect,
+}
+
+/// Draws one continuous frame across a borderless fullscreen window per monitor. The whole
+/// wall is rendered into a canvas of the render resolution, and every window shows the slice
+/// its monitor covers. The main window shows the first monitor's, the others are owned here.
+pub struct Span {
+    wall: (u32, u32),
+    primary: Rect,
+    outputs: Vec<Output>,
+    canvas: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl Span {
+    /// Opens windows on every monitor after the first, which `window` (already made
+    /// fullscreen there) and `pixels` cover; `width` and `height` are the render resolution.
+    /// The new windows present their frames the same way as the main one.
+    pub async fn new<T>(
+        layout: Layout,
+        event_loop: &EventLoop<T>,
+        pixels: &mut Pixels,
+        present_mode: PresentMode,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut monitors = layout.monitors.into_iter();
+        let (_, primary) = monitors.next().ok_or("no monitors to span")?;
+        let mut outputs = Vec::new();
+        for (monitor, rect) in monitors {
+            let window = WindowBuilder::new()
+                .with_title("tunnel-rs")
+                .with_fullscreen(Some(Fullscreen::Borderless(Some(monitor))))
+                .build(event_loop)?;
+            let size = window.inner_size();
+            let surface_texture = SurfaceTexture::new(size.width, size.height, &window);
+            let pixels = PixelsBuilder::new(1, 1, surface_texture)
+                .present_mode(present_mode)
+                .build_async()
+                .await?;
+            outputs.push(Output {
+                window,
+                pixels,
+                rect,
+            });
+        }
+        let mut span = Self {
+            wall: (layout.width, layout.height),
+            primary,
+            outputs,
+            canvas: Vec::new(),
+            width: 0,
+            height: 0,
+        };
+        span.resize(pixels, width, height);
+        Ok(span)
+    }
+
+    /// The whole wall, to draw the next frame into.
+    pub fn canvas(&mut self) -> &mut [u8] {
+        &mut self.canvas
+    }
+
+    /// Changes the render resolution, which is spread over the wall in proportion to the
+    /// monitors' sizes.
+    pub fn resize(&mut self, pixels: &mut Pixels, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.canvas.resize(width as usize * height as usize * 4, 0);
+        let (_, _, w, h) = self.slice(self.primary);
+        pixels.resize_buffer(w, h);
+        for i in 0..self.outputs.len() {
+            let (_, _, w, h) = self.slice(self.outputs[i].rect);
+            self.outputs[i].pixels.resize_buffer(w, h);
+        }
+    }
+
+    /// Follows a resize of one of the windows owned here; the main window's is ignored.
+    pub fn resize_surface(&mut self, window: WindowId, size: PhysicalSize<u32>) {
+        for output in &mut self.outputs {
+            if output.window.id() == window {
+                output.pixels.resize_surface(size.width, size.height);
+            }
+        }
+    }
+
+    /// Configures the surfaces of the windows owned here again, after they were lost.
+    pub fn reconfigure(&mut self) {
+        for output in &mut self.outputs {
+            let size = output.window.inner_size();
+            output.pixels.resize_surface(size.width, size.height);
+        }
+    }
+
+    /// Copies each monitor's slice of the canvas into its window and shows the windows owned
+    /// here; the main one's slice is copied into `pixels` for the caller to show.
+    pub fn render(&mut self, pixels: &mut Pixels) -> Result<(), pixels::Error> {
+        copy(&self.canvas, self.width, self.slice(self.primary), pixels);
+        for i in 0..self.outputs.len() {
+            let slice = self.slice(self.outputs[i].rect);
+            let output = &mut self.outputs[i];
+            copy(&self.canvas, self.width, slice, &mut output.pixels);
+            output.pixels.render()?;
+        }
+        Ok(())
+    }
+
+    /// The part of the canvas a monitor covers.
+    fn slice(&self, (x, y, width, height): Rect) -> Rect {
+        let scale = |value: u32, canvas: u32, wall: u32| {
+            (value as u64 * canvas as u64 / wall.max(1) as u64) as u32
+        };
+        let (left, right) = (
+            scale(x, self.width, self.wall.0),
+            scale(x + width, self.width, self.wall.0),
+        );
+        let (top, bottom) = (
+            scale(y, self.height, self.wall.1),
+            scale(y + height, self.height, self.wall.1),
+        );
+        let left = left.min(self.width - 1);
+        let top = top.min(self.height - 1);
+        (left, top, (right - left).max(1), (bottom - top).max(1))
+    }
+}
+
+fn copy(canvas: &[u8], canvas_width: u32, (x, y, width, height): Rect, pixels: &mut Pixels) {
+    let frame = pixels.get_frame();
+    let row_bytes = width as usize * 4;
+    for (row, target) in frame.chunks_exact_mut(row_bytes).enumerate() {
+        let start = ((y as usize + row) * canvas_width as usize + x as usize) * 4;
+        if let Some(source) = canvas.get(start..start + row_bytes) {
+            target.copy_from_slice(source);
+        }
+    }
+    debug_assert_eq!(frame.len(), row_bytes * height as usize);
+}
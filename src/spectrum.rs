@@ -0,0 +1,137 @@
+use std::f32::consts::PI;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+const FFT_SIZE: usize = 1024;
+const HOP: usize = 256;
+const BASS: (f32, f32) = (20.0, 250.0);
+const TREBLE: (f32, f32) = (4000.0, 12000.0);
+const PEAK_DECAY: f32 = 0.995;
+const NOISE_FLOOR: f32 = 1e-4;
+
+/// Band levels in `[0, 1]` published by an `Analyzer` on the audio thread and read by
+/// `World::update` without locking.
+#[derive(Default)]
+pub struct AudioLevels {
+    bass: AtomicU32,
+    treble: AtomicU32,
+}
+
+impl AudioLevels {
+    pub fn bass(&self) -> f64 {
+        f32::from_bits(self.bass.load(Ordering::Relaxed)) as f64
+    }
+
+    pub fn treble(&self) -> f64 {
+        f32::from_bits(self.treble.load(Ordering::Relaxed)) as f64
+    }
+
+    fn store(&self, bass: f32, treble: f32) {
+        self.bass.store(bass.to_bits(), Ordering::Relaxed);
+        self.treble.store(treble.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Keeps the latest `FFT_SIZE` mono samples in a ring buffer and re-measures the bass and
+/// treble energy every `HOP` samples, normalized against their recent peaks.
+pub struct Analyzer {
+    sample_rate: u32,
+    ring: Vec<f32>,
+    head: usize,
+    pending: usize,
+    window: Vec<f32>,
+    spectrum: Vec<(f32, f32)>,
+    peaks: (f32, f32),
+    levels: Arc<AudioLevels>,
+}
+
+impl Analyzer {
+    pub fn new(sample_rate: u32, levels: Arc<AudioLevels>) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        Self {
+            sample_rate: sample_rate.max(1),
+            ring: vec![0.0; FFT_SIZE],
+            head: 0,
+            pending: 0,
+            window,
+            spectrum: vec![(0.0, 0.0); FFT_SIZE],
+            peaks: (NOISE_FLOOR, NOISE_FLOOR),
+            levels,
+        }
+    }
+
+    /// Feeds interleaved samples in `[-1, 1]`.
+    pub fn push(&mut self, samples: &[f32], channels: u16) {
+        let channels = channels.max(1) as usize;
+        for frame in samples.chunks(channels) {
+            self.ring[self.head] = frame.iter().sum::<f32>() / frame.len() as f32;
+            self.head = (self.head + 1) % FFT_SIZE;
+            self.pending += 1;
+            if self.pending == HOP {
+                self.pending = 0;
+                self.analyze();
+            }
+        }
+    }
+
+    fn analyze(&mut self) {
+        for (i, bin) in self.spectrum.iter_mut().enumerate() {
+            let sample = self.ring[(self.head + i) % FFT_SIZE];
+            *bin = (sample * self.window[i], 0.0);
+        }
+        fft(&mut self.spectrum);
+
+        let bass = self.band(BASS);
+        let treble = self.band(TREBLE);
+        self.peaks = (
+            (self.peaks.0 * PEAK_DECAY).max(bass).max(NOISE_FLOOR),
+            (self.peaks.1 * PEAK_DECAY).max(treble).max(NOISE_FLOOR),
+        );
+        self.levels
+            .store(bass / self.peaks.0, treble / self.peaks.1);
+    }
+
+    fn band(&self, (low, high): (f32, f32)) -> f32 {
+        let bin_width = self.sample_rate as f32 / FFT_SIZE as f32;
+        let first = ((low / bin_width) as usize).max(1);
+        let last = ((high / bin_width) as usize).min(FFT_SIZE / 2);
+        if first >= last {
+            return 0.0;
+        }
+        let energy: f32 = self.spectrum[first..last]
+            .iter()
+            .map(|&(re, im)| re * re + im * im)
+            .sum();
+        energy / (last - first) as f32
+    }
+}
+
+/// In-place iterative radix-2 FFT; the length must be a power of two.
+fn fft(data: &mut [(f32, f32)]) {
+    let n = data.len();
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (re, im) = data[start + k + len / 2];
+                let odd = (re * cos - im * sin, re * sin + im * cos);
+                let even = data[start + k];
+                data[start + k] = (even.0 + odd.0, even.1 + odd.1);
+                data[start + k + len / 2] = (even.0 - odd.0, even.1 - odd.1);
+            }
+        }
+        len *= 2;
+    }
+}
@@ -0,0 +1,139 @@
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+use crate::effect::Effect;
+use crate::renderer::{Filter, RenderStats};
+use crate::world::World;
+
+/// What the right half of a split frame draws differently from the left one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    /// The other texture filter: bilinear next to nearest or the other way around.
+    Filter,
+    /// The palette after the current one.
+    Palette,
+    /// The GPU backend next to the CPU. The pipeline only draws the CPU's frame; whoever
+    /// presents it draws the GPU's over the right half, see `GpuRenderer::set_split`.
+    Backend,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 3] = [Comparison::Filter, Comparison::Palette, Comparison::Backend];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Comparison::Filter => "filter",
+            Comparison::Palette => "palette",
+            Comparison::Backend => "backend",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let i = Self::ALL.iter().position(|&kind| kind == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|kind| kind.name()).collect();
+                format!(
+                    "unknown comparison `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The color of the line between the halves.
+const DIVIDER: [u8; 4] = [0xff, 0xff, 0xff, 0xff];
+
+/// Draws every frame twice, once as it is and once changed by `comparison`, and shows the
+/// first left of `divider` and the second right of it, to compare the two side by side.
+pub struct Split {
+    pub comparison: Comparison,
+    /// Where the halves meet, as a share of the frame's width.
+    pub divider: f64,
+    right: Vec<u8>,
+}
+
+impl Split {
+    pub fn new(comparison: Comparison) -> Self {
+        Self {
+            comparison,
+            divider: 0.5,
+            right: Vec::new(),
+        }
+    }
+
+    /// The first column of the frame `width` pixels wide that belongs to the right half.
+    pub fn column(&self, width: usize) -> usize {
+        (self.divider.clamp(0.0, 1.0) * width as f64).round() as usize
+    }
+
+    /// Calls `draw` for the left half into `frame` and, with the comparison applied, for
+    /// the right half into a buffer of its own, then copies that half over and marks the
+    /// divider; `Comparison::Backend` leaves the right half to the GPU. The returned stats
+    /// are the left half's with the time of both.
+    pub fn draw(
+        &mut self,
+        world: &mut World,
+        effects: &mut [Box<dyn Effect>],
+        frame: &mut [u8],
+        mut draw: impl FnMut(&mut World, &mut [Box<dyn Effect>], &mut [u8]) -> RenderStats,
+    ) -> RenderStats {
+        let mut stats = draw(world, effects, frame);
+        let (width, _) = world.frame_size();
+        let width = width as usize;
+        if self.comparison != Comparison::Backend {
+            self.right.resize(frame.len(), 0);
+        }
+        match self.comparison {
+            Comparison::Filter => {
+                let filters: Vec<_> = effects.iter().map(|effect| effect.filter()).collect();
+                for (effect, &filter) in effects.iter_mut().zip(&filters) {
+                    effect.set_filter(match filter {
+                        Filter::Nearest => Filter::Bilinear,
+                        Filter::Bilinear => Filter::Nearest,
+                    });
+                }
+                stats.total += draw(world, effects, &mut self.right).total;
+                for (effect, filter) in effects.iter_mut().zip(filters) {
+                    effect.set_filter(filter);
+                }
+            }
+            Comparison::Palette => {
+                let palette = world.layer.palette_kind.next().palette();
+                let palette = std::mem::replace(&mut world.layer.palette, palette);
+                stats.total += draw(world, effects, &mut self.right).total;
+                world.layer.palette = palette;
+            }
+            Comparison::Backend => {}
+        }
+
+        let column = self.column(width);
+        let right = (self.comparison != Comparison::Backend).then_some(&self.right);
+        frame
+            .par_chunks_exact_mut(width * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                if let Some(right) = right {
+                    let start = y * width * 4;
+                    row[column * 4..]
+                        .copy_from_slice(&right[start + column * 4..start + width * 4]);
+                }
+                if column < width {
+                    row[column * 4..column * 4 + 4].copy_from_slice(&DIVIDER);
+                }
+            });
+        stats
+    }
+}
@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use rayon::prelude::*;
+
+use crate::renderer::RenderStats;
+use crate::world::World;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StereoMode {
+    /// Red from the left eye, green and blue from the right, for red/cyan glasses.
+    Anaglyph,
+    /// Both eyes squeezed into one half of the frame each, for VR viewers and 3D displays.
+    SideBySide,
+}
+
+impl StereoMode {
+    pub const ALL: [StereoMode; 2] = [StereoMode::Anaglyph, StereoMode::SideBySide];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StereoMode::Anaglyph => "anaglyph",
+            StereoMode::SideBySide => "side-by-side",
+        }
+    }
+}
+
+impl FromStr for StereoMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|mode| mode.name()).collect();
+                format!(
+                    "unknown stereo mode `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Draws every frame once from each eye, moved `separation` apart across the tunnel (as a
+/// share of its radius), and merges the two views.
+pub struct Stereo {
+    pub mode: StereoMode,
+    pub separation: f64,
+    right: Vec<u8>,
+}
+
+impl Stereo {
+    pub fn new(mode: StereoMode, separation: f64) -> Self {
+        Self {
+            mode,
+            separation,
+            right: Vec::new(),
+        }
+    }
+
+    /// Calls `draw` for the left eye into `frame` and for the right eye into a buffer of its
+    /// own, then merges them into `frame`. The returned stats are the left eye's with the
+    /// time of both.
+    pub fn draw(
+        &mut self,
+        world: &mut World,
+        frame: &mut [u8],
+        mut draw: impl FnMut(&World, &mut [u8]) -> RenderStats,
+    ) -> RenderStats {
+        let (width, _) = world.frame_size();
+        let eye = self.separation / 2.0;
+        self.right.resize(frame.len(), 0);
+
+        world.camera_mut().set_eye(-eye);
+        let mut stats = draw(world, frame);
+        world.camera_mut().set_eye(eye);
+        stats.total += draw(world, &mut self.right).total;
+        world.camera_mut().set_eye(0.0);
+
+        let rows = frame
+            .par_chunks_exact_mut(width as usize * 4)
+            .zip(self.right.par_chunks_exact(width as usize * 4));
+        match self.mode {
+            StereoMode::Anaglyph => rows.for_each(|(left, right)| {
+                for (left, right) in left.chunks_exact_mut(4).zip(right.chunks_exact(4)) {
+                    left[1..3].copy_from_slice(&right[1..3]);
+                }
+            }),
+            StereoMode::SideBySide => rows.for_each(|(left, right)| {
+                let width = left.len() / 4;
+                let half = width / 2;
+                // Squeezing in place is safe: pixel `x` only reads pixels from `2x` on.
+                for x in 0..half {
+                    let pixel = average(&left[x * 8..x * 8 + 8]);
+                    left[x * 4..x * 4 + 4].copy_from_slice(&pixel);
+                }
+                for x in half..width {
+                    let from = (2 * (x - half)).min(width.saturating_sub(2));
+                    let pixel = average(&right[from * 4..from * 4 + 8]);
+                    left[x * 4..x * 4 + 4].copy_from_slice(&pixel);
+                }
+            }),
+        }
+        stats
+    }
+}
+
+/// The average of two adjacent RGBA pixels.
+fn average(pair: &[u8]) -> [u8; 4] {
+    std::array::from_fn(|channel| {
+        (pair[channel] as u16 + pair[channel + 4] as u16).div_ceil(2) as u8
+    })
+}
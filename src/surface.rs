@@ -0,0 +1,82 @@
+use std::error::Error;
+use std::fmt;
+
+use pixels::wgpu;
+use tracing::warn;
+
+/// Frames in a row that may fail to show before giving up on the surface.
+const MAX_FAILURES: u32 = 120;
+
+/// Why a frame couldn't be shown, sorted by what can be done about it.
+#[derive(Debug)]
+pub enum SurfaceError {
+    /// The surface no longer matches its window, after a resize, a display change or the GPU
+    /// being reset, and has to be configured again.
+    Outdated(wgpu::SurfaceError),
+    /// The GPU took too long to hand out the next frame; the one after may well make it.
+    Timeout,
+    /// Nothing to recover with: the GPU is out of memory or drawing the frame failed.
+    Fatal(pixels::Error),
+}
+
+impl From<pixels::Error> for SurfaceError {
+    fn from(err: pixels::Error) -> Self {
+        match err {
+            pixels::Error::Surface(
+                err @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost),
+            ) => SurfaceError::Outdated(err),
+            pixels::Error::Surface(wgpu::SurfaceError::Timeout) => SurfaceError::Timeout,
+            err => SurfaceError::Fatal(err),
+        }
+    }
+}
+
+impl fmt::Display for SurfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SurfaceError::Outdated(err) => write!(f, "surface needs reconfiguring: {}", err),
+            SurfaceError::Timeout => write!(f, "timed out waiting for the next frame"),
+            SurfaceError::Fatal(pixels::Error::Surface(err)) => write!(f, "{}", err),
+            SurfaceError::Fatal(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for SurfaceError {}
+
+/// Keeps the window showing frames through the errors presenting them can run into.
+#[derive(Default)]
+pub struct Recovery {
+    failures: u32,
+}
+
+impl Recovery {
+    /// Goes through the outcome of showing a frame: an outdated or lost surface is configured
+    /// again with `reconfigure` and a timeout skips the frame. Returns the error to give up
+    /// on when it can't be recovered from, or keeps coming back for `MAX_FAILURES` frames.
+    pub fn check(
+        &mut self,
+        result: Result<(), pixels::Error>,
+        reconfigure: impl FnOnce(),
+    ) -> Result<(), SurfaceError> {
+        let err = match result {
+            Ok(()) => {
+                self.failures = 0;
+                return Ok(());
+            }
+            Err(err) => SurfaceError::from(err),
+        };
+        self.failures += 1;
+        if matches!(err, SurfaceError::Fatal(_)) || self.failures > MAX_FAILURES {
+            return Err(err);
+        }
+        if let SurfaceError::Outdated(_) = err {
+            reconfigure();
+        }
+        // Only the first of a run is worth reporting.
+        if self.failures == 1 {
+            warn!("Skipped a frame: {}", err);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,193 @@
+//! Keeping several instances in lockstep over UDP, e.g. one per projector of an
+//! installation. The leader sends where its animation has got to and its settings after
+//! every update, as a small TOML document; followers take them over before theirs, and keep
+//! running on their own clock between packets and if the leader goes quiet.
+
+use std::error::Error;
+use std::io::ErrorKind;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use toml::{Table, Value};
+use tracing::{info, warn};
+use tunnel::{Filter, Motion, Pipeline, World};
+
+use crate::performance::Settings;
+
+pub const DEFAULT_PORT: u16 = 7447;
+/// First line of every packet, so that followers ignore anything else sent to the port.
+const HEADER: &str = "# tunnel-rs sync 1\n";
+/// Largest packet UDP can carry.
+const MAX_PACKET: usize = 65_507;
+
+/// Sends the animation to followers at `target`, a host (usually the network's broadcast
+/// address) with an optional port.
+pub struct SyncLeader {
+    socket: UdpSocket,
+    /// Sending failed the last time, which was logged.
+    failed: bool,
+}
+
+impl SyncLeader {
+    pub fn start(target: &str) -> Result<Self, Box<dyn Error>> {
+        let address = target
+            .to_socket_addrs()
+            .or_else(|_| (target, DEFAULT_PORT).to_socket_addrs())
+            .map_err(|err| format!("failed to resolve `{}`: {}", target, err))?
+            .next()
+            .ok_or_else(|| format!("`{}` has no address", target))?;
+        let socket = UdpSocket::bind(if address.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        })?;
+        socket.set_broadcast(true)?;
+        socket.connect(address)?;
+        socket.set_nonblocking(true)?;
+        println!("Leading instances listening on {}", address);
+        Ok(Self {
+            socket,
+            failed: false,
+        })
+    }
+
+    /// Sends the world's motion and `settings`, as `performance::capture` takes them. A
+    /// packet the network can't take at the moment is dropped; the next one replaces it.
+    pub fn send(&mut self, world: &World, settings: Table) {
+        let mut packet = Table::new();
+        packet.insert("paused".to_string(), world.clock().is_paused().into());
+        packet.insert("motion".to_string(), motion_table(world.motion()).into());
+        packet.insert("settings".to_string(), settings.into());
+        let packet = format!("{}{}", HEADER, packet);
+        match self.socket.send(packet.as_bytes()) {
+            Ok(_) => self.failed = false,
+            // Sent to a single host, nothing may be listening yet.
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::ConnectionRefused
+                ) => {}
+            Err(err) => {
+                if !self.failed {
+                    warn!("Failed to send the sync: {}", err);
+                    self.failed = true;
+                }
+            }
+        }
+    }
+}
+
+/// Follows a leader's packets arriving at a port.
+pub struct SyncFollower {
+    socket: UdpSocket,
+    buffer: Vec<u8>,
+    settings: Settings,
+    /// The leader's settings as last applied, so that only changes are applied again.
+    applied: Table,
+    leader: Option<SocketAddr>,
+}
+
+impl SyncFollower {
+    /// Listens on `address`, a port or an address with one; named textures are generated
+    /// with `seed`, which should be the leader's.
+    pub fn listen(address: &str, seed: u64) -> Result<Self, Box<dyn Error>> {
+        let address = match address.parse::<u16>() {
+            Ok(port) => SocketAddr::from(([0, 0, 0, 0], port)),
+            Err(_) => address
+                .to_socket_addrs()
+                .map_err(|err| format!("failed to resolve `{}`: {}", address, err))?
+                .next()
+                .ok_or_else(|| format!("`{}` has no address", address))?,
+        };
+        let socket = UdpSocket::bind(address)
+            .map_err(|err| format!("failed to listen on {}: {}", address, err))?;
+        socket.set_nonblocking(true)?;
+        println!("Waiting for a leader on {}", address);
+        Ok(Self {
+            socket,
+            buffer: vec![0; MAX_PACKET],
+            settings: Settings::new(seed),
+            applied: Table::new(),
+            leader: None,
+        })
+    }
+
+    /// Takes over the newest packet that arrived since the last update, if any. Settings
+    /// that don't fit are skipped with a warning.
+    pub fn update(&mut self, world: &mut World, pipeline: &mut Pipeline, filter: &mut Filter) {
+        let mut latest = None;
+        loop {
+            match self.socket.recv_from(&mut self.buffer) {
+                Ok((len, from)) => {
+                    if let Some(packet) = parse(&self.buffer[..len]) {
+                        latest = Some((packet, from));
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    warn!("Failed to receive the sync: {}", err);
+                    break;
+                }
+            }
+        }
+        let Some(((paused, motion, settings), from)) = latest else {
+            return;
+        };
+        if self.leader != Some(from) {
+            info!("Following {}", from);
+            self.leader = Some(from);
+        }
+
+        world.clock_mut().set_paused(paused);
+        world.set_motion(motion);
+        for (channel, value) in &settings {
+            if self.applied.get(channel) == Some(value) {
+                continue;
+            }
+            if let Err(err) = self.settings.apply(world, pipeline, filter, channel, value) {
+                warn!("Skipping `{}` of the sync: {}", channel, err);
+            }
+        }
+        self.applied = settings;
+    }
+}
+
+fn motion_table(motion: Motion) -> Table {
+    let mut table = Table::new();
+    table.insert("time".to_string(), motion.time.into());
+    table.insert("distance".to_string(), motion.distance.into());
+    table.insert("spin".to_string(), motion.spin.into());
+    table.insert("palette_phase".to_string(), motion.palette_phase.into());
+    table.insert("light_orbit".to_string(), motion.light_orbit.into());
+    let (travel, spin) = motion.flight_speed;
+    table.insert("flight_speed".to_string(), vec![travel, spin].into());
+    table
+}
+
+/// Whether the leader is paused, its motion and its settings, or nothing for packets that
+/// aren't a leader's.
+fn parse(packet: &[u8]) -> Option<(bool, Motion, Table)> {
+    let packet: Table = std::str::from_utf8(packet)
+        .ok()?
+        .strip_prefix(HEADER)?
+        .parse()
+        .ok()?;
+    let motion = packet.get("motion")?;
+    let number = |name: &str| motion.get(name).and_then(Value::as_float);
+    let flight_speed = motion.get("flight_speed")?.as_array()?;
+    let motion = Motion {
+        time: number("time")?,
+        distance: number("distance")?,
+        spin: number("spin")?,
+        palette_phase: number("palette_phase")?,
+        light_orbit: number("light_orbit")?,
+        flight_speed: (
+            flight_speed.first()?.as_float()?,
+            flight_speed.get(1)?.as_float()?,
+        ),
+    };
+    Some((
+        packet.get("paused")?.as_bool()?,
+        motion,
+        packet.get("settings")?.as_table()?.clone(),
+    ))
+}
@@ -0,0 +1,328 @@
+//! Plays the tunnel inside the terminal, e.g. over SSH: every frame is scaled down to fit it
+//! and drawn as colored half blocks, two pixels to a character cell, or as sixel graphics.
+
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crossterm::cursor::{Hide, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use rayon::prelude::*;
+use tunnel::{effect, Renderer, World};
+
+use crate::cli::{Args, Output};
+use crate::pacing::FrameLimiter;
+#[cfg(feature = "scripting")]
+use crate::script::Script;
+use crate::{apply_config, configure, pipeline, setup, Setup};
+
+/// Frame rate to hold to unless `--max-fps` asks for another; terminals can't take much more.
+const FPS: f64 = 30.0;
+/// Size of a character cell in pixels, for terminals that don't tell.
+const CELL: (usize, usize) = (10, 20);
+/// Levels per channel of the sixel palette, which holds every mix of them.
+const SIXEL_LEVELS: usize = 6;
+
+/// Draws `--output terminal` or `--output sixel` until Esc, Q or Ctrl+C is pressed or
+/// `--duration` runs out. Space pauses.
+pub fn play(args: &Args) -> Result<(), Box<dyn Error>> {
+    let Setup {
+        config,
+        params,
+        mut animation,
+        texture,
+        timeline,
+        height_map,
+        atlas,
+        stamps,
+        ..
+    } = setup(args)?;
+    let duration = args.duration.or_else(|| {
+        timeline
+            .as_ref()
+            .filter(|timeline| !timeline.repeats())
+            .map(|timeline| timeline.duration())
+    });
+    let mut world = World::with_texture(params, texture);
+    configure(&mut world, args, timeline, height_map, atlas, stamps);
+    apply_config(&mut world, &config);
+
+    let mut renderer = Renderer::default();
+    renderer.set_simd(!args.scalar);
+    renderer.set_interlace(args.interlace);
+    let mut effects = effect::builtin();
+    effects[0] = Box::new(renderer);
+    let mut pipeline = pipeline(args, effects)?;
+    #[cfg(feature = "scripting")]
+    let mut script = args.script.as_deref().map(Script::load).transpose()?;
+
+    let (width, height) = world.frame_size();
+    let (width, height) = (width as usize, height as usize);
+    let mut frame = vec![0u8; width * height * 4];
+    let mut screen = Screen::new(args.output, (width, height))?;
+    let mut limiter = FrameLimiter::new(args.max_fps.unwrap_or(FPS));
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        while !duration.is_some_and(|duration| world.clock().time() >= duration) {
+            limiter.wait();
+            while event::poll(Duration::ZERO)? {
+                match event::read()? {
+                    Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                        KeyCode::Esc | KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            return Ok(())
+                        }
+                        KeyCode::Char(' ') => world.clock_mut().toggle_pause(),
+                        _ => {}
+                    },
+                    Event::Resize(..) => screen.resize()?,
+                    _ => {}
+                }
+            }
+            if let Some(texture) = animation
+                .as_mut()
+                .and_then(|animation| animation.frame_at(world.clock().time()))
+            {
+                world.set_texture_frame(texture);
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = &mut script {
+                script.update(&mut world, &Default::default());
+            }
+            world.update();
+            pipeline.update(&world);
+            pipeline.draw(&mut world, &mut frame);
+            screen.draw(&frame, &mut stdout)?;
+            world.feed_back(&frame, width as u32, height as u32);
+        }
+        Ok(())
+    })();
+
+    execute!(stdout, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+/// The part of the terminal the frames go to and how big they are there.
+struct Screen {
+    output: Output,
+    frame_size: (usize, usize),
+    /// Size of the scaled down frame in pixels.
+    size: (usize, usize),
+    /// The cell its top left corner goes to, counting from 0.
+    origin: (usize, usize),
+    pixels: Vec<[u8; 3]>,
+    out: Vec<u8>,
+    /// Clear the screen before the next frame, to wipe what a larger one left behind.
+    clear: bool,
+}
+
+impl Screen {
+    fn new(output: Output, frame_size: (usize, usize)) -> io::Result<Self> {
+        let mut screen = Self {
+            output,
+            frame_size,
+            size: (0, 0),
+            origin: (0, 0),
+            pixels: Vec::new(),
+            out: Vec::new(),
+            clear: true,
+        };
+        screen.resize()?;
+        Ok(screen)
+    }
+
+    /// Fits the frame into the terminal's current size, keeping its aspect ratio. Sixel
+    /// images leave the last row free so that the terminal doesn't scroll, and aren't
+    /// scaled up.
+    fn resize(&mut self) -> io::Result<()> {
+        let size = terminal::window_size()?;
+        let (columns, rows) = (size.columns.max(1) as usize, size.rows.max(2) as usize);
+        let (cell, space) = match self.output {
+            Output::Sixel => {
+                let cell = if size.width > 0 && size.height > 0 {
+                    (
+                        (size.width as usize / columns).max(1),
+                        (size.height as usize / rows).max(1),
+                    )
+                } else {
+                    CELL
+                };
+                (cell, (columns * cell.0, (rows - 1) * cell.1))
+            }
+            _ => ((1, 2), (columns, rows * 2)),
+        };
+        let (width, height) = self.frame_size;
+        let mut scale = (space.0 as f64 / width as f64).min(space.1 as f64 / height as f64);
+        if self.output == Output::Sixel {
+            scale = scale.min(1.0);
+        }
+        let mut size = (
+            ((width as f64 * scale) as usize).clamp(1, space.0),
+            ((height as f64 * scale) as usize).clamp(1, space.1),
+        );
+        if self.output != Output::Sixel {
+            // Every cell shows two pixels, so the last one needs its partner.
+            size.1 += size.1 % 2;
+        }
+        self.size = size;
+        self.origin = (
+            (space.0 - size.0) / cell.0 / 2,
+            (space.1 - size.1) / cell.1 / 2,
+        );
+        self.pixels = vec![[0; 3]; size.0 * size.1];
+        self.clear = true;
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &[u8], stdout: &mut impl Write) -> io::Result<()> {
+        self.scale_down(frame);
+        self.out.clear();
+        if self.clear {
+            self.out.extend_from_slice(b"\x1b[2J");
+            self.clear = false;
+        }
+        match self.output {
+            Output::Sixel => self.encode_sixel()?,
+            _ => self.encode_half_blocks()?,
+        }
+        stdout.write_all(&self.out)?;
+        stdout.flush()
+    }
+
+    /// Averages the RGBA `frame` over the block of pixels each of the smaller image covers.
+    fn scale_down(&mut self, frame: &[u8]) {
+        let (width, height) = self.frame_size;
+        let (to_width, to_height) = self.size;
+        let rows = to_height.min(height);
+        self.pixels
+            .par_chunks_exact_mut(to_width)
+            .take(rows)
+            .enumerate()
+            .for_each(|(y, row)| {
+                let top = y * height / rows;
+                let bottom = ((y + 1) * height / rows).max(top + 1);
+                for (x, pixel) in row.iter_mut().enumerate() {
+                    let left = x * width / to_width;
+                    let right = ((x + 1) * width / to_width).max(left + 1);
+                    let mut sum = [0u32; 3];
+                    for y in top..bottom {
+                        for x in left..right {
+                            let i = (y * width + x) * 4;
+                            for (sum, &channel) in sum.iter_mut().zip(&frame[i..i + 3]) {
+                                *sum += u32::from(channel);
+                            }
+                        }
+                    }
+                    let count = ((bottom - top) * (right - left)) as u32;
+                    *pixel = sum.map(|sum| (sum / count) as u8);
+                }
+            });
+    }
+
+    /// Writes every pair of rows as a row of upper half blocks with the top pixel as the
+    /// foreground and the bottom one as the background color, in 24-bit color.
+    fn encode_half_blocks(&mut self) -> io::Result<()> {
+        let (width, height) = self.size;
+        let mut colors = None;
+        for (row, y) in (0..height).step_by(2).enumerate() {
+            write!(
+                self.out,
+                "\x1b[{};{}H",
+                self.origin.1 + row + 1,
+                self.origin.0 + 1
+            )?;
+            for x in 0..width {
+                let top = self.pixels[y * width + x];
+                let bottom = self.pixels[(y + 1) * width + x];
+                if colors != Some((top, bottom)) {
+                    write!(
+                        self.out,
+                        "\x1b[38;2;{};{};{};48;2;{};{};{}m",
+                        top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]
+                    )?;
+                    colors = Some((top, bottom));
+                }
+                self.out.extend_from_slice("▀".as_bytes());
+            }
+        }
+        self.out.extend_from_slice(b"\x1b[0m");
+        Ok(())
+    }
+
+    /// Writes the image as sixels: bands six pixels high, drawn once per color they use,
+    /// with the colors taken from an evenly spaced palette and repeated columns run-length
+    /// encoded.
+    fn encode_sixel(&mut self) -> io::Result<()> {
+        let (width, height) = self.size;
+        let levels = SIXEL_LEVELS;
+        let indices: Vec<usize> = self
+            .pixels
+            .iter()
+            .map(|pixel| {
+                let [r, g, b] = pixel.map(|channel| (channel as usize * (levels - 1) + 127) / 255);
+                (r * levels + g) * levels + b
+            })
+            .collect();
+
+        write!(
+            self.out,
+            "\x1b[{};{}H\x1bPq\"1;1;{};{}",
+            self.origin.1 + 1,
+            self.origin.0 + 1,
+            width,
+            height
+        )?;
+        for color in 0..levels.pow(3) {
+            let [r, g, b] = [
+                color / levels / levels,
+                color / levels % levels,
+                color % levels,
+            ]
+            .map(|level| level * 100 / (levels - 1));
+            write!(self.out, "#{};2;{};{};{}", color, r, g, b)?;
+        }
+        let mut used = vec![false; levels.pow(3)];
+        for top in (0..height).step_by(6) {
+            let rows = (height - top).min(6);
+            used.fill(false);
+            for &color in &indices[top * width..(top + rows) * width] {
+                used[color] = true;
+            }
+            for color in (0..used.len()).filter(|&color| used[color]) {
+                write!(self.out, "#{}", color)?;
+                let sixel = |x: usize| {
+                    let bits = (0..rows)
+                        .filter(|&row| indices[(top + row) * width + x] == color)
+                        .fold(0u8, |bits, row| bits | 1 << row);
+                    b'?' + bits
+                };
+                let mut x = 0;
+                while x < width {
+                    let char = sixel(x);
+                    let run = (x..width).take_while(|&x| sixel(x) == char).count();
+                    // Nothing to draw up to the end of the band.
+                    if char == b'?' && x + run == width {
+                        break;
+                    }
+                    if run > 3 {
+                        write!(self.out, "!{}", run)?;
+                        self.out.push(char);
+                    } else {
+                        self.out.extend(std::iter::repeat_n(char, run));
+                    }
+                    x += run;
+                }
+                self.out.push(b'$');
+            }
+            self.out.push(b'-');
+        }
+        self.out.extend_from_slice(b"\x1b\\");
+        Ok(())
+    }
+}
@@ -0,0 +1,411 @@
+use std::f64::consts::PI;
+
+use rayon::prelude::*;
+
+use super::{Texture, TextureFormat};
+
+pub trait TextureGenerator {
+    fn name(&self) -> &str;
+
+    fn texel(&self, u: f64, v: f64) -> u8;
+
+    /// GLSL defining `float texel(vec2 uv)`, which does what `texel` does with the value as a
+    /// float, for exporting shaders. Random textures may come out with other random numbers.
+    /// Without it, textures from this generator can't be exported.
+    fn glsl(&self) -> Option<String> {
+        None
+    }
+
+    fn generate(&self, width: usize, height: usize) -> Texture {
+        let mut texels = vec![0u32; width * height];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let u = (i % width) as f64 / width as f64;
+            let v = (i / width) as f64 / height as f64;
+            *texel = self.texel(u, v) as u32;
+        }
+        Texture {
+            width,
+            height,
+            format: TextureFormat::Indexed,
+            texels,
+        }
+    }
+}
+
+pub fn builtin(seed: u64) -> Vec<Box<dyn TextureGenerator>> {
+    vec![
+        Box::new(Xor),
+        Box::new(Checkerboard { cells: 8 }),
+        Box::new(Noise {
+            seed,
+            period: 4,
+            octaves: 5,
+        }),
+        Box::new(Brick {
+            columns: 4,
+            rows: 8,
+        }),
+        Box::new(Spiral { bands: 2, turns: 3 }),
+        Box::new(Stripes { count: 8 }),
+    ]
+}
+
+pub struct Xor;
+
+impl TextureGenerator for Xor {
+    fn name(&self) -> &str {
+        "xor"
+    }
+
+    fn texel(&self, u: f64, v: f64) -> u8 {
+        ((u * 256.0) as u32 ^ (v * 256.0) as u32) as u8
+    }
+
+    fn glsl(&self) -> Option<String> {
+        let source = "float texel(vec2 uv) {
+    uvec2 texel = uvec2(uv * 256.0);
+    return float((texel.x ^ texel.y) & 255u);
+}
+";
+        Some(source.to_string())
+    }
+}
+
+pub struct Checkerboard {
+    pub cells: u32,
+}
+
+impl TextureGenerator for Checkerboard {
+    fn name(&self) -> &str {
+        "checkerboard"
+    }
+
+    fn texel(&self, u: f64, v: f64) -> u8 {
+        let cell = (u * self.cells as f64) as u32 + (v * self.cells as f64) as u32;
+        if cell.is_multiple_of(2) {
+            0x30
+        } else {
+            0xd0
+        }
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "float texel(vec2 uv) {{
+    uvec2 cell = uvec2(uv * {:?});
+    return (cell.x + cell.y) % 2u == 0u ? 48.0 : 208.0;
+}}
+",
+            self.cells as f64
+        ))
+    }
+}
+
+pub struct Noise {
+    pub seed: u64,
+    pub period: u32,
+    pub octaves: u32,
+}
+
+pub(crate) fn hash(seed: u64, x: u32, y: u32) -> u64 {
+    let mut h = seed ^ ((x as u64) << 32 | y as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// `hash` for GLSL, which only has 32-bit integers and so can't repeat its numbers.
+const GLSL_HASH: &str = "uint hash(uint seed, uint x, uint y) {
+    uint h = seed ^ (x * 0x9e3779b9u) ^ (y * 0x85ebca6bu);
+    h = (h ^ (h >> 16u)) * 0x7feb352du;
+    h = (h ^ (h >> 15u)) * 0x846ca68bu;
+    return h ^ (h >> 16u);
+}
+";
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+impl Noise {
+    fn gradient(&self, x: u32, y: u32, period: u32) -> (f64, f64) {
+        let angle = (hash(self.seed, x % period, y % period) >> 11) as f64 / (1u64 << 53) as f64;
+        let angle = angle * 2.0 * PI;
+        (angle.cos(), angle.sin())
+    }
+
+    fn perlin(&self, x: f64, y: f64, period: u32) -> f64 {
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let (fx, fy) = (x - x0, y - y0);
+        let (ix, iy) = (x0 as u32, y0 as u32);
+
+        let dot = |cx: u32, cy: u32, dx: f64, dy: f64| {
+            let (gx, gy) = self.gradient(ix + cx, iy + cy, period);
+            gx * dx + gy * dy
+        };
+        let n00 = dot(0, 0, fx, fy);
+        let n10 = dot(1, 0, fx - 1.0, fy);
+        let n01 = dot(0, 1, fx, fy - 1.0);
+        let n11 = dot(1, 1, fx - 1.0, fy - 1.0);
+
+        let (sx, sy) = (fade(fx), fade(fy));
+        let top = n00 + sx * (n10 - n00);
+        let bottom = n01 + sx * (n11 - n01);
+        top + sy * (bottom - top)
+    }
+}
+
+impl TextureGenerator for Noise {
+    fn name(&self) -> &str {
+        "noise"
+    }
+
+    fn texel(&self, u: f64, v: f64) -> u8 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut period = self.period.max(1);
+        for _ in 0..self.octaves.max(1) {
+            let p = period as f64;
+            sum += amplitude * self.perlin(u * p, v * p, period);
+            total += amplitude;
+            amplitude *= 0.5;
+            period *= 2;
+        }
+        let value = 0.5 + sum / total;
+        (value.clamp(0.0, 1.0) * 255.0) as u8
+    }
+
+    fn glsl(&self) -> Option<String> {
+        let seed = (self.seed ^ self.seed >> 32) as u32;
+        Some(format!(
+            "{}
+vec2 gradient(uvec2 cell, uint period) {{
+    float turn = float(hash({}u, cell.x % period, cell.y % period) >> 8u) / 16777216.0;
+    return vec2(cos(2.0 * PI * turn), sin(2.0 * PI * turn));
+}}
+
+float fade(float t) {{
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}}
+
+float perlin(vec2 p, uint period) {{
+    vec2 f = fract(p);
+    uvec2 i = uvec2(floor(p));
+    float n00 = dot(gradient(i, period), f);
+    float n10 = dot(gradient(i + uvec2(1u, 0u), period), f - vec2(1.0, 0.0));
+    float n01 = dot(gradient(i + uvec2(0u, 1u), period), f - vec2(0.0, 1.0));
+    float n11 = dot(gradient(i + uvec2(1u, 1u), period), f - vec2(1.0, 1.0));
+    vec2 s = vec2(fade(f.x), fade(f.y));
+    return mix(mix(n00, n10, s.x), mix(n01, n11, s.x), s.y);
+}}
+
+float texel(vec2 uv) {{
+    float sum = 0.0;
+    float amplitude = 1.0;
+    float total = 0.0;
+    uint period = {}u;
+    for (int octave = 0; octave < {}; octave++) {{
+        sum += amplitude * perlin(uv * float(period), period);
+        total += amplitude;
+        amplitude *= 0.5;
+        period *= 2u;
+    }}
+    return floor(clamp(0.5 + sum / total, 0.0, 1.0) * 255.0);
+}}
+",
+            GLSL_HASH,
+            seed,
+            self.period.max(1),
+            self.octaves.max(1)
+        ))
+    }
+}
+
+/// Fractal gradient noise in three dimensions, sliced at a depth `z` that can move with time so
+/// that the walls undulate. Gradient rather than simplex noise, so that the slices tile like
+/// `Noise` does.
+pub struct Noise3d {
+    pub seed: u64,
+    pub period: u32,
+    pub octaves: u32,
+}
+
+/// Directions to the middles of a cube's edges, the gradients of classic 3D Perlin noise.
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+impl Noise3d {
+    /// The slice at depth `z`, its rows computed in parallel on the rayon pool.
+    pub fn generate(&self, width: usize, height: usize, z: f64) -> Texture {
+        let mut texels = vec![0u32; width * height];
+        texels
+            .par_chunks_exact_mut(width.max(1))
+            .enumerate()
+            .for_each(|(y, row)| {
+                let v = y as f64 / height as f64;
+                for (x, texel) in row.iter_mut().enumerate() {
+                    *texel = self.texel(x as f64 / width as f64, v, z) as u32;
+                }
+            });
+        Texture {
+            width,
+            height,
+            format: TextureFormat::Indexed,
+            texels,
+        }
+    }
+
+    fn texel(&self, u: f64, v: f64, z: f64) -> u8 {
+        let mut sum = 0.0;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut period = self.period.max(1);
+        for octave in 0..self.octaves.max(1) {
+            let p = period as f64;
+            // Each octave drifts through its own part of the volume.
+            let z = z * (1 << octave) as f64 + octave as f64 * 17.0;
+            sum += amplitude * self.perlin(u * p, v * p, z, period);
+            total += amplitude;
+            amplitude *= 0.5;
+            period *= 2;
+        }
+        // 3D gradient noise spans about ±1 rather than ±0.7, so it is squeezed a bit more.
+        let value = 0.5 + 0.7 * sum / total;
+        (value.clamp(0.0, 1.0) * 255.0) as u8
+    }
+
+    fn gradient(&self, x: u32, y: u32, z: i64, period: u32) -> (f64, f64, f64) {
+        let seed = self.seed ^ (z as u64).wrapping_mul(0xd6e8_feb8_6659_fd93);
+        GRADIENTS_3D[(hash(seed, x % period, y % period) % 12) as usize]
+    }
+
+    fn perlin(&self, x: f64, y: f64, z: f64, period: u32) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (ix, iy, iz) = (x0 as u32, y0 as u32, z0 as i64);
+
+        let dot = |cx: u32, cy: u32, cz: i64| {
+            let (gx, gy, gz) = self.gradient(ix + cx, iy + cy, iz + cz, period);
+            gx * (fx - cx as f64) + gy * (fy - cy as f64) + gz * (fz - cz as f64)
+        };
+        let (sx, sy, sz) = (fade(fx), fade(fy), fade(fz));
+        let lerp = |a: f64, b: f64, t: f64| a + t * (b - a);
+        let layer = |cz: i64| {
+            let top = lerp(dot(0, 0, cz), dot(1, 0, cz), sx);
+            let bottom = lerp(dot(0, 1, cz), dot(1, 1, cz), sx);
+            lerp(top, bottom, sy)
+        };
+        lerp(layer(0), layer(1), sz)
+    }
+}
+
+pub struct Brick {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl TextureGenerator for Brick {
+    fn name(&self) -> &str {
+        "brick"
+    }
+
+    fn texel(&self, u: f64, v: f64) -> u8 {
+        let row_f = v * self.rows as f64;
+        let row = row_f as u32;
+        let offset = if row.is_multiple_of(2) { 0.0 } else { 0.5 };
+        let column_f = u * self.columns as f64 + offset;
+        let column = column_f as u32 % self.columns;
+
+        let (fu, fv) = (column_f.fract(), row_f.fract());
+        if fu < 0.06 || fv < 0.12 {
+            return 0x18;
+        }
+
+        let shade = (hash(0, column, row) % 0x40) as u8;
+        0x80 + shade + (fv * 0x20 as f64) as u8
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "{}
+float texel(vec2 uv) {{
+    float rowF = uv.y * {:?};
+    uint row = uint(rowF);
+    float columnF = uv.x * {:?} + (row % 2u == 0u ? 0.0 : 0.5);
+    uint column = uint(columnF) % {}u;
+    vec2 f = fract(vec2(columnF, rowF));
+    if (f.x < 0.06 || f.y < 0.12) {{
+        return 24.0;
+    }}
+    return 128.0 + float(hash(0u, column, row) % 64u) + floor(f.y * 32.0);
+}}
+",
+            GLSL_HASH, self.rows as f64, self.columns as f64, self.columns
+        ))
+    }
+}
+
+pub struct Spiral {
+    pub bands: u32,
+    pub turns: u32,
+}
+
+impl TextureGenerator for Spiral {
+    fn name(&self) -> &str {
+        "spiral"
+    }
+
+    fn texel(&self, u: f64, v: f64) -> u8 {
+        let phase = (u * self.bands as f64 + v * self.turns as f64).fract();
+        (phase * 256.0) as u8
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "float texel(vec2 uv) {{
+    return floor(fract(uv.x * {:?} + uv.y * {:?}) * 256.0);
+}}
+",
+            self.bands as f64, self.turns as f64
+        ))
+    }
+}
+
+pub struct Stripes {
+    pub count: u32,
+}
+
+impl TextureGenerator for Stripes {
+    fn name(&self) -> &str {
+        "stripes"
+    }
+
+    fn texel(&self, u: f64, _v: f64) -> u8 {
+        let wave = (2.0 * PI * u * self.count as f64).sin();
+        (127.5 + 127.5 * wave) as u8
+    }
+
+    fn glsl(&self) -> Option<String> {
+        Some(format!(
+            "float texel(vec2 uv) {{
+    return floor(127.5 + 127.5 * sin(2.0 * PI * uv.x * {:?}));
+}}
+",
+            self.count as f64
+        ))
+    }
+}
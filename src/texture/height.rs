@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use image::ImageResult;
+
+use super::{AddressMode, Texture, TextureFormat};
+use crate::color;
+
+/// How high every point of a wall is, from 0 to 255, for bump mapping. It is stretched over
+/// the texture, so it can have a size of its own.
+#[derive(Clone)]
+pub struct HeightMap {
+    pub width: usize,
+    pub height: usize,
+    pub heights: Vec<u8>,
+}
+
+impl HeightMap {
+    /// Reads the heights off a texture: indexed texels by their value, colored ones by their
+    /// luma, so that bright parts stand out of the wall.
+    pub fn from_texture(texture: &Texture) -> Self {
+        let heights = texture
+            .texels
+            .iter()
+            .map(|&texel| match texture.format {
+                TextureFormat::Indexed => texel as u8,
+                TextureFormat::Rgba => {
+                    let [r, g, b, _] = color::unpack(texel);
+                    ((r as u32 * 54 + g as u32 * 183 + b as u32 * 19) >> 8) as u8
+                }
+            })
+            .collect();
+        Self {
+            width: texture.width,
+            height: texture.height,
+            heights,
+        }
+    }
+
+    /// Loads a grayscale (or colored, read as its luma) image at its own size.
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(path)?.into_luma8();
+        Ok(Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            heights: image.into_raw(),
+        })
+    }
+
+    /// How steeply the wall rises at texel `x`, `y` of a texture `size` texels across, along
+    /// and around the tunnel: the difference between the heights on either side, from -255
+    /// to 255.
+    pub(crate) fn slope(
+        &self,
+        x: usize,
+        y: usize,
+        size: (usize, usize),
+        address: (AddressMode, AddressMode),
+    ) -> (i32, i32) {
+        let x = x * self.width / size.0;
+        let y = y * self.height / size.1;
+        let at = |x: usize, y: usize| {
+            let x = address.0.wrap(x, self.width);
+            let y = address.1.wrap(y, self.height);
+            self.heights[y * self.width + x] as i32
+        };
+        let (left, up) = (
+            before(x, address.0, self.width),
+            before(y, address.1, self.height),
+        );
+        (at(x + 1, y) - at(left, y), at(x, y + 1) - at(x, up))
+    }
+}
+
+/// The coordinate before `coord`, which for the first texel is the last one of the period
+/// the address mode repeats over, or the edge itself when it clamps.
+fn before(coord: usize, address: AddressMode, size: usize) -> usize {
+    match (coord, address) {
+        (0, AddressMode::ClampToEdge) => 0,
+        (0, _) => address.period(size) - 1,
+        _ => coord - 1,
+    }
+}
@@ -0,0 +1,173 @@
+use std::path::Path;
+use std::str::FromStr;
+
+use image::ImageResult;
+
+use crate::color;
+
+mod generator;
+mod height;
+mod stamp;
+
+pub(crate) use generator::hash;
+pub use generator::{
+    builtin, Brick, Checkerboard, Noise, Noise3d, Spiral, Stripes, TextureGenerator, Xor,
+};
+pub use height::HeightMap;
+pub use stamp::Stamp;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    Indexed,
+    Rgba,
+}
+
+/// How texel coordinates outside the texture map back into it, like a GPU sampler's address
+/// mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressMode {
+    /// Tiles the texture.
+    Repeat,
+    /// Tiles the texture, flipping every other copy so that opposite edges meet.
+    MirroredRepeat,
+    /// Repeats the edge texels.
+    ClampToEdge,
+}
+
+impl AddressMode {
+    pub const ALL: [AddressMode; 3] = [
+        AddressMode::Repeat,
+        AddressMode::MirroredRepeat,
+        AddressMode::ClampToEdge,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AddressMode::Repeat => "repeat",
+            AddressMode::MirroredRepeat => "mirror",
+            AddressMode::ClampToEdge => "clamp",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            AddressMode::Repeat => AddressMode::MirroredRepeat,
+            AddressMode::MirroredRepeat => AddressMode::ClampToEdge,
+            AddressMode::ClampToEdge => AddressMode::Repeat,
+        }
+    }
+
+    /// After how many texels the mapping repeats itself.
+    pub fn period(self, size: usize) -> usize {
+        match self {
+            AddressMode::MirroredRepeat => size * 2,
+            AddressMode::Repeat | AddressMode::ClampToEdge => size,
+        }
+    }
+
+    /// The texel `coord` maps to in a texture `size` texels across.
+    pub fn wrap(self, coord: usize, size: usize) -> usize {
+        match self {
+            AddressMode::Repeat => coord % size,
+            AddressMode::MirroredRepeat => {
+                let coord = coord % (size * 2);
+                coord.min(size * 2 - 1 - coord)
+            }
+            AddressMode::ClampToEdge => coord.min(size - 1),
+        }
+    }
+}
+
+impl FromStr for AddressMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|mode| mode.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|mode| mode.name()).collect();
+                format!(
+                    "unknown address mode `{}` (expected one of {})",
+                    s,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Clone)]
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub format: TextureFormat,
+    pub texels: Vec<u32>,
+}
+
+impl Texture {
+    pub fn xor(width: usize, height: usize) -> Self {
+        Xor.generate(width, height)
+    }
+
+    /// Loads an image at its own size; any size can be sampled.
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(path)?.into_rgba8();
+        Ok(Self::from_rgba(
+            image.width() as usize,
+            image.height() as usize,
+            image.as_raw(),
+        ))
+    }
+
+    /// A copy whose last `rows` rows fade into a mirror image of the first ones, so that the
+    /// bottom edge runs into the top one without a seam where the texture wraps around the
+    /// tunnel. Indexed texels are interpolated as indices, which suits the gradient palettes.
+    pub fn blend_seam(&self, rows: usize) -> Self {
+        let rows = rows.min(self.height / 2);
+        let mut texels = self.texels.clone();
+        for i in 0..rows {
+            // Row `height - 1 - i` moves towards row `i`, all the way for the last one.
+            let weight = ((rows - i) * 256 / (rows + 1)) as u32;
+            let target = (self.height - 1 - i) * self.width;
+            let source = i * self.width;
+            for x in 0..self.width {
+                let (from, to) = (self.texels[target + x], self.texels[source + x]);
+                texels[target + x] = match self.format {
+                    TextureFormat::Indexed => lerp(from, to, weight),
+                    TextureFormat::Rgba => {
+                        let (from, to) = (color::unpack(from), color::unpack(to));
+                        color::pack(std::array::from_fn(|c| {
+                            lerp(from[c] as u32, to[c] as u32, weight) as u8
+                        }))
+                    }
+                };
+            }
+        }
+        Self {
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            texels,
+        }
+    }
+
+    pub fn from_rgba(width: usize, height: usize, rgba: &[u8]) -> Self {
+        assert_eq!(rgba.len(), width * height * 4, "texture size mismatch");
+        let texels = rgba
+            .chunks_exact(4)
+            .map(|texel| color::opaque([texel[0], texel[1], texel[2], texel[3]]))
+            .collect();
+
+        Self {
+            width,
+            height,
+            format: TextureFormat::Rgba,
+            texels,
+        }
+    }
+}
+
+/// `from` moved `weight / 256` of the way towards `to`.
+fn lerp(from: u32, to: u32, weight: u32) -> u32 {
+    (from * (256 - weight) + to * weight) >> 8
+}
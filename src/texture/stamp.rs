@@ -0,0 +1,193 @@
+use std::path::Path;
+
+use fontdue::{Font, FontSettings};
+use image::{ImageResult, RgbaImage};
+
+use super::{lerp, Texture, TextureFormat};
+use crate::color;
+use crate::font;
+
+/// Pixels high text is drawn at before being scaled onto the texture.
+const TEXT_HEIGHT: f32 = 64.0;
+/// Scale of the built-in font when no other is given, for about the same height.
+const BITMAP_SCALE: usize = 8;
+
+/// An image put onto the walls, like a logo or a greeting, where it scrolls past with the
+/// texture. It is centred `along` the texture's length and `around` its height (both shares
+/// from 0 to 1) and scaled to `size` of its height, wrapping over the edges as the texture
+/// does.
+#[derive(Clone)]
+pub struct Stamp {
+    pub image: RgbaImage,
+    pub along: f64,
+    pub around: f64,
+    pub size: f64,
+}
+
+impl Stamp {
+    /// `image` on the middle of the texture, a quarter of its height.
+    pub fn new(image: RgbaImage) -> Self {
+        Self {
+            image,
+            along: 0.5,
+            around: 0.5,
+            size: 0.25,
+        }
+    }
+
+    /// Loads an image; its transparent parts leave the texture showing.
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        Ok(Self::new(image::open(path)?.into_rgba8()))
+    }
+
+    /// White `text` on one line, drawn with the TrueType or OpenType font in `font` or, without
+    /// one, the built-in one.
+    pub fn text(text: &str, font: Option<&[u8]>) -> Result<Self, String> {
+        let image = match font {
+            Some(data) => {
+                let font = Font::from_bytes(data, FontSettings::default())?;
+                outline_text(&font, text)
+            }
+            None => bitmap_text(text),
+        };
+        Ok(Self::new(image))
+    }
+
+    /// A copy of `texture` with the image on it. Indexed textures take the brightness of the
+    /// image for the index, blended like `Texture::blend_seam` does, so that it is colored by
+    /// the palette.
+    pub fn apply(&self, texture: &Texture) -> Texture {
+        let (width, height) = (texture.width, texture.height);
+        let mut texels = texture.texels.clone();
+        if self.image.width() == 0 || self.image.height() == 0 {
+            return Texture { texels, ..*texture };
+        }
+
+        // As high as asked, unless that makes it longer than the texture.
+        let aspect = self.image.width() as f64 / self.image.height() as f64;
+        let stamp_height = (self.size.clamp(0.0, 1.0) * height as f64).min(width as f64 / aspect);
+        let stamp_width = ((stamp_height * aspect).round() as usize).min(width);
+        let stamp_height = stamp_height.round() as usize;
+        let left = (self.along.rem_euclid(1.0) * width as f64) as usize + width - stamp_width / 2;
+        let top =
+            (self.around.rem_euclid(1.0) * height as f64) as usize + height - stamp_height / 2;
+        let scale = (
+            self.image.width() as f64 / stamp_width.max(1) as f64,
+            self.image.height() as f64 / stamp_height.max(1) as f64,
+        );
+        for y in 0..stamp_height {
+            for x in 0..stamp_width {
+                let [r, g, b, a] =
+                    self.sample((x as f64 + 0.5) * scale.0, (y as f64 + 0.5) * scale.1);
+                if a == 0 {
+                    continue;
+                }
+                let texel = &mut texels[(top + y) % height * width + (left + x) % width];
+                let weight = a as u32 * 256 / 255;
+                *texel = match texture.format {
+                    TextureFormat::Indexed => {
+                        let index = (r as u32 * 54 + g as u32 * 183 + b as u32 * 19) >> 8;
+                        lerp(*texel, index, weight)
+                    }
+                    TextureFormat::Rgba => {
+                        let under = color::unpack(*texel);
+                        let over = [r, g, b];
+                        color::pack(std::array::from_fn(|c| match c {
+                            3 => under[3],
+                            _ => lerp(under[c] as u32, over[c] as u32, weight) as u8,
+                        }))
+                    }
+                };
+            }
+        }
+        Texture { texels, ..*texture }
+    }
+
+    /// The image's color at `x`, `y` in its pixels, blended between the four nearest with
+    /// their colors weighted by their alpha, so that transparent pixels don't darken edges.
+    fn sample(&self, x: f64, y: f64) -> [u8; 4] {
+        let (width, height) = (self.image.width() as i64, self.image.height() as i64);
+        let (x, y) = (x - 0.5, y - 0.5);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let mut sum = [0.0; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let px = (x0 as i64 + dx).clamp(0, width - 1) as u32;
+            let py = (y0 as i64 + dy).clamp(0, height - 1) as u32;
+            let [r, g, b, a] = self.image.get_pixel(px, py).0;
+            let alpha = a as f64 * weight;
+            for (sum, value) in sum.iter_mut().zip([r, g, b]) {
+                *sum += value as f64 * alpha;
+            }
+            sum[3] += alpha;
+        }
+        if sum[3] == 0.0 {
+            return [0; 4];
+        }
+        let alpha = sum[3];
+        [
+            (sum[0] / alpha).round() as u8,
+            (sum[1] / alpha).round() as u8,
+            (sum[2] / alpha).round() as u8,
+            alpha.round() as u8,
+        ]
+    }
+}
+
+fn outline_text(font: &Font, text: &str) -> RgbaImage {
+    let (ascent, descent) = font
+        .horizontal_line_metrics(TEXT_HEIGHT)
+        .map_or((TEXT_HEIGHT, 0.0), |line| (line.ascent, line.descent));
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        if let Some(previous) = previous {
+            x += font
+                .horizontal_kern(previous, c, TEXT_HEIGHT)
+                .unwrap_or(0.0);
+        }
+        let (metrics, coverage) = font.rasterize(c, TEXT_HEIGHT);
+        glyphs.push((x, metrics, coverage));
+        x += metrics.advance_width;
+        previous = Some(c);
+    }
+    let width = x.ceil() as u32;
+    let height = (ascent - descent).ceil() as u32;
+    let mut image = RgbaImage::new(width.max(1), height.max(1));
+    for (pen, metrics, coverage) in glyphs {
+        // The bitmap's bottom row sits `ymin` above the baseline.
+        let left = pen.round() as i32 + metrics.xmin;
+        let top = ascent.round() as i32 - metrics.ymin - metrics.height as i32;
+        for (i, &alpha) in coverage.iter().enumerate() {
+            let x = left + (i % metrics.width) as i32;
+            let y = top + (i / metrics.width) as i32;
+            if (0..width as i32).contains(&x) && (0..height as i32).contains(&y) {
+                let pixel = image.get_pixel_mut(x as u32, y as u32);
+                *pixel = image::Rgba([0xff, 0xff, 0xff, pixel.0[3].max(alpha)]);
+            }
+        }
+    }
+    image
+}
+
+fn bitmap_text(text: &str) -> RgbaImage {
+    let line = text.lines().next().unwrap_or_default();
+    let width = font::text_width(line, BITMAP_SCALE).max(1);
+    let height = font::line_height(BITMAP_SCALE);
+    let mut frame = vec![0u8; width * height * 4];
+    font::draw_text(
+        &mut frame,
+        width,
+        (0, BITMAP_SCALE),
+        BITMAP_SCALE,
+        [0xff; 4],
+        line,
+    );
+    RgbaImage::from_raw(width as u32, height as u32, frame).expect("frame has the image size")
+}
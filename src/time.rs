@@ -0,0 +1,89 @@
+//! Wall-clock time that also works in the browser, where `std::time::Instant` and
+//! `SystemTime` are unavailable and the Performance API is used instead.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub use web::Instant;
+
+/// Milliseconds since the Unix epoch, used to name recordings and screenshots.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn timestamp() -> u64 {
+    use std::time::SystemTime;
+
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Milliseconds since the Unix epoch, used to name recordings and screenshots.
+#[cfg(target_arch = "wasm32")]
+pub fn timestamp() -> u64 {
+    web::performance().map_or(0, |performance| {
+        (performance.time_origin() + performance.now()) as u64
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::ops::{Add, Sub};
+    use std::time::Duration;
+
+    use web_sys::Performance;
+
+    pub(super) fn performance() -> Option<Performance> {
+        web_sys::window().and_then(|window| window.performance())
+    }
+
+    /// A monotonic point in time backed by `performance.now()`.
+    #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+    pub struct Instant {
+        millis: f64,
+    }
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self {
+                millis: performance().map_or(0.0, |performance| performance.now()),
+            }
+        }
+
+        pub fn duration_since(&self, earlier: Instant) -> Duration {
+            Duration::from_secs_f64((self.millis - earlier.millis).max(0.0) / 1000.0)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Self::now().duration_since(*self)
+        }
+    }
+
+    impl Sub for Instant {
+        type Output = Duration;
+
+        fn sub(self, earlier: Instant) -> Duration {
+            self.duration_since(earlier)
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+
+        fn sub(self, duration: Duration) -> Instant {
+            Instant {
+                millis: self.millis - duration.as_secs_f64() * 1000.0,
+            }
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            Instant {
+                millis: self.millis + duration.as_secs_f64() * 1000.0,
+            }
+        }
+    }
+}
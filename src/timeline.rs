@@ -0,0 +1,346 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::effect;
+use crate::fog::FogCurve;
+use crate::geometry::{self, TunnelGeometry};
+use crate::palette::PaletteKind;
+use crate::texture::{self, Texture};
+
+const DEFAULT_TEXTURE_SIZE: usize = 256;
+
+#[derive(Debug)]
+pub enum TimelineError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Texture(image::ImageError),
+    Invalid(String),
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimelineError::Io(err) => write!(f, "{}", err),
+            TimelineError::Parse(err) => write!(f, "{}", err),
+            TimelineError::Texture(err) => write!(f, "{}", err),
+            TimelineError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for TimelineError {}
+
+/// Number of fields in `Params`.
+const PARAMS: usize = 11;
+
+/// Continuous scene parameters; unset ones keep whatever the world would use otherwise.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+pub struct Params {
+    /// Tunnel radius ratio the breathing modulator oscillates around.
+    pub ratio: Option<f64>,
+    /// Travel speed in texture lengths per second.
+    pub speed: Option<f64>,
+    pub palette_speed: Option<f64>,
+    pub fog_start: Option<f64>,
+    pub fog_end: Option<f64>,
+    pub fog_density: Option<f64>,
+    /// Turns around the axis added to the light's orbit.
+    pub light_angle: Option<f64>,
+    pub light_depth: Option<f64>,
+    pub light_intensity: Option<f64>,
+    /// How far the far end of the tunnel drifts along the curve.
+    pub curve: Option<f64>,
+    /// Turns added to the view's roll.
+    pub roll: Option<f64>,
+}
+
+impl Params {
+    fn fields(&self) -> [Option<f64>; PARAMS] {
+        [
+            self.ratio,
+            self.speed,
+            self.palette_speed,
+            self.fog_start,
+            self.fog_end,
+            self.fog_density,
+            self.light_angle,
+            self.light_depth,
+            self.light_intensity,
+            self.curve,
+            self.roll,
+        ]
+    }
+
+    fn from_fields(fields: [Option<f64>; PARAMS]) -> Self {
+        Self {
+            ratio: fields[0],
+            speed: fields[1],
+            palette_speed: fields[2],
+            fog_start: fields[3],
+            fog_end: fields[4],
+            fog_density: fields[5],
+            light_angle: fields[6],
+            light_depth: fields[7],
+            light_intensity: fields[8],
+            curve: fields[9],
+            roll: fields[10],
+        }
+    }
+
+    fn lerp(&self, to: &Params, t: f64) -> Params {
+        let from = self.fields();
+        let to = to.fields();
+        Params::from_fields(std::array::from_fn(|i| match (from[i], to[i]) {
+            (Some(a), Some(b)) => Some(a + (b - a) * t),
+            (_, b) => b,
+        }))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Keyframe {
+    /// Seconds since the start of the scene.
+    pub time: f64,
+    #[serde(flatten)]
+    pub params: Params,
+}
+
+/// What a scene does to the sine scroller: `true` or `false` shows or hides it, and a
+/// message shows it running that from the start.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ScrollerSpec {
+    Shown(bool),
+    Message(String),
+}
+
+/// What a scene does to the sine scroller.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScrollerCue {
+    Show,
+    Hide,
+    Message(String),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneSpec {
+    duration: f64,
+    #[serde(default)]
+    transition: f64,
+    effect: Option<String>,
+    texture: Option<String>,
+    palette: Option<String>,
+    geometry: Option<String>,
+    fog: Option<String>,
+    scroller: Option<ScrollerSpec>,
+    #[serde(default, rename = "keyframe")]
+    keyframes: Vec<Keyframe>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TimelineSpec {
+    #[serde(default)]
+    seed: u64,
+    texture_size: Option<usize>,
+    #[serde(default, rename = "repeat")]
+    repeats: bool,
+    #[serde(rename = "scene")]
+    scenes: Vec<SceneSpec>,
+}
+
+pub struct Scene {
+    pub start: f64,
+    pub duration: f64,
+    /// Seconds over which parameters blend in from the end of the previous scene, and over
+    /// which its texture, palette and geometry crossfade into this one's.
+    pub transition: f64,
+    /// Name of the effect to switch to, e.g. `plasma`; applying it is up to the caller.
+    pub effect: Option<String>,
+    pub texture: Option<Arc<Texture>>,
+    pub palette: Option<PaletteKind>,
+    pub geometry: Option<Arc<dyn TunnelGeometry>>,
+    pub fog: Option<FogCurve>,
+    /// Applied to the pipeline's scroller once the scene starts.
+    pub scroller: Option<ScrollerCue>,
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Scene {
+    pub fn params(&self, time: f64) -> Params {
+        let mut fields = [None; PARAMS];
+        for (i, field) in fields.iter_mut().enumerate() {
+            let mut keys = self
+                .keyframes
+                .iter()
+                .filter_map(|key| key.params.fields()[i].map(|value| (key.time, value)));
+            let Some(mut previous) = keys.next() else {
+                continue;
+            };
+            *field = Some(previous.1);
+            for (key_time, value) in keys {
+                if key_time <= time {
+                    *field = Some(value);
+                } else {
+                    let span = key_time - previous.0;
+                    let t = if span > 0.0 {
+                        ((time - previous.0) / span).clamp(0.0, 1.0)
+                    } else {
+                        1.0
+                    };
+                    *field = Some(previous.1 + (value - previous.1) * t);
+                    break;
+                }
+                previous = (key_time, value);
+            }
+        }
+        Params::from_fields(fields)
+    }
+}
+
+/// A sequence of scenes played back against the world clock.
+pub struct Timeline {
+    scenes: Vec<Scene>,
+    repeats: bool,
+}
+
+impl Timeline {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, TimelineError> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path).map_err(TimelineError::Io)?;
+        Self::parse(&source, path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// Parses a TOML script; image textures are resolved relative to `base`.
+    pub fn parse(source: &str, base: &Path) -> Result<Self, TimelineError> {
+        let spec: TimelineSpec = toml::from_str(source).map_err(TimelineError::Parse)?;
+        let size = spec.texture_size.unwrap_or(DEFAULT_TEXTURE_SIZE);
+        let generators = texture::builtin(spec.seed);
+        let geometries = geometry::builtin();
+        let effects = effect::builtin();
+
+        let mut start = 0.0;
+        let mut scenes = Vec::with_capacity(spec.scenes.len());
+        for scene in spec.scenes {
+            if scene.duration <= 0.0 {
+                return Err(TimelineError::Invalid(format!(
+                    "scene at {}s must have a positive duration",
+                    start
+                )));
+            }
+
+            if let Some(name) = &scene.effect {
+                if !effects.iter().any(|effect| effect.name() == name) {
+                    return Err(TimelineError::Invalid(format!("unknown effect `{}`", name)));
+                }
+            }
+
+            let texture = match scene.texture {
+                Some(name) => Some(Arc::new(
+                    match generators.iter().find(|generator| generator.name() == name) {
+                        Some(generator) => generator.generate(size, size),
+                        None => Texture::load(base.join(&name)).map_err(TimelineError::Texture)?,
+                    },
+                )),
+                None => None,
+            };
+            let palette = scene
+                .palette
+                .map(|name| name.parse())
+                .transpose()
+                .map_err(TimelineError::Invalid)?;
+            let geometry = scene
+                .geometry
+                .map(|name| {
+                    geometries
+                        .iter()
+                        .find(|geometry| geometry.name() == name)
+                        .cloned()
+                        .ok_or_else(|| format!("unknown geometry `{}`", name))
+                })
+                .transpose()
+                .map_err(TimelineError::Invalid)?;
+            let fog = scene
+                .fog
+                .map(|name| name.parse())
+                .transpose()
+                .map_err(TimelineError::Invalid)?;
+
+            let mut keyframes = scene.keyframes;
+            keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+            scenes.push(Scene {
+                start,
+                duration: scene.duration,
+                transition: scene.transition.max(0.0),
+                effect: scene.effect,
+                texture,
+                palette,
+                geometry,
+                fog,
+                scroller: scene.scroller.map(|spec| match spec {
+                    ScrollerSpec::Shown(true) => ScrollerCue::Show,
+                    ScrollerSpec::Shown(false) => ScrollerCue::Hide,
+                    ScrollerSpec::Message(message) => ScrollerCue::Message(message),
+                }),
+                keyframes,
+            });
+            start += scene.duration;
+        }
+
+        if scenes.is_empty() {
+            return Err(TimelineError::Invalid("timeline has no scenes".to_string()));
+        }
+
+        Ok(Self {
+            scenes,
+            repeats: spec.repeats,
+        })
+    }
+
+    pub fn scenes(&self) -> &[Scene] {
+        &self.scenes
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.scenes
+            .last()
+            .map_or(0.0, |scene| scene.start + scene.duration)
+    }
+
+    pub fn repeats(&self) -> bool {
+        self.repeats
+    }
+
+    /// Returns the index of the scene playing at `time` and its interpolated parameters.
+    /// Past the end, the last scene holds its final values unless the timeline repeats.
+    pub fn sample(&self, time: f64) -> (usize, Params) {
+        let time = if self.repeats {
+            time.rem_euclid(self.duration())
+        } else {
+            time.clamp(0.0, self.duration())
+        };
+        let index = self
+            .scenes
+            .iter()
+            .rposition(|scene| scene.start <= time)
+            .unwrap_or(0);
+        let scene = &self.scenes[index];
+        let local = time - scene.start;
+        let params = scene.params(local);
+
+        if index > 0 && local < scene.transition {
+            let previous = &self.scenes[index - 1];
+            let from = previous.params(previous.duration);
+            (index, from.lerp(&params, local / scene.transition))
+        } else {
+            (index, params)
+        }
+    }
+}
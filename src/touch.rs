@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use tunnel::time::Instant;
+use winit::event::{TouchPhase, WindowEvent};
+
+/// How long and how far (in window pixels) a finger may rest on the screen for a tap, and
+/// how soon and how close the second tap of a double tap has to follow the first.
+const TAP_TIME: Duration = Duration::from_millis(250);
+const TAP_SLOP: f64 = 16.0;
+const DOUBLE_TAP_TIME: Duration = Duration::from_millis(350);
+const DOUBLE_TAP_SLOP: f64 = 48.0;
+
+/// What the fingers did since the last `Touches::take`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gesture {
+    /// How far a single finger dragged, in window pixels.
+    pub drag: (f64, f64),
+    /// How many times farther apart two fingers are than they were; above 1 they spread.
+    pub pinch: f64,
+    pub double_tap: bool,
+}
+
+impl Default for Gesture {
+    fn default() -> Self {
+        Self {
+            drag: (0.0, 0.0),
+            pinch: 1.0,
+            double_tap: false,
+        }
+    }
+}
+
+struct Finger {
+    id: u64,
+    position: (f64, f64),
+    start: (f64, f64),
+    since: Instant,
+}
+
+/// Follows the fingers on a touch screen and turns what they do into gestures.
+#[derive(Default)]
+pub struct Touches {
+    fingers: Vec<Finger>,
+    gesture: Gesture,
+    last_tap: Option<(Instant, (f64, f64))>,
+}
+
+impl Touches {
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        let WindowEvent::Touch(touch) = event else {
+            return;
+        };
+        let position = (touch.location.x, touch.location.y);
+        match touch.phase {
+            TouchPhase::Started => self.fingers.push(Finger {
+                id: touch.id,
+                position,
+                start: position,
+                since: Instant::now(),
+            }),
+            TouchPhase::Moved => {
+                let before = self.spread();
+                let single = self.fingers.len() == 1;
+                if let Some(finger) = self.fingers.iter_mut().find(|f| f.id == touch.id) {
+                    if single {
+                        self.gesture.drag.0 += position.0 - finger.position.0;
+                        self.gesture.drag.1 += position.1 - finger.position.1;
+                    }
+                    finger.position = position;
+                }
+                if let (Some(before), Some(after)) = (before, self.spread()) {
+                    if before > 0.0 && after > 0.0 {
+                        self.gesture.pinch *= after / before;
+                    }
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let Some(index) = self.fingers.iter().position(|f| f.id == touch.id) else {
+                    return;
+                };
+                let finger = self.fingers.remove(index);
+                let tapped = touch.phase == TouchPhase::Ended
+                    && self.fingers.is_empty()
+                    && finger.since.elapsed() < TAP_TIME
+                    && distance(finger.start, position) < TAP_SLOP;
+                if tapped {
+                    self.tap(position);
+                }
+            }
+        }
+    }
+
+    /// The gesture made since the last call.
+    pub fn take(&mut self) -> Gesture {
+        std::mem::take(&mut self.gesture)
+    }
+
+    fn tap(&mut self, position: (f64, f64)) {
+        let double = self.last_tap.is_some_and(|(time, at)| {
+            time.elapsed() < DOUBLE_TAP_TIME && distance(at, position) < DOUBLE_TAP_SLOP
+        });
+        if double {
+            self.gesture.double_tap = true;
+            self.last_tap = None;
+        } else {
+            self.last_tap = Some((Instant::now(), position));
+        }
+    }
+
+    /// How far apart the first two fingers are, if there are two.
+    fn spread(&self) -> Option<f64> {
+        match &self.fingers[..] {
+            [first, second, ..] => Some(distance(first.position, second.position)),
+            _ => None,
+        }
+    }
+}
+
+fn distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
@@ -0,0 +1,158 @@
+use egui::epaint::textures::TexturesDelta;
+use egui::epaint::ClippedPrimitive;
+use egui::{ComboBox, Context, Slider};
+use egui_wgpu::renderer::{RenderPass, ScreenDescriptor};
+use pixels::wgpu::{CommandEncoder, TextureView};
+use pixels::{Pixels, PixelsContext};
+use tunnel::{Flight, FogCurve, Modulator, PaletteKind, World};
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::{MAX_RATIO, MAX_TRAVEL, MIN_RATIO};
+
+/// A window of sliders for tweaking the tunnel while it runs, drawn over the frame with egui.
+pub struct Ui {
+    ctx: Context,
+    state: egui_winit::State,
+    render_pass: RenderPass,
+    size: [u32; 2],
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures: TexturesDelta,
+    visible: bool,
+}
+
+impl Ui {
+    pub fn new(window: &Window, pixels: &Pixels) -> Self {
+        let max_texture_side = pixels.device().limits().max_texture_dimension_2d as usize;
+        let size = window.inner_size();
+        Self {
+            ctx: Context::default(),
+            state: egui_winit::State::new(max_texture_side, window),
+            render_pass: RenderPass::new(pixels.device(), pixels.render_texture_format(), 1),
+            size: [size.width, size.height],
+            paint_jobs: Vec::new(),
+            textures: TexturesDelta::default(),
+            visible: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Passes an event of the main window on to egui.
+    pub fn handle_event(&mut self, event: &WindowEvent) {
+        if let WindowEvent::Resized(size) = event {
+            self.size = [size.width, size.height];
+        }
+        self.state.on_event(&self.ctx, event);
+    }
+
+    /// Whether the pointer is over the window or dragging one of its sliders, so it shouldn't
+    /// steer the camera.
+    pub fn wants_pointer(&self) -> bool {
+        self.visible && self.ctx.wants_pointer_input()
+    }
+
+    /// Lays out the window for the next frame, applying what was changed in it to `world`
+    /// and `threads`.
+    pub fn prepare(&mut self, window: &Window, world: &mut World, threads: &mut usize) {
+        if !self.visible {
+            return;
+        }
+        let input = self.state.take_egui_input(window);
+        let output = self.ctx.run(input, |ctx| {
+            egui::Window::new("tunnel-rs").show(ctx, |ui| {
+                let mut speed = world.clock().speed();
+                ui.add(
+                    Slider::new(&mut speed, 0.0625..=16.0)
+                        .logarithmic(true)
+                        .text("speed"),
+                );
+                world.clock_mut().set_speed(speed);
+
+                let flight = *world.flight();
+                let mut travel = flight.travel;
+                ui.add(Slider::new(&mut travel, -MAX_TRAVEL..=MAX_TRAVEL).text("travel"));
+                if travel != flight.travel {
+                    world.set_flight(Flight { travel, ..flight });
+                }
+
+                let modulator = *world.ratio_modulator();
+                let mut ratio = modulator.base;
+                ui.add(
+                    Slider::new(&mut ratio, MIN_RATIO..=MAX_RATIO)
+                        .logarithmic(true)
+                        .text("ratio"),
+                );
+                if ratio != modulator.base {
+                    world.set_ratio_modulator(Modulator {
+                        base: ratio,
+                        ..modulator
+                    });
+                }
+
+                // Each change starts a crossfade, so only actual changes are applied.
+                let mut palette = world.palette_kind();
+                ComboBox::from_label("palette")
+                    .selected_text(palette.name())
+                    .show_ui(ui, |ui| {
+                        for kind in PaletteKind::ALL {
+                            ui.selectable_value(&mut palette, kind, kind.name());
+                        }
+                    });
+                if palette != world.palette_kind() {
+                    world.set_palette(palette);
+                }
+
+                let mut fog = *world.fog();
+                ComboBox::from_label("fog")
+                    .selected_text(fog.curve.name())
+                    .show_ui(ui, |ui| {
+                        for curve in FogCurve::ALL {
+                            ui.selectable_value(&mut fog.curve, curve, curve.name());
+                        }
+                    });
+                ui.add(Slider::new(&mut fog.start, 0.0..=16.0).text("fog start"));
+                ui.add(Slider::new(&mut fog.end, 1.0..=32.0).text("fog end"));
+                ui.add(Slider::new(&mut fog.density, 0.0..=2.0).text("fog density"));
+                world.set_fog(fog);
+
+                let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+                ui.add(Slider::new(threads, 1..=cores.max(*threads)).text("threads"));
+            });
+        });
+
+        self.textures.append(output.textures_delta);
+        self.state
+            .handle_platform_output(window, &self.ctx, output.platform_output);
+        self.paint_jobs = self.ctx.tessellate(output.shapes);
+    }
+
+    /// Records drawing the window over `target`, e.g. inside `Pixels::render_with`.
+    pub fn render(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        context: &PixelsContext,
+    ) {
+        if !self.visible {
+            return;
+        }
+        let (device, queue) = (&context.device, &context.queue);
+        let screen = ScreenDescriptor {
+            size_in_pixels: self.size,
+            pixels_per_point: self.state.pixels_per_point(),
+        };
+        for (id, delta) in &self.textures.set {
+            self.render_pass.update_texture(device, queue, *id, delta);
+        }
+        self.render_pass
+            .update_buffers(device, queue, &self.paint_jobs, &screen);
+        self.render_pass
+            .execute(encoder, target, &self.paint_jobs, &screen, None);
+        for id in std::mem::take(&mut self.textures).free {
+            self.render_pass.free_texture(&id);
+        }
+    }
+}
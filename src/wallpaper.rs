@@ -0,0 +1,469 @@
+//! Live wallpaper: a borderless window the size of the primary monitor that stays below
+//! everything else, like the desktop. Every platform gets there its own way: X11 has a desktop
+//! window type, Windows draws wallpapers into a `WorkerW` window Progman splits off behind the
+//! desktop icons, and macOS has a desktop window level. Wayland has no such window at all, so
+//! there the wallpaper is a layer-shell surface on the background layer, drawn by a loop of
+//! its own since `winit` only makes toplevel windows.
+
+use std::error::Error;
+
+use winit::event_loop::EventLoop;
+use winit::monitor::MonitorHandle;
+use winit::window::{Window, WindowBuilder};
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub use wayland::{detected as on_wayland, play};
+
+/// The monitor the wallpaper covers.
+pub fn monitor<T>(event_loop: &EventLoop<T>) -> Result<MonitorHandle, Box<dyn Error>> {
+    event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+        .ok_or_else(|| "no monitor to draw the wallpaper on".into())
+}
+
+/// Makes the window a desktop window covering `monitor`.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+pub fn window(
+    builder: WindowBuilder,
+    monitor: &MonitorHandle,
+) -> Result<WindowBuilder, Box<dyn Error>> {
+    use winit::platform::unix::{WindowBuilderExtUnix, XWindowType};
+
+    Ok(builder
+        .with_x11_window_type(vec![XWindowType::Desktop])
+        .with_decorations(false)
+        .with_resizable(false)
+        .with_position(monitor.position())
+        .with_inner_size(monitor.size()))
+}
+
+/// Makes the window a child of the desktop's `WorkerW` window, covering `monitor`, whose
+/// position is taken from the top left of all monitors, where the `WorkerW` starts.
+#[cfg(windows)]
+pub fn window(
+    builder: WindowBuilder,
+    monitor: &MonitorHandle,
+) -> Result<WindowBuilder, Box<dyn Error>> {
+    use winit::dpi::PhysicalPosition;
+    use winit::platform::windows::WindowBuilderExtWindows;
+
+    let worker = desktop::worker().ok_or("the desktop has no `WorkerW` window to draw into")?;
+    let (left, top) = desktop::origin();
+    let position = monitor.position();
+    Ok(builder
+        .with_parent_window(worker)
+        .with_decorations(false)
+        .with_resizable(false)
+        .with_position(PhysicalPosition::new(position.x - left, position.y - top))
+        .with_inner_size(monitor.size()))
+}
+
+/// A borderless window covering `monitor`; `place` lowers it to the desktop once it exists.
+#[cfg(target_os = "macos")]
+pub fn window(
+    builder: WindowBuilder,
+    monitor: &MonitorHandle,
+) -> Result<WindowBuilder, Box<dyn Error>> {
+    Ok(builder
+        .with_decorations(false)
+        .with_resizable(false)
+        .with_position(monitor.position())
+        .with_inner_size(monitor.size()))
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "macos",
+    windows
+)))]
+pub fn window(
+    _builder: WindowBuilder,
+    _monitor: &MonitorHandle,
+) -> Result<WindowBuilder, Box<dyn Error>> {
+    Err("wallpaper mode isn't available on this platform".into())
+}
+
+/// Puts the window on the desktop level, below every other window and the desktop icons, on
+/// all spaces and out of the window cycle.
+#[cfg(target_os = "macos")]
+#[allow(unsafe_code)]
+pub fn place(window: &Window) {
+    use cocoa::appkit::{NSWindow, NSWindowCollectionBehavior};
+    use cocoa::base::id;
+    use winit::platform::macos::WindowExtMacOS;
+
+    /// `kCGDesktopWindowLevelKey`.
+    const DESKTOP_LEVEL_KEY: i32 = 2;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        fn CGWindowLevelForKey(key: i32) -> i32;
+    }
+
+    let ns_window = window.ns_window() as id;
+    // `ns_window` is the `NSWindow` of a live window, used on the main thread.
+    unsafe {
+        ns_window.setLevel_(CGWindowLevelForKey(DESKTOP_LEVEL_KEY) as _);
+        ns_window.setCollectionBehavior_(
+            NSWindowCollectionBehavior::NSWindowCollectionBehaviorCanJoinAllSpaces
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorStationary
+                | NSWindowCollectionBehavior::NSWindowCollectionBehaviorIgnoresCycle,
+        );
+    }
+}
+
+/// Elsewhere the window is placed as it is made.
+#[cfg(not(target_os = "macos"))]
+pub fn place(_window: &Window) {}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn on_wayland() -> bool {
+    false
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+pub fn play(_args: &crate::cli::Args) -> Result<(), Box<dyn Error>> {
+    Err("layer-shell wallpapers need Wayland".into())
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod desktop {
+    use std::ptr;
+
+    use winapi::shared::minwindef::{BOOL, FALSE, LPARAM, TRUE};
+    use winapi::shared::windef::HWND;
+    use winapi::um::winuser::{
+        EnumWindows, FindWindowExW, FindWindowW, GetSystemMetrics, SendMessageTimeoutW,
+        SMTO_NORMAL, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN,
+    };
+
+    /// Has Progman, the desktop, split a `WorkerW` window off behind its icons for the
+    /// wallpaper to be drawn into, if it hasn't yet.
+    const SPAWN_WORKER: u32 = 0x052c;
+    /// Milliseconds Progman gets to answer.
+    const TIMEOUT: u32 = 1000;
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(Some(0)).collect()
+    }
+
+    /// The `WorkerW` window behind the desktop icons.
+    pub fn worker() -> Option<HWND> {
+        let worker_class = wide("WorkerW");
+        // The class names are null-terminated and outlive the calls, and
+        // `find_worker` is handed a pointer to `worker`, which outlives `EnumWindows`.
+        unsafe {
+            let progman = FindWindowW(wide("Progman").as_ptr(), ptr::null());
+            if progman.is_null() {
+                return None;
+            }
+            let mut result = 0;
+            SendMessageTimeoutW(
+                progman,
+                SPAWN_WORKER,
+                0xd,
+                1,
+                SMTO_NORMAL,
+                TIMEOUT,
+                &mut result,
+            );
+            // Windows 11 24H2 keeps it inside Progman; before, it is the top-level window
+            // after the one holding the icons.
+            let mut worker =
+                FindWindowExW(progman, ptr::null_mut(), worker_class.as_ptr(), ptr::null());
+            if worker.is_null() {
+                EnumWindows(Some(find_worker), &mut worker as *mut HWND as LPARAM);
+            }
+            (!worker.is_null()).then_some(worker)
+        }
+    }
+
+    /// Stops at the window holding the desktop icons and writes the `WorkerW` following it
+    /// to `worker`.
+    unsafe extern "system" fn find_worker(window: HWND, worker: LPARAM) -> BOOL {
+        let icons = FindWindowExW(
+            window,
+            ptr::null_mut(),
+            wide("SHELLDLL_DefView").as_ptr(),
+            ptr::null(),
+        );
+        if icons.is_null() {
+            return TRUE;
+        }
+        *(worker as *mut HWND) = FindWindowExW(
+            ptr::null_mut(),
+            window,
+            wide("WorkerW").as_ptr(),
+            ptr::null(),
+        );
+        FALSE
+    }
+
+    /// The top left of the virtual screen, which the `WorkerW` spans.
+    pub fn origin() -> (i32, i32) {
+        // `GetSystemMetrics` only reads a setting.
+        unsafe {
+            (
+                GetSystemMetrics(SM_XVIRTUALSCREEN),
+                GetSystemMetrics(SM_YVIRTUALSCREEN),
+            )
+        }
+    }
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+mod wayland {
+    use std::error::Error;
+    use std::io;
+
+    use pixels::{PixelsBuilder, SurfaceTexture};
+    use raw_window_handle::{HasRawWindowHandle, RawWindowHandle, WaylandHandle};
+    use tunnel::{effect, Filter, Renderer, TunnelParams, World};
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+    use wayland_client::protocol::wl_surface::WlSurface;
+    use wayland_client::{Display, GlobalManager, Main};
+    use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_shell_v1::{
+        Layer, ZwlrLayerShellV1,
+    };
+    use wayland_protocols::wlr::unstable::layer_shell::v1::client::zwlr_layer_surface_v1::{
+        Anchor, Event, ZwlrLayerSurfaceV1,
+    };
+
+    use crate::cli::Args;
+    use crate::pacing::FrameLimiter;
+    use crate::performance;
+    #[cfg(feature = "scripting")]
+    use crate::script::Script;
+    use crate::sync::{SyncFollower, SyncLeader};
+    use crate::{apply_config, configure, pipeline, setup, Setup};
+
+    /// Whether `winit` would open its windows on Wayland, as it does unless told to use X11
+    /// or no compositor is running.
+    pub fn detected() -> bool {
+        std::env::var("WINIT_UNIX_BACKEND").map_or(true, |backend| backend != "x11")
+            && Display::connect_to_env().is_ok()
+    }
+
+    /// What the compositor last told the surface.
+    #[derive(Default)]
+    struct State {
+        size: Option<(u32, u32)>,
+        closed: bool,
+    }
+
+    /// A surface on the background layer, stretched over the whole output, for `pixels` to
+    /// draw into.
+    struct Background {
+        display: Display,
+        surface: Main<WlSurface>,
+        _layer: Main<ZwlrLayerSurfaceV1>,
+    }
+
+    // Both pointers stay valid as long as `Background`, which holds the display and
+    // the surface, and `pixels` is dropped before it.
+    #[allow(unsafe_code)]
+    unsafe impl HasRawWindowHandle for Background {
+        fn raw_window_handle(&self) -> RawWindowHandle {
+            let mut handle = WaylandHandle::empty();
+            handle.surface = self.surface.as_ref().c_ptr().cast();
+            handle.display = self.display.get_display_ptr().cast();
+            RawWindowHandle::Wayland(handle)
+        }
+    }
+
+    /// Draws the wallpaper until `--duration` runs out or the compositor closes it, at the
+    /// size of the output it is put on unless a resolution is given.
+    pub fn play(args: &Args) -> Result<(), Box<dyn Error>> {
+        let Setup {
+            config,
+            params,
+            mut animation,
+            texture,
+            timeline,
+            height_map,
+            atlas,
+            stamps,
+            ..
+        } = setup(args)?;
+        let duration = args.duration.or_else(|| {
+            timeline
+                .as_ref()
+                .filter(|timeline| !timeline.repeats())
+                .map(|timeline| timeline.duration())
+        });
+
+        let display = Display::connect_to_env()?;
+        let mut queue = display.create_event_queue();
+        let globals = GlobalManager::new(&display.attach(queue.token()));
+        queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+        let compositor = globals.instantiate_exact::<WlCompositor>(1)?;
+        let shell = globals
+            .instantiate_exact::<ZwlrLayerShellV1>(1)
+            .map_err(|_| "the compositor doesn't offer the layer-shell protocol")?;
+        let surface = compositor.create_surface();
+        let layer =
+            shell.get_layer_surface(&surface, None, Layer::Background, "wallpaper".to_string());
+        layer.set_anchor(Anchor::Top | Anchor::Bottom | Anchor::Left | Anchor::Right);
+        // Stay put under panels and docks rather than be moved out of their way.
+        layer.set_exclusive_zone(-1);
+        layer.quick_assign(|layer, event, mut data| {
+            let state = data.get::<State>().expect("dispatched with the state");
+            match event {
+                Event::Configure {
+                    serial,
+                    width,
+                    height,
+                } => {
+                    layer.ack_configure(serial);
+                    state.size = Some((width, height));
+                }
+                Event::Closed => state.closed = true,
+                _ => {}
+            }
+        });
+        surface.commit();
+        let mut state = State::default();
+        let (mut width, mut height) = loop {
+            queue.dispatch(&mut state, |_, _, _| {})?;
+            match state.size.take() {
+                _ if state.closed => return Ok(()),
+                Some((width, height)) if width > 0 && height > 0 => break (width, height),
+                _ => {}
+            }
+        };
+        let background = Background {
+            display,
+            surface,
+            _layer: layer,
+        };
+
+        let explicit_resolution = config.resolution.is_some()
+            || args.resolution.is_some()
+            || args.width.is_some()
+            || args.height.is_some();
+        let params = match explicit_resolution {
+            true => params,
+            false => TunnelParams {
+                width,
+                height,
+                ..params
+            },
+        };
+        let mut world = World::with_texture(params, texture);
+        configure(&mut world, args, timeline, height_map, atlas, stamps);
+        apply_config(&mut world, &config);
+
+        let mut renderer = Renderer::default();
+        renderer.set_simd(!args.scalar);
+        renderer.set_interlace(args.interlace);
+        let mut effects = effect::builtin();
+        effects[0] = Box::new(renderer);
+        let mut pipeline = pipeline(args, effects)?;
+        #[cfg(feature = "scripting")]
+        let mut script = args.script.as_deref().map(Script::load).transpose()?;
+        let mut leader = args
+            .sync_lead
+            .as_deref()
+            .map(SyncLeader::start)
+            .transpose()?;
+        let mut follower = args
+            .sync_follow
+            .as_deref()
+            .map(|address| SyncFollower::listen(address, args.seed))
+            .transpose()?;
+        let mut filter = Filter::Nearest;
+
+        let (frame_width, frame_height) = world.frame_size();
+        let mut pixels = PixelsBuilder::new(
+            frame_width,
+            frame_height,
+            SurfaceTexture::new(width, height, &background),
+        )
+        .present_mode(args.vsync.present_mode())
+        .build()?;
+        let mut limiter = args.max_fps.map(FrameLimiter::new);
+        while !duration.is_some_and(|duration| world.clock().time() >= duration) {
+            background.display.flush()?;
+            if let Some(guard) = queue.prepare_read() {
+                match guard.read_events() {
+                    Err(err) if err.kind() != io::ErrorKind::WouldBlock => return Err(err.into()),
+                    _ => {}
+                }
+            }
+            queue.dispatch_pending(&mut state, |_, _, _| {})?;
+            if state.closed {
+                break;
+            }
+            if let Some(size) = state.size.take().filter(|&size| size != (width, height)) {
+                (width, height) = size;
+                pixels.resize_surface(width, height);
+            }
+
+            if let Some(limiter) = &mut limiter {
+                limiter.wait();
+            }
+            if let Some(texture) = animation
+                .as_mut()
+                .and_then(|animation| animation.frame_at(world.clock().time()))
+            {
+                world.set_texture_frame(texture);
+            }
+            #[cfg(feature = "scripting")]
+            if let Some(script) = &mut script {
+                script.update(&mut world, &Default::default());
+            }
+            if let Some(follower) = &mut follower {
+                follower.update(&mut world, &mut pipeline, &mut filter);
+            }
+            world.update();
+            if let Some(leader) = &mut leader {
+                leader.send(
+                    &world,
+                    performance::capture(&world, &pipeline, filter, None),
+                );
+            }
+            pipeline.update(&world);
+            let frame = pixels.get_frame();
+            pipeline.draw(&mut world, frame);
+            world.feed_back(frame, frame_width, frame_height);
+            pixels.render()?;
+        }
+        Ok(())
+    }
+}
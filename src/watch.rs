@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// Reports modifications of a single file. The parent directory is watched instead of the
+/// file itself, because many editors save by writing a new file and renaming it over the old.
+pub struct FileWatcher {
+    path: PathBuf,
+    events: Receiver<notify::Result<Event>>,
+    _watcher: RecommendedWatcher,
+}
+
+impl FileWatcher {
+    pub fn new(path: &Path) -> notify::Result<Self> {
+        let path = path.canonicalize()?;
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        Ok(Self {
+            path,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Drains pending events and tells whether any of them touched the file.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        for event in self.events.try_iter().flatten() {
+            changed |= !event.kind.is_access() && event.paths.contains(&self.path);
+        }
+        changed
+    }
+}
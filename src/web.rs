@@ -0,0 +1,38 @@
+use clap::{CommandFactory, FromArgMatches};
+use winit::platform::web::WindowExtWebSys;
+use winit::window::Window;
+
+use crate::cli::Args;
+
+/// Builds the command line from the page's query string, so that
+/// `index.html?postfx=crt&background=starfield` behaves like
+/// `tunnel-rs --postfx=crt --background=starfield`.
+pub fn args() -> Result<Args, clap::Error> {
+    let query = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    let options = query
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|option| !option.is_empty())
+        .map(|option| format!("--{}", option));
+    let matches = Args::command()
+        .try_get_matches_from(std::iter::once("tunnel-rs".to_string()).chain(options))?;
+    let mut args = Args::from_arg_matches(&matches)?;
+    args.note_given(&matches);
+    Ok(args)
+}
+
+/// Adds the window's canvas to the page, inside `#tunnel` if present or else the body.
+pub fn attach(window: &Window) {
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .expect("page has a document");
+    let parent = document
+        .get_element_by_id("tunnel")
+        .or_else(|| document.body().map(Into::into))
+        .expect("page has a body");
+    parent
+        .append_child(&window.canvas())
+        .expect("append canvas to the page");
+}
@@ -0,0 +1,930 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tracing::debug_span;
+
+use crate::atlas::Atlas;
+use crate::beat::BeatTrack;
+use crate::camera::Camera;
+use crate::clock::Clock;
+use crate::color;
+use crate::color_map::ColorMapper;
+use crate::curve::Curve;
+use crate::flight::Flight;
+use crate::fog::Fog;
+use crate::geometry::{Circle, TunnelGeometry};
+use crate::light::{Light, LightPosition};
+use crate::lut::{Lut, LutQuality, LutStats, Progress};
+use crate::modulation::{ModulationGraph, Param, Sources};
+use crate::modulator::Modulator;
+use crate::palette::{Palette, PaletteKind};
+use crate::spectrum::AudioLevels;
+use crate::texture::{AddressMode, HeightMap, Stamp, Texture, TextureFormat};
+use crate::timeline::{Scene, Timeline};
+
+#[derive(Clone, Copy, Debug)]
+pub struct TunnelParams {
+    pub width: u32,
+    pub height: u32,
+    pub tex_width: usize,
+    pub tex_height: usize,
+    pub ratio: f64,
+    pub palette_speed: f64,
+    pub lut_quality: LutQuality,
+    /// Whether lookup tables are kept in the user's cache directory between runs.
+    pub lut_cache: bool,
+}
+
+impl Default for TunnelParams {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 900,
+            tex_width: 256,
+            tex_height: 256,
+            ratio: 64.0,
+            palette_speed: 64.0,
+            lut_quality: LutQuality::High,
+            lut_cache: false,
+        }
+    }
+}
+
+pub const MIN_RENDER_SCALE: f64 = 0.25;
+
+const BEAT_TRAVEL: f64 = 1.5;
+const BEAT_RADIUS: f64 = 0.2;
+const BEAT_PALETTE: f64 = 96.0;
+const LIVE_TRAVEL: f64 = 2.0;
+const LIVE_LOOK: f64 = 0.25;
+/// What reduced motion holds the world to: texture lengths flown, turns spun, rolled and
+/// orbited by the light, and palette entries cycled per second.
+const REDUCED_TRAVEL: f64 = 0.25;
+const REDUCED_TURNS: f64 = 0.05;
+const REDUCED_PALETTE: f64 = 8.0;
+
+/// Everything that changes when switching textures, palettes or geometries, kept as a unit so
+/// the outgoing configuration can still be drawn while it crossfades into the new one.
+#[derive(Clone)]
+pub(crate) struct Layer {
+    /// What is drawn: `source` with its seam blended and the stamps on it, if those are set.
+    pub(crate) texture: Arc<Texture>,
+    /// The texture as it was given.
+    pub(crate) source: Arc<Texture>,
+    /// `source` read as a height map, the first time bump mapping needs it.
+    pub(crate) relief: OnceLock<Arc<HeightMap>>,
+    pub(crate) palette_kind: PaletteKind,
+    pub(crate) color_map: Arc<dyn ColorMapper>,
+    /// The colors `color_map` gives every texel value.
+    pub(crate) palette: Palette,
+    pub(crate) geometry: Arc<dyn TunnelGeometry>,
+    pub(crate) lut: Arc<Lut>,
+}
+
+pub(crate) struct Fade {
+    pub(crate) from: Layer,
+    pub(crate) start: f64,
+    pub(crate) duration: f64,
+    /// Weight of the new layer, rising from 0 to 1.
+    pub(crate) mix: f64,
+}
+
+/// A lookup table being built on the rayon pool for the current layer.
+pub(crate) struct PendingLut {
+    progress: Progress,
+    lut: Mutex<Option<Lut>>,
+}
+
+impl PendingLut {
+    fn take(&self) -> Option<Lut> {
+        self.lut.lock().unwrap().take()
+    }
+}
+
+/// How far the animation has got: what `World::update` adds up from one frame to the next,
+/// for another instance to carry on from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Motion {
+    pub time: f64,
+    /// Texture lengths flown and turns spun, not counting the beat's kicks.
+    pub distance: f64,
+    pub spin: f64,
+    pub palette_phase: f64,
+    pub light_orbit: f64,
+    /// Travel and spin speed the flight has eased into.
+    pub flight_speed: (f64, f64),
+}
+
+pub struct World {
+    pub(crate) params: TunnelParams,
+    pub(crate) layer: Layer,
+    pub(crate) render_scale: f64,
+    pub(crate) pending_lut: Option<Arc<PendingLut>>,
+    pub(crate) fade: Option<Fade>,
+    pub(crate) crossfade: f64,
+    /// Shading, filtering and blending work on linear light instead of sRGB values.
+    pub(crate) gamma_correct: bool,
+    pub(crate) reduced_motion: bool,
+    pub(crate) palette_shift: u8,
+    pub(crate) ratio_modulator: Modulator,
+    pub(crate) ratio: f64,
+    pub(crate) beat_track: Option<BeatTrack>,
+    pub(crate) pulse: f64,
+    pub(crate) audio_levels: Option<Arc<AudioLevels>>,
+    pub(crate) timeline: Option<Timeline>,
+    pub(crate) scene: Option<usize>,
+    pub(crate) distance: f64,
+    pub(crate) palette_phase: f64,
+    pub(crate) travel: f64,
+    /// Texture turns around the tunnel walls.
+    pub(crate) spin: f64,
+    pub(crate) drive: (f64, f64),
+    pub(crate) flight: Flight,
+    /// Travel and spin speed eased towards the flight's.
+    pub(crate) flight_speed: (f64, f64),
+    /// Turns the view is rolled by around the tunnel's axis, and turns per second it rolls
+    /// by itself.
+    pub(crate) roll: f64,
+    pub(crate) roll_speed: f64,
+    /// The roll with the timeline's added, as drawn.
+    pub(crate) view_roll: f64,
+    pub(crate) fog: Fog,
+    /// The fog with the modulation's added, as drawn.
+    pub(crate) view_fog: Fog,
+    pub(crate) modulation: Option<ModulationGraph>,
+    /// The last value of every MIDI controller, from 0 to 1.
+    pub(crate) controllers: [f64; 128],
+    pub(crate) light: Light,
+    /// Turns the light has circled the axis by.
+    pub(crate) light_orbit: f64,
+    pub(crate) light_position: LightPosition,
+    /// Heights to bump map the walls with instead of the texture's.
+    pub(crate) height_map: Option<Arc<HeightMap>>,
+    pub(crate) curve: Curve,
+    /// How far the curve bent the tunnel at the last update.
+    pub(crate) curve_amount: f64,
+    /// Along the tunnel (depth) and around it (angle).
+    pub(crate) address: (AddressMode, AddressMode),
+    /// How the texture is split into tiles for the bands along the tunnel.
+    pub(crate) atlas: Option<Atlas>,
+    /// Images put onto the texture, in order.
+    pub(crate) stamps: Vec<Stamp>,
+    /// Share of the texture height blended across the seam around the tunnel.
+    pub(crate) seam_blend: f64,
+    /// Weight of the previous frame blended into the texture.
+    pub(crate) feedback: f64,
+    pub(crate) camera: Camera,
+    pub(crate) clock: Clock,
+}
+
+impl World {
+    pub fn new(params: TunnelParams) -> Self {
+        let texture = Texture::xor(params.tex_width, params.tex_height);
+        Self::with_texture(params, texture)
+    }
+
+    pub fn with_texture(params: TunnelParams, texture: Texture) -> Self {
+        let mut world = Self::without_lut(params, texture);
+        world.rebuild_lut();
+        world
+    }
+
+    /// Like `with_texture`, but builds the lookup table on the rayon pool instead of blocking.
+    /// Until `update` picks it up, `loading` reports the progress, the world stands still and
+    /// the tunnel draws as black.
+    pub fn in_background(params: TunnelParams, texture: Texture) -> Self {
+        let mut world = Self::without_lut(params, texture);
+        world.spawn_lut();
+        world
+    }
+
+    fn without_lut(params: TunnelParams, texture: Texture) -> Self {
+        let params = TunnelParams {
+            tex_width: texture.width,
+            tex_height: texture.height,
+            ..params
+        };
+        let geometry: Arc<dyn TunnelGeometry> = Arc::new(Circle);
+
+        let clock = Clock::default();
+        let mut camera = Camera::new(params.width, params.height);
+        camera.update(clock.time());
+
+        let palette_kind = PaletteKind::Green;
+        let texture = Arc::new(texture);
+
+        Self {
+            params,
+            layer: Layer {
+                texture: texture.clone(),
+                source: texture,
+                relief: OnceLock::new(),
+                palette_kind,
+                color_map: Arc::new(palette_kind),
+                palette: palette_kind.palette(),
+                geometry,
+                lut: Arc::new(Lut::empty()),
+            },
+            render_scale: 1.0,
+            pending_lut: None,
+            fade: None,
+            crossfade: 0.0,
+            gamma_correct: false,
+            reduced_motion: false,
+            palette_shift: 0,
+            ratio_modulator: Modulator::constant(params.ratio),
+            ratio: params.ratio,
+            beat_track: None,
+            pulse: 0.0,
+            audio_levels: None,
+            timeline: None,
+            scene: None,
+            distance: 0.0,
+            palette_phase: 0.0,
+            travel: 0.0,
+            spin: 0.0,
+            drive: (0.0, 0.0),
+            flight: Flight::default(),
+            flight_speed: (Flight::default().travel, Flight::default().spin),
+            roll: 0.0,
+            roll_speed: 0.0,
+            view_roll: 0.0,
+            fog: Fog::default(),
+            view_fog: Fog::default(),
+            modulation: None,
+            controllers: [0.0; 128],
+            light: Light::default(),
+            light_orbit: 0.0,
+            light_position: LightPosition::default(),
+            height_map: None,
+            curve: Curve::default(),
+            curve_amount: 0.0,
+            address: (AddressMode::Repeat, AddressMode::Repeat),
+            atlas: None,
+            stamps: Vec::new(),
+            seam_blend: 0.0,
+            feedback: 0.0,
+            camera,
+            clock,
+        }
+    }
+
+    pub fn params(&self) -> &TunnelParams {
+        &self.params
+    }
+
+    /// Cycles the palette by `speed` entries per second.
+    pub fn set_palette_speed(&mut self, speed: f64) {
+        self.params.palette_speed = speed;
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == self.params.width && height == self.params.height {
+            return;
+        }
+        self.params.width = width;
+        self.params.height = height;
+        self.rebuild_lut();
+        self.fade = None;
+        self.camera.resize(width, height);
+    }
+
+    pub fn render_scale(&self) -> f64 {
+        self.render_scale
+    }
+
+    /// Draws frames at this fraction of the `params` size, between `MIN_RENDER_SCALE` and 1,
+    /// by sampling the lookup table more sparsely instead of rebuilding it.
+    pub fn set_render_scale(&mut self, scale: f64) {
+        self.render_scale = scale.clamp(MIN_RENDER_SCALE, 1.0);
+    }
+
+    /// Width and height of the frames effects draw into.
+    pub fn frame_size(&self) -> (u32, u32) {
+        let scale = |size: u32| ((size as f64 * self.render_scale).round() as u32).max(1);
+        (scale(self.params.width), scale(self.params.height))
+    }
+
+    /// How many `params`-sized pixels each frame pixel covers, horizontally and vertically.
+    pub(crate) fn frame_step(&self) -> (f64, f64) {
+        let (width, height) = self.frame_size();
+        (
+            self.params.width as f64 / width as f64,
+            self.params.height as f64 / height as f64,
+        )
+    }
+
+    pub fn crossfade(&self) -> f64 {
+        self.crossfade
+    }
+
+    /// Seconds over which texture, palette and geometry switches blend into each other;
+    /// zero cuts immediately.
+    pub fn set_crossfade(&mut self, seconds: f64) {
+        self.crossfade = seconds.max(0.0);
+    }
+
+    pub fn gamma_correct(&self) -> bool {
+        self.gamma_correct
+    }
+
+    /// Whether fog, the light, bilinear filtering, crossfades and blending stages mix linear
+    /// light, as the eye sees it, rather than sRGB values, which darkens what they mix.
+    pub fn set_gamma_correct(&mut self, on: bool) {
+        self.gamma_correct = on;
+    }
+
+    pub fn reduced_motion(&self) -> bool {
+        self.reduced_motion
+    }
+
+    /// Whether to spare viewers sensitive to motion and flashing: the animation is held to
+    /// real time and slow speeds, the beat and the music no longer jolt the view or the
+    /// palette, and `Pipeline::draw` limits how fast the frame's brightness changes.
+    pub fn set_reduced_motion(&mut self, on: bool) {
+        self.reduced_motion = on;
+    }
+
+    pub fn is_fading(&self) -> bool {
+        self.fade.is_some()
+    }
+
+    pub fn geometry(&self) -> &dyn TunnelGeometry {
+        self.layer.geometry.as_ref()
+    }
+
+    /// What the current layer's lookup table holds; see `LutStats`.
+    pub fn lut_stats(&self) -> LutStats {
+        self.layer.lut.stats()
+    }
+
+    /// The current layer's lookup table drawn as an RGBA image covering twice the frame in
+    /// each direction, see `lut_stats` for its size.
+    pub fn lut_image(&self) -> Vec<u8> {
+        self.layer.lut.visualize()
+    }
+
+    pub fn set_geometry(&mut self, geometry: Arc<dyn TunnelGeometry>) {
+        self.fade_out(self.crossfade);
+        self.layer.geometry = geometry;
+        self.rebuild_lut();
+    }
+
+    /// Rebuilds the lookup table, in the background if the previous build is still running
+    /// there; its result would be stale anyway.
+    fn rebuild_lut(&mut self) {
+        if self.pending_lut.is_some() {
+            self.spawn_lut();
+            return;
+        }
+        self.layer.lut = Arc::new(Lut::new(&self.params, self.layer.geometry.as_ref()));
+    }
+
+    fn spawn_lut(&mut self) {
+        let pending = Arc::new(PendingLut {
+            progress: Progress::default(),
+            lut: Mutex::new(None),
+        });
+        let job = pending.clone();
+        let params = self.params;
+        let geometry = self.layer.geometry.clone();
+        rayon::spawn(move || {
+            let lut = Lut::with_progress(&params, geometry.as_ref(), &job.progress);
+            *job.lut.lock().unwrap() = Some(lut);
+        });
+        self.pending_lut = Some(pending);
+    }
+
+    /// How far the build started by `in_background` is, between 0 and 1, or `None` once its
+    /// table is in use.
+    pub fn loading(&self) -> Option<f64> {
+        self.pending_lut
+            .as_ref()
+            .map(|pending| pending.progress.fraction())
+    }
+
+    pub fn ratio(&self) -> f64 {
+        self.ratio
+    }
+
+    pub fn ratio_modulator(&self) -> &Modulator {
+        &self.ratio_modulator
+    }
+
+    pub fn set_ratio_modulator(&mut self, modulator: Modulator) {
+        self.ratio_modulator = modulator;
+    }
+
+    pub fn flight(&self) -> &Flight {
+        &self.flight
+    }
+
+    /// Flies at the flight's speeds, easing into them.
+    pub fn set_flight(&mut self, flight: Flight) {
+        self.flight = flight;
+    }
+
+    /// The travel and spin speed the flight has eased into so far.
+    pub fn flight_speed(&self) -> (f64, f64) {
+        self.flight_speed
+    }
+
+    pub fn motion(&self) -> Motion {
+        Motion {
+            time: self.clock.time(),
+            distance: self.distance,
+            spin: self.spin,
+            palette_phase: self.palette_phase,
+            light_orbit: self.light_orbit,
+            flight_speed: self.flight_speed,
+        }
+    }
+
+    /// Picks up the animation where `motion` left it; the next update carries on from there.
+    pub fn set_motion(&mut self, motion: Motion) {
+        self.clock.set_time(motion.time);
+        self.distance = motion.distance;
+        self.spin = motion.spin;
+        self.palette_phase = motion.palette_phase;
+        self.light_orbit = motion.light_orbit;
+        self.flight_speed = motion.flight_speed;
+    }
+
+    pub fn roll(&self) -> f64 {
+        self.roll
+    }
+
+    /// Turns the whole tunnel, shape and all, by `turns` around the middle of the view.
+    pub fn set_roll(&mut self, turns: f64) {
+        self.roll = turns.rem_euclid(1.0);
+    }
+
+    pub fn roll_speed(&self) -> f64 {
+        self.roll_speed
+    }
+
+    /// Keeps rolling the view by `turns` per second; 0 stops.
+    pub fn set_roll_speed(&mut self, turns: f64) {
+        self.roll_speed = turns;
+    }
+
+    /// Travel and spin speed added on top of the animation's own, in texture lengths per
+    /// second, e.g. from a gamepad stick.
+    pub fn set_drive(&mut self, travel: f64, spin: f64) {
+        self.drive = (travel, spin);
+    }
+
+    pub fn beat_track(&self) -> Option<&BeatTrack> {
+        self.beat_track.as_ref()
+    }
+
+    /// Syncs travel speed, radius and palette to the beats of `track`, read at clock time.
+    pub fn set_beat_track(&mut self, track: Option<BeatTrack>) {
+        self.beat_track = track;
+    }
+
+    /// Current beat envelope in `[0, 1]`; zero without a beat track.
+    pub fn pulse(&self) -> f64 {
+        self.pulse
+    }
+
+    pub fn audio_levels(&self) -> Option<&Arc<AudioLevels>> {
+        self.audio_levels.as_ref()
+    }
+
+    /// Lets live audio speed up the tunnel with its bass and shake the camera with its treble.
+    pub fn set_audio_levels(&mut self, levels: Option<Arc<AudioLevels>>) {
+        self.audio_levels = levels;
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    /// Plays `timeline` against the clock: each scene switches texture, palette, geometry and
+    /// fog when it starts, and its keyframes drive the continuous parameters.
+    pub fn set_timeline(&mut self, timeline: Option<Timeline>) {
+        self.timeline = timeline;
+        self.scene = None;
+    }
+
+    /// Index of the timeline scene currently playing.
+    pub fn scene(&self) -> Option<usize> {
+        self.scene
+    }
+
+    pub fn fog(&self) -> &Fog {
+        &self.fog
+    }
+
+    pub fn set_fog(&mut self, fog: Fog) {
+        self.fog = fog;
+        self.view_fog = fog;
+    }
+
+    pub fn modulation(&self) -> Option<&ModulationGraph> {
+        self.modulation.as_ref()
+    }
+
+    /// Adds the outputs of `graph` to the parameters it targets, evaluated every update.
+    pub fn set_modulation(&mut self, graph: Option<ModulationGraph>) {
+        self.modulation = graph;
+    }
+
+    /// Records the `value` (0 to 1) a MIDI controller was moved to, for modulation to read.
+    pub fn set_controller(&mut self, controller: u8, value: f64) {
+        if let Some(slot) = self.controllers.get_mut(controller as usize) {
+            *slot = value;
+        }
+    }
+
+    pub fn light(&self) -> &Light {
+        &self.light
+    }
+
+    pub fn set_light(&mut self, light: Light) {
+        self.light = light;
+    }
+
+    /// Where the light was at the last update.
+    pub fn light_position(&self) -> &LightPosition {
+        &self.light_position
+    }
+
+    pub fn curve(&self) -> &Curve {
+        &self.curve
+    }
+
+    pub fn set_curve(&mut self, curve: Curve) {
+        self.curve = curve;
+    }
+
+    pub fn height_map(&self) -> Option<&Arc<HeightMap>> {
+        self.height_map.as_ref()
+    }
+
+    /// Bump maps the walls with `heights` when `Light::bump` is set; without one, the
+    /// texture's brightness is taken for the height.
+    pub fn set_height_map(&mut self, heights: Option<Arc<HeightMap>>) {
+        self.height_map = heights;
+    }
+
+    /// How the texture wraps along the tunnel and around it.
+    pub fn address_modes(&self) -> (AddressMode, AddressMode) {
+        self.address
+    }
+
+    pub fn set_address_modes(&mut self, depth: AddressMode, angle: AddressMode) {
+        self.address = (depth, angle);
+    }
+
+    pub fn atlas(&self) -> Option<&Atlas> {
+        self.atlas.as_ref()
+    }
+
+    /// Draws a tile of the texture in every band along the tunnel as `atlas` says, each
+    /// repeated and wrapped like a texture of its own; without one, the whole texture.
+    pub fn set_atlas(&mut self, atlas: Option<Atlas>) {
+        self.atlas = atlas;
+    }
+
+    pub fn clock(&self) -> &Clock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut Clock {
+        &mut self.clock
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera {
+        &mut self.camera
+    }
+
+    /// The texture as drawn, with its seam blended if `set_seam_blend` asked for it.
+    pub fn texture(&self) -> &Texture {
+        &self.layer.texture
+    }
+
+    pub fn set_texture(&mut self, texture: Texture) {
+        self.fade_out(self.crossfade);
+        self.swap_texture(Arc::new(texture));
+    }
+
+    /// Switches to the next frame of an animated texture, without crossfading.
+    pub fn set_texture_frame(&mut self, texture: Arc<Texture>) {
+        self.swap_texture(texture);
+    }
+
+    /// The lookup table doesn't depend on the texture, so it is kept even across sizes.
+    fn swap_texture(&mut self, texture: Arc<Texture>) {
+        self.params.tex_width = texture.width;
+        self.params.tex_height = texture.height;
+        self.layer.texture = self.finish(&texture);
+        self.layer.source = texture;
+        self.layer.relief = OnceLock::new();
+    }
+
+    pub fn seam_blend(&self) -> f64 {
+        self.seam_blend
+    }
+
+    /// Fades this share of the texture height (at most half) into a mirror image of the
+    /// other edge, so that textures which don't tile vertically show no seam where they wrap
+    /// around the tunnel. Zero draws textures as they are.
+    pub fn set_seam_blend(&mut self, share: f64) {
+        self.seam_blend = share.clamp(0.0, 0.5);
+        self.layer.texture = self.finish(&self.layer.source);
+    }
+
+    pub fn stamps(&self) -> &[Stamp] {
+        &self.stamps
+    }
+
+    /// Puts `stamps` onto every texture drawn from now on, the current one included, so that
+    /// text or a logo scrolls past on the walls.
+    pub fn set_stamps(&mut self, stamps: Vec<Stamp>) {
+        self.stamps = stamps;
+        self.layer.texture = self.finish(&self.layer.source);
+    }
+
+    pub fn feedback(&self) -> f64 {
+        self.feedback
+    }
+
+    /// How much of each frame passed to `feed_back` shows up on the walls, between 0 (off)
+    /// and 0.95; higher values leave longer trails.
+    pub fn set_feedback(&mut self, amount: f64) {
+        self.feedback = amount.clamp(0.0, 0.95);
+        if self.feedback == 0.0 {
+            self.layer.texture = self.finish(&self.layer.source);
+        }
+    }
+
+    /// Blends a drawn frame, shrunk to the texture's size, into the texture the next frame is
+    /// drawn with, so that the tunnel recursively shows itself on its walls.
+    pub fn feed_back(&mut self, frame: &[u8], width: u32, height: u32) {
+        if self.feedback == 0.0 {
+            return;
+        }
+        let base = self.finish(&self.layer.source);
+        let texture = feedback_texture(
+            &base,
+            &self.layer.palette,
+            self.palette_shift,
+            (frame, width as usize, height as usize),
+            (self.feedback * 256.0) as u32,
+        );
+        self.layer.texture = Arc::new(texture);
+    }
+
+    /// `texture` as drawn: with its seam blended and the stamps put on it, where those are set.
+    fn finish(&self, texture: &Arc<Texture>) -> Arc<Texture> {
+        let rows = (texture.height as f64 * self.seam_blend).round() as usize;
+        let blended = match rows {
+            0 => texture.clone(),
+            _ => Arc::new(texture.blend_seam(rows)),
+        };
+        match self.stamps.as_slice() {
+            [] => blended,
+            stamps => Arc::new(
+                stamps
+                    .iter()
+                    .fold((*blended).clone(), |texture, stamp| stamp.apply(&texture)),
+            ),
+        }
+    }
+
+    pub fn palette_kind(&self) -> PaletteKind {
+        self.layer.palette_kind
+    }
+
+    /// Colors indexed textures from the palette `kind`, which is also what `set_color_map`
+    /// goes back to.
+    pub fn set_palette(&mut self, kind: PaletteKind) {
+        self.fade_out(self.crossfade);
+        self.layer.palette_kind = kind;
+        self.map_colors(Arc::new(kind));
+    }
+
+    pub fn color_map(&self) -> &Arc<dyn ColorMapper> {
+        &self.layer.color_map
+    }
+
+    /// Colors indexed textures with `color_map` instead of the palette.
+    pub fn set_color_map(&mut self, color_map: Arc<dyn ColorMapper>) {
+        self.fade_out(self.crossfade);
+        self.map_colors(color_map);
+    }
+
+    fn map_colors(&mut self, color_map: Arc<dyn ColorMapper>) {
+        let time = self.clock.time();
+        self.layer.palette = Palette::from_fn(|value| color_map.color(value, time));
+        self.layer.color_map = color_map;
+    }
+
+    /// Keeps drawing the current layer underneath the next change for `duration` seconds.
+    /// Changes made at the same clock time share one fade from the state before the first.
+    /// Nothing has been drawn while loading, so there is nothing to fade from either.
+    fn fade_out(&mut self, duration: f64) {
+        let time = self.clock.time();
+        if duration <= 0.0
+            || self.pending_lut.is_some()
+            || self.fade.as_ref().is_some_and(|fade| fade.start == time)
+        {
+            return;
+        }
+        self.fade = Some(Fade {
+            from: self.layer.clone(),
+            start: time,
+            duration,
+            mix: 0.0,
+        });
+    }
+
+    pub fn update(&mut self) {
+        let _span = debug_span!("update").entered();
+        // The clock isn't ticked either, so that it doesn't jump ahead by the loading time.
+        if let Some(pending) = &self.pending_lut {
+            let Some(lut) = pending.take() else {
+                return;
+            };
+            self.layer.lut = Arc::new(lut);
+            self.pending_lut = None;
+        }
+
+        let previous = self.clock.time();
+        if self.reduced_motion {
+            let speed = self.clock.speed().clamp(-1.0, 1.0);
+            self.clock.set_speed(speed);
+        }
+        self.clock.tick();
+        let time = self.clock.time();
+        self.camera.update(time);
+        let (bass, treble) = match &self.audio_levels {
+            Some(levels) if !self.reduced_motion => (levels.bass(), levels.treble()),
+            _ => (0.0, 0.0),
+        };
+        let dt = time - previous;
+        self.pulse = match &self.beat_track {
+            Some(track) if !self.reduced_motion => track.envelope(time),
+            _ => 0.0,
+        };
+        if let Some(fade) = &mut self.fade {
+            fade.mix = (time - fade.start) / fade.duration;
+        }
+        if self
+            .fade
+            .as_ref()
+            .is_some_and(|fade| !(0.0..1.0).contains(&fade.mix))
+        {
+            self.fade = None;
+        }
+
+        let mut ratio = self.ratio_modulator.value(time);
+        let mut travel_speed = self.flight.travel;
+        let mut spin_speed = self.flight.spin;
+        let mut palette_speed = self.params.palette_speed;
+        let mut light = LightPosition {
+            angle: 0.0,
+            depth: self.light.depth.value(time),
+            intensity: self.light.intensity.value(time),
+        };
+        let mut curve_amount = self.curve.amount.value(time);
+        let mut roll = 0.0;
+        if let Some(timeline) = self.timeline.take() {
+            let (index, params) = timeline.sample(time);
+            if self.scene != Some(index) {
+                self.scene = Some(index);
+                self.enter_scene(&timeline.scenes()[index]);
+            }
+            if let Some(value) = params.ratio {
+                ratio += value - self.ratio_modulator.base;
+            }
+            travel_speed = params.speed.unwrap_or(travel_speed);
+            palette_speed = params.palette_speed.unwrap_or(palette_speed);
+            self.fog.start = params.fog_start.unwrap_or(self.fog.start);
+            self.fog.end = params.fog_end.unwrap_or(self.fog.end);
+            self.fog.density = params.fog_density.unwrap_or(self.fog.density);
+            light.angle = params.light_angle.unwrap_or(light.angle);
+            light.depth = params.light_depth.unwrap_or(light.depth);
+            light.intensity = params.light_intensity.unwrap_or(light.intensity);
+            curve_amount = params.curve.unwrap_or(curve_amount);
+            roll = params.roll.unwrap_or(roll);
+            self.timeline = Some(timeline);
+        }
+        self.view_fog = self.fog;
+        let mut palette_offset = 0.0;
+        if let Some(graph) = &mut self.modulation {
+            graph.evaluate(&Sources {
+                time,
+                dt,
+                beat: self.pulse,
+                bass,
+                treble,
+                controllers: &self.controllers,
+            });
+            ratio += graph.offset(Param::Ratio);
+            travel_speed += graph.offset(Param::Speed);
+            spin_speed += graph.offset(Param::Spin);
+            palette_speed += graph.offset(Param::PaletteSpeed);
+            palette_offset = graph.offset(Param::Palette);
+            self.view_fog.start += graph.offset(Param::FogStart);
+            self.view_fog.end += graph.offset(Param::FogEnd);
+            self.view_fog.density += graph.offset(Param::FogDensity);
+        }
+
+        let mut light_orbit = self.light.orbit;
+        let mut roll_speed = self.roll_speed;
+        if self.reduced_motion {
+            travel_speed = travel_speed.clamp(-REDUCED_TRAVEL, REDUCED_TRAVEL);
+            spin_speed = spin_speed.clamp(-REDUCED_TURNS, REDUCED_TURNS);
+            palette_speed = palette_speed.clamp(-REDUCED_PALETTE, REDUCED_PALETTE);
+            light_orbit = light_orbit.clamp(-REDUCED_TURNS, REDUCED_TURNS);
+            roll_speed = roll_speed.clamp(-REDUCED_TURNS, REDUCED_TURNS);
+        }
+        self.flight_speed = (
+            self.flight.ease(self.flight_speed.0, travel_speed, dt),
+            self.flight.ease(self.flight_speed.1, spin_speed, dt),
+        );
+        self.distance += dt * (self.flight_speed.0 + bass * LIVE_TRAVEL + self.drive.0);
+        self.spin += dt * (self.flight_speed.1 + self.drive.1);
+        self.palette_phase += dt * palette_speed;
+        self.light_orbit = (self.light_orbit + dt * light_orbit).rem_euclid(1.0);
+        light.angle += self.light_orbit;
+        self.light_position = light;
+        self.curve_amount = curve_amount;
+        self.set_roll(self.roll + dt * roll_speed);
+        self.view_roll = (self.roll + roll).rem_euclid(1.0);
+        let shake = treble * LIVE_LOOK;
+        self.camera
+            .set_offset(shake * (time * 13.0).sin(), shake * (time * 17.0).cos());
+        let beat_travel = match &self.beat_track {
+            Some(track) if !self.reduced_motion => track.integral(time),
+            _ => 0.0,
+        };
+        self.travel = self.distance + beat_travel * BEAT_TRAVEL;
+        self.ratio = ratio * (1.0 + self.pulse * BEAT_RADIUS);
+        let palette = self.palette_phase + self.pulse * BEAT_PALETTE + palette_offset;
+        self.palette_shift = palette as i64 as u8;
+        if self.layer.color_map.animated() {
+            self.map_colors(self.layer.color_map.clone());
+        }
+    }
+
+    /// Switches to the scene's configuration, fading over its `transition` instead of the
+    /// world's own crossfade duration.
+    fn enter_scene(&mut self, scene: &Scene) {
+        self.fade_out(scene.transition);
+        if let Some(texture) = &scene.texture {
+            self.swap_texture(texture.clone());
+        }
+        if let Some(palette) = scene.palette {
+            self.layer.palette_kind = palette;
+            self.map_colors(Arc::new(palette));
+        }
+        if let Some(geometry) = &scene.geometry {
+            self.layer.geometry = geometry.clone();
+            self.rebuild_lut();
+        }
+        if let Some(curve) = scene.fog {
+            self.fog.curve = curve;
+        }
+    }
+}
+
+/// `base` in colour with `frame` stretched over it at `weight` out of 256.
+fn feedback_texture(
+    base: &Texture,
+    palette: &Palette,
+    palette_shift: u8,
+    (frame, width, height): (&[u8], usize, usize),
+    weight: u32,
+) -> Texture {
+    let texels = base
+        .texels
+        .iter()
+        .enumerate()
+        .map(|(i, &texel)| {
+            let color = match base.format {
+                TextureFormat::Indexed => palette.color((texel as u8).wrapping_add(palette_shift)),
+                TextureFormat::Rgba => texel,
+            };
+            let x = i % base.width * width / base.width;
+            let y = i / base.width * height / base.height;
+            let k = (y * width + x) * 4;
+            let from = color::unpack(color);
+            color::pack(std::array::from_fn(|c| {
+                ((from[c] as u32 * (256 - weight) + frame[k + c] as u32 * weight) >> 8) as u8
+            }))
+        })
+        .collect();
+    Texture {
+        width: base.width,
+        height: base.height,
+        format: TextureFormat::Rgba,
+        texels,
+    }
+}
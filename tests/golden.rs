@@ -0,0 +1,336 @@
+//! Renders fixed frames of every geometry, texture generator, effect and post-processing pass,
+//! of a color grading LUT, of the lit, bump mapped, curved, tiled, stamped, rolled,
+//! interlaced and reduced motion tunnel, of a scroller cued by a timeline and of split-screen
+//! comparisons, and compares them against the reference images in `tests/golden`. Run with
+//! `UPDATE_GOLDEN=1` to write the references again after an intended change to the output.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use image::RgbaImage;
+use tunnel::postfx::ColorLut;
+use tunnel::{
+    effect, geometry, texture, Atlas, Clock, Comparison, Compositor, Curve, Flight, Interlace,
+    Light, Modulator, PassKind, Pipeline, PostFx, Renderer, Scroller, Split, Stamp, Texture,
+    Timeline, TunnelGeometry, TunnelParams, World,
+};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 96;
+/// Animation time the frames are taken at, reached in steps of `STEP`.
+const TIME: f64 = 0.75;
+const STEP: f64 = 1.0 / 60.0;
+const SEED: u64 = 1;
+/// How far a channel may drift before the pixel counts as changed, to allow for floating
+/// point differences between platforms.
+const TOLERANCE: u8 = 16;
+/// Share of changed pixels a frame may have and still match its reference.
+const MAX_CHANGED: f64 = 0.002;
+
+fn world(geometry: Arc<dyn TunnelGeometry>, texture: Texture) -> World {
+    let params = TunnelParams {
+        width: WIDTH,
+        height: HEIGHT,
+        ..TunnelParams::default()
+    };
+    let mut world = World::with_texture(params, texture);
+    world.set_crossfade(0.0);
+    world.set_geometry(geometry);
+    *world.clock_mut() = Clock::fixed(STEP);
+    world
+}
+
+/// Steps `world` and `pipeline` to `TIME` and draws the frame shown then.
+fn render(world: &mut World, pipeline: &mut Pipeline) -> RgbaImage {
+    let (width, height) = world.frame_size();
+    let mut frame = vec![0u8; width as usize * height as usize * 4];
+    for _ in 0..(TIME / STEP).round() as usize {
+        world.update();
+        pipeline.update(world);
+    }
+    pipeline.draw(world, &mut frame);
+    RgbaImage::from_raw(width, height, frame).expect("frame has the frame size")
+}
+
+fn xor() -> Texture {
+    let params = TunnelParams::default();
+    Texture::xor(params.tex_width, params.tex_height)
+}
+
+fn circle() -> Arc<dyn TunnelGeometry> {
+    geometry::builtin().remove(0)
+}
+
+/// Compares `frame` with the reference image called `name`, returning what differs.
+fn check(name: &str, frame: &RgbaImage) -> Result<(), String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        frame.save(&path).unwrap();
+        return Ok(());
+    }
+    let reference = match image::open(&path) {
+        Ok(reference) => reference.into_rgba8(),
+        Err(err) => return Err(format!("{}: {} (UPDATE_GOLDEN=1 writes it)", name, err)),
+    };
+    if reference.as_raw() == frame.as_raw() {
+        return Ok(());
+    }
+    if reference.dimensions() != frame.dimensions() {
+        return Err(format!(
+            "{}: {:?} instead of {:?}",
+            name,
+            frame.dimensions(),
+            reference.dimensions()
+        ));
+    }
+    let changed = reference
+        .pixels()
+        .zip(frame.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(&a, b)| a.abs_diff(b) > TOLERANCE))
+        .count();
+    let share = changed as f64 / (frame.width() * frame.height()) as f64;
+    if share <= MAX_CHANGED {
+        return Ok(());
+    }
+    let actual = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{}.png", name));
+    frame.save(&actual).unwrap();
+    Err(format!(
+        "{}: {:.2}% of the pixels changed, see {}",
+        name,
+        share * 100.0,
+        actual.display()
+    ))
+}
+
+fn assert_all(results: Vec<Result<(), String>>) {
+    let failures: Vec<_> = results.into_iter().filter_map(Result::err).collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+#[test]
+fn geometries() {
+    assert_all(
+        geometry::builtin()
+            .into_iter()
+            .map(|geometry| {
+                let name = format!("geometry-{}", geometry.name());
+                let mut world = world(geometry, xor());
+                check(&name, &render(&mut world, &mut Pipeline::default()))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn texture_generators() {
+    let params = TunnelParams::default();
+    assert_all(
+        texture::builtin(SEED)
+            .iter()
+            .map(|generator| {
+                let texture = generator.generate(params.tex_width, params.tex_height);
+                let mut world = world(circle(), texture);
+                let name = format!("texture-{}", generator.name());
+                check(&name, &render(&mut world, &mut Pipeline::default()))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn effects() {
+    let count = Pipeline::default().effects.len();
+    assert_all(
+        (0..count)
+            .map(|i| {
+                let mut pipeline = Pipeline::default();
+                pipeline.compositor = Compositor::new(i);
+                let name = format!("effect-{}", pipeline.effects[i].name());
+                check(&name, &render(&mut world(circle(), xor()), &mut pipeline))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn postfx() {
+    assert_all(
+        PassKind::ALL
+            .iter()
+            .map(|&kind| {
+                let mut pipeline = Pipeline::default();
+                pipeline.postfx = PostFx::new(&[kind]);
+                let name = format!("postfx-{}", kind.name());
+                check(&name, &render(&mut world(circle(), xor()), &mut pipeline))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn color_lut() {
+    // A warm, contrasty grade with a point at mid-gray, so that the interpolation shows.
+    let curve = [0.0, 0.4, 1.0];
+    let mut cube = String::from("TITLE \"warm\"\nLUT_3D_SIZE 3\n");
+    for b in curve {
+        for g in curve {
+            for r in curve {
+                cube += &format!("{} {} {}\n", (r * 1.1f32).min(1.0), g, b * 0.8);
+            }
+        }
+    }
+    let mut pipeline = Pipeline::default();
+    pipeline.postfx = PostFx::new(&[]);
+    pipeline
+        .postfx
+        .push(Box::new(ColorLut::parse(&cube).unwrap()), true);
+    let frame = render(&mut world(circle(), xor()), &mut pipeline);
+    assert_all(vec![check("color-lut", &frame)]);
+}
+
+#[test]
+fn light() {
+    assert_all(
+        [("light", 0.0), ("light-bump", 1.0)]
+            .into_iter()
+            .map(|(name, bump)| {
+                let mut world = world(circle(), xor());
+                world.set_light(Light {
+                    enabled: true,
+                    bump,
+                    ..Light::default()
+                });
+                check(name, &render(&mut world, &mut Pipeline::default()))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn reduced_motion() {
+    // Fast enough to strobe the palette and blur the walls, unless held back.
+    let mut world = world(circle(), xor());
+    world.set_palette_speed(2048.0);
+    world.set_flight(Flight {
+        travel: 4.0,
+        spin: 1.0,
+        easing: 0.0,
+    });
+    world.set_reduced_motion(true);
+    assert_all(vec![check(
+        "reduced-motion",
+        &render(&mut world, &mut Pipeline::default()),
+    )]);
+}
+
+#[test]
+fn curve() {
+    let mut world = world(circle(), xor());
+    world.set_curve(Curve {
+        amount: Modulator::constant(1.0),
+        ..Curve::default()
+    });
+    assert_all(vec![check(
+        "curve",
+        &render(&mut world, &mut Pipeline::default()),
+    )]);
+}
+
+#[test]
+fn atlas() {
+    let mut world = world(circle(), xor());
+    let atlas = Atlas::parse("columns = 2\nrows = 2\ntiles = [0, 0, 1]").unwrap();
+    world.set_atlas(Some(atlas));
+    assert_all(vec![check(
+        "atlas",
+        &render(&mut world, &mut Pipeline::default()),
+    )]);
+}
+
+#[test]
+fn stamps() {
+    let mut world = world(circle(), xor());
+    let text = Stamp::text("GREETZ", None).unwrap();
+    world.set_stamps(vec![Stamp {
+        around: 0.25,
+        size: 0.5,
+        ..text
+    }]);
+    assert_all(vec![check(
+        "stamps",
+        &render(&mut world, &mut Pipeline::default()),
+    )]);
+}
+
+#[test]
+fn scroller() {
+    // The scroller comes in with the second scene.
+    let timeline = Timeline::parse(
+        "[[scene]]\nduration = 0.25\n[[scene]]\nduration = 1.0\nscroller = \"GREETZ\"",
+        Path::new(""),
+    )
+    .unwrap();
+    let mut world = world(circle(), xor());
+    world.set_timeline(Some(timeline));
+    let mut scroller = Scroller::hidden(None);
+    scroller.speed = 1.0;
+    let mut pipeline = Pipeline::default();
+    pipeline.scroller = Some(scroller);
+    assert_all(vec![check("scroller", &render(&mut world, &mut pipeline))]);
+}
+
+#[test]
+fn roll() {
+    let mut world = world(circle(), xor());
+    world.set_roll(0.125);
+    assert_all(vec![check(
+        "roll",
+        &render(&mut world, &mut Pipeline::default()),
+    )]);
+}
+
+#[test]
+fn split() {
+    assert_all(
+        [Comparison::Filter, Comparison::Palette]
+            .into_iter()
+            .map(|comparison| {
+                let mut pipeline = Pipeline::default();
+                pipeline.split = Some(Split::new(comparison));
+                let name = format!("split-{}", comparison.name());
+                check(&name, &render(&mut world(circle(), xor()), &mut pipeline))
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn interlace() {
+    assert_all(
+        [Interlace::Rows, Interlace::Checkerboard]
+            .into_iter()
+            .map(|interlace| {
+                let mut renderer = Renderer::default();
+                renderer.set_interlace(interlace);
+                let mut effects = effect::builtin();
+                effects[0] = Box::new(renderer);
+                let mut pipeline = Pipeline::new(Compositor::new(0), effects);
+                let mut world = world(circle(), xor());
+                // Every step is drawn, since each frame fills in from the ones before.
+                let (width, height) = world.frame_size();
+                let mut frame = vec![0u8; width as usize * height as usize * 4];
+                for _ in 0..(TIME / STEP).round() as usize {
+                    world.update();
+                    pipeline.update(&world);
+                    pipeline.draw(&mut world, &mut frame);
+                }
+                let frame =
+                    RgbaImage::from_raw(width, height, frame).expect("frame has the frame size");
+                check(&format!("interlace-{}", interlace.name()), &frame)
+            })
+            .collect(),
+    );
+}